@@ -0,0 +1,181 @@
+//!
+//! Compares `obj::load` with the previous object loader, which read the whole file in a `String`,
+//! collected the tokens of every line in a `Vec` and parsed floats with `str::parse`. A copy of
+//! that loader, reduced to the statements of the benchmark files, is kept in `legacy_load`.
+//!
+//! ```sh
+//! cargo run --release -p basis --example parse_obj_benchmark -- [file.obj]
+//! ```
+//!
+//! Without a file, a grid of a million quads is generated in the temporary directory.
+//!
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    time::{Duration, Instant},
+};
+
+use basis::{
+    graphics::wavefront::obj::{self, Face, LoadOptions, Object, VertexDataReference, OBJ},
+    math,
+};
+
+const GRID_SIZE: usize = 1000;
+const RUNS: usize = 3;
+
+fn main() {
+    let file_path = match std::env::args().nth(1) {
+        Some(file_path) => file_path,
+        None => generate_grid(GRID_SIZE).expect("Could not generate the benchmark file"),
+    };
+    let size = std::fs::metadata(&file_path)
+        .expect("Could not read the file")
+        .len();
+
+    println!("{} ({:.1} MiB)", file_path, size as f64 / (1024.0 * 1024.0));
+
+    let (legacy, legacy_obj) = measure(|| legacy_load(&file_path));
+    let (streaming, streaming_obj) =
+        measure(|| obj::load(&file_path).expect("Could not load the file"));
+
+    assert_eq!(
+        legacy_obj.vertices, streaming_obj.vertices,
+        "Both loaders should read the same vertices"
+    );
+    assert_eq!(
+        legacy_obj.faces, streaming_obj.faces,
+        "Both loaders should build the same faces"
+    );
+
+    println!(
+        "previous loader, read_to_string + Vec + str::parse: {:?}",
+        legacy
+    );
+    println!(
+        "obj::load, BufRead + split + parse_float:          {:?}",
+        streaming
+    );
+    println!(
+        "{} vertices, {} faces",
+        streaming_obj.vertices.len(),
+        streaming_obj.faces.len()
+    );
+}
+
+///
+/// Best time of a few runs, with the result of the last one
+///
+fn measure<T>(run: impl Fn() -> T) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = Some(run());
+        best = best.min(start.elapsed());
+    }
+
+    (best, result.expect("There is at least one run"))
+}
+
+///
+/// The previous loader, only handling the vertices and the faces of the benchmark files
+///
+fn legacy_load(file_path: &str) -> OBJ {
+    let data = std::fs::read_to_string(file_path).expect("Could not read the file");
+    let mut obj = OBJ::default();
+    let mut face_id: usize = 0;
+
+    obj.objects.push(Object::new("default"));
+    let groups = vec!["default".to_string()];
+
+    for line in data.split("\n").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let mut tokens = line
+            .split(" ")
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<&str>>()
+            .into_iter();
+
+        match tokens.next() {
+            Some("v") => {
+                let x = tokens.next().unwrap().parse::<f32>();
+                let y = tokens.next().unwrap().parse::<f32>();
+                let z = tokens.next().unwrap().parse::<f32>();
+                let w = tokens.next().unwrap_or("1.0").parse::<f32>();
+
+                match (x, y, z, w) {
+                    (Ok(x), Ok(y), Ok(z), Ok(w)) => obj.vertices.push(math::Vec4::new(x, y, z, w)),
+                    _ => panic!("Invalid vertex '{line}'"),
+                }
+            }
+            Some("f") => {
+                let mut face = Face::partial_new(Vec::new(), None);
+
+                for token in tokens {
+                    let v = token
+                        .split("/")
+                        .next()
+                        .unwrap_or_default()
+                        .parse::<usize>()
+                        .expect("Invalid face side");
+                    assert!(
+                        (1..=obj.vertices.len()).contains(&v),
+                        "Reference out of range"
+                    );
+                    face.vertex_references
+                        .push(VertexDataReference::new(v, 0, 0));
+                }
+                face.id = face_id;
+                obj.objects[0].push_face(face_id, &groups);
+                obj.faces.push(face);
+                face_id += 1;
+            }
+            _ => {}
+        }
+    }
+
+    obj.faces.iter_mut().for_each(|face| {
+        face.max_id = face_id;
+    });
+    obj.triangulate(LoadOptions::default().triangulation);
+
+    obj
+}
+
+///
+/// Writes a flat grid of `size` x `size` quads
+///
+fn generate_grid(size: usize) -> std::io::Result<String> {
+    let file_path = std::env::temp_dir().join(format!("basis-grid-{size}.obj"));
+    let mut writer = BufWriter::new(File::create(&file_path)?);
+
+    for y in 0..=size {
+        for x in 0..=size {
+            writeln!(
+                writer,
+                "v {:.6} {:.6} {:.6}",
+                x as f32 / size as f32,
+                y as f32 / size as f32,
+                ((x * y) % 7) as f32 * 0.01
+            )?;
+        }
+    }
+    for y in 0..size {
+        for x in 0..size {
+            let corner = y * (size + 1) + x + 1;
+            writeln!(
+                writer,
+                "f {} {} {} {}",
+                corner,
+                corner + 1,
+                corner + size + 2,
+                corner + size + 1
+            )?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
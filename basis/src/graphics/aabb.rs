@@ -25,4 +25,55 @@ impl AABB {
 
         aabb
     }
+
+    ///
+    /// Box around the corners of this one moved by `matrix`, such as the world space box of a
+    /// mesh
+    ///
+    pub fn transform(&self, matrix: &math::Mat4) -> AABB {
+        let corners = (0..8)
+            .map(|corner| {
+                let x = if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                };
+                let y = if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                };
+                let z = if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                };
+                matrix.transform_point(math::Vec3::new(x, y, z)).extend(1.0)
+            })
+            .collect();
+
+        AABB::from(&corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::VectorFunctions;
+
+    #[test]
+    fn it_should_transform_an_aabb() {
+        let aabb = AABB {
+            min: math::Vec3::new(-1.0, -2.0, -3.0),
+            max: math::Vec3::new(1.0, 2.0, 3.0),
+        };
+        let mut matrix = math::Mat4::identity();
+        matrix.rotate_euler(std::f32::consts::FRAC_PI_2, math::Vec3::new(0.0, 0.0, 1.0));
+        matrix.translate(math::Vec3::new(10.0, 0.0, 0.0));
+
+        let moved = aabb.transform(&matrix);
+
+        assert!(moved.min.approx_eq(math::Vec3::new(8.0, -1.0, -3.0), 1e-5));
+        assert!(moved.max.approx_eq(math::Vec3::new(12.0, 1.0, 3.0), 1e-5));
+    }
 }
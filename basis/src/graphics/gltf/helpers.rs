@@ -0,0 +1,174 @@
+use super::structs::ParseError;
+
+///
+/// Every GLB file starts with these bytes
+///
+pub const GLB_MAGIC: &[u8; 4] = b"glTF";
+
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+fn read_u32(bytes: &[u8], position: usize) -> Option<u32> {
+    bytes
+        .get(position..position + 4)
+        .map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+///
+/// Splits a GLB file into its JSON document and its binary buffer
+///
+pub fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), ParseError> {
+    let invalid = |message: &str| ParseError::InvalidGlb(message.to_string());
+
+    if !bytes.starts_with(GLB_MAGIC) {
+        return Err(invalid("Missing 'glTF' magic"));
+    }
+    let version = read_u32(bytes, 4).ok_or_else(|| invalid("Truncated header"))?;
+    if version != 2 {
+        return Err(ParseError::UnsupportedVersion(version.to_string()));
+    }
+    let length = read_u32(bytes, 8).ok_or_else(|| invalid("Truncated header"))? as usize;
+    if length > bytes.len() {
+        return Err(invalid("The file is shorter than its header says"));
+    }
+
+    let mut json = None;
+    let mut bin = None;
+    let mut position = 12;
+
+    while position < length {
+        let (Some(chunk_length), Some(chunk_type)) =
+            (read_u32(bytes, position), read_u32(bytes, position + 4))
+        else {
+            return Err(invalid("Truncated chunk header"));
+        };
+        let start = position + 8;
+        let chunk = bytes
+            .get(start..start + chunk_length as usize)
+            .filter(|_| start + chunk_length as usize <= length)
+            .ok_or_else(|| invalid("Truncated chunk"))?;
+
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if json.is_some() && bin.is_none() => bin = Some(chunk),
+            // unknown chunks must be ignored
+            _ => {}
+        }
+        // chunks are aligned on 4 bytes
+        position = start + (chunk_length as usize).next_multiple_of(4);
+    }
+
+    let json = json.ok_or_else(|| invalid("Missing JSON chunk"))?;
+    Ok((json, bin))
+}
+
+///
+/// Decodes standard base64, as used by the `data:` URIs of the buffers
+///
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let value = |character: u8| match character {
+        b'A'..=b'Z' => Some(character - b'A'),
+        b'a'..=b'z' => Some(character - b'a' + 26),
+        b'0'..=b'9' => Some(character - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+
+    let text = text.trim_end_matches('=').as_bytes();
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+
+    for chunk in text.chunks(4) {
+        let mut group = 0u32;
+        for (index, character) in chunk.iter().enumerate() {
+            group |= (value(*character)? as u32) << (18 - 6 * index);
+        }
+        let decoded = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+        bytes.extend_from_slice(&decoded[..chunk.len() - 1]);
+    }
+
+    Some(bytes)
+}
+
+///
+/// Decodes the `%XX` escapes of a relative URI
+///
+pub fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let escaped = bytes
+            .get(position + 1..position + 3)
+            .filter(|_| bytes[position] == b'%')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                position += 3;
+            }
+            None => {
+                decoded.push(bytes[position]);
+                position += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_decode_base64() {
+        assert_eq!(decode_base64(""), Some(Vec::new()));
+        assert_eq!(decode_base64("TQ=="), Some(b"M".to_vec()));
+        assert_eq!(decode_base64("TWE="), Some(b"Ma".to_vec()));
+        assert_eq!(decode_base64("TWFu"), Some(b"Man".to_vec()));
+        assert_eq!(decode_base64("AAD/fw"), Some(vec![0, 0, 255, 127]));
+        assert_eq!(decode_base64("TWFu!"), None);
+        assert_eq!(decode_base64("TWFuT"), None);
+    }
+
+    #[test]
+    fn it_should_decode_uri_escapes() {
+        assert_eq!(decode_uri("my%20mesh.bin"), "my mesh.bin");
+        assert_eq!(decode_uri("%C3%A9t%C3%A9.bin"), "été.bin");
+        assert_eq!(decode_uri("100%.bin"), "100%.bin");
+    }
+
+    #[test]
+    fn it_should_split_glb_chunks() {
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&36u32.to_le_bytes());
+        glb.extend_from_slice(&4u32.to_le_bytes());
+        glb.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(b"{}  ");
+        glb.extend_from_slice(&3u32.to_le_bytes());
+        glb.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+        glb.extend_from_slice(&[1, 2, 3, 0]);
+
+        let (json, bin) = split_glb(&glb).expect("This should work");
+        assert_eq!(json, b"{}  ");
+        assert_eq!(bin, Some(&[1, 2, 3][..]));
+
+        glb[4] = 1;
+        assert!(matches!(
+            split_glb(&glb),
+            Err(ParseError::UnsupportedVersion(_))
+        ));
+        glb[4] = 2;
+        glb.truncate(30);
+        assert!(matches!(split_glb(&glb), Err(ParseError::InvalidGlb(_))));
+    }
+}
@@ -0,0 +1,402 @@
+//!
+//! glTF 2.0 scenes, both as `.gltf` JSON documents (with embedded or external buffers) and as
+//! binary `.glb` containers.
+//!
+
+mod helpers;
+mod parse_gltf;
+mod structs;
+
+use std::path::Path;
+
+use crate::json;
+
+pub use structs::AlphaMode;
+pub use structs::Material;
+pub use structs::Node;
+pub use structs::ParseError;
+pub use structs::Scene;
+pub use structs::Transform;
+
+#[derive(Debug)]
+pub enum LoadGLTFError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+impl std::error::Error for LoadGLTFError {}
+
+impl From<std::io::Error> for LoadGLTFError {
+    fn from(err: std::io::Error) -> Self {
+        LoadGLTFError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadGLTFError {
+    fn from(err: ParseError) -> Self {
+        LoadGLTFError::Parse(err)
+    }
+}
+
+impl std::fmt::Display for LoadGLTFError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadGLTFError::Io(err) => write!(f, "IO error: {}", err),
+            LoadGLTFError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Binary,
+}
+
+impl Format {
+    ///
+    /// `Binary` for the GLB containers, `Json` for anything that starts like a JSON object
+    ///
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(helpers::GLB_MAGIC) {
+            return Some(Format::Binary);
+        }
+
+        let text = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(bytes);
+        match text.trim_ascii_start().first() {
+            Some(b'{') => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Parses a `.gltf` or `.glb` file.
+///
+/// Buffers and images stored in external files are looked up in `directory`, without it only
+/// the embedded ones can be read.
+///
+pub fn parse(bytes: &[u8], directory: Option<&Path>) -> Result<Scene, LoadGLTFError> {
+    let (text, bin) = match Format::from_magic(bytes) {
+        Some(Format::Binary) => helpers::split_glb(bytes)?,
+        _ => (bytes, None),
+    };
+
+    let text = std::str::from_utf8(text).map_err(|_| {
+        ParseError::Json(json::ParseError::InvalidToken(
+            1,
+            "Invalid UTF-8".to_string(),
+        ))
+    })?;
+    let document =
+        json::parse(text.strip_prefix('\u{feff}').unwrap_or(text)).map_err(ParseError::from)?;
+
+    let buffers = parse_gltf::load_buffers(&document, bin, directory)?;
+    Ok(parse_gltf::parse_gltf(&document, &buffers, directory)?)
+}
+
+pub fn load(file_path: impl AsRef<Path>) -> Result<Scene, LoadGLTFError> {
+    let file_path = file_path.as_ref();
+    let bytes = std::fs::read(file_path)?;
+
+    // buffers and images are relative to the document
+    let directory = file_path.parent().unwrap_or(Path::new(""));
+    parse(&bytes, Some(directory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+
+        for chunk in bytes.chunks(3) {
+            let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+                group | (*byte as u32) << (16 - 8 * index)
+            });
+            for index in 0..=chunk.len() {
+                text.push(ALPHABET[(group >> (18 - 6 * index) & 63) as usize] as char);
+            }
+        }
+
+        text
+    }
+
+    ///
+    /// A triangle with normals, texture coordinates and 16 bits indices
+    ///
+    fn triangle_buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let floats = [
+            // positions
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // normals
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, // texture coordinates
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0f32,
+        ];
+        for float in floats {
+            bytes.extend_from_slice(&float.to_le_bytes());
+        }
+        for index in [0u16, 1, 2, 0] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    ///
+    /// Document using the triangle twice, once under a translated, rotated and scaled parent
+    ///
+    fn document(uri: Option<&str>) -> String {
+        let uri = uri
+            .map(|uri| format!(r#""uri": "{uri}","#))
+            .unwrap_or_default();
+
+        format!(
+            r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0, 2] }}],
+            "nodes": [
+                {{ "name": "root", "translation": [1, 0, 0], "children": [1] }},
+                {{ "name": "child", "mesh": 0, "rotation": [0, 0, 0.7071068, 0.7071068], "scale": [2, 2, 2] }},
+                {{ "mesh": 0, "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 5, 1] }}
+            ],
+            "meshes": [{{
+                "name": "triangle",
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }},
+                    "indices": 3,
+                    "material": 0
+                }}]
+            }}],
+            "materials": [{{
+                "name": "Gold",
+                "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0.8, 0.2, 0.5], "metallicFactor": 1, "roughnessFactor": 0.25 }},
+                "alphaMode": "BLEND"
+            }}],
+            "buffers": [{{ {uri} "byteLength": 104 }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 96 }},
+                {{ "buffer": 0, "byteOffset": 96, "byteLength": 6 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 0, "byteOffset": 72, "componentType": 5126, "count": 3, "type": "VEC2" }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ]
+        }}"#
+        )
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"glTF");
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"JSON");
+        bytes.extend_from_slice(&json);
+        bytes.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"BIN\0");
+        bytes.extend_from_slice(&bin);
+        bytes
+    }
+
+    fn assert_near(result: math::Vec4, expected: [f32; 3]) {
+        let result = [result.x, result.y, result.z];
+        for (result, expected) in result.iter().zip(expected) {
+            assert!(
+                (result - expected).abs() < 1e-5,
+                "{result:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_place_every_node_of_the_scene() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            encode_base64(&triangle_buffer())
+        );
+        let scene = parse(document(Some(&uri)).as_bytes(), None).expect("This should work");
+
+        assert_eq!(scene.roots, vec![0, 2]);
+        assert_eq!(scene.nodes[0].children, vec![1]);
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.meshes[0].faces.len(), 1);
+        assert_eq!(scene.meshes[0].vertices_texture[2].v, 0.0);
+
+        let obj = scene.to_obj();
+        let names = obj
+            .objects
+            .iter()
+            .map(|object| object.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["child", "node_2"]);
+        assert_eq!(obj.vertices.len(), 6);
+        assert_eq!(obj.faces.len(), 2);
+        assert_eq!(obj.faces[1].vertex_references[2].v, 6);
+        assert_eq!(obj.faces[1].vertex_references[2].vn, 6);
+
+        // scaled by 2, rotated by 90 degrees around z, then moved by the parent
+        assert_near(obj.vertices[0], [1.0, 0.0, 0.0]);
+        assert_near(obj.vertices[1], [1.0, 2.0, 0.0]);
+        assert_near(obj.vertices[2], [-1.0, 0.0, 0.0]);
+        assert_near(obj.vertices[5], [0.0, 1.0, 5.0]);
+        let normal = &obj.vertices_normal[0];
+        assert_near(
+            math::Vec4::new(normal.i, normal.j, normal.k, 0.0),
+            [0.0, 0.0, 1.0],
+        );
+
+        let material = obj.faces[0].material.as_ref().expect("This should work");
+        assert_eq!(material.name, "Gold");
+        assert_eq!(material.diffuse_reflectivity.rgb().g, 0.8);
+        assert_eq!(material.dissolve_factor.factor, 0.5);
+        assert_eq!(material.roughness, 0.25);
+        assert_eq!(scene.materials[0].roughness_factor, 0.25);
+        assert_eq!(scene.materials[0].alpha_mode, AlphaMode::Blend);
+        assert!(obj.mtls[0].contains_key("Gold"));
+        assert_eq!(obj.material_ranges[0].faces, 0..2);
+    }
+
+    #[test]
+    fn it_should_read_external_and_binary_buffers() {
+        let directory = std::env::temp_dir().join("basis-gltf-external");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("triangle data.bin"), triangle_buffer()).unwrap();
+        std::fs::write(
+            directory.join("scene.gltf"),
+            document(Some("triangle%20data.bin")),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("scene.glb"),
+            glb(&document(None), &triangle_buffer()),
+        )
+        .unwrap();
+
+        let external = load(directory.join("scene.gltf")).expect("This should work");
+        let binary = load(directory.join("scene.glb")).expect("This should work");
+
+        assert_eq!(external.to_obj().vertices, binary.to_obj().vertices);
+        assert_eq!(external.nodes, binary.nodes);
+        assert!(matches!(
+            parse(document(Some("triangle%20data.bin")).as_bytes(), None),
+            Err(LoadGLTFError::Parse(ParseError::InvalidBuffer(0, _)))
+        ));
+        assert_eq!(Format::from_magic(&glb("{}", &[])), Some(Format::Binary));
+        assert_eq!(
+            Format::from_magic(b"\n  {\"asset\": {}}"),
+            Some(Format::Json)
+        );
+        assert_eq!(Format::from_magic(b"solid cube"), None);
+    }
+
+    #[test]
+    fn it_should_load_the_stacked_cubes_resource() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../scop/src/resources/stacked_cubes.gltf"
+        );
+
+        let obj = load(path).expect("This should work").to_obj();
+
+        assert_eq!(obj.objects.len(), 2);
+        assert_eq!(obj.faces.len(), 24);
+        // the top cube is half as big and sits on the base
+        let top = &obj.vertices[24..];
+        let lowest = top.iter().map(|vertex| vertex.y).fold(f32::MAX, f32::min);
+        let highest = top.iter().map(|vertex| vertex.y).fold(f32::MIN, f32::max);
+        assert!((lowest - 1.0).abs() < 1e-5 && (highest - 2.0).abs() < 1e-5);
+        assert!(obj.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_documents() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            encode_base64(&triangle_buffer())
+        );
+        let valid = document(Some(&uri));
+
+        let cases = [
+            (
+                valid.replace("\"2.0\"", "\"1.0\""),
+                "Unsupported glTF version '1.0'",
+            ),
+            (
+                valid.replace(
+                    "\"count\": 3, \"type\": \"SCALAR\"",
+                    "\"count\": 4, \"type\": \"SCALAR\"",
+                ),
+                "Invalid accessor 3: The elements go past the end of the buffer view",
+            ),
+            (
+                valid.replace(
+                    "\"count\": 3, \"type\": \"SCALAR\"",
+                    "\"count\": 4611686018427387904, \"type\": \"SCALAR\"",
+                ),
+                "Invalid accessor 3: The elements go past the end of the buffer view",
+            ),
+            (
+                valid.replace(
+                    "\"bufferView\": 1, \"componentType\": 5123, \"count\": 3",
+                    "\"componentType\": 5123, \"count\": 9007199254740991",
+                ),
+                "Invalid accessor 3: Accessors without 'bufferView' hold at most 16777216 elements",
+            ),
+            (
+                valid.replace("\"TEXCOORD_0\": 2", "\"TEXCOORD_0\": 1"),
+                "Invalid mesh 0: Primitive 0: Accessor 1 has the wrong type",
+            ),
+            (
+                valid.replace("\"children\": [1]", "\"children\": [1, 0]"),
+                "Invalid node 0: The node is its own ancestor",
+            ),
+            (
+                valid.replace("\"nodes\": [0, 2]", "\"nodes\": [1]"),
+                "Invalid scene 0: 'nodes' should only hold nodes without parent",
+            ),
+            (
+                valid.replace("\"mesh\": 0, \"matrix\"", "\"mesh\": 1, \"matrix\""),
+                "Invalid node 2: 'mesh' is not a valid index",
+            ),
+            (
+                valid.replace(
+                    "\"asset\"",
+                    "\"extensionsRequired\": [\"KHR_draco_mesh_compression\"], \"asset\"",
+                ),
+                "Unsupported required extension 'KHR_draco_mesh_compression'",
+            ),
+            (
+                r#"{"asset":{"version":"2.0"}}"#.to_string(),
+                "Missing faces, the scene shows no triangle",
+            ),
+            (
+                valid.replace("\"nodes\": [0, 2]", "\"nodes\": []"),
+                "Missing faces, the scene shows no triangle",
+            ),
+            (
+                valid.replace("\"indices\": 3,", "\"indices\": 3, \"mode\": 1,"),
+                "Missing faces, the scene shows no triangle",
+            ),
+            (
+                valid.replace("\"BLEND\"", "BLEND"),
+                "Invalid JSON token at line 21: Expected a value",
+            ),
+        ];
+
+        for (text, message) in cases {
+            let result = parse(text.as_bytes(), None);
+            assert_eq!(result.unwrap_err().to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,812 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::{
+    helpers::{decode_base64, decode_uri},
+    structs::{push_material_ranges, AlphaMode, Material, Node, ParseError, Scene, Transform},
+    LoadGLTFError,
+};
+use crate::{
+    graphics::wavefront::{
+        self,
+        obj::{Face, Group, Object, VertexDataReference, VerticeNormal, VerticeTexture, OBJ},
+    },
+    json::Value,
+    math,
+};
+
+///
+/// Elements of a top level array of the document, a missing array is empty
+///
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value.get(key).and_then(Value::as_array).unwrap_or(&[])
+}
+
+///
+/// Index stored in `key`, checked against the amount of elements it points to
+///
+fn index(value: &Value, key: &str, len: usize) -> Result<Option<usize>, String> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(index) => match index.as_usize() {
+            Some(index) if index < len => Ok(Some(index)),
+            _ => Err(format!("'{}' is not a valid index", key)),
+        },
+    }
+}
+
+fn usize_or(value: &Value, key: &str, default: usize) -> Result<usize, String> {
+    match value.get(key) {
+        None => Ok(default),
+        Some(number) => number
+            .as_usize()
+            .ok_or_else(|| format!("'{}' is not a positive integer", key)),
+    }
+}
+
+fn f32_or(value: &Value, key: &str, default: f32) -> Result<f32, String> {
+    match value.get(key) {
+        None => Ok(default),
+        Some(number) => number
+            .as_f32()
+            .ok_or_else(|| format!("'{}' is not a number", key)),
+    }
+}
+
+fn f32s_or<const N: usize>(
+    value: &Value,
+    key: &str,
+    default: [f32; N],
+) -> Result<[f32; N], String> {
+    let Some(values) = value.get(key) else {
+        return Ok(default);
+    };
+
+    values
+        .as_array()
+        .filter(|values| values.len() == N)
+        .and_then(|values| {
+            let mut result = [0.0; N];
+            for (result, value) in result.iter_mut().zip(values) {
+                *result = value.as_f32()?;
+            }
+            Some(result)
+        })
+        .ok_or_else(|| format!("'{}' should hold {} numbers", key, N))
+}
+
+///
+/// Reads the data of every buffer, from the GLB binary chunk, a `data:` URI or a file next to the
+/// document
+///
+pub fn load_buffers(
+    document: &Value,
+    bin: Option<&[u8]>,
+    directory: Option<&Path>,
+) -> Result<Vec<Vec<u8>>, LoadGLTFError> {
+    let mut buffers = Vec::new();
+
+    for (index, buffer) in array(document, "buffers").iter().enumerate() {
+        let invalid = |message: &str| ParseError::InvalidBuffer(index, message.to_string());
+        let length = buffer
+            .get("byteLength")
+            .and_then(Value::as_usize)
+            .ok_or_else(|| invalid("Missing 'byteLength'"))?;
+
+        let data = match buffer.get("uri").and_then(Value::as_str) {
+            // only the first buffer can point to the binary chunk
+            None if index == 0 => bin
+                .ok_or_else(|| invalid("Missing 'uri' and GLB binary chunk"))?
+                .to_vec(),
+            None => return Err(invalid("Missing 'uri'").into()),
+            Some(uri) if uri.starts_with("data:") => {
+                let (_, data) = uri
+                    .split_once(";base64,")
+                    .ok_or_else(|| invalid("Only base64 data URIs are supported"))?;
+                decode_base64(data).ok_or_else(|| invalid("Invalid base64 data"))?
+            }
+            Some(uri) => {
+                let directory = directory.ok_or_else(|| {
+                    invalid("External files can only be read when loading a path")
+                })?;
+                std::fs::read(directory.join(decode_uri(uri)))?
+            }
+        };
+
+        if data.len() < length {
+            return Err(invalid("The data is shorter than 'byteLength'").into());
+        }
+        buffers.push(data);
+    }
+
+    Ok(buffers)
+}
+
+struct BufferView {
+    buffer: usize,
+    offset: usize,
+    length: usize,
+    stride: Option<usize>,
+}
+
+impl BufferView {
+    fn parse(value: &Value, buffers: &[Vec<u8>]) -> Result<BufferView, String> {
+        let buffer = index(value, "buffer", buffers.len())?.ok_or("Missing 'buffer'")?;
+        let offset = usize_or(value, "byteOffset", 0)?;
+        let length = value
+            .get("byteLength")
+            .and_then(Value::as_usize)
+            .ok_or("Missing 'byteLength'")?;
+        let stride = match value.get("byteStride") {
+            None => None,
+            Some(stride) => Some(
+                stride
+                    .as_usize()
+                    .filter(|stride| (4..=252).contains(stride) && stride % 4 == 0)
+                    .ok_or("'byteStride' should be a multiple of 4 between 4 and 252")?,
+            ),
+        };
+
+        if offset + length > buffers[buffer].len() {
+            return Err("The view goes past the end of its buffer".to_string());
+        }
+
+        Ok(BufferView {
+            buffer,
+            offset,
+            length,
+            stride,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ComponentType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    UInt32,
+    Float32,
+}
+
+impl ComponentType {
+    fn from_code(code: usize) -> Option<ComponentType> {
+        match code {
+            5120 => Some(ComponentType::Int8),
+            5121 => Some(ComponentType::UInt8),
+            5122 => Some(ComponentType::Int16),
+            5123 => Some(ComponentType::UInt16),
+            5125 => Some(ComponentType::UInt32),
+            5126 => Some(ComponentType::Float32),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ComponentType::Int8 | ComponentType::UInt8 => 1,
+            ComponentType::Int16 | ComponentType::UInt16 => 2,
+            ComponentType::UInt32 | ComponentType::Float32 => 4,
+        }
+    }
+
+    fn read(&self, bytes: &[u8], normalized: bool) -> f64 {
+        let value = match self {
+            ComponentType::Int8 => bytes[0] as i8 as f64,
+            ComponentType::UInt8 => bytes[0] as f64,
+            ComponentType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ComponentType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ComponentType::UInt32 => {
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            ComponentType::Float32 => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+        };
+
+        if !normalized {
+            return value;
+        }
+        match self {
+            ComponentType::Int8 => (value / i8::MAX as f64).max(-1.0),
+            ComponentType::UInt8 => value / u8::MAX as f64,
+            ComponentType::Int16 => (value / i16::MAX as f64).max(-1.0),
+            ComponentType::UInt16 => value / u16::MAX as f64,
+            ComponentType::UInt32 | ComponentType::Float32 => value,
+        }
+    }
+}
+
+///
+/// Upper bound of the elements of an accessor without buffer view, they are filled with zeros
+/// so nothing in the file limits them
+///
+const MAX_ZEROED_ELEMENTS: usize = 1 << 24;
+
+struct Accessor {
+    view: Option<usize>,
+    offset: usize,
+    component_type: ComponentType,
+    normalized: bool,
+    count: usize,
+    components: usize,
+}
+
+impl Accessor {
+    fn parse(value: &Value, views: &[BufferView]) -> Result<Accessor, String> {
+        if value.get("sparse").is_some() {
+            return Err("Sparse accessors are not supported".to_string());
+        }
+
+        let view = index(value, "bufferView", views.len())?;
+        let component_type = value
+            .get("componentType")
+            .and_then(Value::as_usize)
+            .and_then(ComponentType::from_code)
+            .ok_or("Invalid 'componentType'")?;
+        let count = value
+            .get("count")
+            .and_then(Value::as_usize)
+            .ok_or("Missing 'count'")?;
+        let components = match value.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err("Invalid 'type'".to_string()),
+        };
+
+        let accessor = Accessor {
+            view,
+            offset: usize_or(value, "byteOffset", 0)?,
+            component_type,
+            normalized: value.get("normalized").and_then(Value::as_bool) == Some(true),
+            count,
+            components,
+        };
+
+        match accessor.view.map(|view| &views[view]) {
+            Some(view) if count > 0 => {
+                let element = accessor.element_size();
+                let stride = view.stride.unwrap_or(element);
+                let end = stride
+                    .checked_mul(count - 1)
+                    .and_then(|last| last.checked_add(accessor.offset))
+                    .and_then(|last| last.checked_add(element));
+                if end.is_none_or(|end| end > view.length) {
+                    return Err("The elements go past the end of the buffer view".to_string());
+                }
+            }
+            None if count > MAX_ZEROED_ELEMENTS => {
+                return Err(format!(
+                    "Accessors without 'bufferView' hold at most {MAX_ZEROED_ELEMENTS} elements"
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(accessor)
+    }
+
+    fn element_size(&self) -> usize {
+        self.component_type.size() * self.components
+    }
+
+    ///
+    /// Components of every element, one after the other. Accessors without buffer view are
+    /// filled with zeros.
+    ///
+    fn read(&self, views: &[BufferView], buffers: &[Vec<u8>]) -> Vec<f64> {
+        let Some(view) = self.view.map(|view| &views[view]) else {
+            return vec![0.0; self.count * self.components];
+        };
+
+        // the bounds are checked while parsing
+        let bytes = &buffers[view.buffer][view.offset..view.offset + view.length];
+        let size = self.component_type.size();
+        let stride = view.stride.unwrap_or(self.element_size());
+        let mut values = Vec::with_capacity(self.count * self.components);
+
+        for element in 0..self.count {
+            let start = self.offset + element * stride;
+            for component in 0..self.components {
+                let position = start + component * size;
+                values.push(
+                    self.component_type
+                        .read(&bytes[position..position + size], self.normalized),
+                );
+            }
+        }
+
+        values
+    }
+}
+
+///
+/// Data shared by every mesh while the document is converted
+///
+struct Document<'a> {
+    views: Vec<BufferView>,
+    accessors: Vec<Accessor>,
+    buffers: &'a [Vec<u8>],
+    materials: Vec<Rc<wavefront::mtl::Material>>,
+}
+
+impl Document<'_> {
+    ///
+    /// Reads an accessor with the given amount of components per element (the first ones are
+    /// kept when it has more)
+    ///
+    fn read(
+        &self,
+        accessor: usize,
+        components: &[usize],
+        expected_count: Option<usize>,
+    ) -> Result<Vec<f64>, String> {
+        let data = &self.accessors[accessor];
+
+        if !components.contains(&data.components) {
+            return Err(format!("Accessor {} has the wrong type", accessor));
+        }
+        if expected_count.is_some_and(|count| count != data.count) {
+            return Err(format!(
+                "Accessor {} does not have one element per vertex",
+                accessor
+            ));
+        }
+
+        Ok(data.read(&self.views, self.buffers))
+    }
+}
+
+pub fn parse_gltf(
+    document: &Value,
+    buffers: &[Vec<u8>],
+    directory: Option<&Path>,
+) -> Result<Scene, ParseError> {
+    let version = document
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if !version.starts_with("2.") {
+        return Err(ParseError::UnsupportedVersion(version.to_string()));
+    }
+    if let Some(extension) = array(document, "extensionsRequired").first() {
+        return Err(ParseError::UnsupportedExtension(
+            extension.as_str().unwrap_or_default().to_string(),
+        ));
+    }
+
+    let mut views = Vec::new();
+    for (index, view) in array(document, "bufferViews").iter().enumerate() {
+        views.push(
+            BufferView::parse(view, buffers)
+                .map_err(|message| ParseError::InvalidBufferView(index, message))?,
+        );
+    }
+
+    let mut accessors = Vec::new();
+    for (index, accessor) in array(document, "accessors").iter().enumerate() {
+        accessors.push(
+            Accessor::parse(accessor, &views)
+                .map_err(|message| ParseError::InvalidAccessor(index, message))?,
+        );
+    }
+
+    let mut scene = Scene::default();
+    for (index, material) in array(document, "materials").iter().enumerate() {
+        let mut material = parse_material(document, material, directory)
+            .map_err(|message| ParseError::InvalidMaterial(index, message))?;
+
+        // the materials are looked up by name
+        if material.name.is_empty()
+            || scene
+                .materials
+                .iter()
+                .any(|other: &Material| other.name == material.name)
+        {
+            material.name = format!("{}material_{}", material.name, index);
+        }
+        scene.materials.push(material);
+    }
+
+    let document_data = Document {
+        views,
+        accessors,
+        buffers,
+        materials: scene
+            .materials
+            .iter()
+            .map(|material| Rc::new(material.to_wavefront()))
+            .collect(),
+    };
+
+    for (index, mesh) in array(document, "meshes").iter().enumerate() {
+        let obj = parse_mesh(&document_data, mesh, index, &mut scene.warnings)
+            .map_err(|message| ParseError::InvalidMesh(index, message))?;
+        scene.meshes.push(obj);
+    }
+
+    for (index, node) in array(document, "nodes").iter().enumerate() {
+        let node = parse_node(node, scene.meshes.len(), array(document, "nodes").len())
+            .map_err(|message| ParseError::InvalidNode(index, message))?;
+        scene.nodes.push(node);
+    }
+    let parents = check_hierarchy(&scene.nodes)?;
+
+    scene.roots = match array(document, "scenes") {
+        [] => (0..scene.nodes.len())
+            .filter(|node| parents[*node].is_none())
+            .collect(),
+        scenes => {
+            let index = usize_or(document, "scene", 0)
+                .ok()
+                .filter(|index| *index < scenes.len())
+                .ok_or_else(|| {
+                    ParseError::InvalidScene(0, "'scene' is not a valid index".to_string())
+                })?;
+            parse_roots(&scenes[index], &parents)
+                .map_err(|message| ParseError::InvalidScene(index, message))?
+        }
+    };
+
+    // like the other formats, a file has to show something
+    let has_faces = scene.world_transforms().iter().any(|(index, _)| {
+        scene.nodes[*index]
+            .mesh
+            .is_some_and(|mesh| !scene.meshes[mesh].faces.is_empty())
+    });
+    if !has_faces {
+        return Err(ParseError::MissingFaces);
+    }
+
+    Ok(scene)
+}
+
+fn parse_material(
+    document: &Value,
+    value: &Value,
+    directory: Option<&Path>,
+) -> Result<Material, String> {
+    let default = Material::default();
+    let pbr = value.get("pbrMetallicRoughness").unwrap_or(&Value::Null);
+
+    let [r, g, b, a] = f32s_or(pbr, "baseColorFactor", [1.0; 4])?;
+    let [er, eg, eb] = f32s_or(value, "emissiveFactor", [0.0; 3])?;
+
+    let alpha_mode = match value.get("alphaMode").and_then(Value::as_str) {
+        None | Some("OPAQUE") => AlphaMode::Opaque,
+        Some("MASK") => AlphaMode::Mask,
+        Some("BLEND") => AlphaMode::Blend,
+        Some(mode) => return Err(format!("Unknown alpha mode '{}'", mode)),
+    };
+
+    // only the textures stored in their own file can be given to the renderer
+    let base_color_texture = pbr
+        .get("baseColorTexture")
+        .and_then(|texture| texture.get("index"))
+        .and_then(Value::as_usize)
+        .and_then(|texture| array(document, "textures").get(texture))
+        .and_then(|texture| texture.get("source"))
+        .and_then(Value::as_usize)
+        .and_then(|image| array(document, "images").get(image))
+        .and_then(|image| image.get("uri"))
+        .and_then(Value::as_str)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| match directory {
+            Some(directory) => directory.join(decode_uri(uri)),
+            None => PathBuf::from(decode_uri(uri)),
+        });
+
+    Ok(Material {
+        name: value
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        base_color_factor: math::Vec4::new(r, g, b, a),
+        metallic_factor: f32_or(pbr, "metallicFactor", default.metallic_factor)?,
+        roughness_factor: f32_or(pbr, "roughnessFactor", default.roughness_factor)?,
+        emissive_factor: math::Vec3::new(er, eg, eb),
+        alpha_mode,
+        alpha_cutoff: f32_or(value, "alphaCutoff", default.alpha_cutoff)?,
+        double_sided: value.get("doubleSided").and_then(Value::as_bool) == Some(true),
+        base_color_texture,
+    })
+}
+
+///
+/// Converts the primitives of a mesh to an `OBJ`, with one face per triangle
+///
+fn parse_mesh(
+    document: &Document,
+    value: &Value,
+    index: usize,
+    warnings: &mut Vec<String>,
+) -> Result<OBJ, String> {
+    let mut obj = OBJ::default();
+
+    for (position, primitive) in array(value, "primitives").iter().enumerate() {
+        let mode = usize_or(primitive, "mode", 4)?;
+        if mode < 4 {
+            warnings.push(format!(
+                "Primitive {} of mesh {} is made of points or lines, it is skipped",
+                position, index
+            ));
+            continue;
+        }
+
+        parse_primitive(document, primitive, mode, &mut obj)
+            .map_err(|message| format!("Primitive {}: {}", position, message))?;
+    }
+
+    // the colors are parallel to the vertices, the primitives without any are white
+    if !obj.vertices_color.is_empty() {
+        obj.vertices_color
+            .resize(obj.vertices.len(), math::Vec3::splat(1.0));
+    }
+
+    let face_count = obj.faces.len();
+    for (id, face) in obj.faces.iter_mut().enumerate() {
+        face.id = id;
+        face.max_id = face_count;
+    }
+    push_material_ranges(&mut obj, 0..face_count);
+
+    let name = value
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("mesh_{}", index));
+    let mut object = Object::new(&name);
+    let mut group = Group::new("default");
+    group.faces.push(0..face_count);
+    object.faces.push(0..face_count);
+    object.groups.push(group);
+    obj.objects.push(object);
+
+    let mut library = wavefront::mtl::MTL::new();
+    for face in &obj.faces {
+        if let Some(material) = &face.material {
+            library
+                .entry(material.name.clone())
+                .or_insert_with(|| wavefront::mtl::Material::clone(material));
+        }
+    }
+    if !library.is_empty() {
+        obj.mtls.push(library);
+    }
+
+    Ok(obj)
+}
+
+fn parse_primitive(
+    document: &Document,
+    primitive: &Value,
+    mode: usize,
+    obj: &mut OBJ,
+) -> Result<(), String> {
+    let accessors = document.accessors.len();
+    let attributes = primitive.get("attributes").unwrap_or(&Value::Null);
+
+    let positions = index(attributes, "POSITION", accessors)?.ok_or("Missing 'POSITION'")?;
+    let positions = document.read(positions, &[3], None)?;
+    let count = positions.len() / 3;
+
+    let read_attribute = |name: &str, components: &[usize]| -> Result<Option<Vec<f64>>, String> {
+        index(attributes, name, accessors)?
+            .map(|accessor| document.read(accessor, components, Some(count)))
+            .transpose()
+    };
+    let normals = read_attribute("NORMAL", &[3])?;
+    let textures = read_attribute("TEXCOORD_0", &[2])?;
+    let colors = read_attribute("COLOR_0", &[3, 4])?;
+
+    let indices = match index(primitive, "indices", accessors)? {
+        Some(accessor) => {
+            let indices = document
+                .read(accessor, &[1], None)?
+                .into_iter()
+                .map(|index| index as usize)
+                .collect::<Vec<usize>>();
+            if indices.iter().any(|index| *index >= count) {
+                return Err("An index is past the last vertex".to_string());
+            }
+            indices
+        }
+        None => (0..count).collect(),
+    };
+
+    let triangles = match mode {
+        4 => indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<[usize; 3]>>(),
+        // every other triangle of a strip is flipped to keep the winding
+        5 => indices
+            .windows(3)
+            .enumerate()
+            .map(|(index, window)| match index % 2 {
+                0 => [window[0], window[1], window[2]],
+                _ => [window[1], window[0], window[2]],
+            })
+            .collect(),
+        6 => indices
+            .windows(2)
+            .skip(1)
+            .map(|window| [indices[0], window[0], window[1]])
+            .collect(),
+        _ => return Err(format!("Unknown mode {}", mode)),
+    };
+
+    let material = index(primitive, "material", document.materials.len())?
+        .map(|material| &document.materials[material]);
+
+    let v_offset = obj.vertices.len();
+    let vt_offset = obj.vertices_texture.len();
+    let vn_offset = obj.vertices_normal.len();
+
+    obj.vertices
+        .extend(positions.chunks_exact(3).map(|position| {
+            math::Vec4::new(
+                position[0] as f32,
+                position[1] as f32,
+                position[2] as f32,
+                1.0,
+            )
+        }));
+    if let Some(normals) = &normals {
+        obj.vertices_normal
+            .extend(normals.chunks_exact(3).map(|normal| {
+                VerticeNormal::new(normal[0] as f32, normal[1] as f32, normal[2] as f32)
+            }));
+    }
+    // glTF textures start at the top, the Wavefront ones at the bottom
+    if let Some(textures) = &textures {
+        obj.vertices_texture.extend(
+            textures.chunks_exact(2).map(|texture| {
+                VerticeTexture::new(texture[0] as f32, 1.0 - texture[1] as f32, 0.0)
+            }),
+        );
+    }
+    if let Some(colors) = &colors {
+        let components = colors.len() / count.max(1);
+        obj.vertices_color.resize(v_offset, math::Vec3::splat(1.0));
+        obj.vertices_color.extend(
+            colors
+                .chunks_exact(components.max(1))
+                .map(|color| math::Vec3::new(color[0] as f32, color[1] as f32, color[2] as f32)),
+        );
+    }
+
+    for triangle in triangles {
+        let references = triangle
+            .iter()
+            .map(|index| {
+                VertexDataReference::new(
+                    v_offset + index + 1,
+                    textures.as_ref().map_or(0, |_| vt_offset + index + 1),
+                    normals.as_ref().map_or(0, |_| vn_offset + index + 1),
+                )
+            })
+            .collect();
+
+        let mut face =
+            Face::partial_new(references, material.map(|material| material.name.clone()));
+        face.set_material(material.cloned());
+        obj.faces.push(face);
+    }
+
+    Ok(())
+}
+
+fn parse_node(value: &Value, meshes: usize, nodes: usize) -> Result<Node, String> {
+    let children = match value.get("children") {
+        None => Vec::new(),
+        Some(children) => children
+            .as_array()
+            .and_then(|children| {
+                children
+                    .iter()
+                    .map(|child| child.as_usize().filter(|child| *child < nodes))
+                    .collect::<Option<Vec<usize>>>()
+            })
+            .ok_or("'children' is not a list of valid indices")?,
+    };
+
+    let transform = match value.get("matrix") {
+        Some(_) => {
+            let m = f32s_or(value, "matrix", [0.0; 16])?;
+            // the matrix is stored column by column
+            Transform::Matrix(math::Mat4 {
+                c0: math::Vec4::new(m[0], m[1], m[2], m[3]),
+                c1: math::Vec4::new(m[4], m[5], m[6], m[7]),
+                c2: math::Vec4::new(m[8], m[9], m[10], m[11]),
+                c3: math::Vec4::new(m[12], m[13], m[14], m[15]),
+            })
+        }
+        None => {
+            let [tx, ty, tz] = f32s_or(value, "translation", [0.0; 3])?;
+            let [x, y, z, w] = f32s_or(value, "rotation", [0.0, 0.0, 0.0, 1.0])?;
+            let [sx, sy, sz] = f32s_or(value, "scale", [1.0; 3])?;
+            let rotation = math::Quaternion::new(x, y, z, w);
+
+            Transform::Decomposed {
+                translation: math::Vec3::new(tx, ty, tz),
+                // exporters write rotations that are only nearly normalized
+                rotation: match (x, y, z, w) {
+                    (0.0, 0.0, 0.0, 0.0) => math::Quaternion::default(),
+                    _ => rotation.normalize(),
+                },
+                scale: math::Vec3::new(sx, sy, sz),
+            }
+        }
+    };
+
+    Ok(Node {
+        name: value
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        mesh: index(value, "mesh", meshes)?,
+        children,
+        transform,
+    })
+}
+
+///
+/// Checks that the nodes form trees, returns the parent of every node
+///
+fn check_hierarchy(nodes: &[Node]) -> Result<Vec<Option<usize>>, ParseError> {
+    let mut parents = vec![None; nodes.len()];
+
+    for (index, node) in nodes.iter().enumerate() {
+        for child in &node.children {
+            if parents[*child].is_some() {
+                return Err(ParseError::InvalidNode(
+                    *child,
+                    "The node has more than one parent".to_string(),
+                ));
+            }
+            parents[*child] = Some(index);
+        }
+    }
+
+    for index in 0..nodes.len() {
+        let mut ancestor = parents[index];
+        // a chain longer than the amount of nodes loops
+        for _ in 0..nodes.len() {
+            match ancestor {
+                Some(parent) if parent == index => {
+                    return Err(ParseError::InvalidNode(
+                        index,
+                        "The node is its own ancestor".to_string(),
+                    ))
+                }
+                Some(parent) => ancestor = parents[parent],
+                None => break,
+            }
+        }
+    }
+
+    Ok(parents)
+}
+
+fn parse_roots(scene: &Value, parents: &[Option<usize>]) -> Result<Vec<usize>, String> {
+    let mut roots = Vec::new();
+
+    for root in array(scene, "nodes") {
+        match root.as_usize() {
+            Some(root) if root < parents.len() && parents[root].is_none() => roots.push(root),
+            _ => return Err("'nodes' should only hold nodes without parent".to_string()),
+        }
+    }
+
+    Ok(roots)
+}
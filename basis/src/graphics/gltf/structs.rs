@@ -0,0 +1,394 @@
+use std::path::PathBuf;
+
+use crate::{
+    graphics::wavefront::{
+        self,
+        obj::{Group, Object, VertexDataReference, VerticeNormal, OBJ},
+    },
+    json,
+    math::{self, VectorFunctions},
+};
+
+#[derive(Debug)]
+pub enum ParseError {
+    InvalidGlb(String),
+    Json(json::ParseError),
+    UnsupportedVersion(String),
+    UnsupportedExtension(String),
+    InvalidBuffer(usize, String),
+    InvalidBufferView(usize, String),
+    InvalidAccessor(usize, String),
+    InvalidMaterial(usize, String),
+    InvalidMesh(usize, String),
+    InvalidNode(usize, String),
+    InvalidScene(usize, String),
+    MissingFaces,
+}
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidGlb(message) => write!(f, "Invalid GLB container: {}", message),
+            ParseError::Json(err) => write!(f, "{}", err),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported glTF version '{}'", version)
+            }
+            ParseError::UnsupportedExtension(extension) => {
+                write!(f, "Unsupported required extension '{}'", extension)
+            }
+            ParseError::InvalidBuffer(index, message) => {
+                write!(f, "Invalid buffer {}: {}", index, message)
+            }
+            ParseError::InvalidBufferView(index, message) => {
+                write!(f, "Invalid buffer view {}: {}", index, message)
+            }
+            ParseError::InvalidAccessor(index, message) => {
+                write!(f, "Invalid accessor {}: {}", index, message)
+            }
+            ParseError::InvalidMaterial(index, message) => {
+                write!(f, "Invalid material {}: {}", index, message)
+            }
+            ParseError::InvalidMesh(index, message) => {
+                write!(f, "Invalid mesh {}: {}", index, message)
+            }
+            ParseError::InvalidNode(index, message) => {
+                write!(f, "Invalid node {}: {}", index, message)
+            }
+            ParseError::InvalidScene(index, message) => {
+                write!(f, "Invalid scene {}: {}", index, message)
+            }
+            ParseError::MissingFaces => write!(f, "Missing faces, the scene shows no triangle"),
+        }
+    }
+}
+
+impl From<json::ParseError> for ParseError {
+    fn from(err: json::ParseError) -> Self {
+        ParseError::Json(err)
+    }
+}
+
+///
+/// Local transform of a node, given either as a matrix or as a translation, rotation and scale
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Matrix(math::Mat4),
+    Decomposed {
+        translation: math::Vec3,
+        rotation: math::Quaternion,
+        scale: math::Vec3,
+    },
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::Decomposed {
+            translation: math::Vec3::splat(0.0),
+            rotation: math::Quaternion::default(),
+            scale: math::Vec3::splat(1.0),
+        }
+    }
+}
+
+impl Transform {
+    ///
+    /// Matrix of the transform, the scale is applied first, then the rotation and the translation
+    ///
+    pub fn matrix(&self) -> math::Mat4 {
+        match self {
+            Transform::Matrix(matrix) => *matrix,
+            Transform::Decomposed {
+                translation,
+                rotation,
+                scale,
+            } => {
+                let mut matrix = math::Mat4::identity();
+                matrix.scale(*scale);
+                matrix.rotate(*rotation);
+                matrix.translate(*translation);
+                matrix
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Node {
+    pub name: Option<String>,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+    pub transform: Transform,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    ///
+    /// Fully transparent below `Material::alpha_cutoff`, fully opaque above
+    ///
+    Mask,
+    Blend,
+}
+
+///
+/// Metallic-roughness material
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub base_color_factor: math::Vec4,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: math::Vec3,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+    ///
+    /// Image of the base color texture, only when it is an external file
+    ///
+    pub base_color_texture: Option<PathBuf>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color_factor: math::Vec4::splat(1.0),
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: math::Vec3::splat(0.0),
+            alpha_mode: AlphaMode::default(),
+            alpha_cutoff: 0.5,
+            double_sided: false,
+            base_color_texture: None,
+        }
+    }
+}
+
+impl Material {
+    ///
+    /// Closest Wavefront material, so the faces can be drawn like the ones of an OBJ file.
+    ///
+    /// The base color becomes the diffuse color, metals reflect it as their specular color and
+    /// the roughness drives the specular exponent. The metallic-roughness values are also kept in
+    /// the PBR statements of the material.
+    ///
+    pub fn to_wavefront(&self) -> wavefront::mtl::Material {
+        let base = self.base_color_factor;
+        let specular = 0.04 + (1.0 - 0.04) * self.metallic_factor;
+
+        wavefront::mtl::Material {
+            name: self.name.clone(),
+            ambient_reflectivity: wavefront::mtl::Color::RGB(wavefront::mtl::RGB {
+                r: self.emissive_factor.x,
+                g: self.emissive_factor.y,
+                b: self.emissive_factor.z,
+            }),
+            diffuse_reflectivity: wavefront::mtl::Color::RGB(wavefront::mtl::RGB {
+                r: base.x,
+                g: base.y,
+                b: base.z,
+            }),
+            atmosphere_reflectivity: wavefront::mtl::Color::RGB(wavefront::mtl::RGB {
+                r: specular * base.x.max(1.0 - self.metallic_factor),
+                g: specular * base.y.max(1.0 - self.metallic_factor),
+                b: specular * base.z.max(1.0 - self.metallic_factor),
+            }),
+            specular_highlight_exponent: (1.0 - self.roughness_factor).powi(2) * 1000.0,
+            dissolve_factor: wavefront::mtl::DissolveFactor {
+                factor: match self.alpha_mode {
+                    AlphaMode::Opaque => 1.0,
+                    AlphaMode::Mask | AlphaMode::Blend => base.w,
+                },
+                halo: false,
+            },
+            illumination_model: wavefront::mtl::IlluminationModel::HighlightOn,
+            roughness: self.roughness_factor,
+            metallic: self.metallic_factor,
+            emissive: wavefront::mtl::Color::RGB(wavefront::mtl::RGB {
+                r: self.emissive_factor.x,
+                g: self.emissive_factor.y,
+                b: self.emissive_factor.z,
+            }),
+            diffuse_map: self
+                .base_color_texture
+                .as_ref()
+                .map(wavefront::mtl::TextureMap::new),
+            ..Default::default()
+        }
+    }
+}
+
+///
+/// Content of a glTF file.
+///
+/// Every mesh is an `OBJ` in its own space, with one face per triangle and the material of its
+/// primitive. The nodes place the meshes in the scene, see `Scene::to_obj`.
+///
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub nodes: Vec<Node>,
+    ///
+    /// Nodes of the displayed scene that have no parent
+    ///
+    pub roots: Vec<usize>,
+    pub meshes: Vec<OBJ>,
+    pub materials: Vec<Material>,
+    ///
+    /// Problems that did not prevent the file from loading
+    ///
+    pub warnings: Vec<String>,
+}
+
+impl Scene {
+    ///
+    /// Every node placed by the scene with its transform in world space, parents come before
+    /// their children
+    ///
+    pub fn world_transforms(&self) -> Vec<(usize, math::Mat4)> {
+        let mut transforms = Vec::with_capacity(self.nodes.len());
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|root| (*root, math::Mat4::identity()))
+            .collect::<Vec<_>>();
+
+        // the hierarchy is checked to be a tree while parsing
+        while let Some((index, parent)) = stack.pop() {
+            let node = &self.nodes[index];
+            let world = parent * node.transform.matrix();
+
+            transforms.push((index, world));
+            stack.extend(node.children.iter().rev().map(|child| (*child, world)));
+        }
+
+        transforms
+    }
+
+    ///
+    /// Merges every mesh placed by the scene into a single `OBJ` in world space, each node with a
+    /// mesh becomes an object named after it
+    ///
+    pub fn to_obj(&self) -> OBJ {
+        let mut obj = OBJ {
+            warnings: self.warnings.clone(),
+            ..Default::default()
+        };
+        let has_colors = self
+            .meshes
+            .iter()
+            .any(|mesh| !mesh.vertices_color.is_empty());
+
+        for (index, world) in self.world_transforms() {
+            let node = &self.nodes[index];
+            let Some(mesh) = node.mesh.map(|mesh| &self.meshes[mesh]) else {
+                continue;
+            };
+            let name = node
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("node_{}", index));
+
+            append_mesh(&mut obj, mesh, &world, &name, has_colors);
+        }
+
+        let mut library = wavefront::mtl::MTL::new();
+        for material in &self.materials {
+            library.insert(material.name.clone(), material.to_wavefront());
+        }
+        if !library.is_empty() {
+            obj.mtls.push(library);
+        }
+
+        let face_count = obj.faces.len();
+        for (id, face) in obj.faces.iter_mut().enumerate() {
+            face.id = id;
+            face.max_id = face_count;
+        }
+
+        obj
+    }
+}
+
+///
+/// Appends the vertices of `mesh` moved by `world`, and its faces as a new object
+///
+fn append_mesh(obj: &mut OBJ, mesh: &OBJ, world: &math::Mat4, name: &str, has_colors: bool) {
+    let v_offset = obj.vertices.len();
+    let vt_offset = obj.vertices_texture.len();
+    let vn_offset = obj.vertices_normal.len();
+    let normal_matrix = world.normal_matrix();
+
+    obj.vertices
+        .extend(mesh.vertices.iter().map(|vertex| *world * *vertex));
+    obj.vertices_texture
+        .extend(mesh.vertices_texture.iter().cloned());
+    obj.vertices_normal
+        .extend(mesh.vertices_normal.iter().map(|normal| {
+            let normal =
+                (normal_matrix * math::Vec3::new(normal.i, normal.j, normal.k)).normalize();
+            VerticeNormal::new(normal.x, normal.y, normal.z)
+        }));
+    // the colors are parallel to the vertices, meshes without any are white
+    if has_colors {
+        obj.vertices_color
+            .extend((0..mesh.vertices.len()).map(|index| {
+                *mesh
+                    .vertices_color
+                    .get(index)
+                    .unwrap_or(&math::Vec3::splat(1.0))
+            }));
+    }
+
+    let first_face = obj.faces.len();
+    for face in &mesh.faces {
+        let offset = |index: usize, offset: usize| if index == 0 { 0 } else { index + offset };
+        let mut face = face.clone();
+        face.vertex_references = face
+            .vertex_references
+            .iter()
+            .map(|reference| {
+                VertexDataReference::new(
+                    reference.v + v_offset,
+                    offset(reference.vt, vt_offset),
+                    offset(reference.vn, vn_offset),
+                )
+            })
+            .collect();
+        obj.faces.push(face);
+    }
+
+    let faces = first_face..obj.faces.len();
+    push_material_ranges(obj, faces.clone());
+
+    let mut object = Object::new(name);
+    let mut group = Group::new("default");
+    group.faces.push(faces.clone());
+    object.faces.push(faces);
+    object.groups.push(group);
+    obj.objects.push(object);
+}
+
+///
+/// Registers the faces in `OBJ::material_ranges`, like the `usemtl` statements do
+///
+pub(super) fn push_material_ranges(obj: &mut OBJ, faces: std::ops::Range<usize>) {
+    for index in faces {
+        let Some(name) = &obj.faces[index].material_name else {
+            continue;
+        };
+        match obj.material_ranges.last_mut() {
+            Some(range) if range.faces.end == index && &range.material_name == name => {
+                range.faces.end += 1;
+            }
+            _ => obj.material_ranges.push(wavefront::obj::MaterialRange {
+                material_name: name.clone(),
+                faces: index..index + 1,
+            }),
+        }
+    }
+}
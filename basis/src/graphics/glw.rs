@@ -110,6 +110,20 @@ impl BufferObject {
         }
     }
 
+    ///
+    /// Store data to the GPU
+    ///
+    pub fn store_u16(&self, data: &[u16]) {
+        unsafe {
+            gl::BufferData(
+                self.r#type,
+                mem::size_of_val(data) as gl::types::GLsizeiptr,
+                &data[0] as *const u16 as *const c_void,
+                self.usage,
+            )
+        }
+    }
+
     ///
     /// Store data to the GPU
     ///
@@ -577,6 +591,15 @@ pub fn polygon_mode(face: types::GLenum, mode: types::GLenum) {
     }
 }
 
+///
+/// Diameter in pixels of the points drawn with `gl::POINTS`
+///
+pub fn point_size(size: types::GLfloat) {
+    unsafe {
+        gl::PointSize(size);
+    }
+}
+
 /// * `pname`
 ///     - `gl::MAX_VERTEX_ATTRIBS`: The amount of input variables (Vertex Attributes) we can send
 ///     to a shader
@@ -588,6 +611,15 @@ pub fn get_integerv(pname: types::GLenum, data: *mut types::GLint) {
     }
 }
 
+///
+/// * `sfactor` How the color being drawn is scaled, e.g. `gl::SRC_ALPHA`
+/// * `dfactor` How the color already in the framebuffer is scaled, e.g. `gl::ONE_MINUS_SRC_ALPHA`
+pub fn blend_func(sfactor: types::GLenum, dfactor: types::GLenum) {
+    unsafe {
+        gl::BlendFunc(sfactor, dfactor);
+    }
+}
+
 pub fn enable(cap: types::GLenum) {
     unsafe {
         gl::Enable(cap);
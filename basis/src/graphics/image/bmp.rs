@@ -0,0 +1,451 @@
+use super::{buffer_size, scale_to_u8, DecodeError, Image};
+
+const FILE_HEADER_LENGTH: usize = 14;
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+///
+/// Most pixels a byte of run-length data can describe, a run of 255 pixels takes 2 bytes
+///
+const MAX_RLE_PIXELS_PER_BYTE: usize = 128;
+
+///
+/// Decodes BMP files with 1, 4, 8, 16, 24 or 32 bits per pixel, either uncompressed, with
+/// bit fields or run-length encoded (RLE4 and RLE8).
+///
+pub fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    if !bytes.starts_with(b"BM") {
+        return Err(DecodeError::UnsupportedFormat(
+            "Missing BMP signature".to_string(),
+        ));
+    }
+    let truncated = || DecodeError::UnexpectedEnd("BMP header is truncated".to_string());
+    let u16_at = |index: usize| -> Result<u16, DecodeError> {
+        let value = bytes.get(index..index + 2).ok_or_else(truncated)?;
+        Ok(u16::from_le_bytes([value[0], value[1]]))
+    };
+    let u32_at = |index: usize| -> Result<u32, DecodeError> {
+        let value = bytes.get(index..index + 4).ok_or_else(truncated)?;
+        Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+    };
+
+    let data_offset = u32_at(10)? as usize;
+    let header_length = u32_at(FILE_HEADER_LENGTH)? as usize;
+
+    let (width, height, bits_per_pixel, compression, colors_used) = match header_length {
+        // BITMAPCOREHEADER
+        12 => (
+            u16_at(18)? as i32,
+            u16_at(20)? as i16 as i32,
+            u16_at(24)?,
+            BI_RGB,
+            0,
+        ),
+        // BITMAPINFOHEADER and its later versions
+        40 | 52 | 56 | 64 | 108 | 124 => (
+            u32_at(18)? as i32,
+            u32_at(22)? as i32,
+            u16_at(28)?,
+            u32_at(30)?,
+            u32_at(46)? as usize,
+        ),
+        _ => {
+            return Err(DecodeError::UnsupportedFormat(format!(
+                "Unknown BMP header length {}",
+                header_length
+            )))
+        }
+    };
+
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(DecodeError::InvalidHeader(format!(
+            "Invalid dimensions {}x{}",
+            width, height
+        )));
+    }
+    // a negative height means the rows are stored from the top
+    let is_top_to_bottom = height < 0;
+    let width = width as u32;
+    let height = height.unsigned_abs();
+    let size = buffer_size(width, height)?;
+
+    let valid = match compression {
+        BI_RGB => matches!(bits_per_pixel, 1 | 4 | 8 | 16 | 24 | 32),
+        BI_RLE8 => bits_per_pixel == 8,
+        BI_RLE4 => bits_per_pixel == 4,
+        BI_BITFIELDS | BI_ALPHABITFIELDS => matches!(bits_per_pixel, 16 | 32),
+        _ => false,
+    };
+    if !valid {
+        return Err(DecodeError::UnsupportedFormat(format!(
+            "BMP compression {} with {} bits per pixel is not supported",
+            compression, bits_per_pixel
+        )));
+    }
+
+    // masks are part of the header since version 2, they follow the version 1 header otherwise
+    let masks_offset = FILE_HEADER_LENGTH + 40;
+    let mut masks_length = 0;
+    let masks = match compression {
+        BI_BITFIELDS | BI_ALPHABITFIELDS => {
+            let has_alpha = compression == BI_ALPHABITFIELDS || header_length >= 56;
+            if header_length == 40 {
+                masks_length = if compression == BI_ALPHABITFIELDS {
+                    16
+                } else {
+                    12
+                };
+            }
+            [
+                u32_at(masks_offset)?,
+                u32_at(masks_offset + 4)?,
+                u32_at(masks_offset + 8)?,
+                if has_alpha {
+                    u32_at(masks_offset + 12)?
+                } else {
+                    0
+                },
+            ]
+        }
+        // without bit fields the unused bits are not an alpha channel
+        _ if bits_per_pixel == 16 => [0x7c00, 0x03e0, 0x001f, 0],
+        _ => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+    };
+
+    let mut palette = Vec::new();
+    if bits_per_pixel <= 8 {
+        let entry_length = if header_length == 12 { 3 } else { 4 };
+        let entries = if colors_used == 0 {
+            1 << bits_per_pixel
+        } else {
+            colors_used.min(1 << bits_per_pixel)
+        };
+        let start = FILE_HEADER_LENGTH + header_length + masks_length;
+        let bytes = bytes
+            .get(start..start + entries * entry_length)
+            .ok_or_else(|| DecodeError::UnexpectedEnd("BMP palette is truncated".to_string()))?;
+        for entry in bytes.chunks(entry_length) {
+            palette.push([entry[2], entry[1], entry[0], 255]);
+        }
+    }
+
+    let pixels = bytes
+        .get(data_offset..)
+        .ok_or_else(|| DecodeError::UnexpectedEnd("BMP image data is missing".to_string()))?;
+
+    // rows from the bottom of the image, unless stored from the top
+    let rows = match compression {
+        BI_RLE8 | BI_RLE4 => decode_rle(pixels, width, height, compression == BI_RLE4, &palette)?,
+        _ => {
+            let row_length = (width as usize * bits_per_pixel as usize).div_ceil(32) * 4;
+            if pixels.len() < row_length * height as usize {
+                return Err(DecodeError::UnexpectedEnd(
+                    "BMP image data is truncated".to_string(),
+                ));
+            }
+            let mut rows = Vec::with_capacity(size);
+            for row in pixels.chunks(row_length).take(height as usize) {
+                for x in 0..width as usize {
+                    let color = match bits_per_pixel {
+                        1 | 4 | 8 => {
+                            let bits = bits_per_pixel as usize;
+                            let bit = x * bits;
+                            let index = (row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1);
+                            palette_color(&palette, index as usize)?
+                        }
+                        24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                        16 => {
+                            let value = u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]);
+                            masked_color(value as u32, &masks)
+                        }
+                        _ => {
+                            let value = &row[x * 4..x * 4 + 4];
+                            let value =
+                                u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+                            masked_color(value, &masks)
+                        }
+                    };
+                    rows.extend_from_slice(&color);
+                }
+            }
+            rows
+        }
+    };
+
+    if is_top_to_bottom {
+        return Ok(Image::new(width, height, rows));
+    }
+
+    let row_length = width as usize * 4;
+    let mut data = Vec::with_capacity(size);
+    for row in rows.chunks(row_length).rev() {
+        data.extend_from_slice(row);
+    }
+
+    Ok(Image::new(width, height, data))
+}
+
+fn palette_color(palette: &[[u8; 4]], index: usize) -> Result<[u8; 4], DecodeError> {
+    palette
+        .get(index)
+        .copied()
+        .ok_or_else(|| DecodeError::InvalidData(format!("Palette index {} is out of range", index)))
+}
+
+///
+/// Extracts each channel of `value` with its mask, a channel without mask is opaque for alpha
+///
+fn masked_color(value: u32, masks: &[u32; 4]) -> [u8; 4] {
+    let mut color = [0, 0, 0, 255];
+
+    for (channel, mask) in masks.iter().enumerate() {
+        if *mask == 0 {
+            continue;
+        }
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+        color[channel] = scale_to_u8((value & mask) >> shift, bits);
+    }
+
+    color
+}
+
+///
+/// Decodes RLE4 and RLE8 data into rows of RGBA pixels going from the bottom of the image.
+/// Pixels skipped by delta escapes are left transparent, but the data has to be long enough to
+/// cover the image with runs.
+///
+fn decode_rle(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    is_rle4: bool,
+    palette: &[[u8; 4]],
+) -> Result<Vec<u8>, DecodeError> {
+    let (width, height) = (width as usize, height as usize);
+    // the pixels are written in any order so the whole image is allocated up front, only when
+    // the data is long enough to fill it with runs
+    if (width * height).div_ceil(MAX_RLE_PIXELS_PER_BYTE) > bytes.len() {
+        return Err(DecodeError::UnexpectedEnd(format!(
+            "BMP run-length data is too short for a {}x{} image",
+            width, height
+        )));
+    }
+    let mut data = vec![0; width * height * 4];
+    let (mut x, mut y) = (0, 0);
+    let mut position = 0;
+    let truncated = || DecodeError::UnexpectedEnd("BMP run-length data is truncated".to_string());
+
+    let mut put = |x: usize, y: usize, index: u8| -> Result<(), DecodeError> {
+        if x < width && y < height {
+            let offset = (y * width + x) * 4;
+            data[offset..offset + 4].copy_from_slice(&palette_color(palette, index as usize)?);
+        }
+        Ok(())
+    };
+
+    while y < height {
+        let pair = bytes.get(position..position + 2).ok_or_else(truncated)?;
+        position += 2;
+
+        match (pair[0], pair[1]) {
+            // encoded run, the second byte holds the index (or two indices for RLE4)
+            (count @ 1..=255, value) => {
+                for i in 0..count as usize {
+                    let index = match is_rle4 {
+                        true if i % 2 == 0 => value >> 4,
+                        true => value & 0x0f,
+                        false => value,
+                    };
+                    put(x, y, index)?;
+                    x += 1;
+                }
+            }
+            // end of line
+            (0, 0) => {
+                x = 0;
+                y += 1;
+            }
+            // end of bitmap
+            (0, 1) => break,
+            // delta
+            (0, 2) => {
+                let delta = bytes.get(position..position + 2).ok_or_else(truncated)?;
+                position += 2;
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+            }
+            // absolute run, padded to 16 bits
+            (0, count) => {
+                let count = count as usize;
+                let length = if is_rle4 { count.div_ceil(2) } else { count };
+                let values = bytes
+                    .get(position..position + length)
+                    .ok_or_else(truncated)?;
+                position += length.div_ceil(2) * 2;
+                for i in 0..count {
+                    let index = match is_rle4 {
+                        true if i % 2 == 0 => values[i / 2] >> 4,
+                        true => values[i / 2] & 0x0f,
+                        false => values[i],
+                    };
+                    put(x, y, index)?;
+                    x += 1;
+                }
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bmp(
+        width: i32,
+        height: i32,
+        bits: u16,
+        compression: u32,
+        extra: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let offset = (FILE_HEADER_LENGTH + 40 + extra.len()) as u32;
+        let mut file = b"BM".to_vec();
+        file.extend_from_slice(&(offset + pixels.len() as u32).to_le_bytes());
+        file.extend_from_slice(&[0, 0, 0, 0]);
+        file.extend_from_slice(&offset.to_le_bytes());
+        file.extend_from_slice(&40u32.to_le_bytes());
+        file.extend_from_slice(&width.to_le_bytes());
+        file.extend_from_slice(&height.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&bits.to_le_bytes());
+        file.extend_from_slice(&compression.to_le_bytes());
+        file.extend_from_slice(&[0; 20]);
+        file.extend_from_slice(extra);
+        file.extend_from_slice(pixels);
+        file
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_24_bits_with_row_padding() {
+        let file = bmp(1, 2, 24, BI_RGB, &[], &[
+            // bottom row
+            255, 0, 0,    0,
+            // top row
+            0, 0, 255,    0,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.width, 1);
+        assert_eq!(image.height, 2);
+        assert_eq!(image.data, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_top_to_bottom_bit_fields() {
+        let masks = [
+            0xff00_0000u32.to_le_bytes(),
+            0x00ff_0000u32.to_le_bytes(),
+            0x0000_ff00u32.to_le_bytes(),
+            0x0000_00ffu32.to_le_bytes(),
+        ].concat();
+        let file = bmp(2, -1, 32, BI_ALPHABITFIELDS, &masks, &[
+            4, 3, 2, 1,    8, 7, 6, 5,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_16_bits() {
+        // R=31 G=0 B=0 using the default 5-5-5 masks
+        let file = bmp(1, 1, 16, BI_RGB, &[], &[0x00, 0x7c, 0, 0]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_palettes() {
+        let mut file = bmp(3, 1, 4, BI_RGB, &[
+            0, 0, 255, 0,
+            0, 255, 0, 0,
+        ], &[0x01, 0x00, 0, 0]);
+        file[46..50].copy_from_slice(&2u32.to_le_bytes());
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![
+            255, 0, 0, 255,
+            0, 255, 0, 255,
+            255, 0, 0, 255,
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_run_length_encoding() {
+        let palette = [
+            0, 0, 0, 0,
+            255, 255, 255, 0,
+        ];
+        let mut rle8 = bmp(4, 2, 8, BI_RLE8, &palette, &[
+            // bottom row: 3 white pixels then black ones from an absolute run
+            3, 1,    0, 3, 0, 0, 0, 0,    0, 0,
+            // top row: skip 2 pixels, then one white
+            0, 2, 2, 0,    1, 1,
+            0, 1,
+        ]);
+        rle8[46..50].copy_from_slice(&2u32.to_le_bytes());
+        let mut rle4 = bmp(4, 1, 4, BI_RLE4, &palette, &[
+            4, 0x10,    0, 1,
+        ]);
+        rle4[46..50].copy_from_slice(&2u32.to_le_bytes());
+
+        let rle8 = decode(&rle8).expect("This should work");
+        let rle4 = decode(&rle4).expect("This should work");
+
+        assert_eq!(rle8.data, vec![
+            0, 0, 0, 0,            0, 0, 0, 0,            255, 255, 255, 255,    0, 0, 0, 0,
+            255, 255, 255, 255,    255, 255, 255, 255,    255, 255, 255, 255,    0, 0, 0, 255,
+        ]);
+        assert_eq!(rle4.data, vec![
+            255, 255, 255, 255,    0, 0, 0, 255,    255, 255, 255, 255,    0, 0, 0, 255,
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_decode_invalid_files() {
+        let cases = [
+            (b"BM\0\0".to_vec(), "BMP header is truncated"),
+            (bmp(2, 2, 24, BI_RGB, &[], &[0; 8]), "BMP image data is truncated"),
+            (bmp(1, 1, 24, 4, &[], &[0; 4]), "BMP compression 4 with 24 bits per pixel is not supported"),
+            (bmp(0, 1, 24, BI_RGB, &[], &[]), "Invalid dimensions 0x1"),
+            (bmp(1, 1, 8, BI_RGB, &[], &[0; 4]), "BMP palette is truncated"),
+            (bmp(2, 1, 8, BI_RLE8, &[0; 1024], &[2, 0]), "BMP run-length data is truncated"),
+            (bmp(60000, 60000, 8, BI_RLE8, &[0; 1024], &[0, 1]), "BMP run-length data is too short for a 60000x60000 image"),
+        ];
+
+        for (file, message) in cases {
+            let result = decode(&file);
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(message), "{}", message);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//!
+//! DEFLATE (RFC 1951) decompression and its zlib (RFC 1950) wrapper
+//!
+
+use super::DecodeError;
+
+const MAX_CODE_LENGTH: usize = 15;
+
+///
+/// Most bytes DEFLATE can produce from one byte of input, 258 bytes from a length and a distance
+/// code that can take 2 bits
+///
+const MAX_COMPRESSION_RATIO: usize = 1032;
+
+/// Base lengths of the length symbols 257 to 285
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances of the distance symbols 0 to 29
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the code length code lengths are stored in dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn truncated() -> DecodeError {
+    DecodeError::UnexpectedEnd("Compressed data is truncated".to_string())
+}
+
+///
+/// Reads bits starting from the least significant bit of each byte
+///
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, DecodeError> {
+        while self.count < n {
+            let byte = *self.bytes.get(self.position).ok_or_else(truncated)?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+
+        Ok(value)
+    }
+
+    ///
+    /// Drops the remaining bits of the current byte
+    ///
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+///
+/// Canonical Huffman code, `counts` holds how many codes there are of each length and
+/// `symbols` the symbols ordered by code
+///
+struct Huffman {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, DecodeError> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+
+        // codes can be incomplete but not over-subscribed
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(DecodeError::InvalidData(
+                    "Over-subscribed Huffman code".to_string(),
+                ));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 1];
+        for length in 1..MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, DecodeError> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for count in self.counts.iter().skip(1) {
+            code |= reader.bits(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(DecodeError::InvalidData("Invalid Huffman code".to_string()))
+    }
+}
+
+///
+/// Decompresses zlib data, checking its header and Adler-32 checksum
+///
+pub fn decompress(bytes: &[u8], size_hint: usize) -> Result<Vec<u8>, DecodeError> {
+    let [cmf, flg, ..] = *bytes else {
+        return Err(truncated());
+    };
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return Err(DecodeError::InvalidData(
+            "Unsupported zlib compression method".to_string(),
+        ));
+    }
+    if !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(DecodeError::InvalidData(
+            "Invalid zlib header check".to_string(),
+        ));
+    }
+    if flg & 0x20 != 0 {
+        return Err(DecodeError::InvalidData(
+            "zlib preset dictionaries are not supported".to_string(),
+        ));
+    }
+
+    let (data, read) = inflate(&bytes[2..], size_hint)?;
+
+    let checksum = bytes.get(2 + read..2 + read + 4).ok_or_else(truncated)?;
+    let checksum = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    if checksum != adler32(&data) {
+        return Err(DecodeError::InvalidData(
+            "zlib checksum mismatch".to_string(),
+        ));
+    }
+
+    Ok(data)
+}
+
+///
+/// Decompresses raw DEFLATE data, returns the data and how many bytes were read.
+///
+/// * `size_hint` - Expected size of the data, the memory reserved for it is bounded by what the
+///   input can hold so a wrong hint doesn't allocate more than needed
+///
+pub fn inflate(bytes: &[u8], size_hint: usize) -> Result<(Vec<u8>, usize), DecodeError> {
+    let mut reader = BitReader::new(bytes);
+    let mut output =
+        Vec::with_capacity(size_hint.min(bytes.len().saturating_mul(MAX_COMPRESSION_RATIO)));
+
+    loop {
+        let is_final = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                compressed_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                compressed_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => {
+                return Err(DecodeError::InvalidData(
+                    "Invalid deflate block type".to_string(),
+                ))
+            }
+        }
+
+        if is_final {
+            break;
+        }
+    }
+
+    Ok((output, reader.position))
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), DecodeError> {
+    reader.align();
+    let header = reader
+        .bytes
+        .get(reader.position..reader.position + 4)
+        .ok_or_else(truncated)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err(DecodeError::InvalidData(
+            "Stored block length doesn't match its complement".to_string(),
+        ));
+    }
+    reader.position += 4;
+
+    let data = reader
+        .bytes
+        .get(reader.position..reader.position + length as usize)
+        .ok_or_else(truncated)?;
+    output.extend_from_slice(data);
+    reader.position += length as usize;
+
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), DecodeError> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), DecodeError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(DecodeError::InvalidData(
+            "Too many length or distance codes".to_string(),
+        ));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_lengths.decode(reader)?;
+        if symbol < 16 {
+            lengths[index] = symbol as u8;
+            index += 1;
+            continue;
+        }
+
+        let (value, repeat) = match symbol {
+            16 => {
+                if index == 0 {
+                    return Err(DecodeError::InvalidData(
+                        "Repeated code length without a previous length".to_string(),
+                    ));
+                }
+                (lengths[index - 1], 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err(DecodeError::InvalidData(
+                "Code lengths exceed the number of codes".to_string(),
+            ));
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(DecodeError::InvalidData(
+            "Missing end of block code".to_string(),
+        ));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn compressed_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), DecodeError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let symbol = symbol - 257;
+                let length = LENGTH_BASES[symbol] as usize
+                    + reader.bits(LENGTH_EXTRA_BITS[symbol] as u32)? as usize;
+
+                let symbol = distances.decode(reader)? as usize;
+                if symbol >= DISTANCE_BASES.len() {
+                    return Err(DecodeError::InvalidData(format!(
+                        "Invalid distance symbol {}",
+                        symbol
+                    )));
+                }
+                let distance = DISTANCE_BASES[symbol] as usize
+                    + reader.bits(DISTANCE_EXTRA_BITS[symbol] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(DecodeError::InvalidData(
+                        "Distance goes back before the start of the data".to_string(),
+                    ));
+                }
+
+                // the copy may overlap the bytes being written
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => {
+                return Err(DecodeError::InvalidData(format!(
+                    "Invalid length symbol {}",
+                    symbol
+                )))
+            }
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // 5552 is the largest block that can't overflow before the modulo
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MODULO;
+        b %= MODULO;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decompress_stored_blocks() {
+        // zlib.compress(b"hello", level=0)
+        let data = [
+            0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x06, 0x2c,
+            0x02, 0x15,
+        ];
+
+        assert_eq!(decompress(&data, 0).expect("This should work"), b"hello");
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decompress_fixed_huffman_blocks() {
+        // zlib.compress(b"abcabcabcabcabcabc")
+        let data = [
+            0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x4e, 0x44, 0x45, 0x00, 0x41, 0x7c, 0x06, 0xe5,
+        ];
+
+        assert_eq!(decompress(&data, 0).expect("This should work"), b"abcabcabcabcabcabc");
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decompress_dynamic_huffman_blocks() {
+        let text = "Sphinx of black quartz, judge my vow. Pack my box with five dozen liquor jugs. \
+                    How vexingly quick daft zebras jump! The five boxing wizards jump quickly.";
+        // zlib.compress(text, 9)
+        let data = [
+            0x78, 0xda, 0x25, 0x8c, 0x4b, 0x0e, 0x02, 0x21, 0x14, 0x04, 0xaf, 0xd2, 0xee, 0x0d, 0xe7, 0x70,
+            0x69, 0xa2, 0x17, 0x00, 0xf9, 0x2a, 0x03, 0x33, 0x0c, 0xff, 0xd3, 0xcf, 0x33, 0x2c, 0x3b, 0xd5,
+            0x55, 0xaf, 0xdd, 0xba, 0xd0, 0x11, 0x35, 0x84, 0xe7, 0x9f, 0x1f, 0x8e, 0xc2, 0x53, 0x9e, 0x77,
+            0x7c, 0x8b, 0x34, 0x0a, 0xdb, 0x40, 0x8d, 0x8d, 0xe1, 0xf9, 0x47, 0x34, 0x44, 0xec, 0x68, 0x2e,
+            0x5b, 0x68, 0x57, 0x15, 0x64, 0x9c, 0x2a, 0xc0, 0xbb, 0xa3, 0xc4, 0x44, 0x82, 0x39, 0x19, 0x1e,
+            0xb1, 0xa1, 0xaa, 0xee, 0x82, 0xf1, 0x83, 0x5a, 0x8e, 0x34, 0xc9, 0x75, 0xc6, 0x54, 0x22, 0xf1,
+            0x93, 0x4e, 0xdb, 0x7e, 0xc3, 0xdb, 0xaa, 0x15, 0xa0, 0x1c, 0x3d, 0xa9, 0x38, 0x79, 0x92, 0x8b,
+            0x2e, 0xc9, 0x0f, 0x76, 0x01, 0x97, 0x71, 0x37, 0x9c,
+        ];
+
+        assert_eq!(decompress(&data, 0).expect("This should work"), text.as_bytes());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_decompress_invalid_data() {
+        let valid = [
+            0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x4e, 0x44, 0x45, 0x00, 0x41, 0x7c, 0x06, 0xe5,
+        ];
+        let mut bad_checksum = valid;
+        bad_checksum[12] ^= 1;
+        let mut bad_header = valid;
+        bad_header[1] = 0x9d;
+
+        let cases: [(&[u8], &str); 6] = [
+            (&valid[..8], "Compressed data is truncated"),
+            (&valid[..11], "Compressed data is truncated"),
+            (&bad_checksum, "zlib checksum mismatch"),
+            (&bad_header, "Invalid zlib header check"),
+            (&[0x78, 0x01, 0x01, 0x05, 0x00, 0x00, 0x00], "Stored block length doesn't match its complement"),
+            (&[0x78, 0x01, 0x07], "Invalid deflate block type"),
+        ];
+
+        for (data, message) in cases {
+            let result = decompress(data, 0);
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(message), "{}", message);
+        }
+    }
+
+    #[test]
+    fn it_should_compute_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
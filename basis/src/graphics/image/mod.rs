@@ -0,0 +1,259 @@
+mod bmp;
+mod inflate;
+mod png;
+mod pnm;
+mod tga;
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    UnsupportedFormat(String),
+    InvalidHeader(String),
+    InvalidData(String),
+    UnexpectedEnd(String),
+}
+impl std::error::Error for DecodeError {}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::UnsupportedFormat(message) => {
+                write!(f, "Unsupported format: {}", message)
+            }
+            DecodeError::InvalidHeader(message) => write!(f, "Invalid header: {}", message),
+            DecodeError::InvalidData(message) => write!(f, "Invalid data: {}", message),
+            DecodeError::UnexpectedEnd(message) => {
+                write!(f, "Unexpected end of data: {}", message)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadImageError {
+    Io(std::io::Error),
+    Decode(DecodeError),
+}
+impl std::error::Error for LoadImageError {}
+
+impl From<std::io::Error> for LoadImageError {
+    fn from(err: std::io::Error) -> Self {
+        LoadImageError::Io(err)
+    }
+}
+
+impl From<DecodeError> for LoadImageError {
+    fn from(err: DecodeError) -> Self {
+        LoadImageError::Decode(err)
+    }
+}
+
+impl std::fmt::Display for LoadImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadImageError::Io(err) => write!(f, "IO error: {}", err),
+            LoadImageError::Decode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Netpbm formats, P1 to P6 (PBM, PGM and PPM)
+    Pnm,
+    Tga,
+    Bmp,
+    Png,
+}
+
+impl Format {
+    ///
+    /// Detects the format from the file signature. TGA files have no signature so they are
+    /// never detected this way.
+    ///
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(&png::SIGNATURE) {
+            return Some(Format::Png);
+        }
+        if bytes.starts_with(b"BM") {
+            return Some(Format::Bmp);
+        }
+        if let [b'P', b'1'..=b'6', ..] = bytes {
+            return Some(Format::Pnm);
+        }
+        None
+    }
+
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Format> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "pbm" | "pgm" | "ppm" | "pnm" => Some(Format::Pnm),
+            "tga" | "icb" | "vda" | "vst" => Some(Format::Tga),
+            "bmp" | "dib" => Some(Format::Bmp),
+            "png" => Some(Format::Png),
+            _ => None,
+        }
+    }
+}
+
+///
+/// A decoded image, `data` holds `width * height` RGBA8 pixels with the rows going from the top
+/// of the image to the bottom.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    ///
+    /// RGBA values of the pixel at column `x` and row `y`, counting from the top left corner
+    ///
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.data[index],
+            self.data[index + 1],
+            self.data[index + 2],
+            self.data[index + 3],
+        ]
+    }
+}
+
+///
+/// Decodes an image detecting its format from the file signature. Data without a known
+/// signature is decoded as TGA.
+///
+pub fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    decode_as(bytes, Format::from_magic(bytes).unwrap_or(Format::Tga))
+}
+
+pub fn decode_as(bytes: &[u8], format: Format) -> Result<Image, DecodeError> {
+    match format {
+        Format::Pnm => pnm::decode(bytes),
+        Format::Tga => tga::decode(bytes),
+        Format::Bmp => bmp::decode(bytes),
+        Format::Png => png::decode(bytes),
+    }
+}
+
+///
+/// Loads an image file, the format is detected from the file signature and then from the file
+/// extension.
+///
+pub fn load(file_path: impl AsRef<Path>) -> Result<Image, LoadImageError> {
+    let bytes = std::fs::read(&file_path)?;
+
+    let format = Format::from_magic(&bytes)
+        .or_else(|| Format::from_extension(&file_path))
+        .ok_or_else(|| {
+            DecodeError::UnsupportedFormat(format!(
+                "'{}' is not a PNM, TGA, BMP or PNG image",
+                file_path.as_ref().display()
+            ))
+        })?;
+
+    Ok(decode_as(&bytes, format)?)
+}
+
+///
+/// Checks the image dimensions and returns the size of its RGBA8 buffer
+///
+fn buffer_size(width: u32, height: u32) -> Result<usize, DecodeError> {
+    if width == 0 || height == 0 {
+        return Err(DecodeError::InvalidHeader(format!(
+            "Invalid dimensions {}x{}",
+            width, height
+        )));
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .filter(|size| *size <= isize::MAX as usize)
+        .ok_or_else(|| {
+            DecodeError::InvalidHeader(format!("Image is too large ({}x{})", width, height))
+        })
+}
+
+///
+/// Scales a `bits` wide value to the 0-255 range
+///
+fn scale_to_u8(value: u32, bits: u32) -> u8 {
+    match bits {
+        0 => 255,
+        8 => value as u8,
+        _ => {
+            let max = (1u64 << bits) - 1;
+            ((value as u64 * 255 + max / 2) / max) as u8
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_detect_formats() {
+        assert_eq!(Format::from_magic(&png::SIGNATURE), Some(Format::Png));
+        assert_eq!(Format::from_magic(b"BM\0\0"), Some(Format::Bmp));
+        assert_eq!(Format::from_magic(b"P6\n1 1\n255\n"), Some(Format::Pnm));
+        assert_eq!(Format::from_magic(b"P7\n"), None);
+        assert_eq!(Format::from_magic(&[0, 0, 2, 0]), None);
+
+        assert_eq!(Format::from_extension("a/texture.TGA"), Some(Format::Tga));
+        assert_eq!(Format::from_extension("texture.ppm"), Some(Format::Pnm));
+        assert_eq!(Format::from_extension("texture.jpg"), None);
+        assert_eq!(Format::from_extension("texture"), None);
+    }
+
+    #[test]
+    fn it_should_scale_values() {
+        assert_eq!(scale_to_u8(1, 1), 255);
+        assert_eq!(scale_to_u8(0, 1), 0);
+        assert_eq!(scale_to_u8(31, 5), 255);
+        assert_eq!(scale_to_u8(16, 5), 132);
+        assert_eq!(scale_to_u8(200, 8), 200);
+        assert_eq!(scale_to_u8(65535, 16), 255);
+    }
+
+    #[test]
+    fn it_should_load_by_extension() {
+        let dir = std::env::temp_dir().join(format!("basis_image_load_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // 1x1 uncompressed true-color TGA with a single red pixel
+        let tga = [
+            0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 24, 0, 0, 0, 255,
+        ];
+        std::fs::write(dir.join("red.tga"), tga).unwrap();
+        std::fs::write(dir.join("red.unknown"), tga).unwrap();
+
+        let image = load(dir.join("red.tga"));
+        let unknown = load(dir.join("red.unknown"));
+        let missing = load(dir.join("missing.tga"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            image.expect("This should work"),
+            Image::new(1, 1, vec![255, 0, 0, 255])
+        );
+        assert!(matches!(
+            unknown,
+            Err(LoadImageError::Decode(DecodeError::UnsupportedFormat(_)))
+        ));
+        assert!(matches!(missing, Err(LoadImageError::Io(_))));
+    }
+}
@@ -0,0 +1,582 @@
+use super::{buffer_size, inflate, scale_to_u8, DecodeError, Image};
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Starting column, starting row, column step and row step of each Adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    is_interlaced: bool,
+}
+
+///
+/// Decodes PNG files of every color type and bit depth, interlaced or not. Ancillary chunks
+/// other than the transparency (tRNS) are ignored.
+///
+pub fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    if !bytes.starts_with(&SIGNATURE) {
+        return Err(DecodeError::UnsupportedFormat(
+            "Missing PNG signature".to_string(),
+        ));
+    }
+
+    let mut position = SIGNATURE.len();
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparency = None;
+    let mut compressed = Vec::new();
+    let mut has_ended = false;
+
+    while !has_ended {
+        let length = bytes
+            .get(position..position + 4)
+            .ok_or_else(|| DecodeError::UnexpectedEnd("Missing IEND chunk".to_string()))?;
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        let chunk = bytes
+            .get(position + 4..position + 8 + length)
+            .ok_or_else(|| DecodeError::UnexpectedEnd("PNG chunk is truncated".to_string()))?;
+        let checksum = bytes
+            .get(position + 8 + length..position + 12 + length)
+            .ok_or_else(|| DecodeError::UnexpectedEnd("PNG chunk is truncated".to_string()))?;
+        position += 12 + length;
+
+        let (name, data) = chunk.split_at(4);
+        let name = String::from_utf8_lossy(name).to_string();
+        if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != crc32(chunk)
+        {
+            return Err(DecodeError::InvalidData(format!(
+                "Checksum mismatch in {} chunk",
+                name
+            )));
+        }
+
+        if header.is_none() && name != "IHDR" {
+            return Err(DecodeError::InvalidHeader(
+                "The first chunk should be IHDR".to_string(),
+            ));
+        }
+
+        match name.as_str() {
+            "IHDR" => header = Some(parse_header(data)?),
+            "PLTE" => {
+                if data.len() % 3 != 0 || data.len() > 256 * 3 {
+                    return Err(DecodeError::InvalidData(
+                        "Invalid PLTE chunk length".to_string(),
+                    ));
+                }
+                palette = data.chunks(3).map(|c| [c[0], c[1], c[2], 255]).collect();
+            }
+            "tRNS" => transparency = Some(data.to_vec()),
+            "IDAT" => compressed.extend_from_slice(data),
+            "IEND" => has_ended = true,
+            _ => {
+                // bit 5 of the first letter tells if the chunk is ancillary
+                if name.as_bytes()[0] & 0x20 == 0 {
+                    return Err(DecodeError::UnsupportedFormat(format!(
+                        "Unknown critical chunk {}",
+                        name
+                    )));
+                }
+            }
+        }
+    }
+
+    let header =
+        header.ok_or_else(|| DecodeError::InvalidHeader("Missing IHDR chunk".to_string()))?;
+    let size = buffer_size(header.width, header.height)?;
+
+    if header.color_type == ColorType::Indexed {
+        if palette.is_empty() {
+            return Err(DecodeError::InvalidData("Missing PLTE chunk".to_string()));
+        }
+        if let Some(alphas) = &transparency {
+            for (color, alpha) in palette.iter_mut().zip(alphas) {
+                color[3] = *alpha;
+            }
+        }
+    }
+
+    let bits_per_pixel = header.color_type.channels() * header.bit_depth as usize;
+    let filtered_size = passes(&header)
+        .iter()
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .map(|(width, height)| ((width * bits_per_pixel).div_ceil(8) + 1) * height)
+        .sum();
+    let filtered = inflate::decompress(&compressed, filtered_size)?;
+    if filtered.len() < filtered_size {
+        return Err(DecodeError::UnexpectedEnd(
+            "PNG image data is truncated".to_string(),
+        ));
+    }
+
+    // samples of the transparent color, for grayscale and RGB images
+    let transparent_color = match (&transparency, header.color_type) {
+        (Some(data), ColorType::Grayscale | ColorType::Rgb) => Some(
+            data.chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+                .collect::<Vec<u32>>(),
+        ),
+        _ => None,
+    };
+
+    let mut data = vec![0; size];
+    let mut offset = 0;
+    for (pass, (pass_width, pass_height)) in passes(&header).into_iter().enumerate() {
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let row_length = (pass_width * bits_per_pixel).div_ceil(8);
+        let length = (row_length + 1) * pass_height;
+        let rows = unfilter(
+            &filtered[offset..offset + length],
+            row_length,
+            bits_per_pixel.div_ceil(8),
+        )?;
+        offset += length;
+
+        let (start_x, start_y, step_x, step_y) = if header.is_interlaced {
+            ADAM7[pass]
+        } else {
+            (0, 0, 1, 1)
+        };
+
+        for (row_index, row) in rows.chunks(row_length).enumerate() {
+            let y = start_y + row_index * step_y;
+            for column in 0..pass_width {
+                let x = start_x + column * step_x;
+                let color = pixel(row, column, &header, &palette, transparent_color.as_deref())?;
+                let index = (y * header.width as usize + x) * 4;
+                data[index..index + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    Ok(Image::new(header.width, header.height, data))
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, DecodeError> {
+    if data.len() != 13 {
+        return Err(DecodeError::InvalidHeader(
+            "Invalid IHDR chunk length".to_string(),
+        ));
+    }
+
+    let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let bit_depth = data[8];
+    let color_type = match data[9] {
+        0 => ColorType::Grayscale,
+        2 => ColorType::Rgb,
+        3 => ColorType::Indexed,
+        4 => ColorType::GrayscaleAlpha,
+        6 => ColorType::Rgba,
+        color_type => {
+            return Err(DecodeError::InvalidHeader(format!(
+                "Invalid color type {}",
+                color_type
+            )))
+        }
+    };
+
+    let valid_depth = match color_type {
+        ColorType::Grayscale => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+        ColorType::Indexed => matches!(bit_depth, 1 | 2 | 4 | 8),
+        _ => matches!(bit_depth, 8 | 16),
+    };
+    if !valid_depth {
+        return Err(DecodeError::InvalidHeader(format!(
+            "Invalid bit depth {} for color type {}",
+            bit_depth, data[9]
+        )));
+    }
+    if data[10] != 0 || data[11] != 0 {
+        return Err(DecodeError::UnsupportedFormat(
+            "Unknown compression or filter method".to_string(),
+        ));
+    }
+    if data[12] > 1 {
+        return Err(DecodeError::UnsupportedFormat(format!(
+            "Unknown interlace method {}",
+            data[12]
+        )));
+    }
+    buffer_size(width, height)?;
+
+    Ok(Header {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        is_interlaced: data[12] == 1,
+    })
+}
+
+///
+/// Width and height of each reduced image, a single one when not interlaced
+///
+fn passes(header: &Header) -> Vec<(usize, usize)> {
+    let (width, height) = (header.width as usize, header.height as usize);
+
+    if !header.is_interlaced {
+        return vec![(width, height)];
+    }
+
+    ADAM7
+        .iter()
+        .map(|(start_x, start_y, step_x, step_y)| {
+            (
+                (width + step_x - start_x - 1) / step_x,
+                (height + step_y - start_y - 1) / step_y,
+            )
+        })
+        .collect()
+}
+
+///
+/// Reverts the filter of each row, `bytes` holds rows prefixed by their filter type
+///
+fn unfilter(bytes: &[u8], row_length: usize, pixel_length: usize) -> Result<Vec<u8>, DecodeError> {
+    let height = bytes.len() / (row_length + 1);
+    let mut rows = vec![0u8; row_length * height];
+
+    for y in 0..height {
+        let filter = bytes[y * (row_length + 1)];
+        let source = &bytes[y * (row_length + 1) + 1..(y + 1) * (row_length + 1)];
+        let (previous, current) = rows.split_at_mut(y * row_length);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&previous[(y - 1) * row_length..])
+        };
+        let current = &mut current[..row_length];
+
+        for x in 0..row_length {
+            let left = if x >= pixel_length {
+                current[x - pixel_length]
+            } else {
+                0
+            };
+            let up = previous.map_or(0, |row| row[x]);
+            let up_left = match previous {
+                Some(row) if x >= pixel_length => row[x - pixel_length],
+                _ => 0,
+            };
+
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => {
+                    return Err(DecodeError::InvalidData(format!(
+                        "Unknown filter type {}",
+                        filter
+                    )))
+                }
+            };
+            current[x] = source[x].wrapping_add(predictor);
+        }
+    }
+
+    Ok(rows)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+///
+/// Converts the pixel at `column` of an unfiltered row to RGBA8
+///
+fn pixel(
+    row: &[u8],
+    column: usize,
+    header: &Header,
+    palette: &[[u8; 4]],
+    transparent_color: Option<&[u32]>,
+) -> Result<[u8; 4], DecodeError> {
+    let depth = header.bit_depth as usize;
+    let channels = header.color_type.channels();
+
+    // samples at their original depth
+    let mut samples = [0u32; 4];
+    for (channel, sample) in samples.iter_mut().take(channels).enumerate() {
+        let bit = (column * channels + channel) * depth;
+        *sample = match depth {
+            16 => u16::from_be_bytes([row[bit / 8], row[bit / 8 + 1]]) as u32,
+            8 => row[bit / 8] as u32,
+            _ => ((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1)) as u32,
+        };
+    }
+    let is_transparent = transparent_color
+        .is_some_and(|color| color.len() >= channels && color[..channels] == samples[..channels]);
+    let alpha = if is_transparent { 0 } else { 255 };
+    let scale = |sample: u32| scale_to_u8(sample, depth as u32);
+
+    Ok(match header.color_type {
+        ColorType::Indexed => *palette.get(samples[0] as usize).ok_or_else(|| {
+            DecodeError::InvalidData(format!("Palette index {} is out of range", samples[0]))
+        })?,
+        ColorType::Grayscale => {
+            let value = scale(samples[0]);
+            [value, value, value, alpha]
+        }
+        ColorType::GrayscaleAlpha => {
+            let value = scale(samples[0]);
+            [value, value, value, scale(samples[1])]
+        }
+        ColorType::Rgb => [
+            scale(samples[0]),
+            scale(samples[1]),
+            scale(samples[2]),
+            alpha,
+        ],
+        ColorType::Rgba => [
+            scale(samples[0]),
+            scale(samples[1]),
+            scale(samples[2]),
+            scale(samples[3]),
+        ],
+    })
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///
+    /// Builds a PNG from its chunks, the image data is stored without compression
+    ///
+    fn png(ihdr: &[u8], chunks: &[(&str, &[u8])], filtered: &[u8]) -> Vec<u8> {
+        let mut zlib = vec![0x78, 0x01, 0x01];
+        zlib.extend_from_slice(&(filtered.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(filtered.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(filtered);
+        zlib.extend_from_slice(&inflate_adler32(filtered).to_be_bytes());
+
+        let mut file = SIGNATURE.to_vec();
+        let mut push = |name: &str, data: &[u8]| {
+            let mut chunk = name.as_bytes().to_vec();
+            chunk.extend_from_slice(data);
+            file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            file.extend_from_slice(&chunk);
+            file.extend_from_slice(&crc32(&chunk).to_be_bytes());
+        };
+        push("IHDR", ihdr);
+        for (name, data) in chunks {
+            push(name, data);
+        }
+        push("IDAT", &zlib);
+        push("IEND", &[]);
+        file
+    }
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> Vec<u8> {
+        let mut data = width.to_be_bytes().to_vec();
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace]);
+        data
+    }
+
+    fn inflate_adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in data {
+            a = (a + *byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        (b << 16) | a
+    }
+
+    #[test]
+    fn it_should_compute_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_rgba() {
+        let file = png(&ihdr(2, 1, 8, 6, 0), &[], &[
+            0, 255, 0, 0, 255,    0, 0, 255, 128,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 1);
+        assert_eq!(image.data, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_revert_filters() {
+        // every row holds the same pixels: (10, 20, 30) and (40, 60, 80)
+        let file = png(&ihdr(2, 5, 8, 2, 0), &[], &[
+            // none
+            0, 10, 20, 30, 40, 60, 80,
+            // sub
+            1, 10, 20, 30, 30, 40, 50,
+            // up
+            2, 0, 0, 0, 0, 0, 0,
+            // average: the first pixel is predicted by half of the one above
+            3, 5, 10, 15, 15, 20, 25,
+            // paeth
+            4, 0, 0, 0, 0, 0, 0,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        for y in 0..5 {
+            assert_eq!(image.pixel(0, y), [10, 20, 30, 255], "row {}", y);
+            assert_eq!(image.pixel(1, y), [40, 60, 80, 255], "row {}", y);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_low_bit_depths_and_palettes() {
+        let gray = png(&ihdr(4, 1, 2, 0, 0), &[], &[0, 0b00_01_10_11]);
+        let indexed = png(&ihdr(3, 1, 1, 3, 0), &[
+            ("PLTE", &[255, 0, 0, 0, 0, 255]),
+            ("tRNS", &[128]),
+        ], &[0, 0b0100_0000]);
+
+        let gray = decode(&gray).expect("This should work");
+        let indexed = decode(&indexed).expect("This should work");
+
+        assert_eq!(gray.data, vec![
+            0, 0, 0, 255,    85, 85, 85, 255,    170, 170, 170, 255,    255, 255, 255, 255,
+        ]);
+        assert_eq!(indexed.data, vec![
+            255, 0, 0, 128,    0, 0, 255, 255,    255, 0, 0, 128,
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_16_bits_and_transparent_colors() {
+        let file = png(&ihdr(2, 1, 16, 2, 0), &[
+            ("tRNS", &[0, 0, 0, 0, 0xff, 0xff]),
+        ], &[
+            0, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc,    0, 0, 0, 0, 0xff, 0xff,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![0x12, 0x56, 0x9a, 255, 0, 0, 255, 0]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_interlaced_images() {
+        // 3x3 grayscale where each pixel is x + y * 3, passes 2 and 3 are empty
+        let file = png(&ihdr(3, 3, 8, 0, 1), &[], &[
+            // pass 1: (0, 0)
+            0, 0,
+            // pass 4: (2, 0)
+            0, 2,
+            // pass 5: (0, 2) and (2, 2)
+            0, 6, 8,
+            // pass 6: (1, 0) and (1, 2)
+            0, 1,
+            0, 7,
+            // pass 7: the whole row 1
+            0, 3, 4, 5,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        for y in 0..3 {
+            for x in 0..3 {
+                let value = (x + y * 3) as u8;
+                assert_eq!(image.pixel(x, y), [value, value, value, 255], "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_decode_invalid_files() {
+        let mut bad_crc = png(&ihdr(1, 1, 8, 0, 0), &[], &[0, 0]);
+        bad_crc[29] ^= 1;
+        let mut truncated = png(&ihdr(1, 1, 8, 0, 0), &[], &[0, 0]);
+        truncated.truncate(truncated.len() - 12);
+
+        let cases = [
+            (bad_crc, "Checksum mismatch in IHDR chunk"),
+            (truncated, "Missing IEND chunk"),
+            (png(&ihdr(1, 1, 8, 1, 0), &[], &[0, 0]), "Invalid color type 1"),
+            (png(&ihdr(1, 1, 4, 2, 0), &[], &[0, 0]), "Invalid bit depth 4 for color type 2"),
+            (png(&ihdr(1, 1, 8, 3, 0), &[], &[0, 0]), "Missing PLTE chunk"),
+            (png(&ihdr(1, 1, 8, 0, 0), &[], &[5, 0]), "Unknown filter type 5"),
+            (png(&ihdr(2, 1, 8, 0, 0), &[], &[0, 0]), "PNG image data is truncated"),
+            (png(&ihdr(1, 1, 8, 0, 0), &[("ABCD", &[])], &[0, 0]), "Unknown critical chunk ABCD"),
+            (png(&ihdr(0, 1, 8, 0, 0), &[], &[]), "Invalid dimensions 0x1"),
+            (png(&ihdr(60000, 60000, 16, 6, 0), &[], &[0, 0]), "PNG image data is truncated"),
+        ];
+
+        for (file, message) in cases {
+            let result = decode(&file);
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(message), "{}", message);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use super::{buffer_size, DecodeError, Image};
+
+///
+/// Decodes the Netpbm formats:
+///
+/// * P1 / P4: ASCII / binary bitmap (PBM), 1 is black
+/// * P2 / P5: ASCII / binary grayscale (PGM)
+/// * P3 / P6: ASCII / binary RGB (PPM)
+///
+pub fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    let kind = match reader.token()? {
+        b"P1" => 1,
+        b"P2" => 2,
+        b"P3" => 3,
+        b"P4" => 4,
+        b"P5" => 5,
+        b"P6" => 6,
+        magic => {
+            return Err(DecodeError::UnsupportedFormat(format!(
+                "Unknown Netpbm magic number '{}'",
+                String::from_utf8_lossy(magic)
+            )))
+        }
+    };
+    let is_bitmap = kind == 1 || kind == 4;
+    let is_binary = kind > 3;
+    let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+
+    let width = reader.header_value("width")?;
+    let height = reader.header_value("height")?;
+    let max_value = if is_bitmap {
+        1
+    } else {
+        reader.header_value("maximum value")?
+    };
+    if max_value == 0 || max_value > 65535 {
+        return Err(DecodeError::InvalidHeader(format!(
+            "Maximum value should range between 1 and 65535, got {}",
+            max_value
+        )));
+    }
+    let size = buffer_size(width, height)?;
+    // a pixel takes at least a byte of the raster, or a bit for binary bitmaps, so the header
+    // can't reserve more memory than the file holds
+    let raster_length = bytes.len().saturating_sub(reader.position);
+    let pixels_per_byte = if is_binary && is_bitmap { 8 } else { 1 };
+    let mut data = Vec::with_capacity(size.min(raster_length.saturating_mul(pixels_per_byte * 4)));
+
+    let scale = |value: u32| -> Result<u8, DecodeError> {
+        if value > max_value {
+            return Err(DecodeError::InvalidData(format!(
+                "Sample {} is greater than the maximum value {}",
+                value, max_value
+            )));
+        }
+        Ok(((value as u64 * 255 + max_value as u64 / 2) / max_value as u64) as u8)
+    };
+
+    if is_binary {
+        // a single whitespace separates the header from the raster
+        reader.position += 1;
+        let raster = &bytes[reader.position.min(bytes.len())..];
+
+        if is_bitmap {
+            let row_length = (width as usize).div_ceil(8);
+            if raster.len() < row_length * height as usize {
+                return Err(DecodeError::UnexpectedEnd(
+                    "PBM raster is truncated".to_string(),
+                ));
+            }
+            for row in raster.chunks(row_length).take(height as usize) {
+                for x in 0..width as usize {
+                    let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+                    let value = if bit == 1 { 0 } else { 255 };
+                    data.extend_from_slice(&[value, value, value, 255]);
+                }
+            }
+        } else {
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            let pixel_size = sample_size * channels;
+            let length = width as usize * height as usize * pixel_size;
+            if raster.len() < length {
+                return Err(DecodeError::UnexpectedEnd(
+                    "Raster is truncated".to_string(),
+                ));
+            }
+            for pixel in raster[..length].chunks(pixel_size) {
+                let mut rgb = [0; 3];
+                for (channel, sample) in pixel.chunks(sample_size).enumerate() {
+                    let value = match sample {
+                        [high, low] => u16::from_be_bytes([*high, *low]) as u32,
+                        _ => sample[0] as u32,
+                    };
+                    rgb[channel] = scale(value)?;
+                }
+                if channels == 1 {
+                    rgb = [rgb[0]; 3];
+                }
+                data.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+            }
+        }
+    } else {
+        for _ in 0..width as usize * height as usize {
+            let mut rgb = [0; 3];
+            for value in rgb.iter_mut().take(channels) {
+                *value = if is_bitmap {
+                    // bitmap samples don't need to be separated by whitespace
+                    match reader.bit()? {
+                        1 => 0,
+                        _ => 255,
+                    }
+                } else {
+                    scale(reader.value("sample")?)?
+                };
+            }
+            if channels == 1 {
+                rgb = [rgb[0]; 3];
+            }
+            data.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+        }
+    }
+
+    Ok(Image::new(width, height, data))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    ///
+    /// Skips whitespace and comments, comments go from "#" to the end of the line
+    ///
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.bytes.get(self.position) {
+            match byte {
+                b'#' => {
+                    while !matches!(self.bytes.get(self.position), None | Some(b'\n' | b'\r')) {
+                        self.position += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a [u8], DecodeError> {
+        self.skip_whitespace();
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(byte) if !byte.is_ascii_whitespace() && *byte != b'#')
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(DecodeError::UnexpectedEnd(
+                "Missing Netpbm header".to_string(),
+            ));
+        }
+        Ok(&self.bytes[start..self.position])
+    }
+
+    fn value(&mut self, name: &str) -> Result<u32, DecodeError> {
+        self.skip_whitespace();
+        if self.position >= self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd(format!("Missing {}", name)));
+        }
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse::<u32>().ok())
+            .ok_or_else(|| {
+                DecodeError::InvalidData(format!(
+                    "Invalid {} '{}'",
+                    name,
+                    String::from_utf8_lossy(token)
+                ))
+            })
+    }
+
+    fn header_value(&mut self, name: &str) -> Result<u32, DecodeError> {
+        self.value(name).map_err(|err| match err {
+            DecodeError::InvalidData(message) => DecodeError::InvalidHeader(message),
+            err => err,
+        })
+    }
+
+    fn bit(&mut self) -> Result<u8, DecodeError> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(byte @ (b'0' | b'1')) => {
+                self.position += 1;
+                Ok(byte - b'0')
+            }
+            Some(byte) => Err(DecodeError::InvalidData(format!(
+                "Invalid bitmap sample '{}'",
+                *byte as char
+            ))),
+            None => Err(DecodeError::UnexpectedEnd("Missing sample".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_ascii_ppm() {
+        let file = b"P3
+            # a comment
+            2 1 # another comment
+            255
+            255 0 0   0 0 255
+";
+
+        let image = decode(file).expect("This should work");
+
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 1);
+        assert_eq!(image.data, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_binary_ppm() {
+        let mut file = b"P6\n1 2\n255\n".to_vec();
+        file.extend_from_slice(&[10, 20, 30, 40, 50, 60]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.width, 1);
+        assert_eq!(image.height, 2);
+        assert_eq!(image.data, vec![10, 20, 30, 255, 40, 50, 60, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_scale_to_the_maximum_value() {
+        let ascii = b"P2 2 1 15 0 15";
+        let mut binary = b"P5 2 1 65535\n".to_vec();
+        binary.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+
+        let ascii = decode(ascii).expect("This should work");
+        let binary = decode(&binary).expect("This should work");
+
+        assert_eq!(ascii.data, vec![0, 0, 0, 255, 255, 255, 255, 255]);
+        assert_eq!(binary.data, vec![0, 0, 0, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_bitmaps() {
+        let ascii = b"P1 3 2\n101\n010";
+        let mut binary = b"P4 3 2\n".to_vec();
+        binary.extend_from_slice(&[0b1010_0000, 0b0100_0000]);
+
+        let ascii = decode(ascii).expect("This should work");
+        let binary = decode(&binary).expect("This should work");
+
+        assert_eq!(ascii, binary);
+        assert_eq!(ascii.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(ascii.pixel(1, 0), [255, 255, 255, 255]);
+        assert_eq!(ascii.pixel(1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_decode_invalid_files() {
+        let cases: [(&[u8], &str); 8] = [
+            (b"P3 1 1 255 0 0", "Unexpected end of data: Missing sample"),
+            (b"P6 2 2 255\n\x00\x00\x00", "Unexpected end of data: Raster is truncated"),
+            (b"P3 a 1 255", "Invalid header: Invalid width 'a'"),
+            (b"P3 1 1 0", "Invalid header: Maximum value should range between 1 and 65535"),
+            (b"P3 1 1 10 11 0 0", "Invalid data: Sample 11 is greater than the maximum value 10"),
+            (b"P3 0 1 255", "Invalid header: Invalid dimensions 0x1"),
+            (b"P6 60000 60000 255\n", "Unexpected end of data: Raster is truncated"),
+            (b"P3 60000 60000 255\n", "Unexpected end of data: Missing sample"),
+        ];
+
+        for (file, message) in cases {
+            let result = decode(file);
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(message), "{}", message);
+        }
+    }
+}
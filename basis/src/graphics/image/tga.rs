@@ -0,0 +1,361 @@
+use super::{buffer_size, scale_to_u8, DecodeError, Image};
+
+const HEADER_LENGTH: usize = 18;
+
+///
+/// Decodes uncompressed and run-length encoded TGA files, true-color (15, 16, 24 and 32 bits),
+/// grayscale (8 bits) and color-mapped.
+///
+pub fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    if bytes.len() < HEADER_LENGTH {
+        return Err(DecodeError::UnexpectedEnd(
+            "TGA header is truncated".to_string(),
+        ));
+    }
+    let u16_at = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let color_map_start = u16_at(3) as usize;
+    let color_map_length = u16_at(5) as usize;
+    let color_map_depth = bytes[7];
+    let width = u16_at(12) as u32;
+    let height = u16_at(14) as u32;
+    let pixel_depth = bytes[16];
+    let descriptor = bytes[17];
+    let alpha_bits = descriptor & 0x0f;
+    let is_right_to_left = descriptor & 0x10 != 0;
+    let is_top_to_bottom = descriptor & 0x20 != 0;
+
+    let (is_color_mapped, is_grayscale, is_rle) = match image_type {
+        1 => (true, false, false),
+        2 => (false, false, false),
+        3 => (false, true, false),
+        9 => (true, false, true),
+        10 => (false, false, true),
+        11 => (false, true, true),
+        0 => {
+            return Err(DecodeError::UnsupportedFormat(
+                "TGA file has no image data".to_string(),
+            ))
+        }
+        _ => {
+            return Err(DecodeError::UnsupportedFormat(format!(
+                "Unknown TGA image type {}",
+                image_type
+            )))
+        }
+    };
+    if color_map_type > 1 || (is_color_mapped && color_map_type != 1) {
+        return Err(DecodeError::InvalidHeader(format!(
+            "Invalid TGA color map type {}",
+            color_map_type
+        )));
+    }
+    let size = buffer_size(width, height)?;
+
+    let valid_depth = match (is_color_mapped, is_grayscale) {
+        (true, _) => matches!(pixel_depth, 8 | 16),
+        (_, true) => matches!(pixel_depth, 8 | 16),
+        _ => matches!(pixel_depth, 15 | 16 | 24 | 32),
+    };
+    if !valid_depth {
+        return Err(DecodeError::UnsupportedFormat(format!(
+            "TGA pixel depth {} is not supported for image type {}",
+            pixel_depth, image_type
+        )));
+    }
+
+    let mut position = HEADER_LENGTH + id_length;
+
+    // the color map is present even when the image isn't color-mapped, and it's skipped then
+    let color_map_entry_length = (color_map_depth as usize).div_ceil(8);
+    let color_map_size = if color_map_type == 1 {
+        color_map_length * color_map_entry_length
+    } else {
+        0
+    };
+    let color_map_bytes = bytes
+        .get(position..position + color_map_size)
+        .ok_or_else(|| DecodeError::UnexpectedEnd("TGA color map is truncated".to_string()))?;
+    position += color_map_size;
+
+    let mut color_map = Vec::new();
+    if is_color_mapped {
+        if !matches!(color_map_depth, 15 | 16 | 24 | 32) {
+            return Err(DecodeError::UnsupportedFormat(format!(
+                "TGA color map depth {} is not supported",
+                color_map_depth
+            )));
+        }
+        for entry in color_map_bytes.chunks(color_map_entry_length) {
+            color_map.push(true_color(entry, color_map_depth, alpha_bits));
+        }
+    }
+
+    let pixel_length = (pixel_depth as usize).div_ceil(8);
+    let pixels = width as usize * height as usize;
+    let raw = if is_rle {
+        decode_rle(&bytes[position..], pixels, pixel_length)?
+    } else {
+        bytes
+            .get(position..position + pixels * pixel_length)
+            .ok_or_else(|| DecodeError::UnexpectedEnd("TGA image data is truncated".to_string()))?
+            .to_vec()
+    };
+
+    let mut colors = Vec::with_capacity(raw.len() / pixel_length);
+    for pixel in raw.chunks(pixel_length) {
+        let color = if is_color_mapped {
+            let index = match pixel {
+                [low, high] => u16::from_le_bytes([*low, *high]) as usize,
+                _ => pixel[0] as usize,
+            };
+            *index
+                .checked_sub(color_map_start)
+                .and_then(|index| color_map.get(index))
+                .ok_or_else(|| {
+                    DecodeError::InvalidData(format!("Color map index {} is out of range", index))
+                })?
+        } else if is_grayscale {
+            // the second byte of 16 bits grayscale is the alpha
+            let alpha = pixel.get(1).copied().unwrap_or(255);
+            [pixel[0], pixel[0], pixel[0], alpha]
+        } else {
+            true_color(pixel, pixel_depth, alpha_bits)
+        };
+        colors.push(color);
+    }
+
+    // TGA images are stored from the bottom left corner unless the descriptor says otherwise
+    let mut data = Vec::with_capacity(size.min(colors.len() * 4));
+    for y in 0..height as usize {
+        let row = if is_top_to_bottom {
+            y
+        } else {
+            height as usize - 1 - y
+        };
+        for x in 0..width as usize {
+            let column = if is_right_to_left {
+                width as usize - 1 - x
+            } else {
+                x
+            };
+            data.extend_from_slice(&colors[row * width as usize + column]);
+        }
+    }
+
+    Ok(Image::new(width, height, data))
+}
+
+///
+/// Converts a BGR(A) pixel, 15 and 16 bits pixels are packed as ARRRRRGG GGGBBBBB
+///
+fn true_color(pixel: &[u8], depth: u8, alpha_bits: u8) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]) as u32;
+            let alpha = if depth == 16 && alpha_bits > 0 && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [
+                scale_to_u8((value >> 10) & 0x1f, 5),
+                scale_to_u8((value >> 5) & 0x1f, 5),
+                scale_to_u8(value & 0x1f, 5),
+                alpha,
+            ]
+        }
+        24 => [pixel[2], pixel[1], pixel[0], 255],
+        _ => {
+            let alpha = if alpha_bits > 0 { pixel[3] } else { 255 };
+            [pixel[2], pixel[1], pixel[0], alpha]
+        }
+    }
+}
+
+///
+/// Most pixels of a run-length packet, its count is stored on 7 bits
+///
+const MAX_RLE_PACKET_PIXELS: usize = 128;
+
+///
+/// Expands the run-length packets, each packet starts with a byte whose high bit tells if the
+/// next pixel is repeated or if raw pixels follow, and whose low bits are the count minus one
+///
+fn decode_rle(bytes: &[u8], pixels: usize, pixel_length: usize) -> Result<Vec<u8>, DecodeError> {
+    // a packet describes at most 128 pixels, the image is only allocated when the data can fill
+    // it
+    let packets = pixels.div_ceil(MAX_RLE_PACKET_PIXELS);
+    if packets.saturating_mul(1 + pixel_length) > bytes.len() {
+        return Err(DecodeError::UnexpectedEnd(format!(
+            "TGA run-length data is too short for {} pixels",
+            pixels
+        )));
+    }
+    let mut raw = Vec::with_capacity(pixels * pixel_length);
+    let mut position = 0;
+    let truncated = || DecodeError::UnexpectedEnd("TGA run-length data is truncated".to_string());
+
+    while raw.len() < pixels * pixel_length {
+        let packet = *bytes.get(position).ok_or_else(truncated)?;
+        position += 1;
+        let count = (packet & 0x7f) as usize + 1;
+
+        if packet & 0x80 != 0 {
+            let pixel = bytes
+                .get(position..position + pixel_length)
+                .ok_or_else(truncated)?;
+            position += pixel_length;
+            for _ in 0..count {
+                raw.extend_from_slice(pixel);
+            }
+        } else {
+            let length = count * pixel_length;
+            let pixels = bytes
+                .get(position..position + length)
+                .ok_or_else(truncated)?;
+            position += length;
+            raw.extend_from_slice(pixels);
+        }
+    }
+
+    // packets may cross the end of the image
+    raw.truncate(pixels * pixel_length);
+
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8> {
+        let mut header = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&height.to_le_bytes());
+        header.extend_from_slice(&[depth, descriptor]);
+        header
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_uncompressed_true_color() {
+        let mut file = header(2, 2, 2, 24, 0);
+        file.extend_from_slice(&[
+            // bottom row
+            255, 0, 0,    0, 255, 0,
+            // top row
+            0, 0, 255,    255, 255, 255,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 2);
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(0, 1), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 1), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_alpha_and_origin() {
+        // top to bottom, right to left, 8 alpha bits
+        let mut file = header(2, 2, 1, 32, 0x38);
+        file.extend_from_slice(&[
+            1, 2, 3, 4,    5, 6, 7, 8,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![7, 6, 5, 8, 3, 2, 1, 4]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_run_length_encoding() {
+        let mut file = header(10, 4, 1, 24, 0x20);
+        file.extend_from_slice(&[
+            // 3 times the same pixel
+            0x82, 10, 20, 30,
+            // 1 raw pixel
+            0x00, 40, 50, 60,
+        ]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![
+            30, 20, 10, 255,
+            30, 20, 10, 255,
+            30, 20, 10, 255,
+            60, 50, 40, 255,
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_grayscale_and_16_bits() {
+        let mut gray = header(11, 2, 1, 8, 0x20);
+        gray.extend_from_slice(&[0x81, 128]);
+        let mut high_color = header(2, 1, 1, 16, 0x21);
+        // A=1 R=31 G=0 B=31
+        high_color.extend_from_slice(&0b1_11111_00000_11111u16.to_le_bytes());
+
+        let gray = decode(&gray).expect("This should work");
+        let high_color = decode(&high_color).expect("This should work");
+
+        assert_eq!(gray.data, vec![128, 128, 128, 255, 128, 128, 128, 255]);
+        assert_eq!(high_color.data, vec![255, 0, 255, 255]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_decode_color_maps() {
+        let mut file = header(1, 3, 1, 8, 0x20);
+        // color map of 2 entries of 24 bits, starting at index 1
+        file[1] = 1;
+        file[3..8].copy_from_slice(&[1, 0, 2, 0, 24]);
+        file.extend_from_slice(&[255, 0, 0,    0, 0, 255]);
+        file.extend_from_slice(&[1, 2, 1]);
+
+        let image = decode(&file).expect("This should work");
+
+        assert_eq!(image.data, vec![0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 255, 255]);
+
+        let last = file.len() - 1;
+        file[last] = 0;
+        assert!(decode(&file).unwrap_err().to_string().contains("Color map index 0 is out of range"));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_decode_invalid_files() {
+        let mut truncated = header(2, 2, 2, 24, 0);
+        truncated.extend_from_slice(&[0, 0, 0]);
+        let mut truncated_rle = header(10, 2, 2, 24, 0);
+        truncated_rle.extend_from_slice(&[0x03, 0, 0, 0, 0, 0, 0]);
+        let mut huge_rle = header(10, 65535, 65535, 24, 0);
+        huge_rle.extend_from_slice(&[0xff, 0, 0, 0]);
+
+        let cases = [
+            (vec![0, 0, 2], "TGA header is truncated"),
+            (truncated, "TGA image data is truncated"),
+            (truncated_rle, "TGA run-length data is truncated"),
+            (huge_rle, "TGA run-length data is too short for 4294836225 pixels"),
+            (header(2, 1, 1, 12, 0), "TGA pixel depth 12 is not supported"),
+            (header(4, 1, 1, 24, 0), "Unknown TGA image type 4"),
+            (header(2, 0, 1, 24, 0), "Invalid dimensions 0x1"),
+        ];
+
+        for (file, message) in cases {
+            let result = decode(&file);
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(message), "{}", message);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, hash::Hash};
+
+///
+/// Index buffer of an `IndexedMesh`, 16 bits indices are used whenever every vertex can be
+/// addressed with them since they take half the memory
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Index at `position` widened to `u32`
+    ///
+    pub fn get(&self, position: usize) -> Option<u32> {
+        match self {
+            Indices::U16(indices) => indices.get(position).map(|index| *index as u32),
+            Indices::U32(indices) => indices.get(position).copied(),
+        }
+    }
+
+    ///
+    /// Type of the indices as expected by `glDrawElements`
+    ///
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            Indices::U16(_) => gl::UNSIGNED_SHORT,
+            Indices::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+impl Default for Indices {
+    fn default() -> Self {
+        Indices::U16(Vec::new())
+    }
+}
+
+///
+/// Compact vertex buffer where every vertex is unique, and the indices of the vertices of each
+/// triangle
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexedMesh {
+    pub vertices: Vec<f32>,
+    pub indices: Indices,
+    ///
+    /// Amount of floats used by each vertex
+    ///
+    pub vertex_length: usize,
+}
+
+impl IndexedMesh {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices
+            .len()
+            .checked_div(self.vertex_length)
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Floats of the vertex at `index`
+    ///
+    pub fn vertex(&self, index: usize) -> &[f32] {
+        &self.vertices[index * self.vertex_length..(index + 1) * self.vertex_length]
+    }
+}
+
+///
+/// Builds an `IndexedMesh` by welding the vertices that share the same key.
+///
+/// The key identifies everything a vertex is made of, so two corners with the same key always
+/// produce the same floats and only the first one is stored.
+///
+/// ```
+/// use basis::graphics::mesh::{Indices, MeshBuilder};
+///
+/// let mut builder = MeshBuilder::new(2);
+/// builder.push(1, &[0.0, 0.0]);
+/// builder.push(2, &[1.0, 0.0]);
+/// builder.push(1, &[0.0, 0.0]);
+///
+/// let mesh = builder.build();
+/// assert_eq!(mesh.vertices, vec![0.0, 0.0, 1.0, 0.0]);
+/// assert_eq!(mesh.indices, Indices::U16(vec![0, 1, 0]));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct MeshBuilder<K: Hash + Eq> {
+    vertex_length: usize,
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+    known_vertices: HashMap<K, u32>,
+}
+
+impl<K: Hash + Eq> MeshBuilder<K> {
+    pub fn new(vertex_length: usize) -> MeshBuilder<K> {
+        MeshBuilder {
+            vertex_length,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            known_vertices: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Reserves room for `corners` more pushes
+    ///
+    pub fn reserve(&mut self, corners: usize) {
+        self.indices.reserve(corners);
+        self.known_vertices.reserve(corners);
+    }
+
+    ///
+    /// Adds a corner, `vertex` is only stored when no corner with the same `key` was pushed before
+    ///
+    pub fn push(&mut self, key: K, vertex: &[f32]) {
+        debug_assert_eq!(vertex.len(), self.vertex_length);
+
+        let next_index = (self.vertices.len() / self.vertex_length.max(1)) as u32;
+        let index = *self.known_vertices.entry(key).or_insert_with(|| {
+            self.vertices.extend_from_slice(vertex);
+            next_index
+        });
+        self.indices.push(index);
+    }
+
+    pub fn build(self) -> IndexedMesh {
+        let vertex_count = self.vertices.len() / self.vertex_length.max(1);
+        let indices = if vertex_count <= u16::MAX as usize + 1 {
+            Indices::U16(self.indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(self.indices)
+        };
+
+        IndexedMesh {
+            vertices: self.vertices,
+            indices,
+            vertex_length: self.vertex_length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_weld_vertices_with_the_same_key() {
+        let mut builder = MeshBuilder::new(3);
+        // a quad made of two triangles sharing an edge
+        builder.push("a", &[0.0, 0.0, 0.0]);
+        builder.push("b", &[1.0, 0.0, 0.0]);
+        builder.push("c", &[1.0, 1.0, 0.0]);
+        builder.push("a", &[0.0, 0.0, 0.0]);
+        builder.push("c", &[1.0, 1.0, 0.0]);
+        builder.push("d", &[0.0, 1.0, 0.0]);
+
+        let mesh = builder.build();
+
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.indices, Indices::U16(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(mesh.vertex(3), [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.indices.gl_type(), gl::UNSIGNED_SHORT);
+    }
+
+    #[test]
+    fn it_should_use_32_bits_indices_for_big_meshes() {
+        let build = |vertex_count: u32| {
+            let mut builder = MeshBuilder::new(1);
+            for key in 0..vertex_count {
+                builder.push(key, &[key as f32]);
+            }
+            builder.push(0, &[0.0]);
+            builder.build()
+        };
+
+        let small = build(u16::MAX as u32 + 1);
+        let big = build(u16::MAX as u32 + 2);
+
+        assert!(matches!(small.indices, Indices::U16(_)));
+        assert_eq!(small.indices.get(u16::MAX as usize), Some(u16::MAX as u32));
+        assert!(matches!(big.indices, Indices::U32(_)));
+        assert_eq!(
+            big.indices.get(u16::MAX as usize + 1),
+            Some(u16::MAX as u32 + 1)
+        );
+        assert_eq!(big.indices.len(), u16::MAX as usize + 3);
+        assert_eq!(big.indices.get(big.indices.len() - 1), Some(0));
+        assert_eq!(big.indices.gl_type(), gl::UNSIGNED_INT);
+    }
+}
@@ -1,5 +1,11 @@
 pub mod aabb;
+pub mod gltf;
 pub mod glw; // OpenGL Wrapper
+pub mod image;
+pub mod mesh;
+pub mod normals;
+pub mod ply;
+pub mod stl;
 pub mod triangulation;
 pub mod wavefront;
 pub mod window;
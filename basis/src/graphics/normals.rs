@@ -0,0 +1,128 @@
+use crate::{
+    graphics::wavefront::obj::VertexDataReference,
+    math::{self, VectorFunctions},
+};
+
+///
+/// How the normals of the faces around a vertex contribute to the vertex normal
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NormalWeighting {
+    ///
+    /// Bigger faces have more influence
+    ///
+    Area,
+    ///
+    /// Faces have more influence the wider their corner at the vertex is, which doesn't depend
+    /// on how the surface was tessellated
+    ///
+    #[default]
+    Angle,
+}
+
+///
+/// Normal of a polygon computed with Newell's method, so it also works for polygons that are not
+/// perfectly planar.
+///
+/// The normal is not normalized, its length is twice the polygon area. Degenerate polygons give
+/// the zero vector.
+///
+pub fn face_normal(
+    vertex_references: &[VertexDataReference],
+    vertices: &[math::Vec4],
+) -> math::Vec3 {
+    let mut normal = math::Vec3::default();
+
+    for (index, reference) in vertex_references.iter().enumerate() {
+        let current = &vertices[reference.v - 1];
+        let next = &vertices[vertex_references[(index + 1) % vertex_references.len()].v - 1];
+
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    normal
+}
+
+///
+/// Angle in radians of the polygon corner at `current`
+///
+pub fn corner_angle(previous: &math::Vec4, current: &math::Vec4, next: &math::Vec4) -> f32 {
+    let a = math::Vec3::new(
+        previous.x - current.x,
+        previous.y - current.y,
+        previous.z - current.z,
+    )
+    .normalize();
+    let b = math::Vec3::new(next.x - current.x, next.y - current.y, next.z - current.z).normalize();
+
+    let cosine = a.x * b.x + a.y * b.y + a.z * b.z;
+
+    cosine.clamp(-1.0, 1.0).acos()
+}
+
+///
+/// Contribution of a face to the normal of one of its vertices, `normal` is the result of
+/// `face_normal`
+///
+pub fn weighted_normal(
+    normal: math::Vec3,
+    corner_angle: f32,
+    weighting: NormalWeighting,
+) -> math::Vec3 {
+    match weighting {
+        NormalWeighting::Area => normal,
+        NormalWeighting::Angle => normal.normalize().scale(corner_angle),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(indices: &[usize]) -> Vec<VertexDataReference> {
+        indices
+            .iter()
+            .map(|v| VertexDataReference::new(*v, 0, 0))
+            .collect()
+    }
+
+    #[test]
+    fn it_should_compute_face_normals() {
+        let vertices = vec![
+            math::Vec4::new(0.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(2.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(2.0, 2.0, 0.0, 1.0),
+            math::Vec4::new(0.0, 2.0, 0.0, 1.0),
+        ];
+
+        // counter-clockwise quad facing +z with an area of 4
+        assert_eq!(
+            face_normal(&references(&[1, 2, 3, 4]), &vertices),
+            math::Vec3::new(0.0, 0.0, 8.0)
+        );
+        // clockwise triangle facing -z with an area of 2
+        assert_eq!(
+            face_normal(&references(&[1, 3, 2]), &vertices),
+            math::Vec3::new(0.0, 0.0, -4.0)
+        );
+        // degenerate
+        assert_eq!(
+            face_normal(&references(&[1, 2, 1]), &vertices),
+            math::Vec3::default()
+        );
+    }
+
+    #[test]
+    fn it_should_compute_corner_angles() {
+        let origin = math::Vec4::new(0.0, 0.0, 0.0, 1.0);
+        let x = math::Vec4::new(3.0, 0.0, 0.0, 1.0);
+        let y = math::Vec4::new(0.0, 5.0, 0.0, 1.0);
+        let diagonal = math::Vec4::new(1.0, 1.0, 0.0, 1.0);
+
+        assert!((corner_angle(&x, &origin, &y) - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!((corner_angle(&x, &origin, &diagonal) - std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+        assert!((corner_angle(&x, &origin, &x)).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,875 @@
+//!
+//! Stanford PLY files, as produced by 3D scanners and photogrammetry tools.
+//!
+//! The header declares the elements of the file and the properties of each one, any element is
+//! read but only the `vertex` and `face` ones are converted to an `OBJ`:
+//!
+//! * `x`, `y` and `z` are the position of the vertices
+//! * `nx`, `ny` and `nz` their normal
+//! * `u` and `v` (or `s` and `t`, `texture_u` and `texture_v`) their texture coordinates
+//! * `red`, `green` and `blue` their color, from 0 to 255 for integers and 0 to 1 for floats
+//! * `vertex_indices` (or `vertex_index`) the list of vertices of the faces, counting from 0
+//!
+
+use std::path::Path;
+
+use crate::{
+    graphics::wavefront::obj::{
+        Face, Group, Object, TriangulationMethod, VertexDataReference, VerticeNormal,
+        VerticeTexture, OBJ,
+    },
+    math,
+};
+
+#[derive(Debug)]
+pub enum ParseError {
+    MissingVertices,
+    MissingFaces,
+    InvalidHeader(usize, String),
+    InvalidValue(String),
+    InvalidFace(String),
+    UnexpectedEnd(String),
+}
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::MissingVertices => write!(f, "Missing vertices"),
+            ParseError::MissingFaces => write!(f, "Missing faces"),
+            ParseError::InvalidHeader(line, message) => {
+                write!(f, "Invalid header at line {}: {}", line, message)
+            }
+            ParseError::InvalidValue(message) => write!(f, "Invalid value: {}", message),
+            ParseError::InvalidFace(message) => write!(f, "Invalid face: {}", message),
+            ParseError::UnexpectedEnd(message) => {
+                write!(f, "Unexpected end of data: {}", message)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadPLYError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+impl std::error::Error for LoadPLYError {}
+
+impl From<std::io::Error> for LoadPLYError {
+    fn from(err: std::io::Error) -> Self {
+        LoadPLYError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadPLYError {
+    fn from(err: ParseError) -> Self {
+        LoadPLYError::Parse(err)
+    }
+}
+
+impl std::fmt::Display for LoadPLYError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadPLYError::Io(err) => write!(f, "IO error: {}", err),
+            LoadPLYError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+impl Format {
+    ///
+    /// Format declared by the header, `None` when the bytes do not start with a PLY header
+    ///
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        if !has_signature(bytes) {
+            return None;
+        }
+
+        parse_header(bytes).ok().map(|(header, _)| header.format)
+    }
+}
+
+///
+/// Whether the bytes start like a PLY file, only the first line is needed
+///
+pub fn has_signature(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"ply\n") || bytes.starts_with(b"ply\r\n")
+}
+
+///
+/// Type of a property value
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    ///
+    /// Parses both the original names (`uchar`) and the sized ones (`uint8`)
+    ///
+    fn from_name(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    ///
+    /// Largest value of the integer types, used to bring colors between 0 and 1
+    ///
+    fn max(&self) -> Option<f64> {
+        match self {
+            ScalarType::Int8 => Some(i8::MAX as f64),
+            ScalarType::UInt8 => Some(u8::MAX as f64),
+            ScalarType::Int16 => Some(i16::MAX as f64),
+            ScalarType::UInt16 => Some(u16::MAX as f64),
+            ScalarType::Int32 => Some(i32::MAX as f64),
+            ScalarType::UInt32 => Some(u32::MAX as f64),
+            ScalarType::Float32 | ScalarType::Float64 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyType {
+    Scalar(ScalarType),
+    ///
+    /// A count followed by that many items
+    ///
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub kind: PropertyType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| property.name == name)
+    }
+
+    ///
+    /// Position of the first property found among `names`
+    ///
+    fn any_property(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|name| self.property(name))
+    }
+
+    ///
+    /// Fewest bytes taken by one element, lists being empty. An ASCII value is at least a digit
+    /// and a separator.
+    ///
+    fn min_size(&self, format: Format) -> usize {
+        self.properties
+            .iter()
+            .map(|property| match (format, &property.kind) {
+                (Format::Ascii, _) => 2,
+                (_, PropertyType::Scalar(kind)) | (_, PropertyType::List(kind, _)) => kind.size(),
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub format: Format,
+    pub elements: Vec<Element>,
+    pub comments: Vec<String>,
+}
+
+///
+/// Parses the header, returns it with the position of the first byte of data
+///
+pub fn parse_header(bytes: &[u8]) -> Result<(Header, usize), ParseError> {
+    let mut position = 0;
+    let mut line_n = 0;
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut comments = Vec::new();
+
+    loop {
+        let end = bytes[position..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(|| ParseError::UnexpectedEnd("Missing 'end_header'".to_string()))?;
+        let line = String::from_utf8_lossy(&bytes[position..position + end]);
+        let mut tokens = line.split_ascii_whitespace();
+        position += end + 1;
+        line_n += 1;
+
+        let invalid = |message: &str| ParseError::InvalidHeader(line_n, message.to_string());
+
+        if line_n == 1 {
+            if line.trim_end() != "ply" {
+                return Err(invalid("Missing 'ply' signature"));
+            }
+            continue;
+        }
+
+        match tokens.next() {
+            Some("format") => {
+                format = match (tokens.next(), tokens.next()) {
+                    (Some("ascii"), Some("1.0")) => Some(Format::Ascii),
+                    (Some("binary_little_endian"), Some("1.0")) => Some(Format::BinaryLittleEndian),
+                    (Some("binary_big_endian"), Some("1.0")) => Some(Format::BinaryBigEndian),
+                    _ => return Err(invalid("Unsupported format")),
+                };
+            }
+            Some("element") => {
+                let (Some(name), Some(count)) = (tokens.next(), tokens.next()) else {
+                    return Err(invalid("Expected 'element name count'"));
+                };
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| invalid("Invalid element count"))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("Property declared before any element"))?;
+                let scalar = |name: Option<&str>| {
+                    name.and_then(ScalarType::from_name)
+                        .ok_or_else(|| invalid("Invalid property type"))
+                };
+
+                let kind = match tokens.next() {
+                    Some("list") => {
+                        PropertyType::List(scalar(tokens.next())?, scalar(tokens.next())?)
+                    }
+                    name => PropertyType::Scalar(scalar(name)?),
+                };
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| invalid("Missing property name"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            Some("comment") | Some("obj_info") => {
+                comments.push(tokens.collect::<Vec<&str>>().join(" "));
+            }
+            Some("end_header") => break,
+            None => {}
+            Some(keyword) => return Err(invalid(&format!("Unknown keyword '{keyword}'"))),
+        }
+    }
+
+    let format =
+        format.ok_or_else(|| ParseError::InvalidHeader(line_n, "Missing format".to_string()))?;
+
+    Ok((
+        Header {
+            format,
+            elements,
+            comments,
+        },
+        position,
+    ))
+}
+
+///
+/// Reads the values of the elements, every value is widened to `f64`
+///
+enum Values<'a> {
+    Ascii {
+        tokens: std::str::SplitAsciiWhitespace<'a>,
+        ///
+        /// Bytes left after the tokens already read, counting a separator after the last one
+        ///
+        remaining: usize,
+    },
+    Binary {
+        bytes: &'a [u8],
+        position: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Values<'a> {
+    fn new(bytes: &'a [u8], format: Format) -> Result<Values<'a>, ParseError> {
+        Ok(match format {
+            Format::Ascii => Values::Ascii {
+                tokens: std::str::from_utf8(bytes)
+                    .map_err(|err| ParseError::InvalidValue(format!("Invalid UTF-8: {err}")))?
+                    .split_ascii_whitespace(),
+                remaining: bytes.len() + 1,
+            },
+            Format::BinaryLittleEndian | Format::BinaryBigEndian => Values::Binary {
+                bytes,
+                position: 0,
+                big_endian: format == Format::BinaryBigEndian,
+            },
+        })
+    }
+
+    fn read(&mut self, kind: ScalarType) -> Result<f64, ParseError> {
+        match self {
+            Values::Ascii { tokens, remaining } => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| ParseError::UnexpectedEnd("Missing value".to_string()))?;
+                *remaining = remaining.saturating_sub(token.len() + 1);
+                token
+                    .parse::<f64>()
+                    .map_err(|_| ParseError::InvalidValue(format!("'{token}' is not a number")))
+            }
+            Values::Binary {
+                bytes,
+                position,
+                big_endian,
+            } => {
+                let size = kind.size();
+                let value = bytes
+                    .get(*position..*position + size)
+                    .ok_or_else(|| ParseError::UnexpectedEnd("Missing value".to_string()))?;
+                *position += size;
+
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(value);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+
+                Ok(match kind {
+                    ScalarType::Int8 => buffer[0] as i8 as f64,
+                    ScalarType::UInt8 => buffer[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::UInt16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::Int32 => {
+                        i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::UInt32 => {
+                        u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::Float32 => {
+                        f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::Float64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    ///
+    /// Upper bound of the bytes left to read
+    ///
+    fn remaining(&self) -> usize {
+        match self {
+            Values::Ascii { remaining, .. } => *remaining,
+            Values::Binary {
+                bytes, position, ..
+            } => bytes.len().saturating_sub(*position),
+        }
+    }
+
+    ///
+    /// Checks that the data left can hold the elements declared by the header, before reserving
+    /// memory for them
+    ///
+    fn check_room(&self, element: &Element, format: Format) -> Result<(), ParseError> {
+        let room = self.remaining() / element.min_size(format).max(1);
+        if room < element.count {
+            return Err(ParseError::UnexpectedEnd(format!(
+                "The header declares {} '{}' elements but there is only room for {}",
+                element.count, element.name, room
+            )));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Reads one element, the scalars go to `scalars` and the items of the lists to `lists`, both
+    /// indexed like the properties (the other slot is left empty)
+    ///
+    fn read_element(
+        &mut self,
+        element: &Element,
+        scalars: &mut [f64],
+        lists: &mut [Vec<f64>],
+    ) -> Result<(), ParseError> {
+        for (index, property) in element.properties.iter().enumerate() {
+            match property.kind {
+                PropertyType::Scalar(kind) => scalars[index] = self.read(kind)?,
+                PropertyType::List(count_kind, item_kind) => {
+                    let count = self.read(count_kind)?;
+                    if count < 0.0 || count.fract() != 0.0 {
+                        return Err(ParseError::InvalidValue(format!(
+                            "Invalid list length {count}"
+                        )));
+                    }
+                    lists[index].clear();
+                    for _ in 0..count as usize {
+                        let item = self.read(item_kind)?;
+                        lists[index].push(item);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Most faces reserved before reading them, whatever the header declares
+///
+const MAX_RESERVED_FACES: usize = 1 << 14;
+
+///
+/// Parses a PLY file, the faces are triangulated with `TriangulationMethod::EarClipping`
+///
+pub fn parse(bytes: &[u8]) -> Result<OBJ, ParseError> {
+    let (header, data) = parse_header(bytes)?;
+    let mut values = Values::new(&bytes[data..], header.format)?;
+    let mut obj = OBJ::default();
+
+    for element in &header.elements {
+        let mut scalars = vec![0.0; element.properties.len()];
+        let mut lists = vec![Vec::new(); element.properties.len()];
+
+        match element.name.as_str() {
+            "vertex" => {
+                let vertex = VertexProperties::new(element)?;
+                values.check_room(element, header.format)?;
+                vertex.reserve(&mut obj, element.count);
+                for _ in 0..element.count {
+                    values.read_element(element, &mut scalars, &mut lists)?;
+                    vertex.push(&mut obj, &scalars);
+                }
+            }
+            "face" => {
+                let indices = element
+                    .any_property(&["vertex_indices", "vertex_index"])
+                    .filter(|index| {
+                        matches!(element.properties[*index].kind, PropertyType::List(..))
+                    })
+                    .ok_or_else(|| {
+                        ParseError::InvalidFace("Missing 'vertex_indices' list".to_string())
+                    })?;
+                values.check_room(element, header.format)?;
+                // the faces are much bigger than their data, only some of them are reserved
+                obj.faces.reserve(element.count.min(MAX_RESERVED_FACES));
+                for _ in 0..element.count {
+                    values.read_element(element, &mut scalars, &mut lists)?;
+                    push_face(&mut obj, &lists[indices])?;
+                }
+            }
+            // other elements, such as edges or materials, are skipped
+            _ => {
+                for _ in 0..element.count {
+                    values.read_element(element, &mut scalars, &mut lists)?;
+                }
+            }
+        }
+    }
+
+    if obj.vertices.is_empty() {
+        return Err(ParseError::MissingVertices);
+    }
+    if obj.faces.is_empty() {
+        return Err(ParseError::MissingFaces);
+    }
+
+    let face_count = obj.faces.len();
+    for (id, face) in obj.faces.iter_mut().enumerate() {
+        face.id = id;
+        face.max_id = face_count;
+    }
+    // PLY files have no grouping, every face goes to the default object and group
+    let mut object = Object::new("default");
+    object.faces.push(0..face_count);
+    let mut group = Group::new("default");
+    group.faces.push(0..face_count);
+    object.groups.push(group);
+    obj.objects.push(object);
+
+    obj.triangulate(TriangulationMethod::EarClipping);
+
+    Ok(obj)
+}
+
+pub fn load(file_path: impl AsRef<Path>) -> Result<OBJ, LoadPLYError> {
+    let bytes = std::fs::read(file_path)?;
+
+    Ok(parse(&bytes)?)
+}
+
+///
+/// Positions of the vertex properties known by the loader
+///
+struct VertexProperties {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    texture: Option<[usize; 2]>,
+    color: Option<([usize; 3], [Option<f64>; 3])>,
+}
+
+impl VertexProperties {
+    fn new(element: &Element) -> Result<VertexProperties, ParseError> {
+        let scalar = |names: &[&str]| {
+            element
+                .any_property(names)
+                .filter(|index| matches!(element.properties[*index].kind, PropertyType::Scalar(_)))
+        };
+        let all =
+            |indices: &[Option<usize>]| indices.iter().copied().collect::<Option<Vec<usize>>>();
+
+        let position = all(&[scalar(&["x"]), scalar(&["y"]), scalar(&["z"])]).ok_or_else(|| {
+            ParseError::InvalidValue("Vertices need 'x', 'y' and 'z' properties".to_string())
+        })?;
+        let normal = all(&[scalar(&["nx"]), scalar(&["ny"]), scalar(&["nz"])]);
+        let texture = all(&[
+            scalar(&["u", "s", "texture_u", "texture_s"]),
+            scalar(&["v", "t", "texture_v", "texture_t"]),
+        ]);
+        let color = all(&[
+            scalar(&["red", "diffuse_red", "r"]),
+            scalar(&["green", "diffuse_green", "g"]),
+            scalar(&["blue", "diffuse_blue", "b"]),
+        ]);
+
+        let max = |index: usize| match element.properties[index].kind {
+            PropertyType::Scalar(kind) => kind.max(),
+            PropertyType::List(..) => None,
+        };
+
+        Ok(VertexProperties {
+            position: [position[0], position[1], position[2]],
+            normal: normal.map(|normal| [normal[0], normal[1], normal[2]]),
+            texture: texture.map(|texture| [texture[0], texture[1]]),
+            color: color.map(|color| {
+                (
+                    [color[0], color[1], color[2]],
+                    [max(color[0]), max(color[1]), max(color[2])],
+                )
+            }),
+        })
+    }
+
+    fn reserve(&self, obj: &mut OBJ, count: usize) {
+        obj.vertices.reserve(count);
+        if self.normal.is_some() {
+            obj.vertices_normal.reserve(count);
+        }
+        if self.texture.is_some() {
+            obj.vertices_texture.reserve(count);
+        }
+        if self.color.is_some() {
+            obj.vertices_color.reserve(count);
+        }
+    }
+
+    fn push(&self, obj: &mut OBJ, scalars: &[f64]) {
+        let [x, y, z] = self.position.map(|index| scalars[index] as f32);
+        obj.vertices.push(math::Vec4::new(x, y, z, 1.0));
+
+        if let Some(normal) = self.normal {
+            let [i, j, k] = normal.map(|index| scalars[index] as f32);
+            obj.vertices_normal.push(VerticeNormal::new(i, j, k));
+        }
+        if let Some(texture) = self.texture {
+            let [u, v] = texture.map(|index| scalars[index] as f32);
+            obj.vertices_texture.push(VerticeTexture::new(u, v, 0.0));
+        }
+        if let Some((color, max)) = self.color {
+            let channel = |channel: usize| {
+                let value = scalars[color[channel]];
+                (value / max[channel].unwrap_or(1.0)) as f32
+            };
+            obj.vertices_color
+                .push(math::Vec3::new(channel(0), channel(1), channel(2)));
+        }
+    }
+}
+
+///
+/// Adds a face, the vertex data with the same index as the vertex is referenced when it exists
+///
+fn push_face(obj: &mut OBJ, indices: &[f64]) -> Result<(), ParseError> {
+    if indices.len() < 3 {
+        return Err(ParseError::InvalidFace(
+            "A face needs at least 3 vertices".to_string(),
+        ));
+    }
+
+    let vertex_count = obj.vertices.len();
+    let has_texture = obj.vertices_texture.len() == vertex_count;
+    let has_normal = obj.vertices_normal.len() == vertex_count;
+
+    let mut references = Vec::with_capacity(indices.len());
+    for index in indices {
+        if *index < 0.0 || *index >= vertex_count as f64 || index.fract() != 0.0 {
+            return Err(ParseError::InvalidFace(format!(
+                "Vertex index {index} is out of range, only {vertex_count} declared"
+            )));
+        }
+
+        let v = *index as usize + 1;
+        references.push(VertexDataReference::new(
+            v,
+            if has_texture { v } else { 0 },
+            if has_normal { v } else { 0 },
+        ));
+    }
+    obj.faces.push(Face::partial_new(references, None));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "ply
+format ascii 1.0
+comment made by a scanner
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 0 0 1 0 0 255 0 0
+1 0 0 0 0 1 1 0 0 255 0
+1 1 0 0 0 1 1 1 0 0 255
+0 1 0 0 0 1 0 1 255 255 255
+4 0 1 2 3
+0 2
+";
+
+    ///
+    /// Binary version of `SQUARE`, without the edges
+    ///
+    fn square_binary(big_endian: bool) -> Vec<u8> {
+        let format = match big_endian {
+            true => "binary_big_endian",
+            false => "binary_little_endian",
+        };
+        let header = SQUARE
+            .replace("format ascii", &format!("format {format}"))
+            .replace(
+                "element edge 1\nproperty int vertex1\nproperty int vertex2\n",
+                "",
+            );
+        let header = &header[..header.find("end_header\n").unwrap() + "end_header\n".len()];
+        let mut bytes = header.as_bytes().to_vec();
+
+        let data = SQUARE.split("end_header\n").nth(1).unwrap();
+        for line in data.lines().take(4) {
+            let values = line.split(' ').collect::<Vec<&str>>();
+            for value in &values[..8] {
+                let value = value.parse::<f32>().unwrap();
+                match big_endian {
+                    true => bytes.extend_from_slice(&value.to_be_bytes()),
+                    false => bytes.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+            for value in &values[8..] {
+                bytes.push(value.parse::<u8>().unwrap());
+            }
+        }
+        bytes.push(4);
+        for index in 0..4i32 {
+            match big_endian {
+                true => bytes.extend_from_slice(&index.to_be_bytes()),
+                false => bytes.extend_from_slice(&index.to_le_bytes()),
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn it_should_parse_the_header() {
+        let (header, data) = parse_header(SQUARE.as_bytes()).expect("This should work");
+
+        assert_eq!(header.format, Format::Ascii);
+        assert_eq!(header.comments, vec!["made by a scanner"]);
+        assert_eq!(header.elements.len(), 3);
+        assert_eq!(header.elements[0].name, "vertex");
+        assert_eq!(header.elements[0].count, 4);
+        assert_eq!(header.elements[0].properties.len(), 11);
+        assert_eq!(
+            header.elements[1].properties[0],
+            Property {
+                name: "vertex_indices".to_string(),
+                kind: PropertyType::List(ScalarType::UInt8, ScalarType::Int32)
+            }
+        );
+        assert!(SQUARE[data..].starts_with("0 0 0 0 0 1"));
+        assert_eq!(
+            Format::from_magic(&square_binary(true)),
+            Some(Format::BinaryBigEndian)
+        );
+        assert_eq!(Format::from_magic(b"v 0 0 0\n"), None);
+        assert!(has_signature(b"ply\r\nformat"));
+    }
+
+    #[test]
+    fn it_should_load_vertex_data_and_triangulate_faces() {
+        let result = parse(SQUARE.as_bytes()).expect("This should work");
+
+        assert_eq!(result.vertices.len(), 4);
+        assert_eq!(result.vertices[2], math::Vec4::new(1.0, 1.0, 0.0, 1.0));
+        assert_eq!(result.vertices_normal[0], VerticeNormal::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            result.vertices_texture[3],
+            VerticeTexture::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(result.vertices_color[1], math::Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(result.faces.len(), 1);
+        assert_eq!(result.faces[0].vertex_references.len(), 6);
+        assert_eq!(
+            result.faces[0].vertex_references[0],
+            VertexDataReference::new(1, 1, 1)
+        );
+        assert_eq!(result.objects[0].faces, vec![0..1]);
+
+        // the colors of the vertices replace the color given to the raw vertices
+        let raw = result.get_raw_vertices(math::Vec3::new(0.5, 0.5, 0.5));
+        let colors = raw
+            .chunks(OBJ::RAW_VERTEX_LENGTH)
+            .map(|vertex| [vertex[4], vertex[5], vertex[6]])
+            .collect::<Vec<[f32; 3]>>();
+        assert!(colors.contains(&[1.0, 0.0, 0.0]));
+        assert!(!colors.contains(&[0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn it_should_read_both_binary_endiannesses() {
+        let ascii = parse(SQUARE.as_bytes()).expect("This should work");
+
+        for big_endian in [false, true] {
+            let result = parse(&square_binary(big_endian)).expect("This should work");
+
+            assert_eq!(result.vertices, ascii.vertices);
+            assert_eq!(result.vertices_normal, ascii.vertices_normal);
+            assert_eq!(result.vertices_texture, ascii.vertices_texture);
+            assert_eq!(result.vertices_color, ascii.vertices_color);
+            assert_eq!(result.faces, ascii.faces);
+        }
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_files() {
+        let mut truncated = square_binary(false);
+        truncated.truncate(truncated.len() - 3);
+
+        let cases = [
+            (
+                SQUARE.replace("ply\n", "plx\n"),
+                "Invalid header at line 1: Missing 'ply' signature",
+            ),
+            (
+                SQUARE.replace("ascii 1.0", "ascii 2.0"),
+                "Invalid header at line 2: Unsupported format",
+            ),
+            (
+                SQUARE.replace("property float z", "property half z"),
+                "Invalid header at line 7: Invalid property type",
+            ),
+            (
+                SQUARE.replace("end_header", "end"),
+                "Invalid header at line 21: Unknown keyword 'end'",
+            ),
+            (
+                SQUARE.replace("4 0 1 2 3", "4 0 1 2 4"),
+                "Invalid face: Vertex index 4 is out of range, only 4 declared",
+            ),
+            (
+                SQUARE.replace("4 0 1 2 3", "2 0 1"),
+                "Invalid face: A face needs at least 3 vertices",
+            ),
+            (
+                SQUARE.replace("0 0 255 0 0", "0 0 x 0 0"),
+                "Invalid value: 'x' is not a number",
+            ),
+            (
+                SQUARE.replace("element face 1", "element face 0"),
+                "Missing faces",
+            ),
+            (
+                SQUARE.replace("element vertex 4", "element vertex 99999999999"),
+                "Unexpected end of data: The header declares 99999999999 'vertex' elements but there is only room for 5",
+            ),
+            (
+                SQUARE.replace("element face 1", "element face 99999999999"),
+                "Unexpected end of data: The header declares 99999999999 'face' elements but there is only room for 7",
+            ),
+        ];
+
+        for (file, message) in cases {
+            let result = parse(file.as_bytes());
+            assert_eq!(result.unwrap_err().to_string(), message);
+        }
+        assert!(matches!(
+            parse(&truncated),
+            Err(ParseError::UnexpectedEnd(_))
+        ));
+        assert!(matches!(
+            parse(b"ply\nformat ascii 1.0\n"),
+            Err(ParseError::UnexpectedEnd(_))
+        ));
+    }
+}
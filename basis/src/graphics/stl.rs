@@ -0,0 +1,602 @@
+//!
+//! STereoLithography files, as exported by CAD and 3D printing tools.
+//!
+//! The facets are converted to an `OBJ` so the models are drawn like Wavefront ones: vertices
+//! shared by several facets are stored once, and every corner references the normal of its facet
+//! so the edges stay sharp.
+//!
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    graphics::{
+        normals,
+        wavefront::obj::{self, Face, Object, VertexDataReference, VerticeNormal, OBJ},
+    },
+    math::{self, VectorFunctions},
+};
+
+///
+/// Size of the header of binary files, followed by the facet count
+///
+const BINARY_HEADER_LENGTH: usize = 80;
+///
+/// Normal, three vertices and the attribute byte count
+///
+const BINARY_FACET_LENGTH: usize = 50;
+
+#[derive(Debug)]
+pub enum ParseError {
+    MissingFacets,
+    InvalidToken(usize, String),
+    InvalidValue(usize, String),
+    UnexpectedEnd(String),
+}
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::MissingFacets => write!(f, "Missing facets"),
+            ParseError::InvalidToken(line, message) => {
+                write!(f, "Invalid token at line {}: {}", line, message)
+            }
+            ParseError::InvalidValue(line, message) => {
+                write!(f, "Invalid value at line {}: {}", line, message)
+            }
+            ParseError::UnexpectedEnd(message) => {
+                write!(f, "Unexpected end of data: {}", message)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadSTLError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+impl std::error::Error for LoadSTLError {}
+
+impl From<std::io::Error> for LoadSTLError {
+    fn from(err: std::io::Error) -> Self {
+        LoadSTLError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadSTLError {
+    fn from(err: ParseError) -> Self {
+        LoadSTLError::Parse(err)
+    }
+}
+
+impl std::fmt::Display for LoadSTLError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadSTLError::Io(err) => write!(f, "IO error: {}", err),
+            LoadSTLError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ascii,
+    Binary,
+}
+
+impl Format {
+    ///
+    /// Detects the format of a whole file.
+    ///
+    /// ASCII files start with `solid`, but so do the headers of many binary files, which are
+    /// recognized by their size matching the facet count instead.
+    ///
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        Format::from_header(bytes, bytes.len() as u64)
+    }
+
+    ///
+    /// Same as `from_magic` without reading the whole file, from its first bytes and its length.
+    /// The first line of an ASCII file and the next one should be part of `header`.
+    ///
+    pub fn from_header(header: &[u8], file_length: u64) -> Option<Format> {
+        if let Some(count) = header.get(BINARY_HEADER_LENGTH..BINARY_HEADER_LENGTH + 4) {
+            let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as u64;
+            let size = count
+                .checked_mul(BINARY_FACET_LENGTH as u64)
+                .and_then(|size| size.checked_add(BINARY_HEADER_LENGTH as u64 + 4));
+            if size == Some(file_length) {
+                return Some(Format::Binary);
+            }
+        }
+
+        // a truncated binary file can still start with `solid`, so the next line is checked too
+        let data = header.trim_ascii_start();
+        let next_line = data
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map(|end| data[end..].trim_ascii_start())
+            .unwrap_or_default();
+        if data.starts_with(b"solid")
+            && (next_line.starts_with(b"facet") || next_line.starts_with(b"endsolid"))
+        {
+            return Some(Format::Ascii);
+        }
+        None
+    }
+}
+
+///
+/// Parses an STL file, files that don't look like ASCII are parsed as binary
+///
+pub fn parse(bytes: &[u8]) -> Result<OBJ, ParseError> {
+    match Format::from_magic(bytes) {
+        Some(Format::Ascii) => parse_ascii(bytes),
+        _ => parse_binary(bytes),
+    }
+}
+
+pub fn load(file_path: impl AsRef<Path>) -> Result<OBJ, LoadSTLError> {
+    let bytes = std::fs::read(file_path)?;
+
+    Ok(parse(&bytes)?)
+}
+
+///
+/// Builds the `OBJ` facet by facet
+///
+struct Builder {
+    obj: OBJ,
+    vertices: HashMap<[u32; 3], usize>,
+    normals: HashMap<[u32; 3], usize>,
+    groups: Vec<String>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        Builder {
+            obj: OBJ::default(),
+            vertices: HashMap::new(),
+            normals: HashMap::new(),
+            groups: vec!["default".to_string()],
+        }
+    }
+
+    ///
+    /// Starts the object of a `solid`
+    ///
+    fn begin_solid(&mut self, name: &str) {
+        let name = if name.is_empty() { "default" } else { name };
+        self.obj.objects.push(Object::new(name));
+    }
+
+    fn push_facet(&mut self, normal: math::Vec3, corners: [math::Vec3; 3]) {
+        let mut references = corners
+            .map(|corner| VertexDataReference::new(self.vertex(corner), 0, 0))
+            .to_vec();
+
+        // many exporters leave the normal to zero, it is then given by the winding order
+        let normal = match normal.normalize() {
+            normal if is_unit(&normal) => normal,
+            _ => normals::face_normal(&references, &self.obj.vertices).normalize(),
+        };
+        let vn = self.normal(normal);
+        references
+            .iter_mut()
+            .for_each(|reference| reference.vn = vn);
+
+        let id = self.obj.faces.len();
+        let mut face = Face::partial_new(references, None);
+        face.id = id;
+        self.obj.faces.push(face);
+
+        if self.obj.objects.is_empty() {
+            self.begin_solid("");
+        }
+        if let Some(object) = self.obj.objects.last_mut() {
+            object.push_face(id, &self.groups);
+        }
+    }
+
+    ///
+    /// 1-based index of the vertex, vertices are welded when their positions are identical
+    ///
+    fn vertex(&mut self, position: math::Vec3) -> usize {
+        let vertices = &mut self.obj.vertices;
+
+        *self.vertices.entry(bits(&position)).or_insert_with(|| {
+            vertices.push(math::Vec4::new(position.x, position.y, position.z, 1.0));
+            vertices.len()
+        })
+    }
+
+    ///
+    /// 1-based index of the normal, shared by the facets with the same normal
+    ///
+    fn normal(&mut self, normal: math::Vec3) -> usize {
+        let normals = &mut self.obj.vertices_normal;
+
+        *self.normals.entry(bits(&normal)).or_insert_with(|| {
+            normals.push(VerticeNormal::new(normal.x, normal.y, normal.z));
+            normals.len()
+        })
+    }
+
+    fn build(mut self) -> Result<OBJ, ParseError> {
+        if self.obj.faces.is_empty() {
+            return Err(ParseError::MissingFacets);
+        }
+
+        let face_count = self.obj.faces.len();
+        self.obj
+            .faces
+            .iter_mut()
+            .for_each(|face| face.max_id = face_count);
+        self.obj.objects.retain(|object| !object.faces.is_empty());
+
+        Ok(self.obj)
+    }
+}
+
+///
+/// Bits identifying a position, `0.0` and `-0.0` are the same
+///
+fn bits(vector: &math::Vec3) -> [u32; 3] {
+    [vector.x, vector.y, vector.z].map(|value| (value + 0.0).to_bits())
+}
+
+fn is_unit(normal: &math::Vec3) -> bool {
+    let length = normal.x * normal.x + normal.y * normal.y + normal.z * normal.z;
+    (length - 1.0).abs() < 1e-3
+}
+
+///
+/// Binary files are an 80 bytes header, the facet count and the facets, every value is little
+/// endian. The attribute byte count of the facets is ignored.
+///
+pub fn parse_binary(bytes: &[u8]) -> Result<OBJ, ParseError> {
+    let count = bytes
+        .get(BINARY_HEADER_LENGTH..BINARY_HEADER_LENGTH + 4)
+        .ok_or_else(|| ParseError::UnexpectedEnd("Missing binary header".to_string()))?;
+    let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+
+    let facets = &bytes[BINARY_HEADER_LENGTH + 4..];
+    if facets.len() / BINARY_FACET_LENGTH < count {
+        return Err(ParseError::UnexpectedEnd(format!(
+            "The header declares {} facets but there is only room for {}",
+            count,
+            facets.len() / BINARY_FACET_LENGTH
+        )));
+    }
+
+    let mut builder = Builder::new();
+    builder.obj.vertices.reserve(count / 2);
+    builder.obj.faces.reserve(count);
+
+    for facet in facets.chunks_exact(BINARY_FACET_LENGTH).take(count) {
+        let float = |index: usize| {
+            let offset = index * 4;
+            f32::from_le_bytes([
+                facet[offset],
+                facet[offset + 1],
+                facet[offset + 2],
+                facet[offset + 3],
+            ])
+        };
+        let vector =
+            |index: usize| math::Vec3::new(float(index), float(index + 1), float(index + 2));
+
+        builder.push_facet(vector(0), [vector(3), vector(6), vector(9)]);
+    }
+
+    builder.build()
+}
+
+///
+/// Tokens of an ASCII file with the line where they were found
+///
+struct AsciiTokens<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+    line: usize,
+}
+
+impl<'a> AsciiTokens<'a> {
+    fn new(data: &'a str) -> AsciiTokens<'a> {
+        AsciiTokens {
+            lines: data.lines().enumerate(),
+            tokens: "".split_ascii_whitespace(),
+            line: 0,
+        }
+    }
+
+    ///
+    /// Rest of the current line, used by the names of the solids
+    ///
+    fn rest_of_line(&mut self) -> String {
+        self.tokens.by_ref().collect::<Vec<&str>>().join(" ")
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.next() {
+            Some(token) if token == keyword => Ok(()),
+            Some(token) => Err(ParseError::InvalidToken(
+                self.line,
+                format!("Expected '{keyword}', found '{token}'"),
+            )),
+            None => Err(ParseError::UnexpectedEnd(format!("Expected '{keyword}'"))),
+        }
+    }
+
+    fn vector(&mut self, name: &str) -> Result<math::Vec3, ParseError> {
+        let mut value = || match self.next() {
+            Some(token) => obj::parse_float(token).map_err(|_| {
+                ParseError::InvalidValue(self.line, format!("Invalid {name} value '{token}'"))
+            }),
+            None => Err(ParseError::UnexpectedEnd(format!("Missing {name} value"))),
+        };
+
+        Ok(math::Vec3::new(value()?, value()?, value()?))
+    }
+}
+
+impl<'a> Iterator for AsciiTokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            if let Some(token) = self.tokens.next() {
+                return Some(token);
+            }
+            let (index, line) = self.lines.next()?;
+            self.line = index + 1;
+            self.tokens = line.split_ascii_whitespace();
+        }
+    }
+}
+
+///
+/// ASCII files are made of one or more solids, each one becomes an object:
+///
+/// ```text
+/// solid name
+///   facet normal ni nj nk
+///     outer loop
+///       vertex x y z
+///       vertex x y z
+///       vertex x y z
+///     endloop
+///   endfacet
+/// endsolid name
+/// ```
+///
+pub fn parse_ascii(bytes: &[u8]) -> Result<OBJ, ParseError> {
+    let data = std::str::from_utf8(bytes)
+        .map_err(|err| ParseError::InvalidToken(0, format!("Invalid UTF-8: {err}")))?;
+    let mut tokens = AsciiTokens::new(data);
+    let mut builder = Builder::new();
+
+    while let Some(token) = tokens.next() {
+        if token != "solid" {
+            return Err(ParseError::InvalidToken(
+                tokens.line,
+                format!("Expected 'solid', found '{token}'"),
+            ));
+        }
+        builder.begin_solid(&tokens.rest_of_line());
+
+        loop {
+            match tokens.next() {
+                Some("facet") => {
+                    tokens.expect("normal")?;
+                    let normal = tokens.vector("normal")?;
+                    tokens.expect("outer")?;
+                    tokens.expect("loop")?;
+                    let mut corners = [math::Vec3::default(); 3];
+                    for corner in &mut corners {
+                        tokens.expect("vertex")?;
+                        *corner = tokens.vector("vertex")?;
+                    }
+                    tokens.expect("endloop")?;
+                    tokens.expect("endfacet")?;
+
+                    builder.push_facet(normal, corners);
+                }
+                Some("endsolid") => {
+                    // the name is optional and often differs from the one of `solid`
+                    tokens.rest_of_line();
+                    break;
+                }
+                Some(token) => {
+                    return Err(ParseError::InvalidToken(
+                        tokens.line,
+                        format!("Expected 'facet' or 'endsolid', found '{token}'"),
+                    ))
+                }
+                None => return Err(ParseError::UnexpectedEnd("Expected 'endsolid'".to_string())),
+            }
+        }
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TETRAHEDRON: &str = "solid tetrahedron
+  facet normal 0 0 -1
+    outer loop
+      vertex 0 0 0
+      vertex 0 1 0
+      vertex 1 0 0
+    endloop
+  endfacet
+  facet normal 0 -1 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 0 1
+    endloop
+  endfacet
+  facet normal -1 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 0 0 1
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 1 0 0
+      vertex 0 1 0
+      vertex 0 0 1
+    endloop
+  endfacet
+endsolid tetrahedron
+";
+
+    ///
+    /// Same facets as the ASCII file, with a header that starts with "solid" like many exporters
+    ///
+    fn tetrahedron_binary() -> Vec<u8> {
+        let ascii = parse_ascii(TETRAHEDRON.as_bytes()).expect("This should work");
+        let mut bytes = b"solid exported by a CAD tool".to_vec();
+        bytes.resize(BINARY_HEADER_LENGTH, 0);
+        bytes.extend_from_slice(&(ascii.faces.len() as u32).to_le_bytes());
+
+        for face in &ascii.faces {
+            // the binary file keeps the zero normal of the last facet
+            let normal = match face.id {
+                3 => VerticeNormal::default(),
+                _ => ascii.vertices_normal[face.vertex_references[0].vn - 1].clone(),
+            };
+            for value in [normal.i, normal.j, normal.k] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for reference in &face.vertex_references {
+                let vertex = ascii.vertices[reference.v - 1];
+                for value in [vertex.x, vertex.y, vertex.z] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn it_should_weld_vertices_and_keep_facet_normals() {
+        let result = parse(TETRAHEDRON.as_bytes()).expect("This should work");
+
+        assert_eq!(result.vertices.len(), 4);
+        assert_eq!(result.faces.len(), 4);
+        assert_eq!(result.vertices_normal.len(), 4);
+        assert_eq!(result.objects.len(), 1);
+        assert_eq!(result.objects[0].name, "tetrahedron");
+        assert_eq!(result.objects[0].faces, vec![0..4]);
+
+        let normal = |face: usize| {
+            let normal = &result.vertices_normal[result.faces[face].vertex_references[0].vn - 1];
+            (normal.i, normal.j, normal.k)
+        };
+        assert_eq!(normal(0), (0.0, 0.0, -1.0));
+        // the zero normal is computed from the winding order
+        let (i, j, k) = normal(3);
+        let expected = 1.0 / 3.0_f32.sqrt();
+        assert!(
+            (i - expected).abs() < 1e-6
+                && (j - expected).abs() < 1e-6
+                && (k - expected).abs() < 1e-6
+        );
+
+        // every corner of a facet shares its normal, so the edges stay sharp
+        let mesh = result.get_indexed_mesh(math::Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(mesh.vertex_count(), 12);
+    }
+
+    #[test]
+    fn it_should_read_binary_files_like_ascii_ones() {
+        let bytes = tetrahedron_binary();
+        let ascii = parse(TETRAHEDRON.as_bytes()).expect("This should work");
+
+        let binary = parse(&bytes).expect("This should work");
+
+        assert_eq!(Format::from_magic(&bytes), Some(Format::Binary));
+        assert_eq!(
+            Format::from_header(&bytes[..84], bytes.len() as u64),
+            Some(Format::Binary)
+        );
+        assert_eq!(Format::from_header(&bytes[..84], 84), None);
+        assert_eq!(
+            Format::from_magic(TETRAHEDRON.as_bytes()),
+            Some(Format::Ascii)
+        );
+        assert_eq!(binary.vertices, ascii.vertices);
+        assert_eq!(binary.vertices_normal, ascii.vertices_normal);
+        assert_eq!(binary.faces, ascii.faces);
+        assert_eq!(binary.objects[0].name, "default");
+    }
+
+    #[test]
+    fn it_should_make_an_object_of_every_solid() {
+        let file = format!(
+            "{}{}",
+            TETRAHEDRON,
+            TETRAHEDRON.replace("tetrahedron", "copy")
+        );
+
+        let result = parse(file.as_bytes()).expect("This should work");
+
+        assert_eq!(result.vertices.len(), 4);
+        assert_eq!(result.faces.len(), 8);
+        assert_eq!(result.faces[7].max_id, 8);
+        assert_eq!(
+            result
+                .objects
+                .iter()
+                .map(|o| o.name.as_str())
+                .collect::<Vec<_>>(),
+            ["tetrahedron", "copy"]
+        );
+        assert_eq!(result.objects[1].faces, vec![4..8]);
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_files() {
+        let mut truncated = tetrahedron_binary();
+        truncated.truncate(truncated.len() - 10);
+
+        let cases = [
+            (
+                TETRAHEDRON.replace("outer loop", "outer"),
+                "Invalid token at line 4: Expected 'loop', found 'vertex'",
+            ),
+            (
+                TETRAHEDRON.replace("vertex 0 1 0", "vertex 0 a 0"),
+                "Invalid value at line 5: Invalid vertex value 'a'",
+            ),
+            (
+                TETRAHEDRON.replace("endsolid tetrahedron", ""),
+                "Unexpected end of data: Expected 'endsolid'",
+            ),
+            (
+                "solid empty\nendsolid empty\n".to_string(),
+                "Missing facets",
+            ),
+        ];
+
+        for (file, message) in cases {
+            let result = parse(file.as_bytes());
+            assert_eq!(result.unwrap_err().to_string(), message);
+        }
+        assert!(matches!(
+            parse(&truncated),
+            Err(ParseError::UnexpectedEnd(_))
+        ));
+        assert!(matches!(parse(b"STL"), Err(ParseError::UnexpectedEnd(_))));
+    }
+}
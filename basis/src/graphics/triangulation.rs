@@ -28,16 +28,369 @@ pub mod _2d {
     }
 
     pub mod ear_clipping {
-        use crate::graphics::wavefront::obj::VertexDataReference;
+        use crate::{graphics::wavefront::obj::VertexDataReference, math};
 
         ///
         /// Triangulate a polygon using the ear clipping algorithm.
         /// It works for both convex and concave polygons.
         ///
+        /// The polygon is projected onto its best-fit plane (computed with Newell's method from the
+        /// referenced `vertices`) before looking for ears. Consecutive duplicated points are skipped
+        /// and fully degenerate polygons (every point collinear) produce no triangles.
+        ///
+        /// For convex polygons the result is the same as the fan method.
+        ///
         pub fn triangulate(
-            _vertex_references: &mut Vec<VertexDataReference>,
+            vertex_references: &[VertexDataReference],
+            vertices: &[math::Vec4],
         ) -> Vec<VertexDataReference> {
-            todo!();
+            if vertex_references.len() <= 3 {
+                return vertex_references.to_vec();
+            }
+
+            let points = project(vertex_references, vertices);
+
+            // indexes of the polygon points that were not clipped yet
+            let mut polygon: Vec<usize> = Vec::with_capacity(points.len());
+            for i in 0..points.len() {
+                if polygon
+                    .last()
+                    .is_some_and(|&last| points[last] == points[i])
+                {
+                    continue;
+                }
+                polygon.push(i);
+            }
+            while polygon.len() > 1 && points[polygon[0]] == points[polygon[polygon.len() - 1]] {
+                polygon.pop();
+            }
+
+            let area = signed_area(&points, &polygon);
+            let epsilon = epsilon(&points);
+            if area.abs() <= epsilon {
+                return Vec::new();
+            }
+            // makes every convex corner have a positive cross product
+            let orientation = area.signum();
+
+            let mut result = Vec::with_capacity((polygon.len() - 2) * 3);
+            let mut start = 1;
+
+            while polygon.len() > 3 {
+                let len = polygon.len();
+                let ear = (0..len)
+                    .map(|offset| (start + offset) % len)
+                    .find(|&i| is_ear(&points, &polygon, i, orientation, epsilon));
+
+                match ear {
+                    Some(i) => {
+                        let previous = polygon[(i + len - 1) % len];
+                        let next = polygon[(i + 1) % len];
+
+                        result.push(vertex_references[previous].clone());
+                        result.push(vertex_references[polygon[i]].clone());
+                        result.push(vertex_references[next].clone());
+                        polygon.remove(i);
+                        start = i % polygon.len();
+                    }
+                    None => {
+                        // Only collinear or self-intersecting leftovers remain
+                        if signed_area(&points, &polygon).abs() <= epsilon {
+                            return result;
+                        }
+                        let i = start % len;
+                        let previous = polygon[(i + len - 1) % len];
+                        let next = polygon[(i + 1) % len];
+
+                        result.push(vertex_references[previous].clone());
+                        result.push(vertex_references[polygon[i]].clone());
+                        result.push(vertex_references[next].clone());
+                        polygon.remove(i);
+                        start = i % polygon.len();
+                    }
+                }
+            }
+
+            if cross(points[polygon[0]], points[polygon[1]], points[polygon[2]]).abs() > epsilon {
+                for i in polygon {
+                    result.push(vertex_references[i].clone());
+                }
+            }
+
+            result
+        }
+
+        ///
+        /// Projects the referenced vertices onto the plane that best fits the polygon by dropping the
+        /// coordinate where the polygon's normal is the largest.
+        ///
+        fn project(
+            vertex_references: &[VertexDataReference],
+            vertices: &[math::Vec4],
+        ) -> Vec<(f32, f32)> {
+            let positions = vertex_references
+                .iter()
+                .map(|reference| {
+                    vertices
+                        .get(reference.v.wrapping_sub(1))
+                        .copied()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<math::Vec4>>();
+
+            // Newell's method
+            let mut normal = math::Vec3::default();
+            for (i, current) in positions.iter().enumerate() {
+                let next = positions[(i + 1) % positions.len()];
+                normal.x += (current.y - next.y) * (current.z + next.z);
+                normal.y += (current.z - next.z) * (current.x + next.x);
+                normal.z += (current.x - next.x) * (current.y + next.y);
+            }
+
+            let (nx, ny, nz) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+
+            positions
+                .iter()
+                .map(|p| {
+                    if nx >= ny && nx >= nz {
+                        (p.y, p.z)
+                    } else if ny >= nz {
+                        (p.z, p.x)
+                    } else {
+                        (p.x, p.y)
+                    }
+                })
+                .collect()
+        }
+
+        fn is_ear(
+            points: &[(f32, f32)],
+            polygon: &[usize],
+            i: usize,
+            orientation: f32,
+            epsilon: f32,
+        ) -> bool {
+            let len = polygon.len();
+            let a = points[polygon[(i + len - 1) % len]];
+            let b = points[polygon[i]];
+            let c = points[polygon[(i + 1) % len]];
+
+            if cross(a, b, c) * orientation <= epsilon {
+                return false;
+            }
+
+            !polygon.iter().any(|&index| {
+                let p = points[index];
+                p != a && p != b && p != c && is_inside_triangle(p, a, b, c, orientation)
+            })
+        }
+
+        ///
+        /// Whether `p` is inside of the triangle `abc` or on one of its edges.
+        ///
+        fn is_inside_triangle(
+            p: (f32, f32),
+            a: (f32, f32),
+            b: (f32, f32),
+            c: (f32, f32),
+            orientation: f32,
+        ) -> bool {
+            cross(a, b, p) * orientation >= 0.0
+                && cross(b, c, p) * orientation >= 0.0
+                && cross(c, a, p) * orientation >= 0.0
+        }
+
+        ///
+        /// Z component of the cross product between `ab` and `bc`, positive when the corner `abc`
+        /// turns counter-clockwise.
+        ///
+        fn cross(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+            (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0)
+        }
+
+        fn signed_area(points: &[(f32, f32)], polygon: &[usize]) -> f32 {
+            let mut area = 0.0;
+            for (i, &current) in polygon.iter().enumerate() {
+                let next = polygon[(i + 1) % polygon.len()];
+                area += points[current].0 * points[next].1 - points[next].0 * points[current].1;
+            }
+            area / 2.0
+        }
+
+        ///
+        /// Tolerance used to decide if an area is zero, relative to the size of the polygon.
+        ///
+        fn epsilon(points: &[(f32, f32)]) -> f32 {
+            let (mut min, mut max) = (points[0], points[0]);
+            for p in points {
+                min = (min.0.min(p.0), min.1.min(p.1));
+                max = (max.0.max(p.0), max.1.max(p.1));
+            }
+            let extent = f32::max(max.0 - min.0, max.1 - min.1);
+
+            extent * extent * 1e-6
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            fn polygon(points: &[(f32, f32)]) -> (Vec<VertexDataReference>, Vec<math::Vec4>) {
+                let vertices = points
+                    .iter()
+                    .map(|(x, y)| math::Vec4::new(*x, *y, 0.0, 1.0))
+                    .collect();
+                let references = (1..=points.len())
+                    .map(|v| VertexDataReference::new(v, 0, 0))
+                    .collect();
+
+                (references, vertices)
+            }
+
+            fn area(triangles: &[VertexDataReference], vertices: &[math::Vec4]) -> f32 {
+                triangles
+                    .chunks(3)
+                    .map(|triangle| {
+                        let a = vertices[triangle[0].v - 1];
+                        let b = vertices[triangle[1].v - 1];
+                        let c = vertices[triangle[2].v - 1];
+                        cross((a.x, a.y), (b.x, b.y), (c.x, c.y)) / 2.0
+                    })
+                    .sum()
+            }
+
+            fn assert_same_winding(triangles: &[VertexDataReference], vertices: &[math::Vec4]) {
+                for triangle in triangles.chunks(3) {
+                    assert!(area(triangle, vertices) > 0.0, "{:?} is flipped", triangle);
+                }
+            }
+
+            #[test]
+            fn it_should_match_fan_for_convex_polygons() {
+                let (mut references, vertices) =
+                    polygon(&[(0.0, 0.0), (2.0, 0.0), (3.0, 1.0), (2.0, 2.0), (0.0, 2.0)]);
+
+                let result = triangulate(&references, &vertices);
+
+                assert_eq!(result, super::super::fan::triangulate(&mut references));
+            }
+
+            #[test]
+            fn it_should_triangulate_l_shape() {
+                let (references, vertices) = polygon(&[
+                    (0.0, 0.0),
+                    (2.0, 0.0),
+                    (2.0, 1.0),
+                    (1.0, 1.0),
+                    (1.0, 2.0),
+                    (0.0, 2.0),
+                ]);
+
+                let result = triangulate(&references, &vertices);
+
+                assert_eq!(result.len(), 4 * 3);
+                assert_eq!(area(&result, &vertices), 3.0);
+                assert_same_winding(&result, &vertices);
+            }
+
+            #[test]
+            fn it_should_triangulate_clockwise_l_shape() {
+                let (references, vertices) = polygon(&[
+                    (0.0, 2.0),
+                    (1.0, 2.0),
+                    (1.0, 1.0),
+                    (2.0, 1.0),
+                    (2.0, 0.0),
+                    (0.0, 0.0),
+                ]);
+
+                let result = triangulate(&references, &vertices);
+
+                assert_eq!(result.len(), 4 * 3);
+                assert_eq!(area(&result, &vertices), -3.0);
+            }
+
+            #[test]
+            fn it_should_triangulate_star() {
+                let points = (0..10)
+                    .map(|i| {
+                        let angle = (i as f32) * std::f32::consts::PI / 5.0;
+                        let radius = if i % 2 == 0 { 2.0 } else { 1.0 };
+                        (radius * angle.cos(), radius * angle.sin())
+                    })
+                    .collect::<Vec<(f32, f32)>>();
+                let (references, vertices) = polygon(&points);
+
+                let result = triangulate(&references, &vertices);
+                let expected = signed_area(&points, &(0..10).collect::<Vec<usize>>());
+
+                assert_eq!(result.len(), 8 * 3);
+                assert!((area(&result, &vertices) - expected).abs() < 1e-5);
+                assert_same_winding(&result, &vertices);
+            }
+
+            #[test]
+            fn it_should_triangulate_polygons_in_other_planes() {
+                let vertices = vec![
+                    math::Vec4::new(0.0, 0.0, 0.0, 1.0),
+                    math::Vec4::new(0.0, 2.0, 0.0, 1.0),
+                    math::Vec4::new(0.0, 2.0, 1.0, 1.0),
+                    math::Vec4::new(0.0, 1.0, 1.0, 1.0),
+                    math::Vec4::new(0.0, 1.0, 2.0, 1.0),
+                    math::Vec4::new(0.0, 0.0, 2.0, 1.0),
+                ];
+                let references = (1..=6)
+                    .map(|v| VertexDataReference::new(v, 0, 0))
+                    .collect::<Vec<VertexDataReference>>();
+
+                let result = triangulate(&references, &vertices);
+
+                let areas = result
+                    .chunks(3)
+                    .map(|triangle| {
+                        let a = vertices[triangle[0].v - 1];
+                        let b = vertices[triangle[1].v - 1];
+                        let c = vertices[triangle[2].v - 1];
+                        cross((a.y, a.z), (b.y, b.z), (c.y, c.z)) / 2.0
+                    })
+                    .collect::<Vec<f32>>();
+
+                assert_eq!(result.len(), 4 * 3);
+                assert!(areas.iter().all(|area| *area > 0.0));
+                assert_eq!(areas.iter().sum::<f32>(), 3.0);
+            }
+
+            #[test]
+            fn it_should_skip_collinear_and_duplicated_points() {
+                let (references, vertices) = polygon(&[
+                    (0.0, 0.0),
+                    (1.0, 0.0),
+                    (1.0, 0.0),
+                    (2.0, 0.0),
+                    (2.0, 2.0),
+                    (0.0, 2.0),
+                    (0.0, 0.0),
+                ]);
+
+                let result = triangulate(&references, &vertices);
+
+                assert_eq!(area(&result, &vertices), 4.0);
+                assert_same_winding(&result, &vertices);
+                assert!(result
+                    .iter()
+                    .all(|reference| reference.v != 3 && reference.v != 7));
+            }
+
+            #[test]
+            fn it_should_ignore_degenerate_polygons() {
+                let (references, vertices) =
+                    polygon(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]);
+                assert!(triangulate(&references, &vertices).is_empty());
+
+                let (references, vertices) =
+                    polygon(&[(1.0, 1.0), (1.0, 1.0), (1.0, 1.0), (1.0, 1.0)]);
+                assert!(triangulate(&references, &vertices).is_empty());
+            }
         }
     }
 }
@@ -0,0 +1,197 @@
+use super::lexer::{Line, Tokens};
+
+///
+/// How the parsers react to invalid statements
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ParseMode {
+    ///
+    /// Stop at the first invalid statement and return its error
+    ///
+    #[default]
+    Strict,
+    ///
+    /// Skip invalid statements and report them as diagnostics, only problems that leave nothing
+    /// to load (such as a file without vertices) are still errors
+    ///
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    ///
+    /// The statement was understood but ignored, such as an unknown statement
+    ///
+    Warning,
+    ///
+    /// The statement is invalid and was skipped
+    ///
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+///
+/// A problem found while parsing in `ParseMode::Lenient`, or a warning about a file the parsed
+/// one refers to
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    ///
+    /// 1-based physical line of `token`, it differs from the line in `message` when the
+    /// statement is continued with `\` before the token. It is 0 for the warnings that are not
+    /// about a statement.
+    ///
+    pub line: usize,
+    ///
+    /// 1-based column of `token`, in bytes
+    ///
+    pub column: usize,
+    ///
+    /// Byte offset of `token` from the start of the file
+    ///
+    pub offset: usize,
+    ///
+    /// The offending token, empty when a token is missing at the end of the statement
+    ///
+    pub token: String,
+    pub severity: Severity,
+    ///
+    /// Same message as the error returned in `ParseMode::Strict`, it includes the line
+    ///
+    pub message: String,
+    ///
+    /// Form of the statement, such as `v x y z [w]`
+    ///
+    pub expected: Option<&'static str>,
+}
+
+impl Diagnostic {
+    ///
+    /// Diagnostic pointing at the last token read from `tokens`
+    ///
+    pub fn new(
+        line: &Line,
+        tokens: &Tokens,
+        severity: Severity,
+        message: String,
+        expected: Option<&'static str>,
+    ) -> Diagnostic {
+        let (index, token) = tokens.current();
+        let position = line.position(index);
+
+        Diagnostic {
+            line: position.line,
+            column: position.column,
+            offset: position.offset,
+            token: token.to_string(),
+            severity,
+            message,
+            expected,
+        }
+    }
+
+    ///
+    /// Warning that is not about a statement of the file, such as a file it refers to that
+    /// can't be read, it has no position
+    ///
+    pub fn warning(message: String) -> Diagnostic {
+        Diagnostic {
+            line: 0,
+            column: 0,
+            offset: 0,
+            token: String::new(),
+            severity: Severity::Warning,
+            message,
+            expected: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.severity, self.message);
+        }
+        write!(
+            f,
+            "{}: {} (column {}",
+            self.severity, self.message, self.column
+        )?;
+        if !self.token.is_empty() {
+            write!(f, ", found '{}'", self.token)?;
+        }
+        write!(f, ")")?;
+        if let Some(expected) = self.expected {
+            write!(f, ", expected '{}'", expected)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Collects the diagnostics of a file, or stops at the first error in `ParseMode::Strict`
+///
+#[derive(Debug, Clone, Default)]
+pub struct Reporter {
+    pub mode: ParseMode,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Reporter {
+    pub fn new(mode: ParseMode) -> Reporter {
+        Reporter {
+            mode,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    ///
+    /// Returns `error` in `ParseMode::Strict`, otherwise records it and lets the parser skip
+    /// the statement
+    ///
+    pub fn report<E: std::fmt::Display>(
+        &mut self,
+        error: E,
+        severity: Severity,
+        line: &Line,
+        tokens: &Tokens,
+        expected: Option<&'static str>,
+    ) -> Result<(), E> {
+        match self.mode {
+            ParseMode::Strict => Err(error),
+            ParseMode::Lenient => {
+                self.diagnostics.push(Diagnostic::new(
+                    line,
+                    tokens,
+                    severity,
+                    error.to_string(),
+                    expected,
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    ///
+    /// Records a problem that is silently ignored in `ParseMode::Strict`
+    ///
+    pub fn warn(&mut self, message: String, line: &Line, tokens: &Tokens) {
+        if self.mode == ParseMode::Lenient {
+            self.diagnostics.push(Diagnostic::new(
+                line,
+                tokens,
+                Severity::Warning,
+                message,
+                None,
+            ));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//!
+//! Files written by the tests that load object files and their material libraries
+//!
+
+use std::path::PathBuf;
+
+///
+/// Writes the `files`, a name and a content, into `directory` under the temporary directory and
+/// returns its path
+///
+pub fn write_fixture(directory: &str, files: &[(&str, &str)]) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("basis-wavefront-{directory}"));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    for (name, content) in files {
+        std::fs::write(directory.join(name), content).unwrap();
+    }
+
+    directory
+}
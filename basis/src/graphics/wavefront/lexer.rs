@@ -0,0 +1,311 @@
+//!
+//! Lexer shared by the Wavefront parsers.
+//!
+//! Statements are read one at a time by `LineReader`, which joins the lines continued with a
+//! trailing `\`, accepts `\n` and `\r\n` line endings and removes the comments. A comment starts
+//! with a token beginning with `#`, either at the start of a line or after data, so names such
+//! as `Material#1` are kept. `Tokens` then splits the statement on any ASCII whitespace.
+//!
+
+use std::io::BufRead;
+
+///
+/// Where something is in a file
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    ///
+    /// 1-based physical line
+    ///
+    pub line: usize,
+    ///
+    /// 1-based column, in bytes
+    ///
+    pub column: usize,
+    ///
+    /// Byte offset from the start of the file
+    ///
+    pub offset: usize,
+}
+
+///
+/// Part of a logical line that comes from one physical line
+///
+#[derive(Debug, Clone, Default)]
+struct Segment {
+    start: usize,
+    number: usize,
+    offset: usize,
+}
+
+///
+/// A statement, made of one physical line or of several ones joined by `\` continuations
+///
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    ///
+    /// 1-based physical line where the statement starts
+    ///
+    pub number: usize,
+    ///
+    /// Text of the statement without its comment, continued lines are joined with a space
+    /// instead of the `\`
+    ///
+    pub text: String,
+    segments: Vec<Segment>,
+}
+
+impl Line {
+    ///
+    /// Position in the file of the byte at `index` in `Line::text`
+    ///
+    pub fn position(&self, index: usize) -> Position {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.start <= index)
+            .cloned()
+            .unwrap_or_default();
+
+        Position {
+            line: segment.number,
+            column: index - segment.start + 1,
+            offset: segment.offset + index - segment.start,
+        }
+    }
+}
+
+///
+/// Reads the statements of a Wavefront file one at a time, while keeping track of the physical
+/// line numbers and byte offsets
+///
+pub struct LineReader<R: BufRead> {
+    reader: R,
+    buffer: String,
+    number: usize,
+    offset: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            buffer: String::new(),
+            number: 0,
+            offset: 0,
+        }
+    }
+
+    ///
+    /// Reads the next statement into `line`, reusing its memory. Returns `false` at the end of
+    /// the file.
+    ///
+    pub fn read_line(&mut self, line: &mut Line) -> std::io::Result<bool> {
+        line.text.clear();
+        line.segments.clear();
+
+        loop {
+            self.buffer.clear();
+            let length = self.reader.read_line(&mut self.buffer)?;
+            if length == 0 {
+                return Ok(!line.segments.is_empty());
+            }
+
+            self.number += 1;
+            if line.segments.is_empty() {
+                line.number = self.number;
+            }
+            line.segments.push(Segment {
+                start: line.text.len(),
+                number: self.number,
+                offset: self.offset,
+            });
+            self.offset += length;
+
+            let content = strip_comment(self.buffer.trim_end_matches(['\n', '\r']));
+            match content.trim_end().strip_suffix('\\') {
+                Some(continued) => {
+                    line.text.push_str(continued);
+                    line.text.push(' ');
+                }
+                None => {
+                    line.text.push_str(content);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+///
+/// `line` without its comment, a `#` only starts a comment at the beginning of a token
+///
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous.is_ascii_whitespace() {
+            return &line[..index];
+        }
+        previous = c;
+    }
+
+    line
+}
+
+///
+/// Splits a statement into its whitespace separated tokens while remembering where the last one
+/// was found, so errors can point at the offending token
+///
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    line: &'a str,
+    position: usize,
+    current: Option<(usize, &'a str)>,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(line: &'a str) -> Tokens<'a> {
+        Tokens {
+            line,
+            position: 0,
+            current: None,
+        }
+    }
+
+    ///
+    /// Byte index in the line and text of the token returned by the last call to `next`, or
+    /// the index right after the end of the line and an empty token when the line had no more
+    /// tokens
+    ///
+    pub fn current(&self) -> (usize, &'a str) {
+        match self.current {
+            Some(current) => current,
+            None => (self.line.trim_ascii_end().len(), ""),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = &self.line[self.position..];
+        let start = self.position + (rest.len() - rest.trim_ascii_start().len());
+        let length = self.line[start..]
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(self.line.len() - start);
+
+        if length == 0 {
+            self.position = self.line.len();
+            self.current = None;
+            return None;
+        }
+
+        let token = &self.line[start..start + length];
+        self.position = start + length;
+        self.current = Some((start, token));
+
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_track_physical_lines_and_offsets() {
+        let file = "v 1 2 3\n\n# comment\r\nf 1 \\\n  2 3\nvt 1\\\n";
+        let mut reader = LineReader::new(file.as_bytes());
+        let mut line = Line::default();
+        let mut lines = Vec::new();
+
+        while reader.read_line(&mut line).expect("This should work") {
+            lines.push((line.number, line.text.clone()));
+        }
+
+        assert_eq!(
+            lines,
+            vec![
+                (1, "v 1 2 3".to_string()),
+                (2, "".to_string()),
+                (3, "".to_string()),
+                (4, "f 1    2 3".to_string()),
+                (6, "vt 1 ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_remove_comments() {
+        let file = "v\t1\t2 3 # corner\r\nusemtl Material#1\n#v 1 2 3\nf 1 2 3 \\\n# \\\n";
+        let mut reader = LineReader::new(file.as_bytes());
+        let mut line = Line::default();
+        let mut lines = Vec::new();
+
+        while reader.read_line(&mut line).expect("This should work") {
+            lines.push(
+                Tokens::new(&line.text)
+                    .map(String::from)
+                    .collect::<Vec<String>>(),
+            );
+        }
+
+        assert_eq!(
+            lines,
+            vec![
+                vec!["v", "1", "2", "3"],
+                vec!["usemtl", "Material#1"],
+                Vec::<&str>::new(),
+                // a comment ends the continuation
+                vec!["f", "1", "2", "3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_locate_bytes_of_continued_lines() {
+        let file = "v 1\nf 1 \\\n  2 3\n";
+        let mut reader = LineReader::new(file.as_bytes());
+        let mut line = Line::default();
+
+        reader.read_line(&mut line).expect("This should work");
+        reader.read_line(&mut line).expect("This should work");
+
+        let two = line.text.find('2').expect("This should work");
+        assert_eq!(
+            line.position(0),
+            Position {
+                line: 2,
+                column: 1,
+                offset: 4
+            }
+        );
+        assert_eq!(
+            line.position(two),
+            Position {
+                line: 3,
+                column: 3,
+                offset: 12
+            }
+        );
+        assert_eq!(&file[12..13], "2");
+    }
+
+    #[test]
+    fn it_should_split_tokens_and_remember_their_index() {
+        let mut tokens = Tokens::new("  v\t1.0  -2 ");
+
+        assert_eq!(tokens.next(), Some("v"));
+        assert_eq!(tokens.current(), (2, "v"));
+        assert_eq!(tokens.next(), Some("1.0"));
+        assert_eq!(tokens.current(), (4, "1.0"));
+        assert_eq!(tokens.next(), Some("-2"));
+        assert_eq!(tokens.current(), (9, "-2"));
+        assert_eq!(tokens.next(), None);
+        assert_eq!(tokens.current(), (11, ""));
+        assert_eq!(tokens.next(), None);
+    }
+}
@@ -1,2 +1,96 @@
+pub mod diagnostic;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod lexer;
 pub mod mtl;
 pub mod obj;
+pub mod writer;
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnostic::ParseMode, obj};
+
+    ///
+    /// The same cube exported by different tools, see `corpus/`
+    ///
+    const CORPUS: [(&str, Option<&str>); 5] = [
+        ("blender.obj", Some("Material")),
+        ("maya.obj", Some("initialShadingGroup")),
+        ("3dsmax.obj", Some("wire_088144225")),
+        ("meshlab.obj", None),
+        ("continued.obj", None),
+    ];
+
+    fn load_corpus(name: &str, mode: ParseMode) -> obj::OBJ {
+        let file_path = format!(
+            "{}/src/graphics/wavefront/corpus/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let options = obj::LoadOptions {
+            mode,
+            ..Default::default()
+        };
+
+        obj::load_with_options(&file_path, &options)
+            .unwrap_or_else(|error| panic!("{name} should load: {error}"))
+    }
+
+    ///
+    /// Positions of the corners of every face
+    ///
+    fn corners(obj: &obj::OBJ) -> Vec<Vec<[f32; 3]>> {
+        obj.faces
+            .iter()
+            .map(|face| {
+                face.vertex_references
+                    .iter()
+                    .map(|reference| {
+                        let vertex = obj.vertices[reference.v - 1];
+                        [vertex.x, vertex.y, vertex.z]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_should_load_the_corpus_without_diagnostics() {
+        let reference = load_corpus("blender.obj", ParseMode::Strict);
+
+        for (name, material) in CORPUS {
+            let strict = load_corpus(name, ParseMode::Strict);
+            let lenient = load_corpus(name, ParseMode::Lenient);
+
+            assert!(
+                lenient.diagnostics.is_empty(),
+                "{name}: {:?}",
+                lenient.diagnostics
+            );
+            assert!(
+                lenient.warnings.is_empty(),
+                "{name}: {:?}",
+                lenient.warnings
+            );
+            assert_eq!(strict.vertices.len(), 8, "{name}");
+            assert_eq!(corners(&strict), corners(&reference), "{name}");
+
+            for face in &strict.faces {
+                let name_used = face.material.as_ref().map(|used| used.name.as_str());
+                assert_eq!(name_used, material, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_keep_the_values_of_the_corpus_materials() {
+        let maya = load_corpus("maya.obj", ParseMode::Strict);
+        let max = load_corpus("3dsmax.obj", ParseMode::Strict);
+        let maya = maya.faces[0].material.as_ref().expect("This should work");
+        let max = max.faces[0].material.as_ref().expect("This should work");
+
+        assert_eq!(maya.diffuse_reflectivity.rgb().r, 0.5);
+        assert_eq!(maya.optical_density, 1.0);
+        assert_eq!(max.specular_highlight_exponent, 32.0);
+        assert_eq!(max.diffuse_reflectivity.rgb().b, 0.8824);
+    }
+}
@@ -0,0 +1,380 @@
+use std::path::{Path, PathBuf};
+
+use super::structs::{ParseError, RGB};
+use super::LoadMTLError;
+use crate::graphics::wavefront::{
+    lexer::{Line, LineReader, Tokens},
+    obj::parse_float,
+};
+
+///
+/// Wavelength, in nanometers, of the first sample of the tables below
+///
+const CIE_START: f32 = 380.0;
+///
+/// Distance, in nanometers, between two samples of the tables below
+///
+const CIE_STEP: f32 = 10.0;
+
+///
+/// CIE 1931 2° standard observer color matching functions x̄ ȳ z̄, from 380nm to 780nm
+///
+#[rustfmt::skip]
+const CIE_1931: [[f32; 3]; 41] = [
+    [0.001368, 0.000039, 0.00645], [0.004243, 0.00012, 0.02005],
+    [0.01431, 0.000396, 0.06785], [0.04351, 0.00121, 0.2074],
+    [0.13438, 0.004, 0.6456], [0.2839, 0.0116, 1.3856],
+    [0.34828, 0.023, 1.74706], [0.3362, 0.038, 1.77211],
+    [0.2908, 0.06, 1.6692], [0.19536, 0.09098, 1.28764],
+    [0.09564, 0.13902, 0.81295], [0.03201, 0.20802, 0.46518],
+    [0.0049, 0.323, 0.272], [0.0093, 0.503, 0.1582],
+    [0.06327, 0.71, 0.07825], [0.1655, 0.862, 0.04216],
+    [0.2904, 0.954, 0.0203], [0.43345, 0.99495, 0.00875],
+    [0.5945, 0.995, 0.0039], [0.7621, 0.952, 0.0021],
+    [0.9163, 0.87, 0.00165], [1.0263, 0.757, 0.0011],
+    [1.0622, 0.631, 0.0008], [1.0026, 0.503, 0.00034],
+    [0.85445, 0.381, 0.00019], [0.6424, 0.265, 0.00005],
+    [0.4479, 0.175, 0.00002], [0.2835, 0.107, 0.0],
+    [0.1649, 0.061, 0.0], [0.0874, 0.032, 0.0],
+    [0.04677, 0.017, 0.0], [0.0227, 0.00821, 0.0],
+    [0.011359, 0.004102, 0.0], [0.00579, 0.002091, 0.0],
+    [0.002899, 0.001047, 0.0], [0.00144, 0.00052, 0.0],
+    [0.00069, 0.000249, 0.0], [0.000332, 0.00012, 0.0],
+    [0.000166, 0.00006, 0.0], [0.000083, 0.00003, 0.0],
+    [0.000042, 0.000015, 0.0],
+];
+
+///
+/// Relative spectral power of the CIE standard illuminant D65, the white point of sRGB, from
+/// 380nm to 780nm
+///
+#[rustfmt::skip]
+const D65: [f32; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342,
+    95.788, 88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778,
+    78.2842, 69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054,
+    63.3828,
+];
+
+///
+/// XYZ to linear sRGB, for the D65 white point
+///
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.969266, 1.8760108, 0.041556],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// "x y z" are the values of the CIE XYZ color space.  The y and z arguments
+/// are optional.  If only x is specified, then y and z are assumed to be
+/// equal to x.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XYZ {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl XYZ {
+    ///
+    /// Linear sRGB color, the values are not clamped so colors outside of the sRGB gamut have
+    /// components below 0 or above 1
+    ///
+    pub fn to_rgb(&self) -> RGB {
+        let [r, g, b] = XYZ_TO_SRGB.map(|row| row[0] * self.x + row[1] * self.y + row[2] * self.z);
+
+        RGB { r, g, b }
+    }
+}
+
+///
+/// Reflectance curve of a `.rfl` file, the samples are sorted by wavelength
+///
+/// The file lists one "wavelength reflectance" pair per line, the wavelength in nanometers and
+/// the reflectance usually between 0.0 and 1.0
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReflectanceCurve {
+    pub samples: Vec<(f32, f32)>,
+}
+
+impl ReflectanceCurve {
+    pub fn parse(data: &str) -> Result<ReflectanceCurve, ParseError> {
+        let mut samples: Vec<(f32, f32)> = Vec::new();
+
+        let mut reader = LineReader::new(data.as_bytes());
+        let mut line = Line::default();
+
+        // reading from a `&str` cannot fail, the data is already valid UTF-8
+        while let Ok(true) = reader.read_line(&mut line) {
+            let line_n = line.number;
+            let mut tokens = Tokens::new(&line.text);
+            let Some(wavelength) = tokens.next() else {
+                continue;
+            };
+            let invalid = |message: String| ParseError::ReflectanceCurve(line_n, message);
+
+            let wavelength = parse_float(wavelength)
+                .map_err(|_| invalid(format!("Invalid wavelength '{wavelength}'")))?;
+            let reflectance = tokens
+                .next()
+                .ok_or_else(|| invalid("Missing reflectance value".to_string()))?;
+            let reflectance = parse_float(reflectance)
+                .map_err(|_| invalid(format!("Invalid reflectance '{reflectance}'")))?;
+
+            if tokens.next().is_some() {
+                return Err(invalid("Expected 'wavelength reflectance'".to_string()));
+            }
+            if wavelength <= 0.0 {
+                return Err(invalid("The wavelength should be positive".to_string()));
+            }
+            if samples.last().is_some_and(|&(last, _)| wavelength <= last) {
+                return Err(invalid(
+                    "The wavelengths should be in increasing order".to_string(),
+                ));
+            }
+
+            samples.push((wavelength, reflectance));
+        }
+
+        if samples.is_empty() {
+            return Err(ParseError::ReflectanceCurve(
+                line.number,
+                "The curve needs at least 1 sample".to_string(),
+            ));
+        }
+
+        Ok(ReflectanceCurve { samples })
+    }
+
+    pub fn load(file_path: impl AsRef<Path>) -> Result<ReflectanceCurve, LoadMTLError> {
+        let file_path = file_path.as_ref();
+        let file_content = std::fs::read_to_string(file_path)?;
+
+        ReflectanceCurve::parse(&file_content).map_err(|error| match error {
+            ParseError::ReflectanceCurve(line, message) => {
+                let message = format!("{}: {message}", file_path.display());
+                ParseError::ReflectanceCurve(line, message).into()
+            }
+            error => error.into(),
+        })
+    }
+
+    ///
+    /// Reflectance at `wavelength`, linearly interpolated between the samples, the curve is
+    /// flat before the first sample and after the last one
+    ///
+    pub fn reflectance(&self, wavelength: f32) -> f32 {
+        let next = self
+            .samples
+            .partition_point(|&(sample, _)| sample < wavelength);
+
+        match (
+            self.samples.get(next.wrapping_sub(1)),
+            self.samples.get(next),
+        ) {
+            (Some(&(w0, r0)), Some(&(w1, r1))) => r0 + (r1 - r0) * (wavelength - w0) / (w1 - w0),
+            (None, Some(&(_, r))) | (Some(&(_, r)), None) => r,
+            (None, None) => 0.0,
+        }
+    }
+
+    ///
+    /// Color of a surface with this reflectance lit by the D65 illuminant, a perfect white
+    /// reflector has a Y of 1.0
+    ///
+    pub fn to_xyz(&self) -> XYZ {
+        let mut xyz = [0.0; 3];
+        let mut white = 0.0;
+
+        for (i, (cmf, power)) in CIE_1931.iter().zip(D65).enumerate() {
+            let reflectance = self.reflectance(CIE_START + CIE_STEP * i as f32);
+            for (value, weight) in xyz.iter_mut().zip(cmf) {
+                *value += reflectance * power * weight;
+            }
+            white += power * cmf[1];
+        }
+
+        let [x, y, z] = xyz.map(|value| value / white);
+        XYZ { x, y, z }
+    }
+}
+
+///
+/// "spectral file.rfl factor" colors, the curve is read when the library is loaded with
+/// `mtl::load`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectral {
+    pub file_path: PathBuf,
+    pub factor: f32,
+    pub curve: Option<ReflectanceCurve>,
+}
+
+impl Spectral {
+    pub fn new(file_path: impl Into<PathBuf>, factor: f32) -> Self {
+        Spectral {
+            file_path: file_path.into(),
+            factor,
+            curve: None,
+        }
+    }
+
+    ///
+    /// Resolves a relative path against `directory` and reads the reflectance curve
+    ///
+    pub fn load_curve(&mut self, directory: &Path) -> Result<(), LoadMTLError> {
+        if self.file_path.is_relative() {
+            self.file_path = directory.join(&self.file_path);
+        }
+        self.curve = Some(ReflectanceCurve::load(&self.file_path)?);
+
+        Ok(())
+    }
+}
+
+///
+/// The three forms of the "Ka", "Kd", "Ks" and "Tf" statements
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    RGB(RGB),
+    XYZ(XYZ),
+    Spectral(Spectral),
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::RGB(RGB::default())
+    }
+}
+
+impl Color {
+    ///
+    /// Linear RGB value used for the rendering.
+    ///
+    /// A spectral color whose curve is not loaded is evaluated as a perfect white reflector
+    /// scaled by its factor.
+    ///
+    pub fn rgb(&self) -> RGB {
+        match self {
+            Color::RGB(rgb) => rgb.clone(),
+            Color::XYZ(xyz) => xyz.to_rgb(),
+            Color::Spectral(spectral) => {
+                let factor = spectral.factor;
+                let rgb = match &spectral.curve {
+                    Some(curve) => curve.to_xyz().to_rgb(),
+                    None => RGB {
+                        r: 1.0,
+                        g: 1.0,
+                        b: 1.0,
+                    },
+                };
+
+                RGB {
+                    r: rgb.r * factor,
+                    g: rgb.g * factor,
+                    b: rgb.b * factor,
+                }
+            }
+        }
+    }
+}
+
+impl From<RGB> for Color {
+    fn from(rgb: RGB) -> Self {
+        Color::RGB(rgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rgb(rgb: RGB, expected: [f32; 3], epsilon: f32) {
+        let actual = [rgb.r, rgb.g, rgb.b];
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < epsilon, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_parse_reflectance_curves() {
+        let file = "
+            # red paint
+            400 0.1
+            500 0.2 # comment
+            700 0.8
+";
+
+        let curve = ReflectanceCurve::parse(file).expect("This should work");
+
+        assert_eq!(curve.samples, vec![(400.0, 0.1), (500.0, 0.2), (700.0, 0.8)]);
+        assert_eq!(curve.reflectance(300.0), 0.1);
+        assert_eq!(curve.reflectance(450.0), 0.15);
+        assert_eq!(curve.reflectance(500.0), 0.2);
+        assert_eq!(curve.reflectance(600.0), 0.5);
+        assert_eq!(curve.reflectance(800.0), 0.8);
+    }
+
+    #[test]
+    fn it_should_fail_to_parse_invalid_reflectance_curves() {
+        let cases = [
+            ("", "Invalid reflectance curve at line 0: The curve needs at least 1 sample"),
+            ("400", "Invalid reflectance curve at line 1: Missing reflectance value"),
+            ("red 0.5", "Invalid reflectance curve at line 1: Invalid wavelength 'red'"),
+            ("400 high", "Invalid reflectance curve at line 1: Invalid reflectance 'high'"),
+            ("400 0.5 1", "Invalid reflectance curve at line 1: Expected 'wavelength reflectance'"),
+            ("-400 0.5", "Invalid reflectance curve at line 1: The wavelength should be positive"),
+            (
+                "500 0.5\n400 0.5",
+                "Invalid reflectance curve at line 2: The wavelengths should be in increasing order",
+            ),
+        ];
+
+        for (file, expected) in cases {
+            let error = ReflectanceCurve::parse(file).unwrap_err();
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn it_should_convert_to_linear_srgb() {
+        let white = XYZ {
+            x: 0.95047,
+            y: 1.0,
+            z: 1.08883,
+        };
+        let red = XYZ {
+            x: 0.4124564,
+            y: 0.2126729,
+            z: 0.0193339,
+        };
+
+        assert_rgb(white.to_rgb(), [1.0, 1.0, 1.0], 1e-3);
+        assert_rgb(red.to_rgb(), [1.0, 0.0, 0.0], 1e-3);
+    }
+
+    #[test]
+    fn it_should_evaluate_every_color_to_rgb() {
+        let flat = ReflectanceCurve {
+            samples: vec![(380.0, 0.5)],
+        };
+        let mut spectral = Spectral::new("flat.rfl", 2.0);
+        let unloaded = Color::Spectral(spectral.clone());
+        spectral.curve = Some(flat);
+        let xyz = Color::XYZ(XYZ {
+            x: 0.95047,
+            y: 1.0,
+            z: 1.08883,
+        });
+
+        // a flat curve reflects every wavelength of the illuminant alike, so it is a grey
+        assert_rgb(Color::Spectral(spectral).rgb(), [1.0, 1.0, 1.0], 1e-2);
+        assert_rgb(unloaded.rgb(), [2.0, 2.0, 2.0], 1e-6);
+        assert_rgb(xyz.rgb(), [1.0, 1.0, 1.0], 1e-3);
+        assert_rgb(Color::default().rgb(), [0.0, 0.0, 0.0], 1e-6);
+    }
+}
@@ -1,179 +1,195 @@
-use std::{iter::Peekable, slice::Iter, vec::IntoIter};
+use std::iter::Peekable;
 
-use super::structs::{DissolveFactor, IlluminationModel, Material, ParseError, RGB};
+use super::color::{Color, Spectral, XYZ};
+use super::structs::{
+    DissolveFactor, IlluminationModel, ImageChannel, Material, ParseError, TextureMap, RGB, UVW,
+};
+use crate::graphics::wavefront::{lexer::Tokens, obj::parse_float};
 
-pub fn parse_material(
-    name: &str,
-    lines: &mut Peekable<Iter<&str>>,
-    initial_line_n: usize,
-) -> Result<(Material, usize), ParseError> {
-    let mut material = Material::default();
-    let mut read_lines = 0;
-
-    while let Some(line) = lines.next() {
-        let line_n = initial_line_n + read_lines;
-        let mut tokens = line
-            .split(" ")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<&str>>()
-            .into_iter();
-
-        let statement = tokens.next();
-
-        if statement.is_none() {
-            return Err(ParseError::InvalidToken(
-                line_n,
-                "Missing statement".to_string(),
-            ));
+///
+/// Applies a statement of a material, returns `false` when the statement is unknown
+///
+pub fn parse_material_statement(
+    statement: &str,
+    tokens: &mut Tokens<'_>,
+    material: &mut Material,
+    line_n: usize,
+) -> Result<bool, ParseError> {
+    match statement {
+        "Ka" => material.ambient_reflectivity = parse_material_color(tokens, line_n)?,
+        "Kd" => material.diffuse_reflectivity = parse_material_color(tokens, line_n)?,
+        "Ks" => material.atmosphere_reflectivity = parse_material_color(tokens, line_n)?,
+        "Tf" => material.transmission_filter = parse_material_color(tokens, line_n)?,
+        "illum" => material.illumination_model = parse_material_illumination_model(tokens, line_n)?,
+        "d" => material.dissolve_factor = parse_material_dissolve_factor(tokens, line_n)?,
+        "Ns" => {
+            material.specular_highlight_exponent = parse_material_value(tokens, "exponent", line_n)?
         }
-
-        material.name = name.to_string();
-
-        match statement.unwrap() {
-            "Ka" => {
-                material.ambient_reflectivity = parse_material_rgb(&mut tokens, line_n)?;
-            }
-            "Kd" => {
-                material.diffuse_reflectivity = parse_material_rgb(&mut tokens, line_n)?;
-            }
-            "Ks" => {
-                material.atmosphere_reflectivity = parse_material_rgb(&mut tokens, line_n)?;
-            }
-            "Tf" => {
-                material.transmission_filter = parse_material_rgb(&mut tokens, line_n)?;
-            }
-            "illum" => {
-                material.illumination_model =
-                    parse_material_illumination_model(&mut tokens, line_n)?;
-            }
-            "d" => {
-                material.dissolve_factor = parse_material_dissolve_factor(&mut tokens, line_n)?;
-            }
-            "Ns" => {
-                material.specular_highlight_exponent =
-                    parse_material_specular_highlight_exponent(&mut tokens, line_n)?;
-            }
-            "sharpness" => {
-                material.sharpness = parse_material_sharpness(&mut tokens, line_n)?;
-            }
-            "Ni" => {
-                material.optical_density = parse_material_optical_density(&mut tokens, line_n)?;
-            }
-            "#" => {
-                continue;
-            }
-            "newmtl" => {
-                break;
-            }
-            _ => {
-                // Err(ParseError::InvalidToken(
-                //     line_n,
-                //     format!("Unknown statement: '{unknown}'"),
-                // ))?
-                continue;
-            }
+        "sharpness" => material.sharpness = parse_material_value(tokens, "sharpness", line_n)?,
+        "Ni" => material.optical_density = parse_material_optical_density(tokens, line_n)?,
+        "map_Ka" => material.ambient_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Kd" => material.diffuse_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Ks" => material.atmosphere_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Ns" => {
+            material.specular_highlight_exponent_map =
+                Some(parse_material_texture_map(tokens, line_n)?)
         }
-
-        read_lines += 1;
+        "map_d" => material.dissolve_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "bump" | "map_bump" => {
+            material.bump_map = Some(parse_material_texture_map(tokens, line_n)?)
+        }
+        "disp" => material.displacement_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "decal" => material.decal_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "Pr" => material.roughness = parse_material_value(tokens, "Pr", line_n)?,
+        "Pm" => material.metallic = parse_material_value(tokens, "Pm", line_n)?,
+        "Ps" => material.sheen = parse_material_value(tokens, "Ps", line_n)?,
+        "Pc" => material.clearcoat_thickness = parse_material_value(tokens, "Pc", line_n)?,
+        "Pcr" => material.clearcoat_roughness = parse_material_value(tokens, "Pcr", line_n)?,
+        "Ke" => material.emissive = parse_material_color(tokens, line_n)?,
+        "aniso" => material.anisotropy = parse_material_value(tokens, "aniso", line_n)?,
+        "anisor" => material.anisotropy_rotation = parse_material_value(tokens, "anisor", line_n)?,
+        "map_Pr" => material.roughness_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Pm" => material.metallic_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Ps" => material.sheen_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "map_Ke" => material.emissive_map = Some(parse_material_texture_map(tokens, line_n)?),
+        "norm" => material.normal_map = Some(parse_material_texture_map(tokens, line_n)?),
+        _ => return Ok(false),
     }
 
-    Ok((material, read_lines))
+    Ok(true)
 }
 
-fn parse_material_rgb(tokens: &mut IntoIter<&str>, line_n: usize) -> Result<RGB, ParseError> {
-    let r = tokens
-        .next()
-        .unwrap()
-        .parse::<f32>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid R value".to_string()))?;
-
-    let g = tokens
-        .next()
-        .map_or(r.to_string().as_str(), |s| s)
-        .parse::<f32>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid G value".to_string()))?;
-
-    let b = tokens
-        .next()
-        .map_or(r.to_string().as_str(), |s| s)
-        .parse::<f32>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid B value".to_string()))?;
-
-    Ok(RGB { r, g, b })
+///
+/// Form of the statements, used by the diagnostics
+///
+pub fn expected_form(statement: &str) -> Option<&'static str> {
+    match statement {
+        "Ka" | "Kd" | "Ks" | "Tf" | "Ke" => {
+            Some("K r [g] [b] | K spectral file.rfl [factor] | K xyz x [y] [z]")
+        }
+        "illum" => Some("illum 0-10"),
+        "d" => Some("d [-halo] factor"),
+        "Ns" => Some("Ns exponent"),
+        "sharpness" => Some("sharpness value"),
+        "Ni" => Some("Ni 0.001-10"),
+        "Pr" => Some("Pr roughness"),
+        "Pm" => Some("Pm metallic"),
+        "Ps" => Some("Ps sheen"),
+        "Pc" => Some("Pc thickness"),
+        "Pcr" => Some("Pcr roughness"),
+        "aniso" => Some("aniso anisotropy"),
+        "anisor" => Some("anisor rotation"),
+        "map_Ka" | "map_Kd" | "map_Ks" | "map_Ns" | "map_d" | "bump" | "map_bump" | "disp"
+        | "decal" | "map_Pr" | "map_Pm" | "map_Ps" | "map_Ke" | "norm" => {
+            Some("map [-option value ...] file")
+        }
+        _ => None,
+    }
 }
 
-fn parse_material_illumination_model(
-    tokens: &mut IntoIter<&str>,
+///
+/// Parses the next token as a float named `name` in the error messages
+///
+fn parse_material_value(
+    tokens: &mut Tokens<'_>,
+    name: &str,
     line_n: usize,
-) -> Result<IlluminationModel, ParseError> {
-    let model = tokens
+) -> Result<f32, ParseError> {
+    let token = tokens
         .next()
-        .unwrap()
-        .parse::<IlluminationModel>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid 'illumn_#' value".to_string()))?;
+        .ok_or_else(|| ParseError::InvalidToken(line_n, format!("Missing '{name}' value")))?;
 
-    Ok(model)
+    parse_float(token)
+        .map_err(|_| ParseError::InvalidToken(line_n, format!("Invalid '{name}' value")))
 }
 
-fn parse_material_dissolve_factor(
-    tokens: &mut IntoIter<&str>,
-    line_n: usize,
-) -> Result<DissolveFactor, ParseError> {
-    let token = tokens.next().unwrap();
+///
+/// Parses "r [g] [b]", "spectral file.rfl [factor]" or "xyz x [y] [z]"
+///
+fn parse_material_color(tokens: &mut Tokens<'_>, line_n: usize) -> Result<Color, ParseError> {
+    let mut tokens = tokens.peekable();
 
-    if token == "-halo" {
-        let factor = tokens
-            .next()
-            .unwrap()
-            .parse::<f32>()
-            .map_err(|_| ParseError::InvalidToken(line_n, "Invalid 'd' value".to_string()))?;
+    if tokens.next_if_eq(&"spectral").is_some() {
+        let file_path = tokens.next().ok_or_else(|| {
+            ParseError::InvalidToken(line_n, "Missing '.rfl' file name".to_string())
+        })?;
+        let factor = match tokens.next() {
+            Some(token) => parse_float(token).map_err(|_| {
+                ParseError::InvalidToken(line_n, "Invalid spectral factor value".to_string())
+            })?,
+            None => 1.0,
+        };
 
-        return Ok(DissolveFactor { factor, halo: true });
+        return Ok(Color::Spectral(Spectral::new(file_path, factor)));
     }
 
-    let factor = token
-        .parse::<f32>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid 'd' value".to_string()))?;
+    if tokens.next_if_eq(&"xyz").is_some() {
+        let [x, y, z] = parse_material_components(&mut tokens, ["X", "Y", "Z"], line_n)?;
+        return Ok(Color::XYZ(XYZ { x, y, z }));
+    }
 
-    Ok(DissolveFactor {
-        factor,
-        halo: false,
-    })
+    let [r, g, b] = parse_material_components(&mut tokens, ["R", "G", "B"], line_n)?;
+    Ok(Color::RGB(RGB { r, g, b }))
 }
 
-fn parse_material_specular_highlight_exponent(
-    tokens: &mut IntoIter<&str>,
+///
+/// Parses "a [b] [c]", the missing components are equal to the first one
+///
+fn parse_material_components<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    names: [&str; 3],
     line_n: usize,
-) -> Result<f32, ParseError> {
-    let exponent =
-        tokens.next().unwrap().parse::<f32>().map_err(|_| {
-            ParseError::InvalidToken(line_n, "Invalid 'exponent' value".to_string())
-        })?;
+) -> Result<[f32; 3], ParseError> {
+    let invalid = |name: &str| ParseError::InvalidToken(line_n, format!("Invalid {name} value"));
 
-    Ok(exponent)
+    let first = tokens
+        .next()
+        .and_then(|token| parse_float(token).ok())
+        .ok_or_else(|| invalid(names[0]))?;
+    let mut optional = |name: &str| match tokens.next() {
+        Some(token) => parse_float(token).map_err(|_| invalid(name)),
+        None => Ok(first),
+    };
+    let second = optional(names[1])?;
+    let third = optional(names[2])?;
+
+    Ok([first, second, third])
 }
 
-fn parse_material_sharpness(tokens: &mut IntoIter<&str>, line_n: usize) -> Result<f32, ParseError> {
-    let sharpness =
-        tokens.next().unwrap().parse::<f32>().map_err(|_| {
-            ParseError::InvalidToken(line_n, "Invalid 'sharpness' value".to_string())
-        })?;
+fn parse_material_illumination_model(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<IlluminationModel, ParseError> {
+    tokens
+        .next()
+        .and_then(|token| token.parse::<IlluminationModel>().ok())
+        .ok_or_else(|| ParseError::InvalidToken(line_n, "Invalid 'illumn_#' value".to_string()))
+}
 
-    Ok(sharpness)
+fn parse_material_dissolve_factor(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<DissolveFactor, ParseError> {
+    let mut tokens = tokens.peekable();
+    let halo = tokens.next_if_eq(&"-halo").is_some();
+    let factor = tokens
+        .next()
+        .and_then(|token| parse_float(token).ok())
+        .ok_or_else(|| ParseError::InvalidToken(line_n, "Invalid 'd' value".to_string()))?;
+
+    Ok(DissolveFactor { factor, halo })
 }
 
 fn parse_material_optical_density(
-    tokens: &mut IntoIter<&str>,
+    tokens: &mut Tokens<'_>,
     line_n: usize,
 ) -> Result<f32, ParseError> {
     let optical_density = tokens
         .next()
-        .unwrap()
-        .parse::<f32>()
-        .map_err(|_| ParseError::InvalidToken(line_n, "Invalid 'Ni' value".to_string()))?;
+        .and_then(|token| parse_float(token).ok())
+        .ok_or_else(|| ParseError::InvalidToken(line_n, "Invalid 'Ni' value".to_string()))?;
 
-    if optical_density < 0.001 || optical_density > 10.0 {
+    if !(0.001..=10.0).contains(&optical_density) {
         return Err(ParseError::InvalidValue(
             line_n,
             "'Ni' value should range between 0.001 and 10".to_string(),
@@ -182,3 +198,123 @@ fn parse_material_optical_density(
 
     Ok(optical_density)
 }
+
+///
+/// Parses "[options] filename" of a texture map statement
+///
+fn parse_material_texture_map(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<TextureMap, ParseError> {
+    let mut map = TextureMap::new("");
+    let mut tokens = tokens.peekable();
+
+    while let Some(option) = tokens.next_if(|token| token.starts_with('-')) {
+        match option {
+            "-blendu" => map.blend_u = parse_texture_map_switch(&mut tokens, option, line_n)?,
+            "-blendv" => map.blend_v = parse_texture_map_switch(&mut tokens, option, line_n)?,
+            "-cc" => map.color_correction = parse_texture_map_switch(&mut tokens, option, line_n)?,
+            "-clamp" => map.clamp = parse_texture_map_switch(&mut tokens, option, line_n)?,
+            "-bm" => map.bump_multiplier = parse_texture_map_value(&mut tokens, option, line_n)?,
+            "-boost" => map.boost = parse_texture_map_value(&mut tokens, option, line_n)?,
+            "-mm" => {
+                map.range_base = parse_texture_map_value(&mut tokens, option, line_n)?;
+                map.range_gain = parse_texture_map_value(&mut tokens, option, line_n)?;
+            }
+            "-o" => map.offset = parse_texture_map_uvw(&mut tokens, map.offset, option, line_n)?,
+            "-s" => map.scale = parse_texture_map_uvw(&mut tokens, map.scale, option, line_n)?,
+            "-t" => {
+                map.turbulence = parse_texture_map_uvw(&mut tokens, map.turbulence, option, line_n)?
+            }
+            "-texres" => {
+                let resolution = parse_texture_map_value(&mut tokens, option, line_n)?;
+                if resolution < 1.0 || resolution.fract() != 0.0 {
+                    return Err(ParseError::InvalidValue(
+                        line_n,
+                        "'-texres' value should be a positive integer".to_string(),
+                    ));
+                }
+                map.resolution = Some(resolution as u32);
+            }
+            "-imfchan" => {
+                let channel = tokens
+                    .next()
+                    .and_then(|token| token.parse::<ImageChannel>().ok());
+                if channel.is_none() {
+                    return Err(ParseError::InvalidToken(
+                        line_n,
+                        "Invalid '-imfchan' value, expected one of r, g, b, m, l or z".to_string(),
+                    ));
+                }
+                map.channel = channel;
+            }
+            unknown => {
+                return Err(ParseError::InvalidToken(
+                    line_n,
+                    format!("Unknown texture map option: '{unknown}'"),
+                ));
+            }
+        }
+    }
+
+    // file names may contain spaces
+    let file_path = tokens.collect::<Vec<&str>>().join(" ");
+    if file_path.is_empty() {
+        return Err(ParseError::InvalidToken(
+            line_n,
+            "Missing texture map file name".to_string(),
+        ));
+    }
+    map.file_path = file_path.into();
+
+    Ok(map)
+}
+
+fn parse_texture_map_switch(
+    tokens: &mut Peekable<&mut Tokens<'_>>,
+    option: &str,
+    line_n: usize,
+) -> Result<bool, ParseError> {
+    match tokens.next() {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err(ParseError::InvalidToken(
+            line_n,
+            format!("Invalid '{option}' value, expected 'on' or 'off'"),
+        )),
+    }
+}
+
+fn parse_texture_map_value(
+    tokens: &mut Peekable<&mut Tokens<'_>>,
+    option: &str,
+    line_n: usize,
+) -> Result<f32, ParseError> {
+    tokens
+        .next()
+        .and_then(|token| parse_float(token).ok())
+        .ok_or_else(|| ParseError::InvalidToken(line_n, format!("Invalid '{option}' value")))
+}
+
+///
+/// "u [v] [w]", the optional values keep the ones from `default`
+///
+fn parse_texture_map_uvw(
+    tokens: &mut Peekable<&mut Tokens<'_>>,
+    default: UVW,
+    option: &str,
+    line_n: usize,
+) -> Result<UVW, ParseError> {
+    let u = parse_texture_map_value(tokens, option, line_n)?;
+    let mut optional = || {
+        let value = tokens.peek().and_then(|token| parse_float(token).ok());
+        if value.is_some() {
+            tokens.next();
+        }
+        value
+    };
+    let v = optional().unwrap_or(default.v);
+    let w = optional().unwrap_or(default.w);
+
+    Ok(UVW { u, v, w })
+}
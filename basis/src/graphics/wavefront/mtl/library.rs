@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::structs::{Material, MTL};
+use super::{load_with_mode, LoadMTLError};
+use crate::graphics::wavefront::{
+    diagnostic::{Diagnostic, ParseMode},
+    obj::OBJ,
+};
+
+#[derive(Debug, Clone)]
+struct Library {
+    file_path: PathBuf,
+    mtl: MTL,
+    diagnostics: Vec<Diagnostic>,
+}
+
+///
+/// Material libraries shared by several object files, each library is read only once.
+///
+/// A material defined by several libraries of an object comes from the first one in the order
+/// of its `mtllib` statements. `override_material` replaces every material of the objects, such
+/// as a clay material to inspect the shapes, and `fallback` replaces the materials no library
+/// defines.
+///
+#[derive(Debug, Clone, Default)]
+pub struct MaterialLibrary {
+    pub override_material: Option<Material>,
+    pub fallback: Option<Material>,
+    libraries: Vec<Library>,
+    ///
+    /// Index of the library and name of the materials given to an object
+    ///
+    used: HashSet<(usize, String)>,
+    undefined: Vec<String>,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary::default()
+    }
+
+    ///
+    /// Reads a library, or returns the index of the one already read from the same file.
+    ///
+    /// The files are compared by their canonical path, so "a/../b.mtl" and "b.mtl" are read once.
+    /// A cached library keeps the diagnostics of the mode it was first read with.
+    ///
+    pub fn load(
+        &mut self,
+        file_path: impl AsRef<Path>,
+        mode: ParseMode,
+    ) -> Result<usize, LoadMTLError> {
+        let file_path = file_path.as_ref();
+        let canonical = std::fs::canonicalize(file_path)?;
+
+        if let Some(index) = self
+            .libraries
+            .iter()
+            .position(|library| library.file_path == canonical)
+        {
+            return Ok(index);
+        }
+
+        let (mtl, diagnostics) = load_with_mode(file_path, mode)?;
+        self.libraries.push(Library {
+            file_path: canonical,
+            mtl,
+            diagnostics,
+        });
+
+        Ok(self.libraries.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.libraries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.libraries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&MTL> {
+        self.libraries.get(index).map(|library| &library.mtl)
+    }
+
+    pub fn file_path(&self, index: usize) -> Option<&Path> {
+        self.libraries
+            .get(index)
+            .map(|library| library.file_path.as_path())
+    }
+
+    ///
+    /// Statements skipped when the library was read in `ParseMode::Lenient`
+    ///
+    pub fn diagnostics(&self, index: usize) -> &[Diagnostic] {
+        self.libraries
+            .get(index)
+            .map_or(&[], |library| library.diagnostics.as_slice())
+    }
+
+    ///
+    /// Material given to the elements using `name` in an object with the `libraries`
+    ///
+    pub fn find(&mut self, libraries: &[usize], name: &str) -> Option<Material> {
+        let found = self.lookup(libraries, name);
+        self.replace(found)
+    }
+
+    ///
+    /// Material defined for `name` by the first of the `libraries`, it is recorded as used or
+    /// undefined
+    ///
+    fn lookup(&mut self, libraries: &[usize], name: &str) -> Option<Material> {
+        let found = libraries.iter().find_map(|&index| {
+            let library = self.libraries.get(index)?;
+            library.mtl.get(name).map(|material| (index, material))
+        });
+
+        match found {
+            Some((index, material)) => {
+                let material = material.clone();
+                self.used.insert((index, name.to_string()));
+                Some(material)
+            }
+            None => {
+                if !self.undefined.iter().any(|undefined| undefined == name) {
+                    self.undefined.push(name.to_string());
+                }
+                None
+            }
+        }
+    }
+
+    fn replace(&self, found: Option<Material>) -> Option<Material> {
+        match (&self.override_material, found) {
+            (Some(material), _) => Some(material.clone()),
+            (None, Some(material)) => Some(material),
+            (None, None) => self.fallback.clone(),
+        }
+    }
+
+    ///
+    /// Names defined by more than one of the `libraries`, sorted
+    ///
+    pub fn collisions(&self, libraries: &[usize]) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut collisions = HashSet::new();
+
+        for library in libraries
+            .iter()
+            .filter_map(|&index| self.libraries.get(index))
+        {
+            for name in library.mtl.keys() {
+                if !seen.insert(name) {
+                    collisions.insert(name.clone());
+                }
+            }
+        }
+
+        let mut collisions = collisions.into_iter().collect::<Vec<String>>();
+        collisions.sort();
+        collisions
+    }
+
+    ///
+    /// Gives `obj` the `libraries` and the material of every face, point and line, the problems
+    /// are added to its warnings
+    ///
+    pub fn resolve(&mut self, obj: &mut OBJ, libraries: &[usize]) {
+        obj.mtls = libraries
+            .iter()
+            .filter_map(|&index| self.get(index).cloned())
+            .collect();
+
+        for name in self.collisions(libraries) {
+            obj.warnings.push(format!(
+                "Material '{}' is defined by several libraries, the first one is used",
+                name
+            ));
+        }
+
+        let mut undefined: Vec<String> = Vec::new();
+        obj.assign_materials(|name| match name {
+            Some(name) => {
+                let found = self.lookup(libraries, name);
+                if found.is_none() && !undefined.iter().any(|undefined| undefined == name) {
+                    undefined.push(name.to_string());
+                }
+                self.replace(found)
+            }
+            None => self.override_material.clone(),
+        });
+
+        for name in undefined {
+            obj.warnings
+                .push(format!("Material '{}' is not defined by any library", name));
+        }
+    }
+
+    ///
+    /// Materials of the read libraries that were never given to an object, by library then
+    /// name
+    ///
+    pub fn unused(&self) -> Vec<(&Path, &str)> {
+        let mut unused = Vec::new();
+
+        for (index, library) in self.libraries.iter().enumerate() {
+            let mut names = library
+                .mtl
+                .keys()
+                .filter(|name| !self.used.contains(&(index, name.to_string())))
+                .map(String::as_str)
+                .collect::<Vec<&str>>();
+            names.sort();
+            unused.extend(
+                names
+                    .into_iter()
+                    .map(|name| (library.file_path.as_path(), name)),
+            );
+        }
+
+        unused
+    }
+
+    ///
+    /// Names used by the objects that no library defines, in the order they were met
+    ///
+    pub fn undefined(&self) -> &[String] {
+        &self.undefined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::fixtures::write_fixture;
+    use crate::graphics::wavefront::obj::{self, LoadOptions};
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_read_shared_libraries_once() {
+        let directory = write_fixture("shared", &[
+            ("shared.mtl", "newmtl Rock\nKd 0.5 0.5 0.5\nnewmtl Sand\n"),
+            ("other.mtl", "newmtl Rock\nKd 1 0 0\nnewmtl Grass\n"),
+            ("a.obj", "mtllib shared.mtl\nv 0 0 0\nusemtl Rock\np 1\n"),
+            ("b.obj", "mtllib ./shared.mtl other.mtl\nv 0 0 0\nusemtl Rock\np 1\nusemtl Dirt\np 1\n"),
+        ]);
+        let options = LoadOptions::default();
+        let mut library = MaterialLibrary::new();
+
+        let a = obj::load_with_library(directory.join("a.obj").to_str().unwrap(), &options, &mut library);
+        let b = obj::load_with_library(directory.join("b.obj").to_str().unwrap(), &options, &mut library);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let (a, b) = (a.expect("This should work"), b.expect("This should work"));
+        assert_eq!(library.len(), 2);
+        assert_eq!(a.points[0].material, b.points[0].material);
+        assert_eq!(b.points[0].material.as_ref().unwrap().diffuse_reflectivity.rgb().r, 0.5);
+        assert_eq!(b.points[1].material, None);
+        assert_eq!(b.mtls.len(), 2);
+        assert_eq!(b.warnings, vec![
+            "Material 'Rock' is defined by several libraries, the first one is used",
+            "Material 'Dirt' is not defined by any library",
+        ]);
+        assert_eq!(library.undefined(), ["Dirt"]);
+
+        // the first library defines 'Rock', so the one of the second library is never used
+        let unused = library.unused().into_iter().map(|(_, name)| name).collect::<Vec<&str>>();
+        assert_eq!(unused, vec!["Sand", "Grass", "Rock"]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_replace_materials_with_the_override_and_the_fallback() {
+        let directory = write_fixture("override", &[
+            ("scene.mtl", "newmtl Rock\nKd 0.5 0.5 0.5\n"),
+            ("scene.obj", "mtllib scene.mtl\nv 0 0 0\np 1\nusemtl Rock\np 1\nusemtl Dirt\np 1\n"),
+        ]);
+        let file_path = directory.join("scene.obj");
+        let options = LoadOptions::default();
+        let clay = Material { name: "Clay".to_string(), ..Default::default() };
+        let mut fallback = MaterialLibrary { fallback: Some(clay.clone()), ..Default::default() };
+        let mut overridden = MaterialLibrary { override_material: Some(clay), ..Default::default() };
+
+        let with_fallback = obj::load_with_library(file_path.to_str().unwrap(), &options, &mut fallback);
+        let with_override = obj::load_with_library(file_path.to_str().unwrap(), &options, &mut overridden);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let names = |obj: &OBJ| {
+            obj.points.iter()
+                .map(|point| point.material.as_ref().map(|material| material.name.as_str()).unwrap_or("-").to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(&with_fallback.expect("This should work")), vec!["-", "Rock", "Clay"]);
+        assert_eq!(names(&with_override.expect("This should work")), vec!["Clay", "Clay", "Clay"]);
+        assert!(overridden.unused().is_empty());
+        assert_eq!(overridden.undefined(), ["Dirt"]);
+    }
+}
@@ -1,11 +1,31 @@
+mod color;
 mod helpers;
+mod library;
 mod parse_mtl;
 mod structs;
+mod write_mtl;
 
-use parse_mtl::parse_mtl;
+use std::path::Path;
+
+use crate::graphics::wavefront::diagnostic::{Diagnostic, ParseMode};
+
+pub use color::Color;
+pub use color::ReflectanceCurve;
+pub use color::Spectral;
+pub use color::XYZ;
+pub use library::MaterialLibrary;
+pub use parse_mtl::parse_mtl as parse;
+pub use parse_mtl::parse_mtl_with_mode as parse_with_mode;
+pub use structs::DissolveFactor;
+pub use structs::IlluminationModel;
+pub use structs::ImageChannel;
 pub use structs::Material;
 use structs::ParseError;
+pub use structs::TextureMap;
 pub use structs::MTL;
+pub use structs::RGB;
+pub use structs::UVW;
+pub use write_mtl::write_mtl as write;
 
 #[derive(Debug)]
 pub enum LoadMTLError {
@@ -35,20 +55,124 @@ impl std::fmt::Display for LoadMTLError {
     }
 }
 
-pub fn load(file_path: &str) -> Result<MTL, LoadMTLError> {
-    let file_content = std::fs::read_to_string(file_path)?;
+pub fn load(file_path: impl AsRef<Path>) -> Result<MTL, LoadMTLError> {
+    load_with_mode(file_path, ParseMode::Strict).map(|(mtl, _)| mtl)
+}
 
-    let obj = parse_mtl(file_content.as_str())?;
+///
+/// Same as `load`, but in `ParseMode::Lenient` the invalid statements are skipped and returned
+/// as diagnostics. The reflectance curves that can't be read are returned as warnings in both
+/// modes.
+///
+pub fn load_with_mode(
+    file_path: impl AsRef<Path>,
+    mode: ParseMode,
+) -> Result<(MTL, Vec<Diagnostic>), LoadMTLError> {
+    let file_content = std::fs::read_to_string(&file_path)?;
 
-    Ok(obj)
+    let (mut mtl, mut diagnostics) = parse_with_mode(file_content.as_str(), mode)?;
+
+    // texture maps and reflectance curves are relative to the material library, the colors
+    // whose curve can't be read fall back to white scaled by their factor
+    let directory = file_path.as_ref().parent().unwrap_or(Path::new(""));
+    let mut problems = Vec::new();
+    for material in mtl.values_mut() {
+        material.resolve_texture_paths(directory);
+        problems.extend(material.load_reflectance_curves(directory));
+    }
+    problems.sort();
+    diagnostics.extend(problems.into_iter().map(Diagnostic::warning));
+
+    Ok((mtl, diagnostics))
 }
 
+///
+/// Loads every library of `file_path` in order, a file listed twice is only read once
+///
 pub fn load_files(file_path: Vec<String>) -> Result<Vec<MTL>, LoadMTLError> {
+    let mut library = MaterialLibrary::new();
     let mut mtl_files = Vec::new();
 
     for file in file_path {
-        mtl_files.push(load(file.as_str())?);
+        let index = library.load(file.as_str(), ParseMode::Strict)?;
+        mtl_files.extend(library.get(index).cloned());
     }
 
     Ok(mtl_files)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::{diagnostic::Severity, fixtures::write_fixture};
+
+    #[test]
+    fn it_should_resolve_texture_maps_relative_to_the_library() {
+        let absolute = std::env::temp_dir().join("absolute.png");
+        let dir = write_fixture(
+            "texture-maps",
+            &[(
+                "wood.mtl",
+                &format!(
+                    "newmtl Wood\nmap_Kd textures/wood.png\nbump {}\n",
+                    absolute.display()
+                ),
+            )],
+        );
+
+        let result = load(dir.join("wood.mtl"));
+
+        let mtl = result.expect("This should work");
+        let wood = mtl.get("Wood").unwrap();
+        assert_eq!(
+            wood.diffuse_map.as_ref().unwrap().file_path,
+            dir.join("textures/wood.png")
+        );
+        assert_eq!(wood.bump_map.as_ref().unwrap().file_path, absolute);
+    }
+
+    #[test]
+    fn it_should_load_reflectance_curves_relative_to_the_library() {
+        let dir = write_fixture(
+            "reflectance-curves",
+            &[
+                ("paint.mtl", "newmtl Paint\nKd spectral curves/grey.rfl 2\n"),
+                ("missing.mtl", "newmtl Paint\nKd spectral none.rfl\n"),
+            ],
+        );
+        let _ = write_fixture(
+            "reflectance-curves/curves",
+            &[("grey.rfl", "380 0.5\n780 0.5\n")],
+        );
+
+        let result = load(dir.join("paint.mtl"));
+        let missing = load_with_mode(dir.join("missing.mtl"), ParseMode::Strict);
+
+        let mtl = result.expect("This should work");
+        let Color::Spectral(spectral) = &mtl.get("Paint").unwrap().diffuse_reflectivity else {
+            panic!("Kd should be spectral");
+        };
+        assert_eq!(spectral.file_path, dir.join("curves/grey.rfl"));
+        assert_eq!(spectral.curve.as_ref().unwrap().samples.len(), 2);
+        let rgb = mtl.get("Paint").unwrap().diffuse_reflectivity.rgb();
+        assert!((rgb.r - 1.0).abs() < 1e-2 && (rgb.b - 1.0).abs() < 1e-2);
+
+        // the curve is not loaded, the color is white scaled by its factor
+        let (missing, diagnostics) = missing.expect("This should work");
+        let kd = &missing.get("Paint").unwrap().diffuse_reflectivity;
+        assert!(matches!(kd, Color::Spectral(Spectral { curve: None, .. })));
+        assert_eq!(
+            kd.rgb(),
+            RGB {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0
+            }
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0]
+            .to_string()
+            .starts_with("warning: Could not load the reflectance curve"));
+    }
+}
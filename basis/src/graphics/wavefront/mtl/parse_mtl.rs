@@ -1,90 +1,96 @@
 use super::helpers;
-use super::structs::{ParseError, MTL};
+use super::structs::{Material, ParseError, MTL};
+use crate::graphics::wavefront::{
+    diagnostic::{Diagnostic, ParseMode, Reporter, Severity},
+    lexer::{Line, LineReader, Tokens},
+};
 
-pub trait Advance {
-    fn advance(&mut self, n: usize) -> &Self;
+pub fn parse_mtl(data: &str) -> Result<MTL, ParseError> {
+    parse_mtl_with_mode(data, ParseMode::Strict).map(|(mtl, _)| mtl)
 }
 
-impl<'a> Advance for std::slice::Iter<'a, &str> {
-    fn advance(&mut self, n: usize) -> &Self {
-        let mut n = n;
+///
+/// Parses a material library, in `ParseMode::Lenient` the invalid statements are skipped and
+/// returned as diagnostics
+///
+pub fn parse_mtl_with_mode(
+    data: &str,
+    mode: ParseMode,
+) -> Result<(MTL, Vec<Diagnostic>), ParseError> {
+    let mut mtl = MTL::default();
+    let mut reporter = Reporter::new(mode);
+    let mut material: Option<Material> = None;
+
+    let mut reader = LineReader::new(data.as_bytes());
+    let mut line = Line::default();
+
+    // reading from a `&str` cannot fail, the data is already valid UTF-8
+    while let Ok(true) = reader.read_line(&mut line) {
+        let current_line = line.number;
+        let mut tokens = Tokens::new(&line.text);
+        let Some(statement) = tokens.next() else {
+            continue;
+        };
+
+        if statement == "newmtl" {
+            if let Some(material) = material.take() {
+                mtl.insert(material.name.clone(), material);
+            }
 
-        while (n > 0) && self.next().is_some() {
-            n -= 1;
+            match tokens.next() {
+                Some(name) => {
+                    material = Some(Material {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                }
+                None => reporter.report(
+                    ParseError::InvalidToken(current_line, "Missing material name".to_string()),
+                    Severity::Error,
+                    &line,
+                    &tokens,
+                    Some("newmtl name"),
+                )?,
+            }
+            continue;
         }
-        self
-    }
-}
-
-pub fn parse_mtl(data: &str) -> Result<MTL, ParseError> {
-    let mut mtl = MTL::default();
 
-    let binding = data
-        .split("\n")
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<&str>>();
-    let mut lines = binding.iter();
-    let mut current_line: usize = 1;
-
-    while let Some(line) = lines.next() {
-        let mut tokens = line
-            .split(" ")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<&str>>()
-            .into_iter();
-        let command = tokens.next();
-
-        if command.is_none() {
-            return Err(ParseError::InvalidToken(
-                current_line,
-                "Missing command".to_string(),
-            ));
+        let Some(material) = material.as_mut() else {
+            reporter.warn(
+                format!("Statement '{statement}' is outside of a material"),
+                &line,
+                &tokens,
+            );
+            continue;
+        };
+
+        match helpers::parse_material_statement(statement, &mut tokens, material, current_line) {
+            Ok(true) => {}
+            Ok(false) => reporter.warn(format!("Unknown statement: '{statement}'"), &line, &tokens),
+            Err(error) => reporter.report(
+                error,
+                Severity::Error,
+                &line,
+                &tokens,
+                helpers::expected_form(statement),
+            )?,
         }
+    }
 
-        match command.unwrap() {
-            "newmtl" => {
-                match tokens.next() {
-                    Some(name) => {
-                        let (material, read) = helpers::parse_material(
-                            name,
-                            &mut lines.clone().peekable(),
-                            current_line,
-                        )?;
-                        mtl.insert(name.to_string(), material);
-                        lines.advance(read);
-                    }
-                    None => {
-                        return Err(ParseError::InvalidToken(
-                            current_line,
-                            "Missing material name".to_string(),
-                        ));
-                    }
-                }
-                Ok(())
-            }
-            "#" => {
-                // Ignore comments
-                Ok(())
-            }
-            _ => {
-                // Err(ParseError::InvalidToken(
-                //     current_line,
-                //     format!("Unknown token: '{unknown}'"),
-                // ))
-                Ok(())
-            }
-        }?;
-        current_line += 1;
+    if let Some(material) = material {
+        mtl.insert(material.name.clone(), material);
     }
 
-    Ok(mtl)
+    Ok((mtl, reporter.diagnostics))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::graphics::wavefront::mtl::structs::IlluminationModel;
+    use std::path::Path;
+
+    use crate::graphics::wavefront::diagnostic::Severity;
+    use crate::graphics::wavefront::mtl::color::{Color, Spectral, XYZ};
+    use crate::graphics::wavefront::mtl::structs::{IlluminationModel, ImageChannel, RGB, UVW};
 
     use super::*;
 
@@ -100,9 +106,9 @@ mod tests {
         let rock = result.get("Rock").unwrap();
 
         assert_eq!(result.len(), 1);
-        assert_eq!(rock.ambient_reflectivity.r, 0.0);
-        assert_eq!(rock.ambient_reflectivity.r, 0.0);
-        assert_eq!(rock.ambient_reflectivity.r, 0.0);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.0);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.0);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.0);
     }
 
     #[test]
@@ -135,21 +141,21 @@ mod tests {
         let rock = result.get("Rock").unwrap();
 
         assert_eq!(result.len(), 1);
-        assert_eq!(rock.ambient_reflectivity.r, 0.2);
-        assert_eq!(rock.ambient_reflectivity.g, 0.5);
-        assert_eq!(rock.ambient_reflectivity.b, 0.1);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.ambient_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.diffuse_reflectivity.r, 0.2);
-        assert_eq!(rock.diffuse_reflectivity.g, 0.5);
-        assert_eq!(rock.diffuse_reflectivity.b, 0.1);
+        assert_eq!(rock.diffuse_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.diffuse_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.atmosphere_reflectivity.r, 0.2);
-        assert_eq!(rock.atmosphere_reflectivity.g, 0.5);
-        assert_eq!(rock.atmosphere_reflectivity.b, 0.1);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.transmission_filter.r, 0.2);
-        assert_eq!(rock.transmission_filter.g, 0.5);
-        assert_eq!(rock.transmission_filter.b, 0.1);
+        assert_eq!(rock.transmission_filter.rgb().r, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().g, 0.5);
+        assert_eq!(rock.transmission_filter.rgb().b, 0.1);
     }
 
     #[test]
@@ -167,21 +173,21 @@ mod tests {
         let rock = result.get("Rock").unwrap();
 
         assert_eq!(result.len(), 1);
-        assert_eq!(rock.ambient_reflectivity.r, 0.2);
-        assert_eq!(rock.ambient_reflectivity.g, 0.2);
-        assert_eq!(rock.ambient_reflectivity.b, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().g, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().b, 0.2);
 
-        assert_eq!(rock.diffuse_reflectivity.r, 0.2);
-        assert_eq!(rock.diffuse_reflectivity.g, 0.2);
-        assert_eq!(rock.diffuse_reflectivity.b, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().g, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().b, 0.2);
 
-        assert_eq!(rock.atmosphere_reflectivity.r, 0.2);
-        assert_eq!(rock.atmosphere_reflectivity.g, 0.2);
-        assert_eq!(rock.atmosphere_reflectivity.b, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().g, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().b, 0.2);
 
-        assert_eq!(rock.transmission_filter.r, 0.2);
-        assert_eq!(rock.transmission_filter.g, 0.2);
-        assert_eq!(rock.transmission_filter.b, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().r, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().g, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().b, 0.2);
     }
 
     #[test]
@@ -199,21 +205,67 @@ mod tests {
         let rock = result.get("Rock").unwrap();
 
         assert_eq!(result.len(), 1);
-        assert_eq!(rock.ambient_reflectivity.r, 0.2);
-        assert_eq!(rock.ambient_reflectivity.g, 0.5);
-        assert_eq!(rock.ambient_reflectivity.b, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.ambient_reflectivity.rgb().b, 0.2);
+
+        assert_eq!(rock.diffuse_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.diffuse_reflectivity.rgb().b, 0.2);
+
+        assert_eq!(rock.atmosphere_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().b, 0.2);
+
+        assert_eq!(rock.transmission_filter.rgb().r, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().g, 0.5);
+        assert_eq!(rock.transmission_filter.rgb().b, 0.2);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_spectral_and_xyz_statements() {
+        let file = "
+            newmtl Rock
+            Ka spectral ident.rfl
+            Kd spectral rock.rfl 0.8
+            Ks xyz 0.5
+            Tf xyz 0.2 0.3 0.4
+";
 
-        assert_eq!(rock.diffuse_reflectivity.r, 0.2);
-        assert_eq!(rock.diffuse_reflectivity.g, 0.5);
-        assert_eq!(rock.diffuse_reflectivity.b, 0.2);
+        let result = parse_mtl(file).expect("This should work");
+        let rock = result.get("Rock").unwrap();
 
-        assert_eq!(rock.atmosphere_reflectivity.r, 0.2);
-        assert_eq!(rock.atmosphere_reflectivity.g, 0.5);
-        assert_eq!(rock.atmosphere_reflectivity.b, 0.2);
+        assert_eq!(rock.ambient_reflectivity, Color::Spectral(Spectral::new("ident.rfl", 1.0)));
+        assert_eq!(rock.diffuse_reflectivity, Color::Spectral(Spectral::new("rock.rfl", 0.8)));
+        assert_eq!(rock.atmosphere_reflectivity, Color::XYZ(XYZ { x: 0.5, y: 0.5, z: 0.5 }));
+        assert_eq!(rock.transmission_filter, Color::XYZ(XYZ { x: 0.2, y: 0.3, z: 0.4 }));
+    }
 
-        assert_eq!(rock.transmission_filter.r, 0.2);
-        assert_eq!(rock.transmission_filter.g, 0.5);
-        assert_eq!(rock.transmission_filter.b, 0.2);
+    #[test]
+    fn it_should_not_resolve_invalid_spectral_and_xyz_statements() {
+        let cases = [
+            (
+                "Ka spectral",
+                "Invalid token at line 2: Missing '.rfl' file name",
+            ),
+            (
+                "Kd spectral rock.rfl high",
+                "Invalid token at line 2: Invalid spectral factor value",
+            ),
+            ("Ks xyz", "Invalid token at line 2: Invalid X value"),
+            (
+                "Tf xyz 0.2 0.3 blue",
+                "Invalid token at line 2: Invalid Z value",
+            ),
+        ];
+
+        for (statement, expected) in cases {
+            let file = format!("newmtl Rock\n{statement}\n");
+            let error = parse_mtl(&file).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
     }
 
     #[test]
@@ -383,21 +435,21 @@ mod tests {
         let dirt = result.get("Dirt").unwrap();
 
         assert_eq!(result.len(), 2);
-        assert_eq!(rock.ambient_reflectivity.r, 0.2);
-        assert_eq!(rock.ambient_reflectivity.g, 0.5);
-        assert_eq!(rock.ambient_reflectivity.b, 0.1);
+        assert_eq!(rock.ambient_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.ambient_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.ambient_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.diffuse_reflectivity.r, 0.2);
-        assert_eq!(rock.diffuse_reflectivity.g, 0.5);
-        assert_eq!(rock.diffuse_reflectivity.b, 0.1);
+        assert_eq!(rock.diffuse_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.diffuse_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.diffuse_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.atmosphere_reflectivity.r, 0.2);
-        assert_eq!(rock.atmosphere_reflectivity.g, 0.5);
-        assert_eq!(rock.atmosphere_reflectivity.b, 0.1);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().r, 0.2);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().g, 0.5);
+        assert_eq!(rock.atmosphere_reflectivity.rgb().b, 0.1);
 
-        assert_eq!(rock.transmission_filter.r, 0.2);
-        assert_eq!(rock.transmission_filter.g, 0.5);
-        assert_eq!(rock.transmission_filter.b, 0.1);
+        assert_eq!(rock.transmission_filter.rgb().r, 0.2);
+        assert_eq!(rock.transmission_filter.rgb().g, 0.5);
+        assert_eq!(rock.transmission_filter.rgb().b, 0.1);
 
         assert_eq!(rock.specular_highlight_exponent, 100.0);
         assert_eq!(rock.sharpness, 50.0);
@@ -406,21 +458,21 @@ mod tests {
 
 
 
-        assert_eq!(dirt.ambient_reflectivity.r, 0.1);
-        assert_eq!(dirt.ambient_reflectivity.g, 0.3);
-        assert_eq!(dirt.ambient_reflectivity.b, 0.5);
+        assert_eq!(dirt.ambient_reflectivity.rgb().r, 0.1);
+        assert_eq!(dirt.ambient_reflectivity.rgb().g, 0.3);
+        assert_eq!(dirt.ambient_reflectivity.rgb().b, 0.5);
 
-        assert_eq!(dirt.diffuse_reflectivity.r, 0.1);
-        assert_eq!(dirt.diffuse_reflectivity.g, 0.3);
-        assert_eq!(dirt.diffuse_reflectivity.b, 0.5);
+        assert_eq!(dirt.diffuse_reflectivity.rgb().r, 0.1);
+        assert_eq!(dirt.diffuse_reflectivity.rgb().g, 0.3);
+        assert_eq!(dirt.diffuse_reflectivity.rgb().b, 0.5);
 
-        assert_eq!(dirt.atmosphere_reflectivity.r, 0.1);
-        assert_eq!(dirt.atmosphere_reflectivity.g, 0.3);
-        assert_eq!(dirt.atmosphere_reflectivity.b, 0.5);
+        assert_eq!(dirt.atmosphere_reflectivity.rgb().r, 0.1);
+        assert_eq!(dirt.atmosphere_reflectivity.rgb().g, 0.3);
+        assert_eq!(dirt.atmosphere_reflectivity.rgb().b, 0.5);
 
-        assert_eq!(dirt.transmission_filter.r, 0.1);
-        assert_eq!(dirt.transmission_filter.g, 0.3);
-        assert_eq!(dirt.transmission_filter.b, 0.5);
+        assert_eq!(dirt.transmission_filter.rgb().r, 0.1);
+        assert_eq!(dirt.transmission_filter.rgb().g, 0.3);
+        assert_eq!(dirt.transmission_filter.rgb().b, 0.5);
 
         assert_eq!(dirt.specular_highlight_exponent, 14.0);
         assert_eq!(dirt.sharpness, 20.0);
@@ -451,17 +503,17 @@ mod tests {
 
         assert_eq!(result.len(), 1);
 
-        assert_eq!(material.ambient_reflectivity.r, 0.0);
-        assert_eq!(material.ambient_reflectivity.g, 0.0);
-        assert_eq!(material.ambient_reflectivity.b, 0.0);
+        assert_eq!(material.ambient_reflectivity.rgb().r, 0.0);
+        assert_eq!(material.ambient_reflectivity.rgb().g, 0.0);
+        assert_eq!(material.ambient_reflectivity.rgb().b, 0.0);
 
-        assert_eq!(material.diffuse_reflectivity.r, 0.64);
-        assert_eq!(material.diffuse_reflectivity.g, 0.64);
-        assert_eq!(material.diffuse_reflectivity.b, 0.64);
+        assert_eq!(material.diffuse_reflectivity.rgb().r, 0.64);
+        assert_eq!(material.diffuse_reflectivity.rgb().g, 0.64);
+        assert_eq!(material.diffuse_reflectivity.rgb().b, 0.64);
 
-        assert_eq!(material.atmosphere_reflectivity.r, 0.5);
-        assert_eq!(material.atmosphere_reflectivity.g, 0.5);
-        assert_eq!(material.atmosphere_reflectivity.b, 0.5);
+        assert_eq!(material.atmosphere_reflectivity.rgb().r, 0.5);
+        assert_eq!(material.atmosphere_reflectivity.rgb().g, 0.5);
+        assert_eq!(material.atmosphere_reflectivity.rgb().b, 0.5);
 
         assert_eq!(material.specular_highlight_exponent, 96.078_43);
         assert_eq!(material.optical_density, 1.0);
@@ -469,4 +521,284 @@ mod tests {
         assert!(!material.dissolve_factor.halo);
         assert_eq!(material.illumination_model, IlluminationModel::HighlightOn);
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_texture_maps() {
+        let file = "
+            newmtl Wood
+            Kd 0.8 0.8 0.8
+            map_Ka ambient.png
+            map_Kd textures/diffuse.png
+            map_Ks specular.png
+            map_Ns exponent.png
+            map_d alpha.png
+            bump normal.png
+            disp height.png
+            decal sticker.png
+";
+
+        let result = parse_mtl(file).expect("This should work");
+        let wood = result.get("Wood").unwrap();
+
+        assert_eq!(wood.diffuse_reflectivity.rgb().r, 0.8);
+        assert_eq!(wood.ambient_map.as_ref().unwrap().file_path, Path::new("ambient.png"));
+        assert_eq!(wood.diffuse_map.as_ref().unwrap().file_path, Path::new("textures/diffuse.png"));
+        assert_eq!(wood.atmosphere_map.as_ref().unwrap().file_path, Path::new("specular.png"));
+        assert_eq!(wood.specular_highlight_exponent_map.as_ref().unwrap().file_path, Path::new("exponent.png"));
+        assert_eq!(wood.dissolve_map.as_ref().unwrap().file_path, Path::new("alpha.png"));
+        assert_eq!(wood.bump_map.as_ref().unwrap().file_path, Path::new("normal.png"));
+        assert_eq!(wood.displacement_map.as_ref().unwrap().file_path, Path::new("height.png"));
+        assert_eq!(wood.decal_map.as_ref().unwrap().file_path, Path::new("sticker.png"));
+        assert_eq!(wood.texture_maps().count(), 8);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_pbr_statements() {
+        let file = "
+            newmtl Metal
+            Kd 0.8 0.8 0.8
+            Pr 0.25
+            Pm 1.0
+            Ps 0.1
+            Pc 0.5
+            Pcr 0.03
+            Ke 1.0 0.5 0.0
+            aniso 0.7
+            anisor 0.2
+            map_Pr roughness.png
+            map_Pm metallic.png
+            map_Ps sheen.png
+            map_Ke emissive.png
+            norm -bm 2 normal.png
+";
+
+        let result = parse_mtl(file).expect("This should work");
+        let metal = result.get("Metal").unwrap();
+
+        assert_eq!(metal.roughness, 0.25);
+        assert_eq!(metal.metallic, 1.0);
+        assert_eq!(metal.sheen, 0.1);
+        assert_eq!(metal.clearcoat_thickness, 0.5);
+        assert_eq!(metal.clearcoat_roughness, 0.03);
+        assert_eq!(metal.emissive.rgb(), RGB { r: 1.0, g: 0.5, b: 0.0 });
+        assert_eq!(metal.anisotropy, 0.7);
+        assert_eq!(metal.anisotropy_rotation, 0.2);
+        assert_eq!(metal.roughness_map.as_ref().unwrap().file_path, Path::new("roughness.png"));
+        assert_eq!(metal.metallic_map.as_ref().unwrap().file_path, Path::new("metallic.png"));
+        assert_eq!(metal.sheen_map.as_ref().unwrap().file_path, Path::new("sheen.png"));
+        assert_eq!(metal.emissive_map.as_ref().unwrap().file_path, Path::new("emissive.png"));
+        assert_eq!(metal.normal_map.as_ref().unwrap().file_path, Path::new("normal.png"));
+        assert_eq!(metal.normal_map.as_ref().unwrap().bump_multiplier, 2.0);
+        assert_eq!(metal.texture_maps().count(), 5);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_texture_map_defaults() {
+        let file = "
+            newmtl Wood
+            map_Kd wood.png
+";
+
+        let result = parse_mtl(file).expect("This should work");
+        let map = result.get("Wood").unwrap().diffuse_map.as_ref().unwrap();
+
+        assert!(map.blend_u);
+        assert!(map.blend_v);
+        assert!(!map.clamp);
+        assert!(!map.color_correction);
+        assert_eq!(map.bump_multiplier, 1.0);
+        assert_eq!(map.boost, 0.0);
+        assert_eq!(map.range_base, 0.0);
+        assert_eq!(map.range_gain, 1.0);
+        assert_eq!(map.offset, UVW::new(0.0, 0.0, 0.0));
+        assert_eq!(map.scale, UVW::new(1.0, 1.0, 1.0));
+        assert_eq!(map.turbulence, UVW::new(0.0, 0.0, 0.0));
+        assert_eq!(map.channel, None);
+        assert_eq!(map.resolution, None);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_texture_map_options() {
+        let file = "
+            newmtl Wood
+            map_Kd -o 0.5 0.25 -s 2 -t 0.1 0.2 0.3 -clamp on -blendu off -blendv off -cc on -boost 1.5 -mm 0.2 0.8 -texres 512 my wood.png
+            bump -imfchan l -bm 0.5 bump.png
+";
+
+        let result = parse_mtl(file).expect("This should work");
+        let wood = result.get("Wood").unwrap();
+        let map = wood.diffuse_map.as_ref().unwrap();
+
+        assert_eq!(map.file_path, Path::new("my wood.png"));
+        assert_eq!(map.offset, UVW::new(0.5, 0.25, 0.0));
+        assert_eq!(map.scale, UVW::new(2.0, 1.0, 1.0));
+        assert_eq!(map.turbulence, UVW::new(0.1, 0.2, 0.3));
+        assert!(map.clamp);
+        assert!(!map.blend_u);
+        assert!(!map.blend_v);
+        assert!(map.color_correction);
+        assert_eq!(map.boost, 1.5);
+        assert_eq!(map.range_base, 0.2);
+        assert_eq!(map.range_gain, 0.8);
+        assert_eq!(map.resolution, Some(512));
+
+        let bump = wood.bump_map.as_ref().unwrap();
+        assert_eq!(bump.file_path, Path::new("bump.png"));
+        assert_eq!(bump.channel, Some(ImageChannel::Luminance));
+        assert_eq!(bump.bump_multiplier, 0.5);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_resolve_invalid_texture_maps() {
+        let cases = [
+            ("map_Kd", "Missing texture map file name"),
+            ("map_Kd -clamp on", "Missing texture map file name"),
+            ("map_Kd -clamp yes wood.png", "Invalid '-clamp' value, expected 'on' or 'off'"),
+            ("map_Kd -o wood.png", "Invalid '-o' value"),
+            ("map_Kd -mm 0.2 wood.png", "Invalid '-mm' value"),
+            ("map_Kd -imfchan x wood.png", "Invalid '-imfchan' value"),
+            ("map_Kd -texres 0 wood.png", "'-texres' value should be a positive integer"),
+            ("map_Kd -unknown wood.png", "Unknown texture map option: '-unknown'"),
+        ];
+
+        for (statement, message) in cases {
+            let file = format!("newmtl Wood\n{statement}\n");
+            let result = parse_mtl(&file);
+
+            assert!(result.is_err(), "{statement}");
+            assert!(result.unwrap_err().to_string().contains(message), "{statement}");
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_collect_diagnostics_in_lenient_mode() {
+        let file = "
+            Kd 1 1 1
+            newmtl Rock
+            Ka 0.1 x
+            Kd 0.5
+            d -halo
+            Ni 11
+            foo bar
+            newmtl
+            Ks 0.3
+";
+
+        let strict = parse_mtl(file);
+        let (result, diagnostics) = parse_mtl_with_mode(file, ParseMode::Lenient).expect("This should work");
+        let diagnostics = diagnostics.iter().map(|diagnostic| {
+            (diagnostic.line, diagnostic.column, diagnostic.token.as_str(), diagnostic.severity, diagnostic.expected)
+        }).collect::<Vec<_>>();
+        let rock = result.get("Rock").unwrap();
+
+        assert!(strict.unwrap_err().to_string().starts_with("Invalid token at line 4: Invalid G value"));
+        assert_eq!(rock.diffuse_reflectivity.rgb().g, 0.5);
+        assert_eq!(diagnostics, vec![
+            (2, 13, "Kd", Severity::Warning, None),
+            (4, 20, "x", Severity::Error, Some("K r [g] [b] | K spectral file.rfl [factor] | K xyz x [y] [z]")),
+            (6, 20, "", Severity::Error, Some("d [-halo] factor")),
+            (7, 16, "11", Severity::Error, Some("Ni 0.001-10")),
+            (8, 13, "foo", Severity::Warning, None),
+            (9, 19, "", Severity::Error, Some("newmtl name")),
+            // the material was closed by the invalid newmtl
+            (10, 13, "Ks", Severity::Warning, None),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_report_physical_positions_in_multiple_materials() {
+        let file = "# Exported from Figuro\r
+\r
+newmtl 4bed15\r
+   Kd 0.29296875 0.92578125 0.08203125\r
+\r
+# the next material is broken\r
+newmtl 2daec2\r
+   Kd 0.17578125 \\\r
+      abc 0.7578125\r
+\r
+newmtl 4602e3\r
+   Ni 20\r
+";
+
+        let strict = parse_mtl(file);
+        let (result, diagnostics) = parse_mtl_with_mode(file, ParseMode::Lenient).expect("This should work");
+        let positions = diagnostics.iter().map(|diagnostic| {
+            (diagnostic.line, diagnostic.column, diagnostic.offset, diagnostic.token.as_str())
+        }).collect::<Vec<_>>();
+
+        assert!(strict.unwrap_err().to_string().starts_with("Invalid token at line 8: Invalid G value"));
+        assert_eq!(result.len(), 3);
+        assert_eq!(positions, vec![
+            (9, 7, file.find("abc").unwrap(), "abc"),
+            (12, 7, file.rfind("20").unwrap(), "20"),
+        ]);
+    }
+
+    const EVERY_STATEMENT: &str = "
+        # comment
+        newmtl Rock
+        Ka 0.1 0.2 0.3
+        Kd 0.4
+        Ks 0.5 0.6 0.7
+        Tf 1 1 1
+        illum 2
+        d -halo 0.5
+        d 1
+        Ns 10
+        sharpness 60
+        Ni 1.5
+        map_Ka -blendu off -blendv on -cc on -clamp off ambient.png
+        map_Kd -bm 2 -boost 1 -mm 0 1 -texres 256 diffuse map.png
+        map_Ks -o 1 2 3 -s 1 2 -t 1 specular.png
+        map_Ns -imfchan r exponent.png
+        map_d dissolve.png
+        bump -bm 0.5 bump.png
+        disp displacement.png
+        decal decal.png
+        Ke xyz 0.1 0.2 0.3
+        Tf spectral filter.rfl 0.5
+        Pr 0.5
+        Pm 1
+        Ps 0.1
+        Pc 0.2
+        Pcr 0.3
+        aniso 0.4
+        anisor 0.5
+        map_Pr roughness.png
+        map_Pm metallic.png
+        map_Ps sheen.png
+        map_Ke emissive.png
+        norm -bm 2 normal.png
+        newmtl Sand
+        Kd 1 1 0
+";
+
+    #[test]
+    fn it_should_not_panic_on_truncated_statements() {
+        let lines = EVERY_STATEMENT.lines().collect::<Vec<&str>>();
+
+        for (index, line) in lines.iter().enumerate() {
+            for (end, _) in line.char_indices() {
+                let mut truncated = lines.clone();
+                truncated[index] = &line[..end];
+                let file = truncated.join("\n");
+
+                let strict = parse_mtl(&file);
+                let lenient = parse_mtl_with_mode(&file, ParseMode::Lenient);
+
+                assert!(lenient.is_ok(), "{file}");
+                if strict.is_err() {
+                    assert!(!lenient.unwrap().1.is_empty(), "{file}");
+                }
+            }
+        }
+    }
 }
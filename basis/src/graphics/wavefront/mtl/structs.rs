@@ -1,9 +1,13 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::color::Color;
 
 #[derive(Debug)]
 pub enum ParseError {
     InvalidToken(usize, String),
     InvalidValue(usize, String),
+    ReflectanceCurve(usize, String),
 }
 impl std::error::Error for ParseError {}
 
@@ -16,6 +20,9 @@ impl std::fmt::Display for ParseError {
             ParseError::InvalidValue(line, message) => {
                 write!(f, "Invalid value at line {}: {}", line, message)
             }
+            ParseError::ReflectanceCurve(line, message) => {
+                write!(f, "Invalid reflectance curve at line {}: {}", line, message)
+            }
         }
     }
 }
@@ -25,7 +32,7 @@ impl std::fmt::Display for ParseError {
 /// specified, then g, and b are assumed to be equal to r.  The r g b values
 /// are normally in the range of 0.0 to 1.0.  Values outside this range
 /// increase or decrease the relectivity accordingly.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct RGB {
     pub r: f32,
     pub g: f32,
@@ -69,24 +76,303 @@ impl std::str::FromStr for IlluminationModel {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+impl std::fmt::Display for IlluminationModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let number = match self {
+            IlluminationModel::ColorOnAmbientOff => 0,
+            IlluminationModel::ColorOnAmbientOn => 1,
+            IlluminationModel::HighlightOn => 2,
+            IlluminationModel::ReflectionOnRayTraceOn => 3,
+            IlluminationModel::TransparencyGlassOnReflectionRayTraceOn => 4,
+            IlluminationModel::ReflectionFresnelOnRayTraceOn => 5,
+            IlluminationModel::TransparencyRefractionOnReflectionFresnelOffRayTraceOn => 6,
+            IlluminationModel::TransparencyRefractionOnReflectionFresnelOnRayTraceOn => 7,
+            IlluminationModel::ReflectionOnRayTraceOff => 8,
+            IlluminationModel::TransparencyGlassOnReflectionRayTraceOff => 9,
+            IlluminationModel::CastsShadows => 10,
+        };
+        write!(f, "{}", number)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct DissolveFactor {
     pub factor: f32,
     pub halo: bool,
 }
 
-#[derive(Debug, Clone, Default)]
+impl Default for DissolveFactor {
+    ///
+    /// Materials are fully opaque unless a "d" statement says otherwise
+    ///
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            halo: false,
+        }
+    }
+}
+
+/// "u v w" values of the "-o", "-s" and "-t" texture map options.
+/// The v and w arguments are optional, when they are not given they keep
+/// the option's default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UVW {
+    pub u: f32,
+    pub v: f32,
+    pub w: f32,
+}
+
+impl UVW {
+    pub fn new(u: f32, v: f32, w: f32) -> Self {
+        Self { u, v, w }
+    }
+}
+
+///
+/// Channel of the texture file used to create a scalar or bump texture ("-imfchan")
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageChannel {
+    Red,
+    Green,
+    Blue,
+    Matte,
+    Luminance,
+    Depth,
+}
+
+impl std::str::FromStr for ImageChannel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" => Ok(ImageChannel::Red),
+            "g" => Ok(ImageChannel::Green),
+            "b" => Ok(ImageChannel::Blue),
+            "m" => Ok(ImageChannel::Matte),
+            "l" => Ok(ImageChannel::Luminance),
+            "z" => Ok(ImageChannel::Depth),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let channel = match self {
+            ImageChannel::Red => "r",
+            ImageChannel::Green => "g",
+            ImageChannel::Blue => "b",
+            ImageChannel::Matte => "m",
+            ImageChannel::Luminance => "l",
+            ImageChannel::Depth => "z",
+        };
+        write!(f, "{}", channel)
+    }
+}
+
+///
+/// A texture map statement, e.g. "map_Kd -o 0.5 0.5 -clamp on wood.png"
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureMap {
+    ///
+    /// Path of the texture file, relative paths are resolved against the MTL file directory
+    /// when the library is loaded with `mtl::load`
+    ///
+    pub file_path: PathBuf,
+    /// "-blendu on | off"
+    pub blend_u: bool,
+    /// "-blendv on | off"
+    pub blend_v: bool,
+    /// "-bm mult", only used by bump maps
+    pub bump_multiplier: f32,
+    /// "-boost value"
+    pub boost: f32,
+    /// "-cc on | off"
+    pub color_correction: bool,
+    /// "-clamp on | off"
+    pub clamp: bool,
+    /// "-imfchan r | g | b | m | l | z"
+    pub channel: Option<ImageChannel>,
+    /// "-mm base gain"
+    pub range_base: f32,
+    pub range_gain: f32,
+    /// "-o u v w"
+    pub offset: UVW,
+    /// "-s u v w"
+    pub scale: UVW,
+    /// "-t u v w"
+    pub turbulence: UVW,
+    /// "-texres value"
+    pub resolution: Option<u32>,
+}
+
+impl TextureMap {
+    pub fn new(file_path: impl Into<PathBuf>) -> Self {
+        Self {
+            file_path: file_path.into(),
+            blend_u: true,
+            blend_v: true,
+            bump_multiplier: 1.0,
+            boost: 0.0,
+            color_correction: false,
+            clamp: false,
+            channel: None,
+            range_base: 0.0,
+            range_gain: 1.0,
+            offset: UVW::new(0.0, 0.0, 0.0),
+            scale: UVW::new(1.0, 1.0, 1.0),
+            turbulence: UVW::new(0.0, 0.0, 0.0),
+            resolution: None,
+        }
+    }
+
+    ///
+    /// Makes a relative `file_path` relative to `directory` instead
+    ///
+    pub fn resolve_path(&mut self, directory: &Path) {
+        if self.file_path.is_relative() {
+            self.file_path = directory.join(&self.file_path);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Material {
     pub name: String,
-    pub ambient_reflectivity: RGB,
-    pub diffuse_reflectivity: RGB,
-    pub atmosphere_reflectivity: RGB,
-    pub transmission_filter: RGB,
+    pub ambient_reflectivity: Color,
+    pub diffuse_reflectivity: Color,
+    pub atmosphere_reflectivity: Color,
+    pub transmission_filter: Color,
     pub illumination_model: IlluminationModel,
     pub dissolve_factor: DissolveFactor,
     pub specular_highlight_exponent: f32,
     pub sharpness: f32,
     pub optical_density: f32,
+    /// "map_Ka"
+    pub ambient_map: Option<TextureMap>,
+    /// "map_Kd"
+    pub diffuse_map: Option<TextureMap>,
+    /// "map_Ks"
+    pub atmosphere_map: Option<TextureMap>,
+    /// "map_Ns"
+    pub specular_highlight_exponent_map: Option<TextureMap>,
+    /// "map_d"
+    pub dissolve_map: Option<TextureMap>,
+    /// "bump" or "map_bump"
+    pub bump_map: Option<TextureMap>,
+    /// "disp"
+    pub displacement_map: Option<TextureMap>,
+    /// "decal"
+    pub decal_map: Option<TextureMap>,
+    /// "Pr"
+    pub roughness: f32,
+    /// "Pm"
+    pub metallic: f32,
+    /// "Ps"
+    pub sheen: f32,
+    /// "Pc"
+    pub clearcoat_thickness: f32,
+    /// "Pcr"
+    pub clearcoat_roughness: f32,
+    /// "Ke"
+    pub emissive: Color,
+    /// "aniso"
+    pub anisotropy: f32,
+    /// "anisor"
+    pub anisotropy_rotation: f32,
+    /// "map_Pr"
+    pub roughness_map: Option<TextureMap>,
+    /// "map_Pm"
+    pub metallic_map: Option<TextureMap>,
+    /// "map_Ps"
+    pub sheen_map: Option<TextureMap>,
+    /// "map_Ke"
+    pub emissive_map: Option<TextureMap>,
+    /// "norm"
+    pub normal_map: Option<TextureMap>,
+}
+
+impl Material {
+    ///
+    /// Every texture map declared by the material
+    ///
+    pub fn texture_maps(&self) -> impl Iterator<Item = &TextureMap> {
+        [
+            &self.ambient_map,
+            &self.diffuse_map,
+            &self.atmosphere_map,
+            &self.specular_highlight_exponent_map,
+            &self.dissolve_map,
+            &self.bump_map,
+            &self.displacement_map,
+            &self.decal_map,
+            &self.roughness_map,
+            &self.metallic_map,
+            &self.sheen_map,
+            &self.emissive_map,
+            &self.normal_map,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    ///
+    /// Resolves the relative paths of every texture map against `directory`
+    ///
+    pub fn resolve_texture_paths(&mut self, directory: &Path) {
+        [
+            &mut self.ambient_map,
+            &mut self.diffuse_map,
+            &mut self.atmosphere_map,
+            &mut self.specular_highlight_exponent_map,
+            &mut self.dissolve_map,
+            &mut self.bump_map,
+            &mut self.displacement_map,
+            &mut self.decal_map,
+            &mut self.roughness_map,
+            &mut self.metallic_map,
+            &mut self.sheen_map,
+            &mut self.emissive_map,
+            &mut self.normal_map,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|map| map.resolve_path(directory));
+    }
+
+    ///
+    /// Reads the `.rfl` files of the spectral colors, relative paths are resolved against
+    /// `directory`. A curve that can't be read is left unloaded and described in the returned
+    /// messages.
+    ///
+    pub fn load_reflectance_curves(&mut self, directory: &Path) -> Vec<String> {
+        let mut problems = Vec::new();
+        let colors = [
+            &mut self.ambient_reflectivity,
+            &mut self.diffuse_reflectivity,
+            &mut self.atmosphere_reflectivity,
+            &mut self.transmission_filter,
+            &mut self.emissive,
+        ];
+
+        for color in colors {
+            if let Color::Spectral(spectral) = color {
+                if let Err(err) = spectral.load_curve(directory) {
+                    problems.push(format!(
+                        "Could not load the reflectance curve '{}' of material '{}': {}",
+                        spectral.file_path.display(),
+                        self.name,
+                        err
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
 }
 
 /*
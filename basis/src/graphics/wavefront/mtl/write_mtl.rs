@@ -0,0 +1,319 @@
+use std::io::Write;
+
+use super::color::Color;
+use super::structs::{Material, TextureMap, MTL};
+use crate::graphics::wavefront::writer::WriteOptions;
+
+///
+/// Writes a material library, the materials are sorted by name so the output does not depend on
+/// the order of the `HashMap`
+///
+pub fn write_mtl<W: Write>(
+    mtl: &MTL,
+    mut writer: W,
+    options: &WriteOptions,
+) -> std::io::Result<()> {
+    let mut materials = mtl.values().collect::<Vec<&Material>>();
+    materials.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for (index, material) in materials.into_iter().enumerate() {
+        if index > 0 {
+            writeln!(writer)?;
+        }
+        material.write_to(&mut writer, options)?;
+    }
+
+    Ok(())
+}
+
+impl Material {
+    ///
+    /// Writes the `newmtl` statement of the material followed by every value that differs from
+    /// the default one, so reading it back gives the same material
+    ///
+    pub fn write_to<W: Write>(&self, mut writer: W, options: &WriteOptions) -> std::io::Result<()> {
+        let default = Material::default();
+        let float = |value: f32| options.float(value);
+
+        writeln!(writer, "newmtl {}", self.name)?;
+
+        let colors = [
+            (
+                "Ka",
+                &self.ambient_reflectivity,
+                &default.ambient_reflectivity,
+            ),
+            (
+                "Kd",
+                &self.diffuse_reflectivity,
+                &default.diffuse_reflectivity,
+            ),
+            (
+                "Ks",
+                &self.atmosphere_reflectivity,
+                &default.atmosphere_reflectivity,
+            ),
+            (
+                "Tf",
+                &self.transmission_filter,
+                &default.transmission_filter,
+            ),
+            ("Ke", &self.emissive, &default.emissive),
+        ];
+        for (statement, color, default) in colors {
+            if color != default {
+                write_color(&mut writer, statement, color, options)?;
+            }
+        }
+
+        if self.specular_highlight_exponent != default.specular_highlight_exponent {
+            writeln!(writer, "Ns {}", float(self.specular_highlight_exponent))?;
+        }
+        if self.sharpness != default.sharpness {
+            writeln!(writer, "sharpness {}", float(self.sharpness))?;
+        }
+        if self.optical_density != default.optical_density {
+            writeln!(writer, "Ni {}", float(self.optical_density))?;
+        }
+
+        let values = [
+            ("Pr", self.roughness, default.roughness),
+            ("Pm", self.metallic, default.metallic),
+            ("Ps", self.sheen, default.sheen),
+            ("Pc", self.clearcoat_thickness, default.clearcoat_thickness),
+            ("Pcr", self.clearcoat_roughness, default.clearcoat_roughness),
+            ("aniso", self.anisotropy, default.anisotropy),
+            (
+                "anisor",
+                self.anisotropy_rotation,
+                default.anisotropy_rotation,
+            ),
+        ];
+        for (statement, value, default) in values {
+            if value != default {
+                writeln!(writer, "{} {}", statement, float(value))?;
+            }
+        }
+
+        if self.dissolve_factor != default.dissolve_factor {
+            let halo = if self.dissolve_factor.halo {
+                "-halo "
+            } else {
+                ""
+            };
+            writeln!(writer, "d {}{}", halo, float(self.dissolve_factor.factor))?;
+        }
+        if self.illumination_model != default.illumination_model {
+            writeln!(writer, "illum {}", self.illumination_model)?;
+        }
+
+        let maps = [
+            ("map_Ka", &self.ambient_map),
+            ("map_Kd", &self.diffuse_map),
+            ("map_Ks", &self.atmosphere_map),
+            ("map_Ns", &self.specular_highlight_exponent_map),
+            ("map_d", &self.dissolve_map),
+            ("bump", &self.bump_map),
+            ("disp", &self.displacement_map),
+            ("decal", &self.decal_map),
+            ("map_Pr", &self.roughness_map),
+            ("map_Pm", &self.metallic_map),
+            ("map_Ps", &self.sheen_map),
+            ("map_Ke", &self.emissive_map),
+            ("norm", &self.normal_map),
+        ];
+        for (statement, map) in maps {
+            if let Some(map) = map {
+                write_texture_map(&mut writer, statement, map, options)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_color<W: Write>(
+    writer: &mut W,
+    statement: &str,
+    color: &Color,
+    options: &WriteOptions,
+) -> std::io::Result<()> {
+    let float = |value: f32| options.float(value);
+
+    match color {
+        Color::RGB(rgb) => writeln!(
+            writer,
+            "{} {} {} {}",
+            statement,
+            float(rgb.r),
+            float(rgb.g),
+            float(rgb.b)
+        ),
+        Color::XYZ(xyz) => writeln!(
+            writer,
+            "{} xyz {} {} {}",
+            statement,
+            float(xyz.x),
+            float(xyz.y),
+            float(xyz.z)
+        ),
+        Color::Spectral(spectral) => writeln!(
+            writer,
+            "{} spectral {} {}",
+            statement,
+            spectral.file_path.display(),
+            float(spectral.factor)
+        ),
+    }
+}
+
+///
+/// Writes the options that differ from `TextureMap::new` before the file name
+///
+fn write_texture_map<W: Write>(
+    writer: &mut W,
+    statement: &str,
+    map: &TextureMap,
+    options: &WriteOptions,
+) -> std::io::Result<()> {
+    let default = TextureMap::new("");
+    let float = |value: f32| options.float(value);
+    let switch = |value: bool| if value { "on" } else { "off" };
+
+    write!(writer, "{}", statement)?;
+
+    let switches = [
+        ("-blendu", map.blend_u, default.blend_u),
+        ("-blendv", map.blend_v, default.blend_v),
+        ("-cc", map.color_correction, default.color_correction),
+        ("-clamp", map.clamp, default.clamp),
+    ];
+    for (option, value, default) in switches {
+        if value != default {
+            write!(writer, " {} {}", option, switch(value))?;
+        }
+    }
+
+    if map.bump_multiplier != default.bump_multiplier {
+        write!(writer, " -bm {}", float(map.bump_multiplier))?;
+    }
+    if map.boost != default.boost {
+        write!(writer, " -boost {}", float(map.boost))?;
+    }
+    if map.range_base != default.range_base || map.range_gain != default.range_gain {
+        write!(
+            writer,
+            " -mm {} {}",
+            float(map.range_base),
+            float(map.range_gain)
+        )?;
+    }
+
+    let vectors = [
+        ("-o", &map.offset, &default.offset),
+        ("-s", &map.scale, &default.scale),
+        ("-t", &map.turbulence, &default.turbulence),
+    ];
+    for (option, value, default) in vectors {
+        if value != default {
+            write!(
+                writer,
+                " {} {} {} {}",
+                option,
+                float(value.u),
+                float(value.v),
+                float(value.w)
+            )?;
+        }
+    }
+
+    if let Some(channel) = &map.channel {
+        write!(writer, " -imfchan {}", channel)?;
+    }
+    if let Some(resolution) = map.resolution {
+        write!(writer, " -texres {}", resolution)?;
+    }
+
+    writeln!(writer, " {}", map.file_path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::mtl::parse_mtl::parse_mtl;
+
+    fn write(mtl: &MTL, options: &WriteOptions) -> String {
+        let mut output = Vec::new();
+        write_mtl(mtl, &mut output, options).expect("This should work");
+        String::from_utf8(output).expect("This should work")
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_round_trip_every_statement() {
+        let file = "
+            newmtl Rock
+            Ka 0.1 0.2 0.3
+            Kd 0.4 0.5 0.6
+            Ks 0.7 0.8 0.9
+            Tf 1 0.5 0.25
+            Ns 96.078431
+            sharpness 60
+            Ni 1.45
+            d -halo 0.5
+            illum 7
+            map_Ka -blendu off -cc on -o 0.5 0.5 -s 2 texture.png
+            map_Kd -clamp on -mm 0.1 0.9 -imfchan r -texres 256 my texture.png
+            map_Ks -blendv off -t 0.1 0.2 0.3 specular.png
+            map_Ns -boost 2.5 exponent.png
+            map_d alpha.png
+            bump -bm 0.5 -imfchan l bump.png
+            disp displacement.png
+            decal decal.png
+            Ke 0.1 0.1 0
+            Pr 0.4
+            Pm 1
+            Ps 0.2
+            Pc 0.3
+            Pcr 0.05
+            aniso 0.6
+            anisor 0.25
+            map_Pr roughness.png
+            map_Pm metallic.png
+            map_Ps sheen.png
+            map_Ke -clamp on emissive.png
+            norm -bm 0.5 normal.png
+
+            newmtl Dirt
+            Kd 0.3 0.2 0.1
+
+            newmtl Varnish
+            Ka xyz 0.2 0.3 0.4
+            Kd spectral red.rfl 0.75
+";
+
+        let mtl = parse_mtl(file).expect("This should work");
+        let written = write(&mtl, &WriteOptions::default());
+        let result = parse_mtl(&written).expect("This should work");
+
+        assert_eq!(result, mtl);
+        assert_eq!(write(&result, &WriteOptions::default()), written);
+        assert!(written.starts_with("newmtl Dirt\nKd 0.3 0.2 0.1\n\nnewmtl Rock\n"));
+        assert!(written.contains("\nd -halo 0.5\nillum 7\n"));
+        assert!(written.contains("\nnewmtl Varnish\nKa xyz 0.2 0.3 0.4\nKd spectral red.rfl 0.75\n"));
+        assert!(written.contains("\nmap_Kd -clamp on -mm 0.1 0.9 -imfchan r -texres 256 my texture.png\n"));
+    }
+
+    #[test]
+    fn it_should_leave_out_default_values() {
+        let mtl = parse_mtl("newmtl Empty\nKa 0 0 0\nd 1\nillum 0\n").expect("This should work");
+        let rounded = WriteOptions { precision: Some(2) };
+        let colored = parse_mtl("newmtl Color\nKd 0.123 0.5 1\n").expect("This should work");
+
+        assert_eq!(write(&mtl, &WriteOptions::default()), "newmtl Empty\n");
+        assert_eq!(
+            write(&colored, &rounded),
+            "newmtl Color\nKd 0.12 0.50 1.00\n"
+        );
+    }
+}
@@ -0,0 +1,1278 @@
+use crate::math::{self, VectorFunctions};
+
+use super::structs::{VertexDataReference, VerticeNormal, VerticeParameterSpace, VerticeTexture};
+use super::OBJ;
+
+///
+/// Upper bound of the subdivisions of a single polynomial segment, whatever the approximation
+/// technique asks for
+///
+const MAX_SUBDIVISIONS: usize = 256;
+
+///
+/// Upper bound of the points of the grid evaluated for a whole surface, a surface made of many
+/// segments would otherwise ask for billions of them
+///
+const MAX_SURFACE_SAMPLES: usize = 1 << 20;
+
+///
+/// Highest degree accepted by `deg`, real files rarely go above 7
+///
+const MAX_DEGREE: usize = 32;
+
+///
+/// How many times a piece of a segment can be halved by `Approximation::CurvatureDependent`
+///
+const MAX_CURVATURE_DEPTH: usize = 8;
+
+///
+/// Amount of samples used to measure the length of a segment for `Approximation::ConstantSpatial`
+///
+const LENGTH_SAMPLES: usize = 16;
+
+///
+/// Catmull-Rom matrix used by the cardinal splines, see `BasisType::BasisMatrix` for the layout
+///
+#[rustfmt::skip]
+const CARDINAL_MATRIX: [f32; 16] = [
+    0.0, -0.5,  1.0, -0.5,
+    1.0,  0.0, -2.5,  1.5,
+    0.0,  0.5,  2.0, -1.5,
+    0.0,  0.0, -0.5,  0.5,
+];
+
+///
+/// Family of the curves and surfaces, set by the `cstype` statement
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BasisType {
+    ///
+    /// Custom basis given by `bmat`, each row belongs to a control point of the segment and each
+    /// column to a power of the parameter (`1, t, t², ...`)
+    ///
+    BasisMatrix,
+    #[default]
+    Bezier,
+    BSpline,
+    ///
+    /// Cubic Catmull-Rom splines, the curve goes through every control point but the first and
+    /// the last
+    ///
+    Cardinal,
+    ///
+    /// Polynomials in the power basis, the control points are their coefficients
+    ///
+    Taylor,
+}
+
+///
+/// Parametric direction of a surface, curves only use `u`
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    U,
+    V,
+}
+
+///
+/// How the curves (`ctech`) and the surfaces (`stech`) are turned into line strips and triangles
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Approximation {
+    ///
+    /// Every polynomial segment is split `resolution * degree` times in parameter space, set by
+    /// `ctech cparm`, `stech cparma` and `stech cparmb`
+    ///
+    ConstantParametric {
+        u_resolution: f32,
+        v_resolution: f32,
+    },
+    ///
+    /// The pieces are at most `max_length` long in object space
+    ///
+    ConstantSpatial { max_length: f32 },
+    ///
+    /// Pieces are split until they are at most `max_distance` away from the real curve and the
+    /// angle between two of them is at most `max_angle` degrees
+    ///
+    CurvatureDependent { max_distance: f32, max_angle: f32 },
+}
+impl Default for Approximation {
+    fn default() -> Self {
+        Approximation::ConstantParametric {
+            u_resolution: 4.0,
+            v_resolution: 4.0,
+        }
+    }
+}
+
+///
+/// State of the free-form statements (`cstype`, `deg`, `bmat`, `step`, `ctech` and `stech`), each
+/// curve and surface keeps a copy of the state it was declared with
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FreeFormAttributes {
+    pub basis: BasisType,
+    ///
+    /// Rational curves and surfaces use the weight of their control points (`w` of `v` and `vp`)
+    ///
+    pub rational: bool,
+    ///
+    /// `0` until set by `deg`
+    ///
+    pub degree_u: usize,
+    pub degree_v: usize,
+    pub basis_matrix_u: Vec<f32>,
+    pub basis_matrix_v: Vec<f32>,
+    ///
+    /// Only used by `BasisType::BasisMatrix`, `0` until set by `step`
+    ///
+    pub step_u: usize,
+    pub step_v: usize,
+    pub curve_technique: Approximation,
+    pub surface_technique: Approximation,
+}
+
+///
+/// Curve in object space created by `curv`, `control_points` are indices of `OBJ::vertices`
+/// (1-based, like the faces)
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Curve {
+    pub attributes: FreeFormAttributes,
+    pub start: f32,
+    pub end: f32,
+    pub control_points: Vec<usize>,
+    ///
+    /// Set by `parm u`, the knot vector of the B-splines or the global parameter of each segment
+    /// boundary for the other types
+    ///
+    pub parameters: Vec<f32>,
+}
+
+///
+/// Curve in the parameter space of a surface created by `curv2`, `control_points` are indices of
+/// `OBJ::vertices_parameter_space` (1-based)
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Curve2D {
+    pub attributes: FreeFormAttributes,
+    pub control_points: Vec<usize>,
+    pub parameters: Vec<f32>,
+}
+
+///
+/// Part of a trimming loop or of a special curve: the `curve` (index of `OBJ::curves_2d`) between
+/// the `start` and `end` parameters
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CurveOnSurface {
+    pub start: f32,
+    pub end: f32,
+    pub curve: usize,
+}
+
+///
+/// Surface created by `surf`, the control points are listed with `u` varying the fastest
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Surface {
+    pub attributes: FreeFormAttributes,
+    pub start_u: f32,
+    pub end_u: f32,
+    pub start_v: f32,
+    pub end_v: f32,
+    pub control_points: Vec<VertexDataReference>,
+    pub parameters_u: Vec<f32>,
+    pub parameters_v: Vec<f32>,
+    ///
+    /// Outer boundaries (`trim`), the whole surface is kept when there is none
+    ///
+    pub trimming_loops: Vec<Vec<CurveOnSurface>>,
+    ///
+    /// Inner boundaries (`hole`)
+    ///
+    pub holes: Vec<Vec<CurveOnSurface>>,
+    ///
+    /// Curves (`scrv`) and points (`sp`, indices of `OBJ::vertices_parameter_space`) that should
+    /// be part of the tessellation, they are stored but not used by `Surface::tessellate`
+    ///
+    pub special_curves: Vec<Vec<CurveOnSurface>>,
+    pub special_points: Vec<usize>,
+    pub material_name: Option<String>,
+    pub smoothing_group: Option<usize>,
+    pub merging_group: Option<usize>,
+}
+
+///
+/// Triangles of a tessellated surface, `triangles` are 0-based indices of `vertices`
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tessellation {
+    pub vertices: Vec<math::Vec4>,
+    ///
+    /// Empty unless every control point of the surface has a texture vertex
+    ///
+    pub textures: Vec<VerticeTexture>,
+    ///
+    /// `None` where the surface is degenerated, such as the poles of a sphere
+    ///
+    pub normals: Vec<Option<VerticeNormal>>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Curve {
+    ///
+    /// Checks that the curve can be evaluated, returns the reason when it can't
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        curve_basis(
+            &self.attributes,
+            &self.parameters,
+            self.control_points.len(),
+        )
+        .map(|_| ())
+    }
+
+    ///
+    /// Evaluates the curve into a line strip, following `FreeFormAttributes::curve_technique`
+    ///
+    pub fn tessellate(&self, vertices: &[math::Vec4]) -> Result<Vec<math::Vec4>, String> {
+        let points = self
+            .control_points
+            .iter()
+            .map(|index| {
+                let vertex = vertices
+                    .get(index.wrapping_sub(1))
+                    .ok_or_else(|| format!("Vertex {index} is not declared"))?;
+                Ok((math::Vec3::new(vertex.x, vertex.y, vertex.z), vertex.w))
+            })
+            .collect::<Result<Vec<(math::Vec3, f32)>, String>>()?;
+
+        let strip = tessellate_curve(
+            &self.attributes,
+            &self.parameters,
+            self.start,
+            self.end,
+            &points,
+        )?;
+        Ok(strip
+            .into_iter()
+            .map(|point| math::Vec4::new(point.x, point.y, point.z, 1.0))
+            .collect())
+    }
+}
+
+impl Curve2D {
+    ///
+    /// Checks that the curve can be evaluated, returns the reason when it can't
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        curve_basis(
+            &self.attributes,
+            &self.parameters,
+            self.control_points.len(),
+        )
+        .map(|_| ())
+    }
+
+    ///
+    /// Evaluates the curve between `start` and `end` into a line strip of `(u, v)` points
+    ///
+    pub fn tessellate(
+        &self,
+        start: f32,
+        end: f32,
+        vertices_parameter_space: &[VerticeParameterSpace],
+    ) -> Result<Vec<(f32, f32)>, String> {
+        let points = self
+            .control_points
+            .iter()
+            .map(|index| {
+                let vertex = vertices_parameter_space
+                    .get(index.wrapping_sub(1))
+                    .ok_or_else(|| format!("Parameter space vertex {index} is not declared"))?;
+                Ok((math::Vec3::new(vertex.u, vertex.v, 0.0), vertex.w))
+            })
+            .collect::<Result<Vec<(math::Vec3, f32)>, String>>()?;
+
+        let strip = tessellate_curve(&self.attributes, &self.parameters, start, end, &points)?;
+        Ok(strip.into_iter().map(|point| (point.x, point.y)).collect())
+    }
+}
+
+impl Surface {
+    ///
+    /// Checks that the surface can be evaluated, returns the reason when it can't
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        self.bases().map(|_| ())
+    }
+
+    fn bases(&self) -> Result<(Basis<'_>, Basis<'_>), String> {
+        let basis_u = Basis::new(&self.attributes, Direction::U, &self.parameters_u)?;
+        let basis_v = Basis::new(&self.attributes, Direction::V, &self.parameters_v)?;
+
+        let expected = basis_u.control_count() * basis_v.control_count();
+        if self.control_points.len() != expected {
+            return Err(format!(
+                "The parameters need {} by {} control points, but {} are given",
+                basis_u.control_count(),
+                basis_v.control_count(),
+                self.control_points.len()
+            ));
+        }
+
+        Ok((basis_u, basis_v))
+    }
+
+    ///
+    /// Evaluates the surface into triangles following `FreeFormAttributes::surface_technique`.
+    ///
+    /// The trimming loops and the holes are applied to the triangles of the regular grid: a
+    /// triangle is kept when its center is inside of the trimmed region, so the edges of the
+    /// trimmed areas follow the grid.
+    ///
+    pub fn tessellate(&self, obj: &OBJ) -> Result<Tessellation, String> {
+        let (basis_u, basis_v) = self.bases()?;
+        let count_u = basis_u.control_count();
+
+        let mut points = Vec::with_capacity(self.control_points.len());
+        for reference in &self.control_points {
+            let vertex = obj
+                .vertices
+                .get(reference.v.wrapping_sub(1))
+                .ok_or_else(|| format!("Vertex {} is not declared", reference.v))?;
+            points.push((math::Vec3::new(vertex.x, vertex.y, vertex.z), vertex.w));
+        }
+        let textures = self
+            .control_points
+            .iter()
+            .map(|reference| obj.vertices_texture.get(reference.vt.wrapping_sub(1)))
+            .collect::<Option<Vec<&VerticeTexture>>>();
+
+        let weights = |u: f32, v: f32| {
+            let weights_u = basis_u.weights(u);
+            basis_v
+                .weights(v)
+                .into_iter()
+                .flat_map(|(j, weight_v)| {
+                    weights_u
+                        .iter()
+                        .map(move |&(i, weight_u)| (j * count_u + i, weight_u * weight_v))
+                })
+                .collect::<Vec<(usize, f32)>>()
+        };
+        let evaluate = |u: f32, v: f32| {
+            combine(&weights(u, v), self.attributes.rational, |index| {
+                points[index]
+            })
+        };
+
+        let loops = |loops: &[Vec<CurveOnSurface>]| {
+            loops
+                .iter()
+                .map(|curves| trimming_polygon(curves, obj))
+                .collect::<Result<Vec<Vec<(f32, f32)>>, String>>()
+        };
+        let trimming_loops = loops(&self.trimming_loops)?;
+        let holes = loops(&self.holes)?;
+
+        let (start_v, end_v) = (self.start_v, self.end_v);
+        let (start_u, end_u) = (self.start_u, self.end_u);
+        let middle_v = (start_v + end_v) / 2.0;
+        let middle_u = (start_u + end_u) / 2.0;
+
+        // the iso-curve that needs the most samples is used for the whole surface
+        let samples_u = [start_v, middle_v, end_v]
+            .iter()
+            .map(|&v| {
+                let technique = &self.attributes.surface_technique;
+                samples(&basis_u, start_u, end_u, technique, Direction::U, &|u| {
+                    evaluate(u, v)
+                })
+            })
+            .max_by_key(|samples| samples.len())
+            .unwrap_or_default();
+        let samples_v = [start_u, middle_u, end_u]
+            .iter()
+            .map(|&u| {
+                let technique = &self.attributes.surface_technique;
+                samples(&basis_v, start_v, end_v, technique, Direction::V, &|v| {
+                    evaluate(u, v)
+                })
+            })
+            .max_by_key(|samples| samples.len())
+            .unwrap_or_default();
+
+        let sample_count = samples_u
+            .len()
+            .checked_mul(samples_v.len())
+            .filter(|&count| count <= MAX_SURFACE_SAMPLES)
+            .ok_or_else(|| {
+                format!(
+                    "{} by {} samples are more than the {MAX_SURFACE_SAMPLES} allowed",
+                    samples_u.len(),
+                    samples_v.len()
+                )
+            })?;
+
+        let (domain_u, domain_v) = (basis_u.domain(), basis_v.domain());
+        let delta_u = (domain_u.1 - domain_u.0) * 1e-3;
+        let delta_v = (domain_v.1 - domain_v.0) * 1e-3;
+
+        let mut grid = Vec::with_capacity(sample_count);
+        for &v in &samples_v {
+            for &u in &samples_u {
+                let position = evaluate(u, v);
+
+                let tangent_u = evaluate((u + delta_u).min(domain_u.1), v)
+                    - evaluate((u - delta_u).max(domain_u.0), v);
+                let tangent_v = evaluate(u, (v + delta_v).min(domain_v.1))
+                    - evaluate(u, (v - delta_v).max(domain_v.0));
+                let normal = tangent_u.cross(tangent_v);
+                let normal = (normal.length() > f32::EPSILON).then(|| {
+                    let normal = normal.normalize();
+                    VerticeNormal::new(normal.x, normal.y, normal.z)
+                });
+
+                let texture = textures.as_ref().map(|textures| {
+                    let texture = combine(&weights(u, v), self.attributes.rational, |index| {
+                        let texture = textures[index];
+                        (
+                            math::Vec3::new(texture.u, texture.v, texture.w),
+                            points[index].1,
+                        )
+                    });
+                    VerticeTexture::new(texture.x, texture.y, texture.z)
+                });
+
+                grid.push((position, normal, texture));
+            }
+        }
+
+        let is_kept = |u: f32, v: f32| {
+            let is_trimmed = !trimming_loops.is_empty()
+                && !trimming_loops
+                    .iter()
+                    .any(|polygon| contains_point(polygon, u, v));
+            !is_trimmed && !holes.iter().any(|polygon| contains_point(polygon, u, v))
+        };
+
+        let mut tessellation = Tessellation::default();
+        // index of each grid point inside of the tessellation, only the used ones are kept
+        let mut indices: Vec<Option<usize>> = vec![None; grid.len()];
+        let width = samples_u.len();
+
+        for j in 0..samples_v.len().saturating_sub(1) {
+            for i in 0..width.saturating_sub(1) {
+                let corners = [
+                    j * width + i,
+                    j * width + i + 1,
+                    (j + 1) * width + i + 1,
+                    (j + 1) * width + i,
+                ];
+                for triangle in [
+                    [corners[0], corners[1], corners[2]],
+                    [corners[0], corners[2], corners[3]],
+                ] {
+                    let positions = triangle.map(|corner| grid[corner].0);
+                    if positions[0] == positions[1]
+                        || positions[1] == positions[2]
+                        || positions[0] == positions[2]
+                    {
+                        continue;
+                    }
+
+                    let parameters = triangle
+                        .map(|corner| (samples_u[corner % width], samples_v[corner / width]));
+                    let center_u = parameters.iter().map(|(u, _)| u).sum::<f32>() / 3.0;
+                    let center_v = parameters.iter().map(|(_, v)| v).sum::<f32>() / 3.0;
+                    if !is_kept(center_u, center_v) {
+                        continue;
+                    }
+
+                    let triangle = triangle.map(|corner| {
+                        *indices[corner].get_or_insert_with(|| {
+                            let (position, normal, texture) = &grid[corner];
+                            tessellation
+                                .vertices
+                                .push(math::Vec4::new(position.x, position.y, position.z, 1.0));
+                            tessellation.normals.push(normal.clone());
+                            if let Some(texture) = texture {
+                                tessellation.textures.push(texture.clone());
+                            }
+                            tessellation.vertices.len() - 1
+                        })
+                    });
+                    tessellation.triangles.push(triangle);
+                }
+            }
+        }
+
+        Ok(tessellation)
+    }
+}
+
+///
+/// Basis functions of one direction of a curve or a surface
+///
+struct Basis<'a> {
+    basis: BasisType,
+    degree: usize,
+    step: usize,
+    ///
+    /// `(degree + 1)²` values, empty for the B-splines
+    ///
+    matrix: Vec<f32>,
+    parameters: &'a [f32],
+}
+
+impl<'a> Basis<'a> {
+    fn new(
+        attributes: &FreeFormAttributes,
+        direction: Direction,
+        parameters: &'a [f32],
+    ) -> Result<Basis<'a>, String> {
+        let (name, degree, step, matrix) = match direction {
+            Direction::U => (
+                "u",
+                attributes.degree_u,
+                attributes.step_u,
+                &attributes.basis_matrix_u,
+            ),
+            Direction::V => (
+                "v",
+                attributes.degree_v,
+                attributes.step_v,
+                &attributes.basis_matrix_v,
+            ),
+        };
+        if degree == 0 {
+            return Err(format!("Missing the {name} degree ('deg' statement)"));
+        }
+        if degree > MAX_DEGREE {
+            return Err(format!("The {name} degree must be at most {MAX_DEGREE}"));
+        }
+        let order = degree + 1;
+
+        let (matrix, step) = match attributes.basis {
+            BasisType::BSpline => (Vec::new(), 1),
+            BasisType::Bezier => (bernstein_matrix(degree), degree),
+            BasisType::Cardinal if degree == 3 => (CARDINAL_MATRIX.to_vec(), 1),
+            BasisType::Cardinal => return Err("Cardinal splines must be of degree 3".to_string()),
+            BasisType::Taylor => {
+                let mut identity = vec![0.0; order * order];
+                (0..order).for_each(|i| identity[i * order + i] = 1.0);
+                (identity, order)
+            }
+            BasisType::BasisMatrix => {
+                if matrix.len() != order * order {
+                    return Err(format!(
+                        "The {name} basis matrix needs {} values ('bmat' statement)",
+                        order * order
+                    ));
+                }
+                if step == 0 {
+                    return Err(format!("Missing the {name} step ('step' statement)"));
+                }
+                (matrix.clone(), step)
+            }
+        };
+
+        let minimum = match attributes.basis {
+            BasisType::BSpline => 2 * order,
+            _ => 2,
+        };
+        if parameters.len() < minimum {
+            return Err(format!(
+                "At least {minimum} {name} parameters are needed ('parm {name}' statement)"
+            ));
+        }
+        // also rejects the NaN
+        if !parameters.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return Err(format!("The {name} parameters must be increasing"));
+        }
+
+        let basis = Basis {
+            basis: attributes.basis,
+            degree,
+            step,
+            matrix,
+            parameters,
+        };
+        let (start, end) = basis.domain();
+        if start >= end || !start.is_finite() || !end.is_finite() {
+            return Err(format!("The {name} parameters give an empty domain"));
+        }
+
+        Ok(basis)
+    }
+
+    ///
+    /// Amount of control points expected in this direction
+    ///
+    fn control_count(&self) -> usize {
+        match self.basis {
+            BasisType::BSpline => self.parameters.len() - self.degree - 1,
+            _ => (self.parameters.len() - 1) * self.step + self.degree + 1 - self.step,
+        }
+    }
+
+    ///
+    /// Range of the global parameter where the basis is defined
+    ///
+    fn domain(&self) -> (f32, f32) {
+        match self.basis {
+            BasisType::BSpline => (
+                self.parameters[self.degree],
+                self.parameters[self.parameters.len() - self.degree - 1],
+            ),
+            _ => (
+                self.parameters[0],
+                self.parameters[self.parameters.len() - 1],
+            ),
+        }
+    }
+
+    ///
+    /// Parameters where the polynomial segments start and end
+    ///
+    fn breakpoints(&self) -> Vec<f32> {
+        let (start, end) = self.domain();
+        let mut breakpoints = self
+            .parameters
+            .iter()
+            .copied()
+            .filter(|parameter| (start..=end).contains(parameter))
+            .collect::<Vec<f32>>();
+        breakpoints.dedup();
+        breakpoints
+    }
+
+    ///
+    /// Control points (0-based) that influence the curve at `u`, with their weight
+    ///
+    fn weights(&self, u: f32) -> Vec<(usize, f32)> {
+        let (start, end) = self.domain();
+        let u = u.clamp(start, end);
+        let parameters = self.parameters;
+
+        match self.basis {
+            BasisType::BSpline => {
+                let degree = self.degree;
+                // the domain is not empty, so there is at least one span
+                let span = (degree..self.control_count())
+                    .rev()
+                    .find(|&k| parameters[k] <= u && parameters[k] < parameters[k + 1])
+                    .unwrap_or(degree);
+
+                // Cox-de Boor recursion, only the non-zero functions are computed
+                let mut values = vec![0.0; degree + 1];
+                let mut left = vec![0.0; degree + 1];
+                let mut right = vec![0.0; degree + 1];
+                values[0] = 1.0;
+                for j in 1..=degree {
+                    left[j] = u - parameters[span + 1 - j];
+                    right[j] = parameters[span + j] - u;
+                    let mut saved = 0.0;
+                    for r in 0..j {
+                        let temp = values[r] / (right[r + 1] + left[j - r]);
+                        values[r] = saved + right[r + 1] * temp;
+                        saved = left[j - r] * temp;
+                    }
+                    values[j] = saved;
+                }
+
+                values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(r, value)| (span - degree + r, value))
+                    .collect()
+            }
+            _ => {
+                let segment = (0..parameters.len() - 1)
+                    .rev()
+                    .find(|&s| parameters[s] <= u && parameters[s] < parameters[s + 1])
+                    .unwrap_or(0);
+                let t = (u - parameters[segment]) / (parameters[segment + 1] - parameters[segment]);
+
+                let order = self.degree + 1;
+                (0..order)
+                    .map(|i| {
+                        let row = &self.matrix[i * order..(i + 1) * order];
+                        let weight = row.iter().rev().fold(0.0, |sum, value| sum * t + value);
+                        (segment * self.step + i, weight)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+///
+/// Bezier basis in the layout of `BasisType::BasisMatrix`, the coefficient of `tʲ` in the
+/// Bernstein polynomial `i` is `C(n, j) * C(j, i) * (-1)^(j - i)`
+///
+fn bernstein_matrix(degree: usize) -> Vec<f32> {
+    let binomial =
+        |n: usize, k: usize| (0..k).fold(1.0, |value, i| value * (n - i) as f32 / (i + 1) as f32);
+    let order = degree + 1;
+
+    let mut matrix = vec![0.0; order * order];
+    for i in 0..order {
+        for j in i..order {
+            let sign = if (j - i) % 2 == 0 { 1.0 } else { -1.0 };
+            matrix[i * order + j] = sign * binomial(degree, j) * binomial(j, i);
+        }
+    }
+    matrix
+}
+
+fn curve_basis<'a>(
+    attributes: &FreeFormAttributes,
+    parameters: &'a [f32],
+    control_count: usize,
+) -> Result<Basis<'a>, String> {
+    let basis = Basis::new(attributes, Direction::U, parameters)?;
+
+    if control_count != basis.control_count() {
+        return Err(format!(
+            "The parameters need {} control points, but {} are given",
+            basis.control_count(),
+            control_count
+        ));
+    }
+    Ok(basis)
+}
+
+fn tessellate_curve(
+    attributes: &FreeFormAttributes,
+    parameters: &[f32],
+    start: f32,
+    end: f32,
+    points: &[(math::Vec3, f32)],
+) -> Result<Vec<math::Vec3>, String> {
+    let basis = curve_basis(attributes, parameters, points.len())?;
+    let evaluate = |u: f32| {
+        combine(&basis.weights(u), attributes.rational, |index| {
+            points[index]
+        })
+    };
+
+    Ok(samples(
+        &basis,
+        start,
+        end,
+        &attributes.curve_technique,
+        Direction::U,
+        &evaluate,
+    )
+    .into_iter()
+    .map(evaluate)
+    .collect())
+}
+
+///
+/// Sum of the control points multiplied by their weight, the rational ones are also multiplied
+/// by their own weight and the result is projected back from homogeneous coordinates
+///
+fn combine(
+    weights: &[(usize, f32)],
+    rational: bool,
+    point: impl Fn(usize) -> (math::Vec3, f32),
+) -> math::Vec3 {
+    let mut sum = math::Vec3::default();
+    let mut total = 0.0;
+
+    for &(index, weight) in weights {
+        let (position, point_weight) = point(index);
+        let weight = if rational {
+            weight * point_weight
+        } else {
+            weight
+        };
+        sum += position.scale(weight);
+        total += weight;
+    }
+
+    if rational && total != 0.0 {
+        sum.scale(1.0 / total)
+    } else {
+        sum
+    }
+}
+
+///
+/// Parameters where the curve (or an iso-curve of a surface) is evaluated, between `start` and
+/// `end` (clamped to the domain of the basis)
+///
+fn samples(
+    basis: &Basis,
+    start: f32,
+    end: f32,
+    technique: &Approximation,
+    direction: Direction,
+    evaluate: &dyn Fn(f32) -> math::Vec3,
+) -> Vec<f32> {
+    let (domain_start, domain_end) = basis.domain();
+    let clamp = |parameter: f32| parameter.clamp(domain_start, domain_end);
+    let (low, high) = (clamp(start.min(end)), clamp(start.max(end)));
+
+    let mut boundaries = vec![low];
+    boundaries.extend(
+        basis
+            .breakpoints()
+            .into_iter()
+            .filter(|&parameter| low < parameter && parameter < high),
+    );
+    boundaries.push(high);
+
+    let mut samples = Vec::new();
+    for pair in boundaries.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if a == b {
+            continue;
+        }
+        let split = |count: usize| (0..count).map(move |i| a + (b - a) * i as f32 / count as f32);
+
+        match *technique {
+            Approximation::ConstantParametric {
+                u_resolution,
+                v_resolution,
+            } => {
+                let resolution = match direction {
+                    Direction::U => u_resolution,
+                    Direction::V => v_resolution,
+                };
+                let count = (resolution * basis.degree as f32).round() as usize;
+                samples.extend(split(count.clamp(1, MAX_SUBDIVISIONS)));
+            }
+            Approximation::ConstantSpatial { max_length } => {
+                let points = (0..=LENGTH_SAMPLES)
+                    .map(|i| evaluate(a + (b - a) * i as f32 / LENGTH_SAMPLES as f32))
+                    .collect::<Vec<math::Vec3>>();
+                let total = points
+                    .windows(2)
+                    .map(|pair| pair[1].distance(pair[0]))
+                    .sum::<f32>();
+                let count = (total / max_length).ceil() as usize;
+                samples.extend(split(count.clamp(1, MAX_SUBDIVISIONS)));
+            }
+            Approximation::CurvatureDependent {
+                max_distance,
+                max_angle,
+            } => {
+                let pieces = split(basis.degree).chain([b]).collect::<Vec<f32>>();
+                for piece in pieces.windows(2) {
+                    samples.push(piece[0]);
+                    refine(
+                        piece[0],
+                        piece[1],
+                        (evaluate(piece[0]), evaluate(piece[1])),
+                        (max_distance, max_angle),
+                        evaluate,
+                        0,
+                        &mut samples,
+                    );
+                }
+            }
+        }
+    }
+    samples.push(high);
+
+    if start > end {
+        samples.reverse();
+    }
+    samples
+}
+
+///
+/// Halves `[a, b]` until the piece is close enough to the curve, the parameters between `a` and
+/// `b` are pushed in order
+///
+fn refine(
+    a: f32,
+    b: f32,
+    (point_a, point_b): (math::Vec3, math::Vec3),
+    (max_distance, max_angle): (f32, f32),
+    evaluate: &dyn Fn(f32) -> math::Vec3,
+    depth: usize,
+    samples: &mut Vec<f32>,
+) {
+    if depth == MAX_CURVATURE_DEPTH {
+        return;
+    }
+    let middle = (a + b) / 2.0;
+    let point_middle = evaluate(middle);
+
+    let distance = distance_to_segment(point_middle, point_a, point_b);
+    let (first, second) = (point_middle - point_a, point_b - point_middle);
+    let angle = match first.length() * second.length() {
+        0.0 => 0.0,
+        lengths => (first.dot(second) / lengths)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees(),
+    };
+
+    if distance > max_distance || angle > max_angle {
+        let limits = (max_distance, max_angle);
+        refine(
+            a,
+            middle,
+            (point_a, point_middle),
+            limits,
+            evaluate,
+            depth + 1,
+            samples,
+        );
+        samples.push(middle);
+        refine(
+            middle,
+            b,
+            (point_middle, point_b),
+            limits,
+            evaluate,
+            depth + 1,
+            samples,
+        );
+    }
+}
+
+fn distance_to_segment(point: math::Vec3, start: math::Vec3, end: math::Vec3) -> f32 {
+    let segment = end - start;
+    let squared_length = segment.length_squared();
+    if squared_length == 0.0 {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(segment) / squared_length).clamp(0.0, 1.0);
+    point.distance(start.lerp(end, t))
+}
+
+///
+/// Closed polygon in parameter space made of the curves of a trimming loop
+///
+fn trimming_polygon(curves: &[CurveOnSurface], obj: &OBJ) -> Result<Vec<(f32, f32)>, String> {
+    let mut polygon = Vec::new();
+
+    for part in curves {
+        let curve = obj
+            .curves_2d
+            .get(part.curve)
+            .ok_or_else(|| format!("Curve {} is not declared", part.curve + 1))?;
+        polygon.extend(curve.tessellate(part.start, part.end, &obj.vertices_parameter_space)?);
+    }
+    Ok(polygon)
+}
+
+///
+/// Even-odd rule
+///
+fn contains_point(polygon: &[(f32, f32)], u: f32, v: f32) -> bool {
+    let mut is_inside = false;
+
+    for (index, &(u1, v1)) in polygon.iter().enumerate() {
+        let (u2, v2) = polygon[(index + 1) % polygon.len()];
+        if (v1 > v) != (v2 > v) && u < u1 + (v - v1) * (u2 - u1) / (v2 - v1) {
+            is_inside = !is_inside;
+        }
+    }
+    is_inside
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+
+    fn attributes(basis: BasisType, rational: bool, degree: usize) -> FreeFormAttributes {
+        FreeFormAttributes {
+            basis,
+            rational,
+            degree_u: degree,
+            degree_v: degree,
+            curve_technique: Approximation::ConstantParametric {
+                u_resolution: 1.0,
+                v_resolution: 1.0,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_bezier_curves() {
+        let vertices = vec![
+            math::Vec4::new(0.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(1.0, 2.0, 0.0, 1.0),
+            math::Vec4::new(2.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(3.0, -2.0, 0.0, 1.0),
+            math::Vec4::new(4.0, 0.0, 0.0, 1.0),
+        ];
+        // two quadratic segments
+        let curve = Curve {
+            attributes: attributes(BasisType::Bezier, false, 2),
+            start: 0.0,
+            end: 2.0,
+            control_points: vec![1, 2, 3, 4, 5],
+            parameters: vec![0.0, 1.0, 2.0],
+        };
+
+        let strip = curve.tessellate(&vertices).expect("This should work");
+
+        assert_eq!(
+            strip,
+            vec![
+                math::Vec4::new(0.0, 0.0, 0.0, 1.0),
+                math::Vec4::new(1.0, 1.0, 0.0, 1.0),
+                math::Vec4::new(2.0, 0.0, 0.0, 1.0),
+                math::Vec4::new(3.0, -1.0, 0.0, 1.0),
+                math::Vec4::new(4.0, 0.0, 0.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_evaluate_rational_b_splines() {
+        // a quarter of the unit circle
+        let vertices = vec![
+            math::Vec4::new(1.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(1.0, 1.0, 0.0, FRAC_1_SQRT_2),
+            math::Vec4::new(0.0, 1.0, 0.0, 1.0),
+        ];
+        let mut curve = Curve {
+            attributes: attributes(BasisType::BSpline, true, 2),
+            start: 0.0,
+            end: 1.0,
+            control_points: vec![1, 2, 3],
+            parameters: vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+        };
+        curve.attributes.curve_technique = Approximation::CurvatureDependent {
+            max_distance: 0.001,
+            max_angle: 5.0,
+        };
+
+        let strip = curve.tessellate(&vertices).expect("This should work");
+
+        assert!(strip.len() > 16);
+        assert_eq!(strip[0], vertices[0]);
+        assert_eq!(strip[strip.len() - 1], vertices[2]);
+        for point in strip {
+            let radius = (point.x * point.x + point.y * point.y).sqrt();
+            assert!((radius - 1.0).abs() < 1e-5, "{point:?}");
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_the_other_bases() {
+        let vertices = (0..4)
+            .map(|i| math::Vec4::new(i as f32, (i * i) as f32, 0.0, 1.0))
+            .collect::<Vec<math::Vec4>>();
+        let mut curve = Curve {
+            attributes: attributes(BasisType::Cardinal, false, 3),
+            start: 0.0,
+            end: 1.0,
+            control_points: vec![1, 2, 3, 4],
+            parameters: vec![0.0, 1.0],
+        };
+
+        // a cardinal spline goes through the inner control points
+        let strip = curve.tessellate(&vertices).expect("This should work");
+        assert_eq!(strip[0], vertices[1]);
+        assert_eq!(strip[strip.len() - 1], vertices[2]);
+
+        // x = t + 2t² + 3t³ and y = t + 4t² + 9t³
+        curve.attributes = attributes(BasisType::Taylor, false, 3);
+        let strip = curve.tessellate(&vertices).expect("This should work");
+        assert_eq!(strip[strip.len() - 1], math::Vec4::new(6.0, 14.0, 0.0, 1.0));
+
+        // the bezier basis written by hand
+        curve.attributes = FreeFormAttributes {
+            basis_matrix_u: bernstein_matrix(3),
+            step_u: 3,
+            ..attributes(BasisType::BasisMatrix, false, 3)
+        };
+        let bezier = Curve {
+            attributes: attributes(BasisType::Bezier, false, 3),
+            ..curve.clone()
+        };
+        assert_eq!(curve.tessellate(&vertices), bezier.tessellate(&vertices));
+        #[rustfmt::skip]
+        assert_eq!(bernstein_matrix(3), vec![
+            1.0, -3.0,  3.0, -1.0,
+            0.0,  3.0, -6.0,  3.0,
+            0.0,  0.0,  3.0, -3.0,
+            0.0,  0.0,  0.0,  1.0,
+        ]);
+    }
+
+    #[test]
+    fn it_should_not_evaluate_invalid_curves() {
+        let curve = Curve {
+            attributes: attributes(BasisType::BSpline, false, 3),
+            start: 0.0,
+            end: 1.0,
+            control_points: vec![1, 2, 3, 4],
+            parameters: vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+        };
+        assert_eq!(curve.validate(), Ok(()));
+
+        let invalid = [
+            Curve {
+                control_points: vec![1, 2, 3],
+                ..curve.clone()
+            },
+            Curve {
+                parameters: vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+                ..curve.clone()
+            },
+            Curve {
+                parameters: vec![1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+                ..curve.clone()
+            },
+            Curve {
+                parameters: vec![0.0; 8],
+                ..curve.clone()
+            },
+            Curve {
+                attributes: attributes(BasisType::Cardinal, false, 2),
+                ..curve.clone()
+            },
+            Curve {
+                attributes: attributes(BasisType::BasisMatrix, false, 3),
+                ..curve.clone()
+            },
+            Curve {
+                attributes: attributes(BasisType::BSpline, false, 0),
+                ..curve.clone()
+            },
+        ];
+        for curve in invalid {
+            assert!(curve.validate().is_err(), "{curve:?}");
+        }
+    }
+
+    fn plane() -> (OBJ, Surface) {
+        let mut obj = OBJ::default();
+        for (x, y) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            obj.vertices.push(math::Vec4::new(x, y, 0.0, 1.0));
+        }
+
+        let surface = Surface {
+            attributes: FreeFormAttributes {
+                surface_technique: Approximation::ConstantParametric {
+                    u_resolution: 4.0,
+                    v_resolution: 2.0,
+                },
+                ..attributes(BasisType::Bezier, false, 1)
+            },
+            start_u: 0.0,
+            end_u: 1.0,
+            start_v: 0.0,
+            end_v: 1.0,
+            control_points: (1..=4).map(|v| VertexDataReference::new(v, 0, 0)).collect(),
+            parameters_u: vec![0.0, 1.0],
+            parameters_v: vec![0.0, 1.0],
+            ..Default::default()
+        };
+        (obj, surface)
+    }
+
+    #[test]
+    fn it_should_tessellate_surfaces() {
+        let (obj, surface) = plane();
+
+        let tessellation = surface.tessellate(&obj).expect("This should work");
+
+        // 5 by 3 samples
+        assert_eq!(tessellation.vertices.len(), 15);
+        assert_eq!(tessellation.triangles.len(), 16);
+        assert!(tessellation.textures.is_empty());
+        for normal in &tessellation.normals {
+            assert_eq!(normal, &Some(VerticeNormal::new(0.0, 0.0, 1.0)));
+        }
+        for vertex in &tessellation.vertices {
+            assert_eq!(vertex.z, 0.0);
+            assert!((0.0..=1.0).contains(&vertex.x) && (0.0..=1.0).contains(&vertex.y));
+        }
+        // counter-clockwise when looking at the front of the surface
+        let [a, b, c] = tessellation.triangles[0].map(|index| tessellation.vertices[index]);
+        let normal = math::Vec3::new(b.x - a.x, b.y - a.y, 0.0).cross(math::Vec3::new(
+            c.x - a.x,
+            c.y - a.y,
+            0.0,
+        ));
+        assert!(normal.z > 0.0);
+    }
+
+    #[test]
+    fn it_should_not_tessellate_surfaces_with_too_many_samples() {
+        let mut obj = OBJ::default();
+        for y in 0..=100 {
+            for x in 0..=100 {
+                obj.vertices
+                    .push(math::Vec4::new(x as f32, y as f32, 0.0, 1.0));
+            }
+        }
+        let parameters = (0..=100).map(|i| i as f32).collect::<Vec<f32>>();
+
+        // 100 by 100 linear segments of 256 samples each
+        let surface = Surface {
+            attributes: FreeFormAttributes {
+                surface_technique: Approximation::ConstantParametric {
+                    u_resolution: 1000.0,
+                    v_resolution: 1000.0,
+                },
+                ..attributes(BasisType::Bezier, false, 1)
+            },
+            start_u: 0.0,
+            end_u: 100.0,
+            start_v: 0.0,
+            end_v: 100.0,
+            control_points: (1..=obj.vertices.len())
+                .map(|v| VertexDataReference::new(v, 0, 0))
+                .collect(),
+            parameters_u: parameters.clone(),
+            parameters_v: parameters,
+            ..Default::default()
+        };
+
+        let error = surface.tessellate(&obj).expect_err("This should fail");
+
+        assert!(error.contains("samples are more than"), "{error}");
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_trim_surfaces() {
+        let (mut obj, mut surface) = plane();
+        // square hole in the middle, as a closed linear bezier curve
+        for (u, v) in [(0.3, 0.3), (0.7, 0.3), (0.7, 0.7), (0.3, 0.7), (0.3, 0.3)] {
+            obj.vertices_parameter_space.push(VerticeParameterSpace::new(u, v, 1.0));
+        }
+        obj.curves_2d.push(Curve2D {
+            attributes: attributes(BasisType::Bezier, false, 1),
+            control_points: vec![1, 2, 3, 4, 5],
+            parameters: vec![0.0, 1.0, 2.0, 3.0, 4.0],
+        });
+        surface.holes.push(vec![CurveOnSurface { start: 0.0, end: 4.0, curve: 0 }]);
+        surface.attributes.surface_technique = Approximation::ConstantParametric { u_resolution: 10.0, v_resolution: 10.0 };
+
+        let tessellation = surface.tessellate(&obj).expect("This should work");
+
+        assert_eq!(tessellation.triangles.len(), 200 - 32);
+        for triangle in &tessellation.triangles {
+            let center = triangle.iter().fold((0.0, 0.0), |(u, v), index| {
+                let vertex = tessellation.vertices[*index];
+                (u + vertex.x / 3.0, v + vertex.y / 3.0)
+            });
+            assert!(!contains_point(&[(0.3, 0.3), (0.7, 0.3), (0.7, 0.7), (0.3, 0.7)], center.0, center.1));
+        }
+
+        // the same loop as the outer boundary keeps only the middle
+        surface.trimming_loops = std::mem::take(&mut surface.holes);
+        let tessellation = surface.tessellate(&obj).expect("This should work");
+        assert_eq!(tessellation.triangles.len(), 32);
+    }
+}
@@ -1,95 +1,185 @@
-use std::vec::IntoIter;
+use std::num::ParseFloatError;
 
-use crate::{graphics::triangulation, math};
+use crate::{
+    graphics::{triangulation, wavefront::lexer::Tokens},
+    math,
+};
 
 use super::{
+    free_form::{
+        Approximation, BasisType, Curve, Curve2D, CurveOnSurface, Direction, FreeFormAttributes,
+        Surface,
+    },
     structs::{
-        Face, ParseError, VertexDataReference, VerticeNormal, VerticeParameterSpace, VerticeTexture,
+        Face, Line, MergingGroup, ParseError, Point, TriangulationMethod, VertexDataReference,
+        VerticeNormal, VerticeParameterSpace, VerticeTexture,
     },
     OBJ,
 };
 
-pub fn parse_vertice(tokens: &mut IntoIter<&str>, line_n: usize) -> Result<math::Vec4, ParseError> {
-    let x = tokens.next().unwrap().parse::<f32>();
-    let y = tokens.next().unwrap().parse::<f32>();
-    let z = tokens.next().unwrap().parse::<f32>();
-    let w = tokens.next().unwrap_or("1.0").parse::<f32>();
+///
+/// Parses a float, the common forms found in object files such as `-0.123456` are converted
+/// directly and anything else (exponents, `inf`, long mantissas...) goes through `str::parse`.
+///
+/// The fast path gives the same results as `str::parse`: the digits fit in the 24 bits mantissa
+/// of a `f32` and powers of ten up to `1e10` are exact, so the single division is correctly
+/// rounded.
+///
+pub fn parse_float(token: &str) -> Result<f32, ParseFloatError> {
+    const POWERS_OF_TEN: [f32; 11] = [1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10];
+
+    let bytes = token.as_bytes();
+    let (is_negative, digits) = match bytes.first() {
+        Some(b'-') => (true, &bytes[1..]),
+        Some(b'+') => (false, &bytes[1..]),
+        _ => (false, bytes),
+    };
+
+    let mut mantissa: u32 = 0;
+    let mut fraction_digits = 0;
+    let mut has_digits = false;
+    let mut has_dot = false;
+
+    for byte in digits {
+        match byte {
+            b'0'..=b'9' => {
+                mantissa = mantissa * 10 + (byte - b'0') as u32;
+                fraction_digits += has_dot as usize;
+                has_digits = true;
+
+                if mantissa >= 1 << 24 || fraction_digits >= POWERS_OF_TEN.len() {
+                    return token.parse();
+                }
+            }
+            b'.' if !has_dot => has_dot = true,
+            _ => return token.parse(),
+        }
+    }
 
-    match (x, y, z, w) {
-        (Ok(x), Ok(y), Ok(z), Ok(w)) => Ok(math::Vec4::new(x, y, z, w)),
-        _ => Err(ParseError::InvalidVertex(line_n, "Error".to_string())),
+    if !has_digits {
+        return token.parse();
     }
+
+    let value = mantissa as f32 / POWERS_OF_TEN[fraction_digits];
+    Ok(if is_negative { -value } else { value })
+}
+
+///
+/// Parses the next token as a float, `default` is used when there are no more tokens
+///
+fn parse_next_float(
+    tokens: &mut Tokens<'_>,
+    name: &str,
+    default: Option<f32>,
+    line_n: usize,
+    error: fn(usize, String) -> ParseError,
+) -> Result<f32, ParseError> {
+    match tokens.next() {
+        Some(token) => parse_float(token)
+            .map_err(|_| error(line_n, format!("Invalid '{name}' value '{token}'"))),
+        None => default.ok_or_else(|| error(line_n, format!("Missing '{name}' value"))),
+    }
+}
+
+pub fn parse_vertice(tokens: &mut Tokens<'_>, line_n: usize) -> Result<math::Vec4, ParseError> {
+    let error = ParseError::InvalidVertex;
+    let x = parse_next_float(tokens, "x", None, line_n, error)?;
+    let y = parse_next_float(tokens, "y", None, line_n, error)?;
+    let z = parse_next_float(tokens, "z", None, line_n, error)?;
+    let w = parse_next_float(tokens, "w", Some(1.0), line_n, error)?;
+
+    Ok(math::Vec4::new(x, y, z, w))
 }
 
 pub fn parse_vertice_parameter_space(
-    tokens: &mut IntoIter<&str>,
+    tokens: &mut Tokens<'_>,
     line_n: usize,
 ) -> Result<VerticeParameterSpace, ParseError> {
-    let u = tokens.next().unwrap().parse::<f32>();
-    let v = tokens.next().unwrap().parse::<f32>();
-    let w = tokens.next().unwrap_or("1.0").parse::<f32>();
+    let error = ParseError::InvalidVertexParameterSpace;
+    let u = parse_next_float(tokens, "u", None, line_n, error)?;
+    let v = parse_next_float(tokens, "v", None, line_n, error)?;
+    let w = parse_next_float(tokens, "w", Some(1.0), line_n, error)?;
 
-    match (u, v, w) {
-        (Ok(u), Ok(v), Ok(w)) => Ok(VerticeParameterSpace { u, v, w }),
-        _ => Err(ParseError::InvalidVertexParameterSpace(
-            line_n,
-            "Error".to_string(),
-        )),
-    }
+    Ok(VerticeParameterSpace { u, v, w })
 }
 
 pub fn parse_vertice_normal(
-    tokens: &mut IntoIter<&str>,
+    tokens: &mut Tokens<'_>,
     line_n: usize,
 ) -> Result<VerticeNormal, ParseError> {
-    let i = tokens.next().unwrap().parse::<f32>();
-    let j = tokens.next().unwrap().parse::<f32>();
-    let k = tokens.next().unwrap().parse::<f32>();
+    let error = ParseError::InvalidVertexNormal;
+    let i = parse_next_float(tokens, "i", None, line_n, error)?;
+    let j = parse_next_float(tokens, "j", None, line_n, error)?;
+    let k = parse_next_float(tokens, "k", None, line_n, error)?;
 
-    match (i, j, k) {
-        (Ok(i), Ok(j), Ok(k)) => Ok(VerticeNormal { i, j, k }),
-        _ => Err(ParseError::InvalidVertexNormal(line_n, "Error".to_string())),
-    }
+    Ok(VerticeNormal { i, j, k })
 }
 
 pub fn parse_vertice_texture(
-    tokens: &mut IntoIter<&str>,
+    tokens: &mut Tokens<'_>,
     line_n: usize,
 ) -> Result<VerticeTexture, ParseError> {
-    let u = tokens.next().unwrap().parse::<f32>();
-    let v = tokens.next().unwrap_or("0.0").parse::<f32>();
-    let w = tokens.next().unwrap_or("0.0").parse::<f32>();
+    let error = ParseError::InvalidVertexTexture;
+    let u = parse_next_float(tokens, "u", None, line_n, error)?;
+    let v = parse_next_float(tokens, "v", Some(0.0), line_n, error)?;
+    let w = parse_next_float(tokens, "w", Some(0.0), line_n, error)?;
+
+    Ok(VerticeTexture { u, v, w })
+}
 
-    match (u, v, w) {
-        (Ok(u), Ok(v), Ok(w)) => Ok(VerticeTexture { u, v, w }),
-        _ => Err(ParseError::InvalidVertexTexture(
+pub fn parse_usemtl(tokens: &mut Tokens<'_>, line_n: usize) -> Result<String, ParseError> {
+    match (tokens.next(), tokens.next()) {
+        (Some(name), None) => Ok(name.to_string()),
+        (None, _) => Err(ParseError::InvalidFaceMaterial(
             line_n,
-            "Error".to_string(),
+            "Missing material name".to_string(),
+        )),
+        (Some(_), Some(_)) => Err(ParseError::InvalidFaceMaterial(
+            line_n,
+            "You can only specify one material".to_string(),
         )),
     }
 }
 
-fn parse_usemtl(line: Option<&str>) -> Option<String> {
-    let mut tokens = line?
-        .split(" ")
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<&str>>()
-        .into_iter();
+///
+/// Resolves a single `v`, `vt` or `vn` index of an element statement into an absolute 1-based index.
+///
+/// Positive indices refer to the data counting from the top of the file, while negative indices
+/// are relative to the amount of data defined so far (`-1` is the last one declared before the element).
+///
+/// * `count` - How many items of this kind were declared until the current line
+///
+pub fn resolve_vertex_reference(
+    token: &str,
+    count: usize,
+    line_n: usize,
+) -> Result<usize, ParseError> {
+    let index = token.parse::<isize>().map_err(|_| {
+        ParseError::InvalidVertexReference(line_n, format!("Invalid reference '{token}'"))
+    })?;
 
-    if tokens.next() == Some("usemtl") {
-        Some(tokens.next().unwrap().to_string().clone())
+    let resolved = if index < 0 {
+        count as isize + index + 1
     } else {
-        None
+        index
+    };
+
+    if resolved < 1 || resolved as usize > count {
+        return Err(ParseError::InvalidVertexReference(
+            line_n,
+            format!("Reference '{token}' is out of range, only {count} declared"),
+        ));
     }
+
+    Ok(resolved as usize)
 }
 
 pub fn parse_face(
-    tokens: &mut IntoIter<&str>,
-    previous_line: Option<&str>,
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    material_name: Option<String>,
     line_n: usize,
 ) -> Result<Face, ParseError> {
-    let material_name = parse_usemtl(previous_line);
     let mut face = Face::partial_new(Vec::new(), material_name);
     let mut is_tripplets_format = false;
     let mut is_twins_format = false;
@@ -97,39 +187,135 @@ pub fn parse_face(
     for token in tokens {
         let mut parts = token.split("/");
 
-        let v = parts.next().unwrap().parse::<usize>();
-        let vt = parts.next().map_or("0", |s| s).parse::<usize>();
-        let vn = parts.next().map_or("0", |s| s).parse::<usize>();
+        let v = parts.next().unwrap_or_default();
+        let vt = parts.next().unwrap_or_default();
+        let vn = parts.next().unwrap_or_default();
 
-        match (v, vt, vn) {
-            (Ok(v), Ok(vt), Ok(vn)) => {
-                face.vertex_references
-                    .push(VertexDataReference::new(v, vt, vn));
-                is_tripplets_format = true
-            }
-            (Ok(v), Err(_), Ok(vn)) => {
-                face.vertex_references
-                    .push(VertexDataReference::new(v, 0, vn));
-                is_twins_format = true
-            }
-            _ => return Err(ParseError::InvalidFaceSide(line_n, "Error".to_string())),
+        if v.is_empty() || parts.next().is_some() {
+            return Err(ParseError::InvalidFaceSide(
+                line_n,
+                format!("Invalid face side '{token}'"),
+            ));
+        }
+
+        let v = resolve_vertex_reference(v, obj.vertices.len(), line_n)?;
+        let vn = if vn.is_empty() {
+            0
+        } else {
+            resolve_vertex_reference(vn, obj.vertices_normal.len(), line_n)?
+        };
+
+        if vt.is_empty() && token.contains("//") {
+            face.vertex_references
+                .push(VertexDataReference::new(v, 0, vn));
+            is_twins_format = true
+        } else {
+            let vt = if vt.is_empty() {
+                0
+            } else {
+                resolve_vertex_reference(vt, obj.vertices_texture.len(), line_n)?
+            };
+            face.vertex_references
+                .push(VertexDataReference::new(v, vt, vn));
+            is_tripplets_format = true
+        }
+
+        if is_tripplets_format && is_twins_format {
+            return Err(ParseError::InvalidFace(
+                line_n,
+                "Illegal to give vertex texture for some vertices, but not all".to_string(),
+            ));
         }
     }
 
-    if is_tripplets_format && is_twins_format {
+    if face.vertex_references.len() < 3 {
         return Err(ParseError::InvalidFace(
             line_n,
-            "Illegal to give vertex texture for some vertices, but not all".to_string(),
+            "A face needs at least 3 vertices".to_string(),
         ));
     }
 
     Ok(face)
 }
 
-pub fn parse_smoothing_group(
-    tokens: &mut IntoIter<&str>,
+pub fn parse_point(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    material_name: Option<String>,
     line_n: usize,
-) -> Result<usize, ParseError> {
+) -> Result<Point, ParseError> {
+    let vertices = tokens
+        .map(|token| resolve_vertex_reference(token, obj.vertices.len(), line_n))
+        .collect::<Result<Vec<usize>, ParseError>>()?;
+
+    if vertices.is_empty() {
+        return Err(ParseError::InvalidPoint(
+            line_n,
+            "A point needs at least 1 vertex".to_string(),
+        ));
+    }
+
+    Ok(Point {
+        id: 0,
+        vertices,
+        material_name,
+        material: None,
+    })
+}
+
+pub fn parse_line(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    material_name: Option<String>,
+    line_n: usize,
+) -> Result<Line, ParseError> {
+    let mut vertex_references = Vec::new();
+
+    for token in tokens {
+        let mut parts = token.split('/');
+        let v = parts.next().unwrap_or_default();
+        let vt = parts.next();
+
+        if v.is_empty() || vt == Some("") || parts.next().is_some() {
+            return Err(ParseError::InvalidLine(
+                line_n,
+                format!("Invalid line vertex '{token}'"),
+            ));
+        }
+
+        let v = resolve_vertex_reference(v, obj.vertices.len(), line_n)?;
+        let vt = match vt {
+            Some(vt) => resolve_vertex_reference(vt, obj.vertices_texture.len(), line_n)?,
+            None => 0,
+        };
+        vertex_references.push(VertexDataReference::new(v, vt, 0));
+    }
+
+    if vertex_references.len() < 2 {
+        return Err(ParseError::InvalidLine(
+            line_n,
+            "A line needs at least 2 vertices".to_string(),
+        ));
+    }
+    if vertex_references
+        .iter()
+        .any(|reference| (reference.vt == 0) != (vertex_references[0].vt == 0))
+    {
+        return Err(ParseError::InvalidLine(
+            line_n,
+            "Illegal to give texture vertices for some vertices, but not all".to_string(),
+        ));
+    }
+
+    Ok(Line {
+        id: 0,
+        vertex_references,
+        material_name,
+        material: None,
+    })
+}
+
+pub fn parse_smoothing_group(tokens: &mut Tokens<'_>, line_n: usize) -> Result<usize, ParseError> {
     match tokens.next() {
         None => Ok(0_usize),
         Some("off") => Ok(0_usize),
@@ -142,9 +328,534 @@ pub fn parse_smoothing_group(
     }
 }
 
-pub fn triangulate_polygons(obj: &mut OBJ) {
+///
+/// Parses the names given to the `g` statement, when none is given the spec says the elements
+/// go back to the "default" group
+///
+pub fn parse_group(tokens: &mut Tokens<'_>) -> Vec<String> {
+    let names = tokens.map(|name| name.to_string()).collect::<Vec<String>>();
+
+    if names.is_empty() {
+        vec!["default".to_string()]
+    } else {
+        names
+    }
+}
+
+///
+/// Returns `None` when merging is turned off (`mg off` or `mg 0`)
+///
+pub fn parse_merging_group(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<Option<MergingGroup>, ParseError> {
+    let id = match tokens.next() {
+        None => {
+            return Err(ParseError::InvalidMergingGroup(
+                line_n,
+                "Missing merging group number".to_string(),
+            ))
+        }
+        Some("off") => 0,
+        Some(str) => str.parse::<usize>().map_err(|_| {
+            ParseError::InvalidMergingGroup(line_n, "Invalid merging group number".to_string())
+        })?,
+    };
+
+    if id == 0 {
+        return Ok(None);
+    }
+
+    let resolution = tokens
+        .next()
+        .ok_or_else(|| {
+            ParseError::InvalidMergingGroup(line_n, "Missing merging resolution".to_string())
+        })?
+        .parse::<f32>()
+        .map_err(|_| {
+            ParseError::InvalidMergingGroup(line_n, "Invalid merging resolution".to_string())
+        })?;
+
+    if resolution <= 0.0 {
+        return Err(ParseError::InvalidMergingGroup(
+            line_n,
+            "Merging resolution must be greater than 0".to_string(),
+        ));
+    }
+
+    Ok(Some(MergingGroup { id, resolution }))
+}
+
+pub fn parse_object_name(tokens: &mut Tokens<'_>, line_n: usize) -> Result<String, ParseError> {
+    let name = tokens.collect::<Vec<&str>>().join(" ");
+
+    if name.is_empty() {
+        return Err(ParseError::InvalidObjectName(
+            line_n,
+            "Missing object name".to_string(),
+        ));
+    }
+
+    Ok(name)
+}
+
+///
+/// Parses `cstype`, returns the type and whether it is rational
+///
+pub fn parse_curve_surface_type(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<(BasisType, bool), ParseError> {
+    let mut token = tokens.next();
+    let rational = token == Some("rat");
+    if rational {
+        token = tokens.next();
+    }
+
+    let basis = match token {
+        Some("bmatrix") => BasisType::BasisMatrix,
+        Some("bezier") => BasisType::Bezier,
+        Some("bspline") => BasisType::BSpline,
+        Some("cardinal") => BasisType::Cardinal,
+        Some("taylor") => BasisType::Taylor,
+        Some(unknown) => {
+            return Err(ParseError::InvalidFreeFormAttribute(
+                line_n,
+                format!("Unknown curve or surface type '{unknown}'"),
+            ))
+        }
+        None => {
+            return Err(ParseError::InvalidFreeFormAttribute(
+                line_n,
+                "Missing curve or surface type".to_string(),
+            ))
+        }
+    };
+
+    Ok((basis, rational))
+}
+
+fn parse_next_count(
+    tokens: &mut Tokens<'_>,
+    name: &str,
+    line_n: usize,
+) -> Result<usize, ParseError> {
+    let token = tokens.next().ok_or_else(|| {
+        ParseError::InvalidFreeFormAttribute(line_n, format!("Missing '{name}' value"))
+    })?;
+
+    match token.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(ParseError::InvalidFreeFormAttribute(
+            line_n,
+            format!("Invalid '{name}' value '{token}', it must be greater than 0"),
+        )),
+    }
+}
+
+///
+/// Parses `deg`, the `v` degree is only needed by the surfaces and is `0` when missing
+///
+pub fn parse_degree(tokens: &mut Tokens<'_>, line_n: usize) -> Result<(usize, usize), ParseError> {
+    let degree_u = parse_next_count(tokens, "degu", line_n)?;
+    let degree_v = match tokens.clone().next() {
+        Some(_) => parse_next_count(tokens, "degv", line_n)?,
+        None => 0,
+    };
+
+    Ok((degree_u, degree_v))
+}
+
+///
+/// Parses `step`, the `v` step is only needed by the surfaces
+///
+pub fn parse_step(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<(usize, Option<usize>), ParseError> {
+    let step_u = parse_next_count(tokens, "stepu", line_n)?;
+    let step_v = match tokens.clone().next() {
+        Some(_) => Some(parse_next_count(tokens, "stepv", line_n)?),
+        None => None,
+    };
+
+    Ok((step_u, step_v))
+}
+
+fn parse_direction(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+    error: fn(usize, String) -> ParseError,
+) -> Result<Direction, ParseError> {
+    match tokens.next() {
+        Some("u") => Ok(Direction::U),
+        Some("v") => Ok(Direction::V),
+        Some(token) => Err(error(line_n, format!("Invalid direction '{token}'"))),
+        None => Err(error(line_n, "Missing direction".to_string())),
+    }
+}
+
+fn parse_floats(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+    error: fn(usize, String) -> ParseError,
+) -> Result<Vec<f32>, ParseError> {
+    let values = tokens
+        .map(|token| {
+            parse_float(token).map_err(|_| error(line_n, format!("Invalid value '{token}'")))
+        })
+        .collect::<Result<Vec<f32>, ParseError>>()?;
+
+    if values.is_empty() {
+        return Err(error(line_n, "Missing values".to_string()));
+    }
+    Ok(values)
+}
+
+///
+/// Parses `bmat`, the size of the matrix is checked against the degree when the curves and
+/// surfaces using it are complete
+///
+pub fn parse_basis_matrix(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<(Direction, Vec<f32>), ParseError> {
+    let error = ParseError::InvalidFreeFormAttribute;
+    let direction = parse_direction(tokens, line_n, error)?;
+    let matrix = parse_floats(tokens, line_n, error)?;
+
+    Ok((direction, matrix))
+}
+
+fn parse_positive_float(
+    tokens: &mut Tokens<'_>,
+    name: &str,
+    line_n: usize,
+) -> Result<f32, ParseError> {
+    let error = ParseError::InvalidFreeFormAttribute;
+    let value = parse_next_float(tokens, name, None, line_n, error)?;
+
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(error(line_n, format!("'{name}' must be greater than 0")))
+    }
+}
+
+///
+/// Parses the techniques shared by `ctech` and `stech`
+///
+fn parse_approximation(
+    technique: Option<&str>,
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<Approximation, ParseError> {
+    match technique {
+        Some("cspace") => Ok(Approximation::ConstantSpatial {
+            max_length: parse_positive_float(tokens, "maxlength", line_n)?,
+        }),
+        Some("curv") => Ok(Approximation::CurvatureDependent {
+            max_distance: parse_positive_float(tokens, "maxdist", line_n)?,
+            max_angle: parse_positive_float(tokens, "maxangle", line_n)?,
+        }),
+        Some(unknown) => Err(ParseError::InvalidFreeFormAttribute(
+            line_n,
+            format!("Unknown approximation technique '{unknown}'"),
+        )),
+        None => Err(ParseError::InvalidFreeFormAttribute(
+            line_n,
+            "Missing approximation technique".to_string(),
+        )),
+    }
+}
+
+pub fn parse_curve_technique(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<Approximation, ParseError> {
+    match tokens.next() {
+        Some("cparm") => {
+            let resolution = parse_positive_float(tokens, "res", line_n)?;
+            Ok(Approximation::ConstantParametric {
+                u_resolution: resolution,
+                v_resolution: resolution,
+            })
+        }
+        technique => parse_approximation(technique, tokens, line_n),
+    }
+}
+
+///
+/// Parses `stech`, `cparma` and `cparmb` are both constant parametric subdivisions
+///
+pub fn parse_surface_technique(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<Approximation, ParseError> {
+    match tokens.next() {
+        Some("cparma") => Ok(Approximation::ConstantParametric {
+            u_resolution: parse_positive_float(tokens, "ures", line_n)?,
+            v_resolution: parse_positive_float(tokens, "vres", line_n)?,
+        }),
+        Some("cparmb") => {
+            let resolution = parse_positive_float(tokens, "uvres", line_n)?;
+            Ok(Approximation::ConstantParametric {
+                u_resolution: resolution,
+                v_resolution: resolution,
+            })
+        }
+        technique => parse_approximation(technique, tokens, line_n),
+    }
+}
+
+fn parse_references(
+    tokens: &mut Tokens<'_>,
+    count: usize,
+    minimum: usize,
+    line_n: usize,
+) -> Result<Vec<usize>, ParseError> {
+    let references = tokens
+        .map(|token| resolve_vertex_reference(token, count, line_n))
+        .collect::<Result<Vec<usize>, ParseError>>()?;
+
+    if references.len() < minimum {
+        return Err(ParseError::InvalidCurveOrSurface(
+            line_n,
+            format!("At least {minimum} vertices are needed"),
+        ));
+    }
+    Ok(references)
+}
+
+///
+/// Parses `curv u0 u1 v1 v2 ...`, the parameters are added later by `parm`
+///
+pub fn parse_curve(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    attributes: &FreeFormAttributes,
+    line_n: usize,
+) -> Result<Curve, ParseError> {
+    let error = ParseError::InvalidCurveOrSurface;
+    let start = parse_next_float(tokens, "u0", None, line_n, error)?;
+    let end = parse_next_float(tokens, "u1", None, line_n, error)?;
+    let control_points = parse_references(tokens, obj.vertices.len(), 2, line_n)?;
+
+    Ok(Curve {
+        attributes: attributes.clone(),
+        start,
+        end,
+        control_points,
+        parameters: Vec::new(),
+    })
+}
+
+///
+/// Parses `curv2 vp1 vp2 ...`, the parameters are added later by `parm`
+///
+pub fn parse_curve_2d(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    attributes: &FreeFormAttributes,
+    line_n: usize,
+) -> Result<Curve2D, ParseError> {
+    let control_points = parse_references(tokens, obj.vertices_parameter_space.len(), 2, line_n)?;
+
+    Ok(Curve2D {
+        attributes: attributes.clone(),
+        control_points,
+        parameters: Vec::new(),
+    })
+}
+
+///
+/// Parses `surf s0 s1 t0 t1 v1/vt1/vn1 ...`, the parameters and the trimming loops are added
+/// later by the body statements
+///
+pub fn parse_surface(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    attributes: &FreeFormAttributes,
+    line_n: usize,
+) -> Result<Surface, ParseError> {
+    let error = ParseError::InvalidCurveOrSurface;
+    let start_u = parse_next_float(tokens, "s0", None, line_n, error)?;
+    let end_u = parse_next_float(tokens, "s1", None, line_n, error)?;
+    let start_v = parse_next_float(tokens, "t0", None, line_n, error)?;
+    let end_v = parse_next_float(tokens, "t1", None, line_n, error)?;
+
+    let mut control_points = Vec::new();
+    for token in tokens {
+        let mut parts = token.split('/');
+        let v = parts.next().unwrap_or_default();
+        let vt = parts.next().unwrap_or_default();
+        let vn = parts.next().unwrap_or_default();
+
+        if v.is_empty() || parts.next().is_some() {
+            return Err(error(line_n, format!("Invalid control point '{token}'")));
+        }
+
+        let resolve = |reference: &str, count: usize| match reference {
+            "" => Ok(0),
+            reference => resolve_vertex_reference(reference, count, line_n),
+        };
+        control_points.push(VertexDataReference::new(
+            resolve(v, obj.vertices.len())?,
+            resolve(vt, obj.vertices_texture.len())?,
+            resolve(vn, obj.vertices_normal.len())?,
+        ));
+    }
+
+    if control_points.len() < 4 {
+        return Err(error(
+            line_n,
+            "At least 4 control points are needed".to_string(),
+        ));
+    }
+
+    Ok(Surface {
+        attributes: attributes.clone(),
+        start_u,
+        end_u,
+        start_v,
+        end_v,
+        control_points,
+        ..Default::default()
+    })
+}
+
+///
+/// Parses `parm u|v p1 p2 ...`, the values are checked when the curve or surface is complete
+///
+pub fn parse_parameters(
+    tokens: &mut Tokens<'_>,
+    line_n: usize,
+) -> Result<(Direction, Vec<f32>), ParseError> {
+    let error = ParseError::InvalidCurveOrSurface;
+    let direction = parse_direction(tokens, line_n, error)?;
+    let parameters = parse_floats(tokens, line_n, error)?;
+
+    Ok((direction, parameters))
+}
+
+///
+/// Parses the `u0 u1 curv2d` triplets of `trim`, `hole` and `scrv`, `curv2d` is resolved like
+/// the vertex references into an index of `OBJ::curves_2d`
+///
+pub fn parse_curves_on_surface(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    line_n: usize,
+) -> Result<Vec<CurveOnSurface>, ParseError> {
+    let error = ParseError::InvalidCurveOrSurface;
+    let mut curves = Vec::new();
+
+    while tokens.clone().next().is_some() {
+        let start = parse_next_float(tokens, "u0", None, line_n, error)?;
+        let end = parse_next_float(tokens, "u1", None, line_n, error)?;
+        let token = tokens
+            .next()
+            .ok_or_else(|| error(line_n, "Missing 'curv2d' value".to_string()))?;
+        let curve = resolve_vertex_reference(token, obj.curves_2d.len(), line_n)
+            .map_err(|_| error(line_n, format!("Curve '{token}' is not declared")))?;
+
+        curves.push(CurveOnSurface {
+            start,
+            end,
+            curve: curve - 1,
+        });
+    }
+
+    if curves.is_empty() {
+        return Err(error(line_n, "Missing curves".to_string()));
+    }
+    Ok(curves)
+}
+
+///
+/// Parses `sp vp1 vp2 ...`
+///
+pub fn parse_special_points(
+    tokens: &mut Tokens<'_>,
+    obj: &OBJ,
+    line_n: usize,
+) -> Result<Vec<usize>, ParseError> {
+    parse_references(tokens, obj.vertices_parameter_space.len(), 1, line_n)
+}
+
+pub fn triangulate_polygons(obj: &mut OBJ, method: TriangulationMethod) {
     for face in obj.faces.iter_mut() {
-        let triangulated = triangulation::_2d::fan::triangulate(&mut face.vertex_references);
+        let triangulated = match method {
+            TriangulationMethod::Fan => {
+                triangulation::_2d::fan::triangulate(&mut face.vertex_references)
+            }
+            TriangulationMethod::EarClipping => triangulation::_2d::ear_clipping::triangulate(
+                &face.vertex_references,
+                &obj.vertices,
+            ),
+        };
         face.vertex_references = triangulated;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_floats_like_the_standard_library() {
+        let mut tokens = vec![
+            "0",
+            "-0",
+            "+1",
+            "1.",
+            ".5",
+            "-.5",
+            "0.000001",
+            "16777215",
+            "16777216",
+            "16777217",
+            "0.1234567890",
+            "0.12345678901",
+            "-123.456",
+            "1e3",
+            "-2.5E-3",
+            "inf",
+            "-NaN",
+            "3.4028236e38",
+            "0001.5000",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>();
+
+        // pseudo random decimals, with the usual amount of digits of exported files
+        let mut seed: u64 = 42;
+        for _ in 0..10_000 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let integer = (seed >> 33) % 1000;
+            let fraction = (seed >> 13) % 1_000_000;
+            let sign = if seed & 1 == 0 { "-" } else { "" };
+            tokens.push(format!("{sign}{integer}.{fraction:06}"));
+        }
+
+        for token in &tokens {
+            let expected = token.parse::<f32>().expect("This should work");
+            let result = parse_float(token).expect("This should work");
+
+            assert!(
+                result.to_bits() == expected.to_bits() || (result.is_nan() && expected.is_nan()),
+                "{token}: {result} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_not_parse_invalid_floats() {
+        for token in ["", "-", ".", "1..2", "1.2.3", "--1", "1-", "abc", "0x10"] {
+            assert!(parse_float(token).is_err(), "{token}");
+        }
+    }
+}
@@ -1,13 +1,45 @@
+mod free_form;
 mod helpers;
 mod parse_obj;
 mod structs;
+mod write_obj;
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
 
 use structs::ParseError;
 
 use crate::graphics::wavefront;
+use crate::graphics::wavefront::mtl::MaterialLibrary;
 use crate::graphics::wavefront::obj::parse_obj::parse_obj;
 
+pub use free_form::Approximation;
+pub use free_form::BasisType;
+pub use free_form::Curve;
+pub use free_form::Curve2D;
+pub use free_form::CurveOnSurface;
+pub use free_form::Direction;
+pub use free_form::FreeFormAttributes;
+pub use free_form::Surface;
+pub use free_form::Tessellation;
+pub use helpers::parse_float;
+pub use structs::Face;
+pub use structs::Group;
+pub use structs::Line;
+pub use structs::LoadOptions;
+pub use structs::MaterialRange;
+pub use structs::MergingGroup;
+pub use structs::MissingMaterialLibrary;
+pub use structs::Object;
+pub use structs::Point;
+pub use structs::TriangulationMethod;
 pub use structs::VertexDataReference;
+pub use structs::VerticeNormal;
+pub use structs::VerticeParameterSpace;
+pub use structs::VerticeTexture;
 pub use structs::OBJ;
 
 #[derive(Debug)]
@@ -54,24 +86,209 @@ impl std::fmt::Display for LoadOBJError {
 }
 
 pub fn load(file_path: &str) -> Result<OBJ, LoadOBJError> {
-    let file_content = std::fs::read_to_string(file_path)?;
+    load_with_options(file_path, &LoadOptions::default())
+}
+
+///
+/// Size of the chunks read from object files, big enough to avoid most system calls
+///
+const READ_BUFFER_LENGTH: usize = 64 * 1024;
+
+///
+/// Parses an object file from any reader, such as the standard input or a network stream.
+///
+/// Material libraries are not loaded since there is no directory to look them up from, they
+/// can be given to `OBJ::load_mtls` afterwards.
+///
+pub fn parse<R: BufRead>(reader: R, options: &LoadOptions) -> Result<OBJ, LoadOBJError> {
+    parse_obj(reader, None, options)
+}
+
+pub fn load_with_options(file_path: &str, options: &LoadOptions) -> Result<OBJ, LoadOBJError> {
+    load_with_library(file_path, options, &mut MaterialLibrary::new())
+}
+
+///
+/// Same as `load_with_options`, but the material libraries are read through `library`, so the
+/// libraries shared by several object files are only read once
+///
+pub fn load_with_library(
+    file_path: &str,
+    options: &LoadOptions,
+    library: &mut MaterialLibrary,
+) -> Result<OBJ, LoadOBJError> {
+    let file = File::open(file_path)?;
+    let size = file.metadata().map(|metadata| metadata.len()).ok();
+
+    let mut obj = parse_obj(
+        BufReader::with_capacity(READ_BUFFER_LENGTH, file),
+        size,
+        options,
+    )?;
+
+    // material libraries are relative to the object file
+    let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let mut libraries = Vec::with_capacity(obj.mtls_identifiers.len());
+
+    for identifier in &obj.mtls_identifiers {
+        let mtl_path = directory.join(identifier);
+
+        match library.load(&mtl_path, options.mode) {
+            Ok(index) => {
+                libraries.push(index);
+                obj.diagnostics.extend(
+                    library
+                        .diagnostics(index)
+                        .iter()
+                        .map(|diagnostic| (Some(identifier.clone()), diagnostic.clone())),
+                );
+            }
+            Err(wavefront::mtl::LoadMTLError::Io(err))
+                if options.missing_material_library == MissingMaterialLibrary::Warn =>
+            {
+                obj.warnings.push(format!(
+                    "Could not load material library '{}': {}",
+                    mtl_path.display(),
+                    err
+                ));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    library.resolve(&mut obj, &libraries);
+
+    Ok(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::{diagnostic::ParseMode, fixtures::write_fixture};
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_load_material_libraries_relative_to_the_object() {
+        let directory = write_fixture("mtllib", &[
+            ("model.obj", "
+                mtllib first.mtl second.mtl
+                mtllib first.mtl
+                mtllib materials/third.mtl
+                v 0.0 0.0 0.0
+                v 1.0 0.0 0.0
+                v 1.0 1.0 0.0
+                usemtl Rock
+                f 1 2 3
+                f 1 2 3
+                usemtl Grass
+                f 1 2 3
+                usemtl Missing
+                f 1 2 3
+            "),
+            ("first.mtl", "newmtl Rock\nKd 0.1 0.2 0.3\n"),
+            ("second.mtl", "newmtl Dirt\nKd 0.3 0.2 0.1\nnewmtl Rock\nKd 1.0 1.0 1.0\n"),
+        ]);
+        let _ = write_fixture("mtllib/materials", &[("third.mtl", "newmtl Grass\nKd 0.0 1.0 0.0\n")]);
+
+        let result = load(directory.join("model.obj").to_str().unwrap()).expect("This should work");
+
+        assert_eq!(result.mtls_identifiers, vec!["first.mtl", "second.mtl", "materials/third.mtl"]);
+        assert_eq!(result.mtls.len(), 3);
+        assert!(result.mtls[0].contains_key("Rock"));
+        assert!(result.mtls[1].contains_key("Dirt"));
+        assert!(result.mtls[2].contains_key("Grass"));
+
+        // the first library defining a material wins
+        assert_eq!(result.faces[0].material.as_ref().unwrap().diffuse_reflectivity.rgb().b, 0.3);
+        assert_eq!(result.faces[1].material.as_ref().unwrap().diffuse_reflectivity.rgb().b, 0.3);
+        assert_eq!(result.faces[2].material.as_ref().unwrap().diffuse_reflectivity.rgb().g, 1.0);
+        assert!(result.faces[3].material.is_none());
+        // the faces sharing a material share a single copy of it
+        assert!(std::rc::Rc::ptr_eq(result.faces[0].material.as_ref().unwrap(), result.faces[1].material.as_ref().unwrap()));
+        assert!(std::mem::size_of::<Face>() <= 128);
+        assert_eq!(result.warnings, vec![
+            "Material 'Rock' is defined by several libraries, the first one is used",
+            "Material 'Missing' is not defined by any library",
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_report_exact_positions_in_objects_and_libraries() {
+        let model = "# two materials\n\nmtllib materials.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\n\nusemtl Rock\nf 1 2 \\\n  3 x\nusemtl Dirt\nf 1 2 3\n";
+        let materials = "newmtl Rock\nKd 0.1 0.2 0.3\n\n# dirt\nnewmtl Dirt\nKd 0.3 y 0.1\n";
+        let directory = write_fixture("positions", &[("model.obj", model), ("materials.mtl", materials)]);
+        let options = LoadOptions { mode: ParseMode::Lenient, ..Default::default() };
+
+        let result = load_with_options(directory.join("model.obj").to_str().unwrap(), &options).expect("This should work");
+        let positions = result.diagnostics.iter().map(|(file, diagnostic)| {
+            (file.as_deref(), diagnostic.line, diagnostic.column, diagnostic.offset, diagnostic.token.as_str())
+        }).collect::<Vec<_>>();
 
-    let obj = parse_obj(file_content)?;
+        assert_eq!(result.faces.len(), 1);
+        assert_eq!(positions, vec![
+            // the face starts at line 9 and is continued on line 10
+            (None, 10, 5, model.find('x').unwrap(), "x"),
+            (Some("materials.mtl"), 6, 8, materials.find('y').unwrap(), "y"),
+        ]);
+        assert!(result.diagnostics[0].1.message.contains("at line 9"));
+        assert!(result.diagnostics[1].1.message.contains("at line 6"));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_warn_about_missing_material_libraries() {
+        let directory = write_fixture("missing-mtllib-warn", &[
+            ("model.obj", "
+                mtllib missing.mtl
+                v 0.0 0.0 0.0
+                v 1.0 0.0 0.0
+                v 1.0 1.0 0.0
+                f 1 2 3
+            "),
+        ]);
 
-    // let mtls_paths = obj
-    //     .mtls_identifiers
-    //     .iter()
-    //     .map(|name| {
-    //         let mut path = file_path.split('/').collect::<Vec<&str>>();
-    //         path.pop();
-    //         path.push(name.as_str());
-    //         path.join("/")
-    //     })
-    //     .collect::<Vec<String>>();
+        let result = load(directory.join("model.obj").to_str().unwrap()).expect("This should work");
 
-    // let mtls = wavefront::mtl::load_files(mtls_paths)?;
+        assert!(result.mtls.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("missing.mtl"));
+    }
 
-    // Ok(obj.load_mtls(mtls).clone())
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_keep_materials_with_missing_reflectance_curves() {
+        let directory = write_fixture("missing-rfl", &[
+            ("model.obj", "mtllib paint.mtl\nv 0 0 0\nusemtl Paint\np 1\n"),
+            ("paint.mtl", "newmtl Paint\nKd spectral missing.rfl 0.5\n"),
+        ]);
 
-    Ok(obj.clone())
+        let result = load(directory.join("model.obj").to_str().unwrap()).expect("This should work");
+
+        let paint = result.points[0].material.as_ref().expect("This should work");
+        assert_eq!(paint.diffuse_reflectivity.rgb().g, 0.5);
+        assert!(result.warnings.is_empty());
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].0.as_deref(), Some("paint.mtl"));
+        assert!(result.diagnostics[0].1.message.contains("missing.rfl"));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_on_missing_material_libraries() {
+        let directory = write_fixture("missing-mtllib-fail", &[
+            ("model.obj", "
+                mtllib missing.mtl
+                v 0.0 0.0 0.0
+                v 1.0 0.0 0.0
+                v 1.0 1.0 0.0
+                f 1 2 3
+            "),
+        ]);
+        let options = LoadOptions { missing_material_library: MissingMaterialLibrary::Fail, ..Default::default() };
+
+        let result = load_with_options(directory.join("model.obj").to_str().unwrap(), &options);
+
+        assert!(matches!(result, Err(LoadOBJError::Material(wavefront::mtl::LoadMTLError::Io(_)))));
+    }
 }
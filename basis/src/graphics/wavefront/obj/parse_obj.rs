@@ -1,260 +1,625 @@
+use std::io::BufRead;
+
+use super::free_form::{Curve, Curve2D, Direction, FreeFormAttributes, Surface};
 use super::helpers;
-use super::structs::{ParseError, OBJ};
+use super::structs::{
+    Face, LoadOptions, MaterialRange, Object, ParseError, VertexDataReference, OBJ,
+};
+use super::LoadOBJError;
+use crate::graphics::wavefront::{
+    diagnostic::{Reporter, Severity},
+    lexer::{Line, LineReader, Tokens},
+};
+
+///
+/// Average amount of bytes used by a vertex statement of real world files, used to reserve
+/// memory before parsing
+///
+const BYTES_PER_VERTEX: u64 = 64;
 
-pub fn parse_obj(data: String) -> Result<OBJ, ParseError> {
+///
+/// Most bytes reserved for the vertices before parsing, whatever the size of the file, the rest
+/// grows with the statements actually read
+///
+const MAX_RESERVED_BYTES: usize = 16 * 1024 * 1024;
+
+///
+/// Vertices reserved before parsing a file of `size` bytes
+///
+fn reserved_vertices(size: u64) -> usize {
+    let vertices = usize::try_from(size / BYTES_PER_VERTEX).unwrap_or(usize::MAX);
+    vertices.min(MAX_RESERVED_BYTES / std::mem::size_of::<crate::math::Vec4>())
+}
+
+///
+/// Parses an object file line by line, only the current line is kept in memory.
+///
+/// * `size_hint` - Size of the file in bytes when it is known, used to reserve some vertices
+///   before parsing instead of growing them many times for big files. The faces are not reserved,
+///   they are much bigger than their statements
+///
+pub fn parse_obj<R: BufRead>(
+    reader: R,
+    size_hint: Option<u64>,
+    options: &LoadOptions,
+) -> Result<OBJ, LoadOBJError> {
     let mut obj = OBJ::default();
 
-    let lines = data.split("\n").map(|s| s.trim()).filter(|s| !s.is_empty());
+    if let Some(size) = size_hint {
+        obj.vertices.reserve(reserved_vertices(size));
+    }
+
+    let mut state = State::default();
+    let mut reporter = Reporter::new(options.mode);
 
-    if data.is_empty() {
-        return Err(ParseError::EmptyFile(0, "Object file is empty".to_string()));
+    let mut reader = LineReader::new(reader);
+    let mut line = Line::default();
+    let mut is_empty = true;
+
+    while reader.read_line(&mut line)? {
+        is_empty = false;
+
+        let mut tokens = Tokens::new(&line.text);
+        let Some(command) = tokens.next() else {
+            continue;
+        };
+
+        if let Err(error) = parse_statement(command, &mut tokens, &mut state, &mut obj, line.number)
+        {
+            let expected = expected_form(command);
+            // only unknown statements have no form, they are skipped like the ones that are
+            // not supported yet
+            let severity = match expected {
+                Some(_) => Severity::Error,
+                None => Severity::Warning,
+            };
+            reporter.report(error, severity, &line, &tokens, expected)?;
+        }
     }
 
-    let mut previous_line = Option::None;
-    let mut current_line: usize = 1;
-    let mut smoothing_group: usize = 0;
-    let mut face_id: usize = 0;
+    if is_empty {
+        return Err(ParseError::EmptyFile(0, "Object file is empty".to_string()).into());
+    }
+    if obj.vertices.is_empty() {
+        return Err(ParseError::MissingVertices.into());
+    }
+    if state.element.is_some() {
+        obj.warnings
+            .push("The last curve or surface is missing its 'end' statement".to_string());
+    }
+    push_surface_faces(&mut obj, &state.surface_owners);
+    // a file made of lines or points is a valid wireframe or point cloud
+    if obj.faces.is_empty() && obj.lines.is_empty() && obj.points.is_empty() {
+        return Err(ParseError::MissingFaces.into());
+    }
 
-    for line in lines {
-        let mut tokens = line
-            .split(" ")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<&str>>()
-            .into_iter();
-        let command = tokens.next();
+    obj.diagnostics.extend(
+        reporter
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| (None, diagnostic)),
+    );
+    let max_id = obj.faces.len();
+    obj.faces.iter_mut().for_each(|face| {
+        face.max_id = max_id;
+    });
+
+    helpers::triangulate_polygons(&mut obj, options.triangulation);
+
+    Ok(obj)
+}
+
+///
+/// Object and groups of a surface, its faces are only created once the whole file is parsed so the
+/// relative references of the following statements are not changed by the new vertices
+///
+type SurfaceOwner = (usize, Vec<String>);
+
+///
+/// Curve or surface being declared, until its `end` statement
+///
+enum Element {
+    Curve(Curve),
+    Curve2D(Curve2D),
+    Surface(Box<Surface>, SurfaceOwner),
+}
+
+///
+/// State of the parser that applies to the following statements
+///
+struct State {
+    material_name: Option<String>,
+    smoothing_group: usize,
+    merging_group: Option<usize>,
+    current_object: Option<usize>,
+    current_groups: Vec<String>,
+    free_form: FreeFormAttributes,
+    element: Option<Element>,
+    ///
+    /// Owner of each surface of `OBJ::surfaces`
+    ///
+    surface_owners: Vec<SurfaceOwner>,
+}
 
-        if command.is_none() {
-            return Err(ParseError::InvalidToken(
-                current_line,
-                "Missing command".to_string(),
+impl Default for State {
+    fn default() -> Self {
+        State {
+            material_name: None,
+            smoothing_group: 0,
+            merging_group: None,
+            current_object: None,
+            current_groups: vec!["default".to_string()],
+            free_form: FreeFormAttributes::default(),
+            element: None,
+            surface_owners: Vec::new(),
+        }
+    }
+}
+
+impl State {
+    ///
+    /// Index of the current object, the "default" object is created by the first element
+    /// declared before any `o` statement
+    ///
+    fn object(&mut self, obj: &mut OBJ) -> usize {
+        *self.current_object.get_or_insert_with(|| {
+            obj.objects.push(Object::new("default"));
+            obj.objects.len() - 1
+        })
+    }
+
+    fn open(&mut self, element: Element, line_n: usize) -> Result<(), ParseError> {
+        if self.element.is_some() {
+            return Err(ParseError::InvalidCurveOrSurface(
+                line_n,
+                "The previous curve or surface is missing its 'end' statement".to_string(),
             ));
         }
+        self.element = Some(element);
+        Ok(())
+    }
 
-        match command.unwrap() {
-            //
-            // Vertex data
-            //
-            "v" => {
-                // Parse vertex
-                let result = helpers::parse_vertice(&mut tokens, current_line)?;
-                obj.vertices.push(result);
-                Ok(())
-            }
-            "vt" => {
-                // Parse vertex texture
-                let result = helpers::parse_vertice_texture(&mut tokens, current_line)?;
-                obj.vertices_texture.push(result);
-                Ok(())
-            }
-            "vn" => {
-                // Parse vertex normal
-                let result = helpers::parse_vertice_normal(&mut tokens, current_line)?;
-                obj.vertices_normal.push(result);
-                Ok(())
-            }
-            "vp" => {
-                // Parse parameter space vertices
-                let result = helpers::parse_vertice_parameter_space(&mut tokens, current_line)?;
-                obj.vertices_parameter_space.push(result);
-                Ok(())
-            }
-            //
-            // Free-form curve/surface attributes
-            //
-            "cstype" => {
-                // Parse curve or surface type
-                todo("Implement cstype")
+    fn element(&mut self, command: &str, line_n: usize) -> Result<&mut Element, ParseError> {
+        self.element.as_mut().ok_or_else(|| {
+            ParseError::InvalidCurveOrSurface(
+                line_n,
+                format!("'{command}' is only valid inside of a curve or a surface"),
+            )
+        })
+    }
+
+    fn surface(&mut self, command: &str, line_n: usize) -> Result<&mut Surface, ParseError> {
+        match self.element(command, line_n)? {
+            Element::Surface(surface, _) => Ok(surface),
+            _ => Err(ParseError::InvalidCurveOrSurface(
+                line_n,
+                format!("'{command}' is only valid inside of a surface"),
+            )),
+        }
+    }
+}
+
+///
+/// Registers the face in the object, its groups and the material ranges
+///
+fn push_face(obj: &mut OBJ, mut face: Face, object: usize, groups: &[String]) {
+    let id = obj.faces.len();
+    face.id = id;
+    obj.objects[object].push_face(id, groups);
+
+    if let Some(name) = &face.material_name {
+        match obj.material_ranges.last_mut() {
+            Some(range) if range.faces.end == id && &range.material_name == name => {
+                range.faces.end += 1;
             }
-            "deg" => {
-                // Parse degree
-                todo("Implement deg")
+            _ => obj.material_ranges.push(MaterialRange {
+                material_name: name.clone(),
+                faces: id..id + 1,
+            }),
+        }
+    }
+    obj.faces.push(face);
+}
+
+///
+/// Tessellates the surfaces into faces, appending the new vertex data after the one of the file
+///
+fn push_surface_faces(obj: &mut OBJ, owners: &[SurfaceOwner]) {
+    for (index, (object, groups)) in owners.iter().enumerate() {
+        let surface = &obj.surfaces[index];
+        let tessellation = match surface.tessellate(obj) {
+            Ok(tessellation) => tessellation,
+            Err(message) => {
+                obj.warnings.push(format!(
+                    "Surface {} is not tessellated: {message}",
+                    index + 1
+                ));
+                continue;
             }
-            "bmat" => {
-                // Parse basis matrix
-                todo("Implement bmat")
+        };
+        let mut template = Face::partial_new(Vec::new(), surface.material_name.clone());
+        template.smoothing_group = surface.smoothing_group;
+        template.merging_group = surface.merging_group;
+
+        let first_vertex = obj.vertices.len() + 1;
+        let first_texture = obj.vertices_texture.len() + 1;
+        let has_textures = !tessellation.textures.is_empty();
+        obj.vertices.extend(tessellation.vertices);
+        obj.vertices_texture.extend(tessellation.textures);
+
+        let normals = tessellation
+            .normals
+            .into_iter()
+            .map(|normal| {
+                normal.map_or(0, |normal| {
+                    obj.vertices_normal.push(normal);
+                    obj.vertices_normal.len()
+                })
+            })
+            .collect::<Vec<usize>>();
+
+        for triangle in tessellation.triangles {
+            let mut face = template.clone();
+            face.vertex_references = triangle
+                .iter()
+                .map(|&index| {
+                    let vt = if has_textures {
+                        first_texture + index
+                    } else {
+                        0
+                    };
+                    VertexDataReference::new(first_vertex + index, vt, normals[index])
+                })
+                .collect();
+            push_face(obj, face, *object, groups);
+        }
+    }
+}
+
+///
+/// Form of the statements that can be invalid, used by the diagnostics
+///
+fn expected_form(command: &str) -> Option<&'static str> {
+    match command {
+        "v" => Some("v x y z [w]"),
+        "vt" => Some("vt u [v] [w]"),
+        "vn" => Some("vn i j k"),
+        "vp" => Some("vp u v [w]"),
+        "cstype" => Some("cstype [rat] bmatrix | bezier | bspline | cardinal | taylor"),
+        "deg" => Some("deg degu [degv]"),
+        "bmat" => Some("bmat u | v matrix ..."),
+        "step" => Some("step stepu [stepv]"),
+        "p" => Some("p v1 ..."),
+        "l" => Some("l v1[/vt1] v2[/vt2] ..."),
+        "f" => Some("f v1[/vt1][/vn1] v2[/vt2][/vn2] v3[/vt3][/vn3] ..."),
+        "curv" => Some("curv u0 u1 v1 v2 ..."),
+        "curv2" => Some("curv2 vp1 vp2 ..."),
+        "surf" => Some("surf s0 s1 t0 t1 v1[/vt1][/vn1] v2[/vt2][/vn2] ..."),
+        "parm" => Some("parm u | v p1 p2 ..."),
+        "trim" => Some("trim u0 u1 curv2d ..."),
+        "hole" => Some("hole u0 u1 curv2d ..."),
+        "scrv" => Some("scrv u0 u1 curv2d ..."),
+        "sp" => Some("sp vp1 ..."),
+        "end" => Some("end"),
+        "g" => Some("g [name ...]"),
+        "s" => Some("s group_number | off"),
+        "mg" => Some("mg group_number resolution | off"),
+        "o" => Some("o name"),
+        "usemtl" => Some("usemtl name"),
+        "mtllib" => Some("mtllib file ..."),
+        "ctech" => Some("ctech cparm res | cspace maxlength | curv maxdist maxangle"),
+        "stech" => {
+            Some("stech cparma ures vres | cparmb uvres | cspace maxlength | curv maxdist maxangle")
+        }
+        _ => None,
+    }
+}
+
+fn parse_statement(
+    command: &str,
+    tokens: &mut Tokens,
+    state: &mut State,
+    obj: &mut OBJ,
+    line_n: usize,
+) -> Result<(), ParseError> {
+    match command {
+        //
+        // Vertex data
+        //
+        "v" => {
+            // Parse vertex
+            let result = helpers::parse_vertice(tokens, line_n)?;
+            obj.vertices.push(result);
+            Ok(())
+        }
+        "vt" => {
+            // Parse vertex texture
+            let result = helpers::parse_vertice_texture(tokens, line_n)?;
+            obj.vertices_texture.push(result);
+            Ok(())
+        }
+        "vn" => {
+            // Parse vertex normal
+            let result = helpers::parse_vertice_normal(tokens, line_n)?;
+            obj.vertices_normal.push(result);
+            Ok(())
+        }
+        "vp" => {
+            // Parse parameter space vertices
+            let result = helpers::parse_vertice_parameter_space(tokens, line_n)?;
+            obj.vertices_parameter_space.push(result);
+            Ok(())
+        }
+        //
+        // Free-form curve/surface attributes
+        //
+        "cstype" => {
+            // Parse curve or surface type
+            let (basis, rational) = helpers::parse_curve_surface_type(tokens, line_n)?;
+            state.free_form.basis = basis;
+            state.free_form.rational = rational;
+            Ok(())
+        }
+        "deg" => {
+            // Parse degree
+            let (degree_u, degree_v) = helpers::parse_degree(tokens, line_n)?;
+            state.free_form.degree_u = degree_u;
+            state.free_form.degree_v = degree_v;
+            Ok(())
+        }
+        "bmat" => {
+            // Parse basis matrix
+            match helpers::parse_basis_matrix(tokens, line_n)? {
+                (Direction::U, matrix) => state.free_form.basis_matrix_u = matrix,
+                (Direction::V, matrix) => state.free_form.basis_matrix_v = matrix,
             }
-            "step" => {
-                // Parse step size
-                todo("Implement step")
+            Ok(())
+        }
+        "step" => {
+            // Parse step size
+            let (step_u, step_v) = helpers::parse_step(tokens, line_n)?;
+            state.free_form.step_u = step_u;
+            if let Some(step_v) = step_v {
+                state.free_form.step_v = step_v;
             }
-            //
-            // Elements
-            //
-            "p" => {
-                // Parse point
-                todo("Implement p")
+            Ok(())
+        }
+        //
+        // Elements
+        //
+        "p" => {
+            // Parse point
+            let mut result =
+                helpers::parse_point(tokens, obj, state.material_name.clone(), line_n)?;
+            result.id = obj.points.len();
+
+            let object = state.object(obj);
+            obj.objects[object].push_point(result.id, &state.current_groups);
+            obj.points.push(result);
+            Ok(())
+        }
+        "l" => {
+            // Parse line
+            let mut result = helpers::parse_line(tokens, obj, state.material_name.clone(), line_n)?;
+            result.id = obj.lines.len();
+
+            let object = state.object(obj);
+            obj.objects[object].push_line(result.id, &state.current_groups);
+            obj.lines.push(result);
+            Ok(())
+        }
+        "f" => {
+            // Parse face
+            let mut result = helpers::parse_face(tokens, obj, state.material_name.clone(), line_n)?;
+            if state.smoothing_group != 0 {
+                result.smoothing_group = Some(state.smoothing_group);
             }
-            "l" => {
-                // Parse line
-                todo("Implement l")
+            result.merging_group = state.merging_group;
+
+            let object = state.object(obj);
+            push_face(obj, result, object, &state.current_groups);
+            Ok(())
+        }
+        "curv" => {
+            // Parse curve
+            let curve = helpers::parse_curve(tokens, obj, &state.free_form, line_n)?;
+            state.open(Element::Curve(curve), line_n)
+        }
+        "curv2" => {
+            // Parse 2D curve
+            let curve = helpers::parse_curve_2d(tokens, obj, &state.free_form, line_n)?;
+            state.open(Element::Curve2D(curve), line_n)
+        }
+        "surf" => {
+            // Parse surface, it uses the display attributes and the groups of the faces
+            let mut surface = helpers::parse_surface(tokens, obj, &state.free_form, line_n)?;
+            surface.material_name = state.material_name.clone();
+            if state.smoothing_group != 0 {
+                surface.smoothing_group = Some(state.smoothing_group);
             }
-            "f" => {
-                // Parse face
-                let mut result = helpers::parse_face(&mut tokens, previous_line, current_line)?;
-                if smoothing_group != 0 {
-                    result.smoothing_group = Some(smoothing_group);
+            surface.merging_group = state.merging_group;
+
+            let owner = (state.object(obj), state.current_groups.clone());
+            state.open(Element::Surface(Box::new(surface), owner), line_n)
+        }
+        //
+        // Free-form curve/surface body statements
+        //
+        "parm" => {
+            // Parse parameter values
+            let (direction, parameters) = helpers::parse_parameters(tokens, line_n)?;
+            match (state.element(command, line_n)?, direction) {
+                (Element::Curve(curve), Direction::U) => curve.parameters = parameters,
+                (Element::Curve2D(curve), Direction::U) => curve.parameters = parameters,
+                (Element::Surface(surface, _), Direction::U) => surface.parameters_u = parameters,
+                (Element::Surface(surface, _), Direction::V) => surface.parameters_v = parameters,
+                (_, Direction::V) => {
+                    return Err(ParseError::InvalidCurveOrSurface(
+                        line_n,
+                        "Curves only have 'u' parameters".to_string(),
+                    ))
                 }
-                result.id = face_id;
-                obj.faces.push(result);
-                face_id += 1;
-                Ok(())
             }
-            "curv" => {
-                // Parse curve
-                todo("Implement curv")
-            }
-            "curv2" => {
-                // Parse 2D curve
-                todo("Implement curv2")
-            }
-            "surf" => {
-                // Parse surface
-                todo("Implement surf")
-            }
-            //
-            // Free-form curve/surface body statements
-            //
-            "parm" => {
-                // Parse parameter values
-                todo("Implement parm")
-            }
-            "trim" => {
-                // Parse outer trimming loop
-                todo("Implement trim")
-            }
-            "hole" => {
-                // Parse inner trimming loop
-                todo("Implement hole")
-            }
-            "scrv" => {
-                // Parse special curve
-                todo("Implement srcv")
-            }
-            "sp" => {
-                // Parse special point
-                todo("Implement sp")
-            }
-            "end" => {
-                // Parse end statement
-                todo("Implement end")
-            }
-            //
-            // Connectivity between free-form surfaces
-            //
-            "con" => {
-                // Parse connect
-                todo("Implement con")
-            }
-            //
-            // Grouping
-            //
-            "g" => {
-                // Parse group name
-                todo("Implement g")
-            }
-            "s" => {
-                // Parse smoothing group
-                smoothing_group = helpers::parse_smoothing_group(&mut tokens, current_line)?;
-                Ok(())
-            }
-            "mg" => {
-                // Parse merging group
-                todo("Implement mg")
-            }
-            "o" => {
-                // Parse object name
-                tokens.next().map(|name| obj.name = Some(name.to_string()));
-                Ok(())
-            }
-            //
-            // Display/render attributes
-            //
-            "bevel" => {
-                // Parse bevel interpolation
-                todo("Implement bevel")
-            }
-            "c_interp" => {
-                // Parse color interpolation
-                todo("Implement c_interp")
-            }
-            "d_interp" => {
-                // Parse dissolve interpolation
-                todo("Implement d_interp")
-            }
-            "lod" => {
-                // Parse level of detail
-                todo("Implement lod")
+            Ok(())
+        }
+        "trim" => {
+            // Parse outer trimming loop
+            let curves = helpers::parse_curves_on_surface(tokens, obj, line_n)?;
+            state.surface(command, line_n)?.trimming_loops.push(curves);
+            Ok(())
+        }
+        "hole" => {
+            // Parse inner trimming loop
+            let curves = helpers::parse_curves_on_surface(tokens, obj, line_n)?;
+            state.surface(command, line_n)?.holes.push(curves);
+            Ok(())
+        }
+        "scrv" => {
+            // Parse special curve
+            let curves = helpers::parse_curves_on_surface(tokens, obj, line_n)?;
+            state.surface(command, line_n)?.special_curves.push(curves);
+            Ok(())
+        }
+        "sp" => {
+            // Parse special point, they only matter to the tessellation of the surfaces
+            let points = helpers::parse_special_points(tokens, obj, line_n)?;
+            if let Element::Surface(surface, _) = state.element(command, line_n)? {
+                surface.special_points.extend(points);
             }
-            "usemtl" => {
-                // Skip... This is done during the face parsing
-                if tokens.len() > 2 {
-                    return Err(ParseError::InvalidFaceMaterial(
-                        current_line,
-                        "You can only specify one material".to_string(),
-                    ));
+            Ok(())
+        }
+        "end" => {
+            // Parse end statement, the element is dropped when it can't be evaluated
+            let element = state.element.take().ok_or_else(|| {
+                ParseError::InvalidCurveOrSurface(
+                    line_n,
+                    "'end' without a curve or a surface".to_string(),
+                )
+            })?;
+            let invalid = |message: String| ParseError::InvalidCurveOrSurface(line_n, message);
+
+            match element {
+                Element::Curve(curve) => {
+                    curve.validate().map_err(invalid)?;
+                    obj.curves.push(curve);
+                }
+                Element::Curve2D(curve) => {
+                    curve.validate().map_err(invalid)?;
+                    obj.curves_2d.push(curve);
+                }
+                Element::Surface(surface, owner) => {
+                    surface.validate().map_err(invalid)?;
+                    obj.surfaces.push(*surface);
+                    state.surface_owners.push(owner);
                 }
-                Ok(())
-            }
-            "mtllib" => {
-                // Parse material library
-                let mtllib = tokens
-                    .clone()
-                    .filter(|s| s != &"mtllib")
-                    .map(|s| s.to_string())
-                    .collect();
-                obj.mtls_identifiers = mtllib;
-                Ok(())
-            }
-            "shadow_obj" => {
-                // Parse shadow casting
-                todo("Implement shadow_obj")
-            }
-            "trace_obj" => {
-                // Parse ray tracing
-                todo("Implement trace_obj")
-            }
-            "ctech" => {
-                // Parse curve approximation technique
-                todo("Implement c_tech")
-            }
-            "stech" => {
-                // Parse surface approximation technique
-                todo("Implement stech")
             }
-            "#" => {
-                // Ignore comments
-                Ok(())
+            Ok(())
+        }
+        //
+        // Connectivity between free-form surfaces
+        //
+        "con" => {
+            // Parse connect
+            todo("Implement con")
+        }
+        //
+        // Grouping
+        //
+        "g" => {
+            // Parse group names, every following element belongs to all of them
+            state.current_groups = helpers::parse_group(tokens);
+            Ok(())
+        }
+        "s" => {
+            // Parse smoothing group
+            state.smoothing_group = helpers::parse_smoothing_group(tokens, line_n)?;
+            Ok(())
+        }
+        "mg" => {
+            // Parse merging group
+            let result = helpers::parse_merging_group(tokens, line_n)?;
+            state.merging_group = result.as_ref().map(|group| group.id);
+            if let Some(group) = result {
+                match obj.merging_groups.iter_mut().find(|g| g.id == group.id) {
+                    Some(existing) => existing.resolution = group.resolution,
+                    None => obj.merging_groups.push(group),
+                }
             }
-            unknown => {
-                if unknown.starts_with("#") {
-                    Ok(())
-                } else {
-                    Err(ParseError::InvalidToken(
-                        current_line,
-                        format!("Unknown token: '{unknown}'"),
-                    ))
+            Ok(())
+        }
+        "o" => {
+            // Parse object name, reopening the object if it was already declared
+            let name = helpers::parse_object_name(tokens, line_n)?;
+            state.current_object = match obj.objects.iter().position(|o| o.name == name) {
+                Some(position) => Some(position),
+                None => {
+                    obj.objects.push(Object::new(&name));
+                    Some(obj.objects.len() - 1)
+                }
+            };
+            Ok(())
+        }
+        //
+        // Display/render attributes
+        //
+        "bevel" => {
+            // Parse bevel interpolation
+            todo("Implement bevel")
+        }
+        "c_interp" => {
+            // Parse color interpolation
+            todo("Implement c_interp")
+        }
+        "d_interp" => {
+            // Parse dissolve interpolation
+            todo("Implement d_interp")
+        }
+        "lod" => {
+            // Parse level of detail
+            todo("Implement lod")
+        }
+        "usemtl" => {
+            // Parse material name, used by every following element until the next usemtl
+            state.material_name = Some(helpers::parse_usemtl(tokens, line_n)?);
+            Ok(())
+        }
+        "mtllib" => {
+            // Parse material library
+            for mtllib in tokens {
+                if !obj
+                    .mtls_identifiers
+                    .iter()
+                    .any(|identifier| identifier == mtllib)
+                {
+                    obj.mtls_identifiers.push(mtllib.to_string());
                 }
             }
-        }?;
-        current_line += 1;
-        previous_line = Some(line);
-    }
-
-    if obj.vertices.is_empty() {
-        return Err(ParseError::MissingVertices);
-    }
-    if obj.faces.is_empty() {
-        return Err(ParseError::MissingFaces);
+            Ok(())
+        }
+        "shadow_obj" => {
+            // Parse shadow casting
+            todo("Implement shadow_obj")
+        }
+        "trace_obj" => {
+            // Parse ray tracing
+            todo("Implement trace_obj")
+        }
+        "ctech" => {
+            // Parse curve approximation technique
+            state.free_form.curve_technique = helpers::parse_curve_technique(tokens, line_n)?;
+            Ok(())
+        }
+        "stech" => {
+            // Parse surface approximation technique
+            state.free_form.surface_technique = helpers::parse_surface_technique(tokens, line_n)?;
+            Ok(())
+        }
+        // comments are removed by the lexer
+        unknown => Err(ParseError::InvalidToken(
+            line_n,
+            format!("Unknown token: '{unknown}'"),
+        )),
     }
-
-    obj.faces.iter_mut().for_each(|face| {
-        face.max_id = face_id;
-    });
-
-    helpers::triangulate_polygons(&mut obj);
-
-    Ok(obj)
 }
 
 fn todo(_line: &str) -> Result<(), ParseError> {
@@ -263,9 +628,16 @@ fn todo(_line: &str) -> Result<(), ParseError> {
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
     use crate::{
+        graphics::mesh::Indices,
+        graphics::normals::NormalWeighting,
+        graphics::wavefront::diagnostic::ParseMode,
+        graphics::wavefront::mtl::{Color, IlluminationModel, Material, MTL, RGB},
         graphics::wavefront::obj::structs::{
-            VertexDataReference, VerticeNormal, VerticeParameterSpace, VerticeTexture,
+            TriangulationMethod, VertexDataReference, VerticeNormal, VerticeParameterSpace,
+            VerticeTexture,
         },
         math,
     };
@@ -290,7 +662,8 @@ mod tests {
             f 1 1 1 1
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result =
+            parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(result.vertices.len(), 1);
         assert_eq!(result.vertices_texture.len(), 0);
@@ -310,9 +683,11 @@ mod tests {
             f 1 1 1 1
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result =
+            parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
-        assert_eq!(result.name, Some("cube39".to_string()));
+        assert_eq!(result.objects.len(), 1);
+        assert_eq!(result.objects[0].name, "cube39");
     }
 
     #[test]
@@ -327,7 +702,8 @@ mod tests {
             f 1 1 1 1
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result =
+            parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(
             result.mtls_identifiers,
@@ -362,7 +738,7 @@ mod tests {
             f       1 1 1 1
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(result.vertices.len(), 4);
         assert_eq!(result.vertices_texture.len(), 4);
@@ -399,14 +775,23 @@ mod tests {
             mtllib cube.mtl
             o cube39
             v      -5.000000       5.000000       0.000000
+            v      -5.000000      -5.000000       0.000000
+            v       5.000000      -5.000000       0.000000
+            v       5.000000       5.000000       0.000000
             vt     -5.000000       5.000000       0.000000
+            vt     -5.000000      -5.000000       0.000000
+            vt      5.000000      -5.000000       0.000000
+            vt      5.000000       5.000000       0.000000
+            vn      0.000000       0.000000       1.000000
+            vn      0.000000       0.000000       1.000000
+            vn      0.000000       0.000000       1.000000
             vn      0.000000       0.000000       1.000000
             vp      0.210000       3.590000
             f 1/1/1 2/2/2 3/3/3 4/4/4
             f 1//1 2//2 3//3 4//4
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(result.faces[0].vertex_references[0], VertexDataReference::new(1, 1, 1));
         assert_eq!(result.faces[0].vertex_references[1], VertexDataReference::new(2, 2, 2));
@@ -441,7 +826,7 @@ mod tests {
             f 8 7 6 5
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
 
         assert_eq!(result.faces[0].vertex_references[0], VertexDataReference::new(1, 0, 0));
@@ -470,7 +855,16 @@ mod tests {
             mtllib cube.mtl
             o cube39
             v      -5.000000       5.000000       0.000000
+            v      -5.000000      -5.000000       0.000000
+            v       5.000000      -5.000000       0.000000
+            v       5.000000       5.000000       0.000000
             vt     -5.000000       5.000000       0.000000
+            vt     -5.000000      -5.000000       0.000000
+            vt      5.000000      -5.000000       0.000000
+            vt      5.000000       5.000000       0.000000
+            vn      0.000000       0.000000       1.000000
+            vn      0.000000       0.000000       1.000000
+            vn      0.000000       0.000000       1.000000
             vn      0.000000       0.000000       1.000000
             vp      0.210000       3.590000
             f 1/1/1 2/2/2 3/3/3 4/4/4
@@ -478,7 +872,7 @@ mod tests {
             f 1/1/1 2/2/2 3//3 4//4
 ";
 
-        let result = parse_obj(file.to_string());
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
 
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Illegal to give vertex texture for some vertices, but not all"));
@@ -492,10 +886,46 @@ mod tests {
 
             mtllib cube.mtl
             o cube39
-            v      -5.000000       5.000000       0.000000
-            vt     -5.000000       5.000000       0.000000
-            vn      0.000000       0.000000       1.000000
-            vp      0.210000       3.590000
+            v      -1.000000       1.000000       1.000000
+            v      -1.000000      -1.000000       1.000000
+            v       1.000000      -1.000000       1.000000
+            v       1.000000       1.000000       1.000000
+            v      -1.000000       1.000000      -1.000000
+            v      -1.000000      -1.000000      -1.000000
+            v       1.000000      -1.000000      -1.000000
+            v       1.000000       1.000000      -1.000000
+            vt      0.000000       0.000000
+            vt      0.333333       0.000000
+            vt      0.666667       0.000000
+            vt      1.000000       0.000000
+            vt      0.000000       0.200000
+            vt      0.333333       0.200000
+            vt      0.666667       0.200000
+            vt      1.000000       0.200000
+            vt      0.000000       0.400000
+            vt      0.333333       0.400000
+            vt      0.666667       0.400000
+            vt      1.000000       0.400000
+            vt      0.000000       0.600000
+            vt      0.333333       0.600000
+            vt      0.666667       0.600000
+            vt      1.000000       0.600000
+            vt      0.000000       0.800000
+            vt      0.333333       0.800000
+            vt      0.666667       0.800000
+            vt      1.000000       0.800000
+            vt      0.000000       1.000000
+            vt      0.333333       1.000000
+            vt      0.666667       1.000000
+            vt      1.000000       1.000000
+            vn     -0.577350       0.577350       0.577350
+            vn     -0.577350      -0.577350       0.577350
+            vn      0.577350      -0.577350       0.577350
+            vn      0.577350       0.577350       0.577350
+            vn     -0.577350       0.577350      -0.577350
+            vn     -0.577350      -0.577350      -0.577350
+            vn      0.577350      -0.577350      -0.577350
+            vn      0.577350       0.577350      -0.577350
             usemtl 4bed15
             f 1/4/1 2/3/2 3/2/3 4/1/4 
             usemtl 2daec2
@@ -508,15 +938,77 @@ mod tests {
             f 7/20/7 5/19/5 2/18/2 1/17/1 
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(result.faces.len(), 6);
         assert_eq!(result.faces[0].material_name, Some("4bed15".to_string()));
         assert_eq!(result.faces[1].material_name, Some("2daec2".to_string()));
-        assert_eq!(result.faces[2].material_name, None);
-        assert_eq!(result.faces[3].material_name, None);
+        assert_eq!(result.faces[2].material_name, Some("2daec2".to_string()));
+        assert_eq!(result.faces[3].material_name, Some("2daec2".to_string()));
         assert_eq!(result.faces[4].material_name, Some("4602e3".to_string()));
         assert_eq!(result.faces[5].material_name, Some("c41dde".to_string()));
+
+        assert_eq!(result.material_ranges, vec![
+            MaterialRange { material_name: "4bed15".to_string(), faces: 0..1 },
+            MaterialRange { material_name: "2daec2".to_string(), faces: 1..4 },
+            MaterialRange { material_name: "4602e3".to_string(), faces: 4..5 },
+            MaterialRange { material_name: "c41dde".to_string(), faces: 5..6 },
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_keep_the_material_across_groups_and_objects() {
+        let file = "
+            v 0.000000 0.000000 0.000000
+            v 1.000000 0.000000 0.000000
+            v 1.000000 1.000000 0.000000
+            f 1 2 3
+            usemtl Rock
+            o first
+            g body
+            f 1 2 3
+            s 1
+            o second
+            g head
+            f 1 2 3
+            usemtl Dirt
+            f 1 2 3
+            usemtl Rock
+            f 1 2 3
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+
+        assert_eq!(result.faces[0].material_name, None);
+        assert_eq!(result.faces[1].material_name, Some("Rock".to_string()));
+        assert_eq!(result.faces[2].material_name, Some("Rock".to_string()));
+        assert_eq!(result.faces[3].material_name, Some("Dirt".to_string()));
+        assert_eq!(result.faces[4].material_name, Some("Rock".to_string()));
+
+        assert_eq!(result.get_material_ranges_by_name("Rock"), vec![
+            &MaterialRange { material_name: "Rock".to_string(), faces: 1..3 },
+            &MaterialRange { material_name: "Rock".to_string(), faces: 4..5 },
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_use_invalid_materials() {
+        for usemtl in ["usemtl", "usemtl Rock Dirt"] {
+            let file = format!("
+                v 0.000000 0.000000 0.000000
+                v 1.000000 0.000000 0.000000
+                v 1.000000 1.000000 0.000000
+                {usemtl}
+                f 1 2 3
+");
+
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().starts_with("Invalid face material at line 5"));
+        }
     }
 
     #[test]
@@ -527,10 +1019,46 @@ mod tests {
 
             mtllib cube.mtl
             o cube39
-            v      -5.000000       5.000000       0.000000
-            vt     -5.000000       5.000000       0.000000
-            vn      0.000000       0.000000       1.000000
-            vp      0.210000       3.590000
+            v      -1.000000       1.000000       1.000000
+            v      -1.000000      -1.000000       1.000000
+            v       1.000000      -1.000000       1.000000
+            v       1.000000       1.000000       1.000000
+            v      -1.000000       1.000000      -1.000000
+            v      -1.000000      -1.000000      -1.000000
+            v       1.000000      -1.000000      -1.000000
+            v       1.000000       1.000000      -1.000000
+            vt      0.000000       0.000000
+            vt      0.333333       0.000000
+            vt      0.666667       0.000000
+            vt      1.000000       0.000000
+            vt      0.000000       0.200000
+            vt      0.333333       0.200000
+            vt      0.666667       0.200000
+            vt      1.000000       0.200000
+            vt      0.000000       0.400000
+            vt      0.333333       0.400000
+            vt      0.666667       0.400000
+            vt      1.000000       0.400000
+            vt      0.000000       0.600000
+            vt      0.333333       0.600000
+            vt      0.666667       0.600000
+            vt      1.000000       0.600000
+            vt      0.000000       0.800000
+            vt      0.333333       0.800000
+            vt      0.666667       0.800000
+            vt      1.000000       0.800000
+            vt      0.000000       1.000000
+            vt      0.333333       1.000000
+            vt      0.666667       1.000000
+            vt      1.000000       1.000000
+            vn     -0.577350       0.577350       0.577350
+            vn     -0.577350      -0.577350       0.577350
+            vn      0.577350      -0.577350       0.577350
+            vn      0.577350       0.577350       0.577350
+            vn     -0.577350       0.577350      -0.577350
+            vn     -0.577350      -0.577350      -0.577350
+            vn      0.577350      -0.577350      -0.577350
+            vn      0.577350       0.577350      -0.577350
             s off
             usemtl 4bed15
             f 1/4/1 2/3/2 3/2/3 4/1/4 
@@ -546,7 +1074,7 @@ mod tests {
             f 7/20/7 5/19/5 2/18/2 1/17/1 
 ";
 
-        let result = parse_obj(file.to_string()).expect("This should work");
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
 
         assert_eq!(result.faces.len(), 6);
         assert_eq!(result.faces[0].smoothing_group, None);
@@ -556,4 +1084,815 @@ mod tests {
         assert_eq!(result.faces[4].smoothing_group, Some(2));
         assert_eq!(result.faces[4].smoothing_group, Some(2));
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_resolve_negative_references() {
+        let file = "
+            v      -5.000000       5.000000       0.000000
+            v      -5.000000      -5.000000       0.000000
+            v       5.000000      -5.000000       0.000000
+            vt      0.000000       1.000000
+            vt      0.000000       0.000000
+            vt      1.000000       0.000000
+            vn      0.000000       0.000000       1.000000
+            f -3/-3/-1 -2/-2/-1 -1/-1/-1
+            v       5.000000       5.000000       0.000000
+            f -4 -2 -1
+            f 1//-1 -1//1 3//-1
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+
+        assert_eq!(result.faces[0].vertex_references[0], VertexDataReference::new(1, 1, 1));
+        assert_eq!(result.faces[0].vertex_references[1], VertexDataReference::new(2, 2, 1));
+        assert_eq!(result.faces[0].vertex_references[2], VertexDataReference::new(3, 3, 1));
+
+        assert_eq!(result.faces[1].vertex_references[0], VertexDataReference::new(1, 0, 0));
+        assert_eq!(result.faces[1].vertex_references[1], VertexDataReference::new(3, 0, 0));
+        assert_eq!(result.faces[1].vertex_references[2], VertexDataReference::new(4, 0, 0));
+
+        assert_eq!(result.faces[2].vertex_references[0], VertexDataReference::new(1, 0, 1));
+        assert_eq!(result.faces[2].vertex_references[1], VertexDataReference::new(4, 0, 1));
+        assert_eq!(result.faces[2].vertex_references[2], VertexDataReference::new(3, 0, 1));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_resolve_out_of_range_references() {
+        let file = "
+            v      -5.000000       5.000000       0.000000
+            v      -5.000000      -5.000000       0.000000
+            v       5.000000      -5.000000       0.000000
+            f 1 2 3
+            f -4 -2 -1
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid vertex reference at line 6: Reference '-4' is out of range, only 3 declared"
+        );
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_resolve_references_to_missing_data() {
+        let invalid_faces = ["f 1/1 2/1 3/1", "f 1//1 2//1 3//1", "f 0 1 2", "f 1 2 4", "f 1 a 3"];
+
+        for face in invalid_faces {
+            let file = format!("
+                v      -5.000000       5.000000       0.000000
+                v      -5.000000      -5.000000       0.000000
+                v       5.000000      -5.000000       0.000000
+                {face}
+");
+
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+            assert!(result.is_err(), "'{face}' should not be accepted");
+            assert!(result.unwrap_err().to_string().starts_with("Invalid vertex reference at line 5"));
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_triangulate_concave_faces_using_the_chosen_method() {
+        let file = "
+            v 0.000000 0.000000 0.000000
+            v 2.000000 0.000000 0.000000
+            v 2.000000 2.000000 0.000000
+            v 1.000000 0.500000 0.000000
+            v 0.000000 2.000000 0.000000
+            f 5 1 2 3 4
+";
+
+        let fan = parse_obj(file.as_bytes(), None, &LoadOptions { triangulation: TriangulationMethod::Fan, ..Default::default() }).expect("This should work");
+        let ear_clipping = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let covers_notch = |triangle: &[VertexDataReference]| {
+            let mut vertices = triangle.iter().map(|reference| reference.v).collect::<Vec<usize>>();
+            vertices.sort();
+            vertices == vec![3, 4, 5]
+        };
+
+        // the last triangle of the fan fills the notch, which is outside of the polygon
+        assert!(covers_notch(&fan.faces[0].vertex_references[6..9]));
+
+        assert_eq!(ear_clipping.faces[0].vertex_references.len(), 9);
+        assert!(!ear_clipping.faces[0].vertex_references.chunks(3).any(covers_notch));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_store_objects_and_groups() {
+        let file = "
+            v 0.000000 0.000000 0.000000
+            v 1.000000 0.000000 0.000000
+            v 1.000000 1.000000 0.000000
+            f 1 2 3
+            o car
+            g body
+            f 1 2 3
+            f 1 2 3
+            g wheel front
+            f 1 2 3
+            g wheel back
+            f 1 2 3
+            o plane
+            f 1 2 3
+            g
+            f 1 2 3
+            o car
+            g body
+            f 1 2 3
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let names = result.objects().iter().map(|object| object.name.as_str()).collect::<Vec<&str>>();
+
+        assert_eq!(names, vec!["default", "car", "plane"]);
+
+        let default = result.object_by_name("default").unwrap();
+        assert_eq!(default.faces, vec![0..1]);
+        assert_eq!(default.group_by_name("default").unwrap().faces, vec![0..1]);
+
+        let car = result.object_by_name("car").unwrap();
+        assert_eq!(car.faces, vec![1..5, 7..8]);
+        assert_eq!(car.group_by_name("body").unwrap().faces, vec![1..3, 7..8]);
+        assert_eq!(car.group_by_name("wheel").unwrap().faces, vec![3..5]);
+        assert_eq!(car.group_by_name("front").unwrap().faces, vec![3..4]);
+        assert_eq!(car.group_by_name("back").unwrap().faces, vec![4..5]);
+
+        // the active groups are kept when the object changes
+        let plane = result.object_by_name("plane").unwrap();
+        assert_eq!(plane.group_by_name("wheel").unwrap().faces, vec![5..6]);
+        assert_eq!(plane.group_by_name("back").unwrap().faces, vec![5..6]);
+        assert_eq!(plane.group_by_name("default").unwrap().faces, vec![6..7]);
+
+        assert_eq!(result.groups_by_name("wheel").len(), 2);
+        assert!(result.group_by_name("front").unwrap().contains_face(3));
+        assert!(!result.group_by_name("front").unwrap().contains_face(4));
+
+        let wheel = result.group_by_name("wheel").unwrap();
+        let ids = result.get_group_faces(wheel).map(|face| face.id).collect::<Vec<usize>>();
+        assert_eq!(ids, vec![3, 4]);
+
+        let ids = result.get_object_faces(car).map(|face| face.id).collect::<Vec<usize>>();
+        assert_eq!(ids, vec![1, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_store_merging_groups() {
+        let file = "
+            v 0.000000 0.000000 0.000000
+            v 1.000000 0.000000 0.000000
+            v 1.000000 1.000000 0.000000
+            f 1 2 3
+            mg 1 0.5
+            f 1 2 3
+            mg 2 0.25
+            f 1 2 3
+            mg off
+            f 1 2 3
+            mg 1 0.75
+            f 1 2 3
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+
+        assert_eq!(result.faces[0].merging_group, None);
+        assert_eq!(result.faces[1].merging_group, Some(1));
+        assert_eq!(result.faces[2].merging_group, Some(2));
+        assert_eq!(result.faces[3].merging_group, None);
+        assert_eq!(result.faces[4].merging_group, Some(1));
+
+        assert_eq!(result.merging_groups.len(), 2);
+        assert_eq!(result.get_merging_group_by_id(1).unwrap().resolution, 0.75);
+        assert_eq!(result.get_merging_group_by_id(2).unwrap().resolution, 0.25);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_store_invalid_merging_groups_and_objects() {
+        for statement in ["mg", "mg 1", "mg a 0.5", "mg 1 0", "o"] {
+            let file = format!("
+                v 0.000000 0.000000 0.000000
+                v 1.000000 0.000000 0.000000
+                v 1.000000 1.000000 0.000000
+                {statement}
+                f 1 2 3
+");
+
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+            assert!(result.is_err(), "'{statement}' should not be accepted");
+            assert!(result.unwrap_err().to_string().contains("at line 5"));
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_expose_materials_in_the_raw_vertices() {
+        let file = "
+            v 0.000000 0.000000 0.000000
+            v 1.000000 0.000000 0.000000
+            v 1.000000 1.000000 0.000000
+            f 1 2 3
+            usemtl Rock
+            f 1 2 3
+";
+        let mut rock = Material { name: "Rock".to_string(), ..Default::default() };
+        rock.ambient_reflectivity = Color::RGB(RGB { r: 0.1, g: 0.0, b: 0.0 });
+        rock.diffuse_reflectivity = Color::RGB(RGB { r: 0.0, g: 0.2, b: 0.0 });
+        rock.atmosphere_reflectivity = Color::RGB(RGB { r: 0.0, g: 0.0, b: 0.3 });
+        rock.specular_highlight_exponent = 10.0;
+        rock.illumination_model = IlluminationModel::HighlightOn;
+
+        let mut result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        result.load_mtls(vec![MTL::from([("Rock".to_string(), rock)])]);
+        let vertices = result.get_raw_vertices(math::Vec3::new(1.0, 0.0, 0.0));
+        let length = OBJ::RAW_VERTEX_LENGTH;
+
+        assert_eq!(vertices.len(), 6 * length);
+
+        // without material
+        assert_eq!(vertices[10..22], [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0]);
+        assert_eq!(vertices[22..25], [0.0, 0.0, 1.0]);
+
+        // with material
+        let vertice = &vertices[3 * length..4 * length];
+        assert_eq!(vertice[0..4], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(vertice[4..7], [1.0, 0.0, 0.0]);
+        assert_eq!(vertice[10..22], [0.1, 0.0, 0.0, 0.0, 0.2, 0.0, 0.0, 0.0, 0.3, 10.0, 1.0, 2.0]);
+    }
+
+    fn assert_normal(normal: &math::Vec3, expected: (f32, f32, f32)) {
+        assert!(
+            (normal.x - expected.0).abs() < 1e-4
+                && (normal.y - expected.1).abs() < 1e-4
+                && (normal.z - expected.2).abs() < 1e-4,
+            "{:?} != {:?}",
+            normal,
+            expected
+        );
+    }
+
+    // two faces sharing the edge 1-2, the first one faces +z and the second one, which is twice
+    // as big, faces +y
+    const HINGE: &str = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 0 0 2
+";
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_compute_face_normals() {
+        let file = format!("{HINGE}
+            f 1 2 3
+            f 2 1 4
+");
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let normals = result.face_normals();
+
+        assert_eq!(normals.len(), 2);
+        assert_normal(&normals[0], (0.0, 0.0, 1.0));
+        assert_normal(&normals[1], (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_smooth_normals_inside_a_smoothing_group() {
+        let file = format!("{HINGE}
+            s 1
+            f 1 2 3
+            f 2 1 4
+");
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let angle = result.vertex_normals(NormalWeighting::Angle);
+        let area = result.vertex_normals(NormalWeighting::Area);
+
+        // both corners at vertex 1 are right angles
+        assert_normal(&angle[0][0], (0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+        assert_normal(&angle[1][1], (0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+        // the second face has twice the area
+        assert_normal(&area[0][0], (0.0, 0.894_427_2, 0.447_213_6));
+        assert_normal(&area[1][1], (0.0, 0.894_427_2, 0.447_213_6));
+        // vertices used by a single face keep its normal
+        assert_normal(&angle[0][2], (0.0, 0.0, 1.0));
+        assert_normal(&angle[1][2], (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_keep_hard_edges_between_smoothing_groups() {
+        let different_groups = format!("{HINGE}
+            s 1
+            f 1 2 3
+            s 2
+            f 2 1 4
+");
+        let smoothing_off = format!("{HINGE}
+            s 1
+            f 1 2 3
+            s off
+            f 2 1 4
+");
+
+        for file in [different_groups, smoothing_off] {
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+            let normals = result.vertex_normals(NormalWeighting::default());
+
+            for normal in &normals[0] {
+                assert_normal(normal, (0.0, 0.0, 1.0));
+            }
+            for normal in &normals[1] {
+                assert_normal(normal, (0.0, 1.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_prefer_the_file_normals() {
+        let file = format!("{HINGE}
+            vn 2 0 0
+            s 1
+            f 1//1 2//1 3//1
+            f 2 1 4
+");
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let normals = result.vertex_normals(NormalWeighting::default());
+        let vertices = result.get_raw_vertices(math::Vec3::default());
+        let length = OBJ::RAW_VERTEX_LENGTH;
+
+        for normal in &normals[0] {
+            assert_normal(normal, (1.0, 0.0, 0.0));
+        }
+        // the geometry of the first face is still used to smooth the second one
+        assert_normal(&normals[1][1], (0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+        assert_eq!(vertices[22..25], [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[4 * length + 22..4 * length + 25], [normals[1][1].x, normals[1][1].y, normals[1][1].z]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_weld_shared_vertices_into_an_indexed_mesh() {
+        let file = format!("{HINGE}
+            v 1 1 0
+            s 1
+            f 1 2 5 3
+            s off
+            f 2 1 4
+");
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let raw = result.get_raw_vertices(math::Vec3::default());
+        let mesh = result.get_indexed_mesh(math::Vec3::default());
+        let length = OBJ::RAW_VERTEX_LENGTH;
+
+        // the quad is made of 2 triangles sharing 2 corners, and the flat triangle doesn't share
+        // its normals with the smooth quad
+        assert_eq!(raw.len(), 9 * length);
+        assert_eq!(mesh.vertex_count(), 7);
+        assert_eq!(mesh.indices.len(), 9);
+        assert!(matches!(mesh.indices, Indices::U16(_)));
+
+        // every corner still gets the same vertex
+        for corner in 0..9 {
+            let index = mesh.indices.get(corner).expect("This should work") as usize;
+            assert_eq!(mesh.vertex(index), &raw[corner * length..(corner + 1) * length]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_weld_vertices_with_different_materials() {
+        let file = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 1 1 0
+            usemtl Rock
+            f 1 2 3
+            usemtl Sand
+            f 3 2 4
+";
+
+        let mut result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+        let mut mesh = result.get_indexed_mesh(math::Vec3::default());
+
+        // the materials are not loaded, so the faces are alike
+        assert_eq!(mesh.vertex_count(), 4);
+
+        result.load_mtls(vec![MTL::from([
+            ("Rock".to_string(), Material { name: "Rock".to_string(), ..Default::default() }),
+            ("Sand".to_string(), Material { name: "Sand".to_string(), ..Default::default() }),
+        ])]);
+        mesh = result.get_indexed_mesh(math::Vec3::default());
+
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.indices, Indices::U16(vec![0, 1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_parse_from_a_stream() {
+        let file = "v 0 0 0\r\nv 1 0 0\r\n\r\nv\t1 1 0\r\nf 1 2 3\r\nf 3 2 1";
+
+        // a tiny buffer so lines are split between reads
+        let reader = std::io::BufReader::with_capacity(3, file.as_bytes());
+        let result = parse_obj(reader, Some(file.len() as u64), &LoadOptions::default()).expect("This should work");
+
+        assert_eq!(result.vertices.len(), 3);
+        assert_eq!(result.vertices[2], math::Vec4::new(1.0, 1.0, 0.0, 1.0));
+        assert_eq!(result.faces.len(), 2);
+        assert_eq!(result.faces[1].vertex_references[0], VertexDataReference::new(3, 0, 0));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_bound_the_memory_reserved_for_big_files() {
+        let file = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\n";
+        // a grid of 5 million quads takes about 330 MB
+        let size = 329_000_000;
+
+        let result = parse_obj(file.as_bytes(), Some(size), &LoadOptions::default()).expect("This should work");
+        let reserved = result.vertices.capacity() * std::mem::size_of::<math::Vec4>()
+            + result.faces.capacity() * std::mem::size_of::<Face>();
+
+        assert!(result.vertices.capacity() * std::mem::size_of::<math::Vec4>() <= MAX_RESERVED_BYTES);
+        assert!(reserved < size as usize / 10);
+        assert!(result.faces.capacity() < 16);
+        assert_eq!(reserved_vertices(u64::MAX), MAX_RESERVED_BYTES / std::mem::size_of::<math::Vec4>());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_not_parse_invalid_streams() {
+        let empty = parse_obj("".as_bytes(), None, &LoadOptions::default());
+        let invalid_utf8 = parse_obj(&b"v 0 0 0\nv 1 \xff 0\n"[..], None, &LoadOptions::default());
+
+        assert!(matches!(empty, Err(LoadOBJError::Parse(ParseError::EmptyFile(..)))));
+        assert!(matches!(invalid_utf8, Err(LoadOBJError::Io(_))));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_collect_diagnostics_in_lenient_mode() {
+        let file = "
+            v 0 0 0
+            v 1 abc 0
+            v 1 0 0
+            vn 1
+            v 0 1 0
+            xyz 1 2
+            f 1 2
+            f 1 2 9
+            f 1 2 3
+";
+        let lenient = LoadOptions { mode: ParseMode::Lenient, ..Default::default() };
+
+        let strict = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+        let result = parse_obj(file.as_bytes(), None, &lenient).expect("This should work");
+        let diagnostics = result.diagnostics.iter().map(|(file, diagnostic)| {
+            assert!(file.is_none());
+            (diagnostic.line, diagnostic.column, diagnostic.token.as_str(), diagnostic.severity, diagnostic.expected)
+        }).collect::<Vec<_>>();
+
+        assert!(strict.unwrap_err().to_string().starts_with("Invalid vertex at line 3: Invalid 'y' value 'abc'"));
+        assert_eq!(result.vertices.len(), 3);
+        assert_eq!(result.faces.len(), 1);
+        assert_eq!(diagnostics, vec![
+            (3, 17, "abc", Severity::Error, Some("v x y z [w]")),
+            (5, 17, "", Severity::Error, Some("vn i j k")),
+            (7, 13, "xyz", Severity::Warning, None),
+            (8, 18, "", Severity::Error, Some("f v1[/vt1][/vn1] v2[/vt2][/vn2] v3[/vt3][/vn3] ...")),
+            (9, 19, "9", Severity::Error, Some("f v1[/vt1][/vn1] v2[/vt2][/vn2] v3[/vt3][/vn3] ...")),
+        ]);
+        assert_eq!(
+            result.diagnostics[1].1.to_string(),
+            "error: Invalid vertex normal at line 5: Missing 'j' value (column 17), expected 'vn i j k'"
+        );
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_store_points_and_lines() {
+        let file = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            vt 0 0
+            vt 1 0
+            o guides
+            g axis
+            usemtl red
+            l 1/1 2/2
+            l -3 -2 -1
+            g corners
+            p 1 2 3
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+
+        assert!(result.faces.is_empty());
+        assert_eq!(result.lines.len(), 2);
+        assert_eq!(result.lines[0].vertex_references, vec![VertexDataReference::new(1, 1, 0), VertexDataReference::new(2, 2, 0)]);
+        assert_eq!(result.lines[1].vertex_references, vec![
+            VertexDataReference::new(1, 0, 0),
+            VertexDataReference::new(2, 0, 0),
+            VertexDataReference::new(3, 0, 0),
+        ]);
+        assert_eq!(result.lines[1].id, 1);
+        assert_eq!(result.points.len(), 1);
+        assert_eq!(result.points[0].vertices, vec![1, 2, 3]);
+        assert_eq!(result.points[0].material_name, Some("red".to_string()));
+
+        let object = result.object_by_name("guides").expect("This should work");
+        assert_eq!(object.lines, vec![0..2]);
+        assert_eq!(object.points, vec![0..1]);
+        let axis = result.group_by_name("axis").expect("This should work");
+        assert_eq!(result.get_group_lines(axis).count(), 2);
+        assert_eq!(result.get_group_points(axis).count(), 0);
+        let corners = result.group_by_name("corners").expect("This should work");
+        assert_eq!(result.get_group_points(corners).count(), 1);
+
+        // every segment is a pair of indices, the shared corner is only stored once
+        let lines = result.get_indexed_lines(math::Vec3::new(0.5, 0.5, 0.5));
+        assert_eq!(lines.indices.len(), 2 * 3);
+        assert_eq!(lines.vertex_count(), 5);
+        assert_eq!(&lines.vertex(0)[4..10], &[0.5, 0.5, 0.5, 0.0, 0.0, 0.0]);
+        let points = result.get_indexed_points(math::Vec3::splat(1.0));
+        assert_eq!(points.indices, Indices::U16(vec![0, 1, 2]));
+        assert_eq!(points.vertex(2)[21], -1.0);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_store_invalid_points_and_lines() {
+        let cases = [
+            ("p", "Invalid point at line 4: A point needs at least 1 vertex"),
+            ("p 1 5", "Invalid vertex reference at line 4: Reference '5' is out of range, only 2 declared"),
+            ("l 1", "Invalid line at line 4: A line needs at least 2 vertices"),
+            ("l 1/1 2", "Invalid line at line 4: Illegal to give texture vertices for some vertices, but not all"),
+            ("l 1//1 2//1", "Invalid line at line 4: Invalid line vertex '1//1'"),
+        ];
+
+        for (statement, expected) in cases {
+            let file = format!("v 0 0 0\nv 1 0 0\nvt 0 0\n{statement}\n");
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+            assert_eq!(result.unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_tessellate_free_form_surfaces() {
+        let file = "
+            v 0 0 0
+            v 1 0 0
+            v 2 0 0
+            v 0 1 0
+            v 1 1 1
+            v 2 1 0
+            v 0 2 0
+            v 1 2 0
+            v 2 2 0
+            o patch
+            g top
+            usemtl red
+            s 1
+            cstype bezier
+            deg 2 2
+            stech cparma 2 2
+            surf 0 1 0 1 1 2 3 4 5 6 7 8 9
+            parm u 0 1
+            parm v 0 1
+            end
+            ctech cparm 1
+            curv 0 1 1 -5 -1
+            parm u 0 1
+            end
+            f 1 2 4
+";
+
+        let result = parse_obj(file.as_bytes(), None, &LoadOptions::default()).expect("This should work");
+
+        assert_eq!(result.surfaces.len(), 1);
+        assert_eq!(result.surfaces[0].attributes.degree_v, 2);
+        assert_eq!(result.surfaces[0].material_name, Some("red".to_string()));
+        // 5 by 5 samples, the new vertices do not change the relative references that follow
+        assert_eq!(result.vertices.len(), 9 + 25);
+        assert_eq!(result.vertices_normal.len(), 25);
+        assert_eq!(result.faces.len(), 1 + 32);
+        assert_eq!(result.faces[0].vertex_references, vec![
+            VertexDataReference::new(1, 0, 0),
+            VertexDataReference::new(2, 0, 0),
+            VertexDataReference::new(4, 0, 0),
+        ]);
+        assert!(result.vertices.contains(&math::Vec4::new(1.0, 1.0, 0.25, 1.0)));
+
+        let group = result.group_by_name("top").expect("This should work");
+        assert_eq!(group.faces, vec![0..33]);
+        assert_eq!(result.material_ranges.len(), 1);
+        assert!(result.faces.iter().all(|face| face.smoothing_group == Some(1) && face.max_id == 33));
+
+        assert_eq!(result.curve_line_strips(), vec![vec![
+            math::Vec4::new(0.0, 0.0, 0.0, 1.0),
+            math::Vec4::new(1.0, 1.0, 0.5, 1.0),
+            math::Vec4::new(2.0, 2.0, 0.0, 1.0),
+        ]]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_fail_to_parse_invalid_curves_and_surfaces() {
+        let cases = [
+            ("cstype nurbs", "Invalid free-form attribute at line 4: Unknown curve or surface type 'nurbs'"),
+            ("deg 0", "Invalid free-form attribute at line 4: Invalid 'degu' value '0', it must be greater than 0"),
+            ("stech cparma 1", "Invalid free-form attribute at line 4: Missing 'vres' value"),
+            ("parm u 0 1", "Invalid curve or surface at line 4: 'parm' is only valid inside of a curve or a surface"),
+            ("end", "Invalid curve or surface at line 4: 'end' without a curve or a surface"),
+            ("curv 0 1 1 9", "Invalid vertex reference at line 4: Reference '9' is out of range, only 3 declared"),
+            ("curv 0 1 1 2\nparm u 0 1\nend", "Invalid curve or surface at line 6: Missing the u degree ('deg' statement)"),
+            ("deg 1\ncurv 0 1 1 2 3\nparm u 0 1\nend", "Invalid curve or surface at line 7: The parameters need 2 control points, but 3 are given"),
+            ("deg 1\ncurv 0 1 1 2\ntrim 0 1 1", "Invalid curve or surface at line 6: Curve '1' is not declared"),
+            ("deg 1\ncurv 0 1 1 2\ncurv 0 1 1 2", "Invalid curve or surface at line 6: The previous curve or surface is missing its 'end' statement"),
+        ];
+
+        for (statements, expected) in cases {
+            let file = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\n{statements}\nf 1 2 3\n");
+            let result = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+
+            assert_eq!(result.unwrap_err().to_string(), expected);
+        }
+    }
+
+    // every statement the parser knows, and some that are valid but unusual
+    const EVERY_STATEMENT: &str = "
+        # comment
+        mtllib a.mtl b.mtl
+        o object name
+        g group other
+        s 1
+        mg 1 0.5
+        v 0.0 0.0 0.0
+        v 1.0 0.0 0.0 1.0
+        v 1.0 1.0 0.0
+        v nan inf -inf
+        vt 0.5 0.5 0.0
+        vn 0.0 0.0 1.0
+        vp 0.1 0.2 0.3
+        vp 0.9 0.8
+        usemtl material
+        f 1/1/1 2/1/1 3/1/1
+        f 1//1 2//1 3//1
+        f -1 -2 -3 -4
+        s off
+        mg off
+        f 1 2 3
+        cstype rat bspline
+        deg 1 1
+        bmat u 1 0 0 1
+        step 1 1
+        ctech cparm 2
+        stech cparma 2 2
+        curv2 1 2
+        parm u 0 0 1 1
+        end
+        surf 0 1 0 1 1/1 2/1 3/1 4/1
+        parm u 0 0 1 1
+        parm v 0 0 1 1
+        hole 0 1 -1
+        scrv 0 1 1
+        sp 1 2
+        end
+        curv 0 1 -1 -2
+        parm u 0 0 1 1
+        end
+        p 1
+        l 1 2
+";
+
+    fn assert_no_panic(file: &str) {
+        let lenient = LoadOptions {
+            mode: ParseMode::Lenient,
+            ..Default::default()
+        };
+
+        let strict = parse_obj(file.as_bytes(), None, &LoadOptions::default());
+        let result = parse_obj(file.as_bytes(), None, &lenient);
+
+        match (strict, result) {
+            (Ok(strict), Ok(result)) => {
+                assert_eq!(strict.faces.len(), result.faces.len(), "{file}")
+            }
+            (Err(_), Ok(result)) => assert!(!result.diagnostics.is_empty(), "{file}"),
+            // only the problems that leave nothing to load are errors in lenient mode
+            (_, Err(error)) => assert!(
+                matches!(
+                    error,
+                    LoadOBJError::Parse(ParseError::MissingVertices | ParseError::MissingFaces)
+                ),
+                "{file}: {error}"
+            ),
+        }
+    }
+
+    #[test]
+    fn it_should_not_panic_on_truncated_statements() {
+        let lines = EVERY_STATEMENT.lines().collect::<Vec<&str>>();
+
+        for (index, line) in lines.iter().enumerate() {
+            for (end, _) in line.char_indices() {
+                let mut truncated = lines.clone();
+                truncated[index] = &line[..end];
+
+                assert_no_panic(&truncated.join("\n"));
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_not_panic_on_invalid_statements() {
+        let invalid = [
+            "v",
+            "v 1",
+            "v 1 2",
+            "v a b c",
+            "vt",
+            "vn 1 2",
+            "vp",
+            "f",
+            "f 0 1 2",
+            "f 1 2 99",
+            "f -99 1 2",
+            "f 1/1/1/1 2 3",
+            "f 1/ 2/ 3/",
+            "f 1// 2// 3//",
+            "f /1 /2 /3",
+            "f a b c",
+            "f 1 2 3/1",
+            "f 1//1 2/1/1 3//1",
+            "s",
+            "s a",
+            "mg",
+            "mg 1",
+            "mg 1 -1",
+            "mg a 1",
+            "o",
+            "usemtl",
+            "usemtl a b",
+            "g",
+            "mtllib",
+            "p",
+            "p 0",
+            "l 1",
+            "l 1/ 2/",
+            "l 1/1 2",
+            "l 1//1 2//1",
+            "cstype",
+            "cstype rat",
+            "deg",
+            "deg 99",
+            "bmat",
+            "bmat w 1",
+            "step 0",
+            "ctech cparm 0",
+            "ctech curv 1",
+            "stech cparmb",
+            "stech cspace -1",
+            "curv 0 1 1",
+            "curv2",
+            "surf 0 1 0 1 1 2 3",
+            "surf nan nan nan nan 1 2 3 4",
+            "parm u a",
+            "parm v 1 0",
+            "trim 0 1 9",
+            "hole 0 1",
+            "sp",
+            "end",
+            "\u{1F600}",
+            "v 1 2 3 \u{1F600}",
+            "#",
+            "f 1 1 1",
+            "f 2 2 2 2",
+        ];
+
+        for statement in invalid {
+            let file = EVERY_STATEMENT.replacen("s off", statement, 1);
+
+            assert_no_panic(&file);
+            assert_no_panic(statement);
+        }
+    }
 }
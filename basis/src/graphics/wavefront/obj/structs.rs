@@ -1,14 +1,26 @@
+use std::{collections::HashMap, rc::Rc};
+
 use crate::{
     graphics::{
         aabb::AABB,
+        mesh::{IndexedMesh, MeshBuilder},
+        normals::{self, NormalWeighting},
         wavefront::{
             self,
+            diagnostic::{Diagnostic, ParseMode},
             mtl::{Material, MTL},
         },
     },
-    math,
+    math::{self, VectorFunctions},
 };
 
+use super::free_form::{Curve, Curve2D, Surface};
+
+///
+/// Identifies a raw vertex: `v`, `vt`, `vn`, material name and the bits of the normal
+///
+type RawVertexKey<'a> = (usize, usize, usize, Option<&'a str>, [u32; 3]);
+
 #[derive(Debug)]
 pub enum ParseError {
     MissingFaces,
@@ -22,10 +34,17 @@ pub enum ParseError {
     InvalidVertexParameterSpace(usize, String),
     InvalidFace(usize, String),
     InvalidFaceSide(usize, String),
+    InvalidPoint(usize, String),
+    InvalidLine(usize, String),
+    InvalidVertexReference(usize, String),
     InvalidFaceMaterial(usize, String),
     InvalidGroup(usize, String),
     InvalidSmoothingGroup(usize, String),
+    InvalidMergingGroup(usize, String),
+    InvalidObjectName(usize, String),
     InvalidMaterialLibrary(usize, String),
+    InvalidFreeFormAttribute(usize, String),
+    InvalidCurveOrSurface(usize, String),
 }
 impl std::error::Error for ParseError {}
 
@@ -77,6 +96,19 @@ impl std::fmt::Display for ParseError {
             ParseError::InvalidFaceSide(line, face_side) => {
                 write!(f, "Invalid face side at line {}: {}", line, face_side)
             }
+            ParseError::InvalidPoint(line, point) => {
+                write!(f, "Invalid point at line {}: {}", line, point)
+            }
+            ParseError::InvalidLine(line, polyline) => {
+                write!(f, "Invalid line at line {}: {}", line, polyline)
+            }
+            ParseError::InvalidVertexReference(line, reference) => {
+                write!(
+                    f,
+                    "Invalid vertex reference at line {}: {}",
+                    line, reference
+                )
+            }
             ParseError::InvalidFaceMaterial(line, face_material) => {
                 write!(
                     f,
@@ -90,6 +122,12 @@ impl std::fmt::Display for ParseError {
             ParseError::InvalidSmoothingGroup(line, group) => {
                 write!(f, "Invalid smoothing group at line {}: {}", line, group)
             }
+            ParseError::InvalidMergingGroup(line, group) => {
+                write!(f, "Invalid merging group at line {}: {}", line, group)
+            }
+            ParseError::InvalidObjectName(line, name) => {
+                write!(f, "Invalid object name at line {}: {}", line, name)
+            }
             ParseError::InvalidMaterialLibrary(line, material_library) => {
                 write!(
                     f,
@@ -97,10 +135,63 @@ impl std::fmt::Display for ParseError {
                     line, material_library
                 )
             }
+            ParseError::InvalidFreeFormAttribute(line, attribute) => {
+                write!(
+                    f,
+                    "Invalid free-form attribute at line {}: {}",
+                    line, attribute
+                )
+            }
+            ParseError::InvalidCurveOrSurface(line, element) => {
+                write!(f, "Invalid curve or surface at line {}: {}", line, element)
+            }
         }
     }
 }
 
+///////////////////////////////////
+// Loading
+///////////////////////////////////
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TriangulationMethod {
+    ///
+    /// Fast, but only correct for convex polygons
+    ///
+    Fan,
+    ///
+    /// Slower, but correct for both convex and concave polygons
+    ///
+    #[default]
+    EarClipping,
+}
+
+///
+/// What to do when a library referenced by `mtllib` can't be read
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MissingMaterialLibrary {
+    ///
+    /// Keep loading the object and register a warning in `OBJ::warnings`
+    ///
+    #[default]
+    Warn,
+    ///
+    /// Stop loading with a `LoadOBJError::Material`
+    ///
+    Fail,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub triangulation: TriangulationMethod,
+    pub missing_material_library: MissingMaterialLibrary,
+    ///
+    /// Also used for the material libraries
+    ///
+    pub mode: ParseMode,
+}
+
 ///////////////////////////////////
 // Vertex data
 ///////////////////////////////////
@@ -166,6 +257,198 @@ pub struct SmoothingGroup<'a> {
     pub faces: Vec<&'a Face>,
 }
 
+///
+/// Merging groups are used to blend adjacent free-form surfaces, `resolution` is the maximum
+/// distance between two surfaces that will be merged together.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergingGroup {
+    pub id: usize,
+    pub resolution: f32,
+}
+
+///
+/// Kind of the elements stored by `Object` and `Group`
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ElementKind {
+    Point,
+    Line,
+    Face,
+}
+
+///
+/// Named collection of elements created by the `g` statement.
+///
+/// An element can belong to many groups at the same time, and a group can be reopened later in
+/// the file, that is why the elements are stored as lists of ranges.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub faces: Vec<std::ops::Range<usize>>,
+    pub points: Vec<std::ops::Range<usize>>,
+    pub lines: Vec<std::ops::Range<usize>>,
+}
+impl Group {
+    pub fn new(name: &str) -> Group {
+        Group {
+            name: name.to_string(),
+            faces: Vec::new(),
+            points: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    ///
+    /// Indices of the faces (inside of `OBJ::faces`) that belong to this group
+    ///
+    pub fn face_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.faces.iter().flat_map(|range| range.clone())
+    }
+
+    ///
+    /// Indices of the points (inside of `OBJ::points`) that belong to this group
+    ///
+    pub fn point_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.points.iter().flat_map(|range| range.clone())
+    }
+
+    ///
+    /// Indices of the lines (inside of `OBJ::lines`) that belong to this group
+    ///
+    pub fn line_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines.iter().flat_map(|range| range.clone())
+    }
+
+    pub fn contains_face(&self, index: usize) -> bool {
+        self.faces.iter().any(|range| range.contains(&index))
+    }
+
+    pub(super) fn ranges(&self, kind: ElementKind) -> &[std::ops::Range<usize>] {
+        match kind {
+            ElementKind::Point => &self.points,
+            ElementKind::Line => &self.lines,
+            ElementKind::Face => &self.faces,
+        }
+    }
+
+    fn ranges_mut(&mut self, kind: ElementKind) -> &mut Vec<std::ops::Range<usize>> {
+        match kind {
+            ElementKind::Point => &mut self.points,
+            ElementKind::Line => &mut self.lines,
+            ElementKind::Face => &mut self.faces,
+        }
+    }
+}
+
+///
+/// Named part of the file created by the `o` statement, all the groups declared while the object
+/// is active belong to it.
+///
+/// Elements declared before any `o` statement belong to an object called "default".
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Object {
+    pub name: String,
+    pub groups: Vec<Group>,
+    pub faces: Vec<std::ops::Range<usize>>,
+    pub points: Vec<std::ops::Range<usize>>,
+    pub lines: Vec<std::ops::Range<usize>>,
+}
+impl Object {
+    pub fn new(name: &str) -> Object {
+        Object {
+            name: name.to_string(),
+            groups: Vec::new(),
+            faces: Vec::new(),
+            points: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn group_by_name(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    ///
+    /// Indices of the faces (inside of `OBJ::faces`) that belong to this object
+    ///
+    pub fn face_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.faces.iter().flat_map(|range| range.clone())
+    }
+
+    ///
+    /// Indices of the points (inside of `OBJ::points`) that belong to this object
+    ///
+    pub fn point_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.points.iter().flat_map(|range| range.clone())
+    }
+
+    ///
+    /// Indices of the lines (inside of `OBJ::lines`) that belong to this object
+    ///
+    pub fn line_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines.iter().flat_map(|range| range.clone())
+    }
+
+    ///
+    /// Registers the face in this object and in each one of the given groups
+    ///
+    pub fn push_face(&mut self, index: usize, group_names: &[String]) {
+        self.push_element(ElementKind::Face, index, group_names);
+    }
+
+    ///
+    /// Registers the point in this object and in each one of the given groups
+    ///
+    pub fn push_point(&mut self, index: usize, group_names: &[String]) {
+        self.push_element(ElementKind::Point, index, group_names);
+    }
+
+    ///
+    /// Registers the line in this object and in each one of the given groups
+    ///
+    pub fn push_line(&mut self, index: usize, group_names: &[String]) {
+        self.push_element(ElementKind::Line, index, group_names);
+    }
+
+    pub(super) fn ranges(&self, kind: ElementKind) -> &[std::ops::Range<usize>] {
+        match kind {
+            ElementKind::Point => &self.points,
+            ElementKind::Line => &self.lines,
+            ElementKind::Face => &self.faces,
+        }
+    }
+
+    fn push_element(&mut self, kind: ElementKind, index: usize, group_names: &[String]) {
+        let ranges = match kind {
+            ElementKind::Point => &mut self.points,
+            ElementKind::Line => &mut self.lines,
+            ElementKind::Face => &mut self.faces,
+        };
+        push_to_ranges(ranges, index);
+
+        for name in group_names {
+            let group = match self.groups.iter().position(|group| &group.name == name) {
+                Some(position) => &mut self.groups[position],
+                None => {
+                    self.groups.push(Group::new(name));
+                    self.groups.last_mut().unwrap()
+                }
+            };
+            push_to_ranges(group.ranges_mut(kind), index);
+        }
+    }
+}
+
+fn push_to_ranges(ranges: &mut Vec<std::ops::Range<usize>>, index: usize) {
+    match ranges.last_mut() {
+        Some(range) if range.end == index => range.end += 1,
+        _ => ranges.push(index..index + 1),
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct VertexDataReference {
     pub v: usize,
@@ -189,8 +472,9 @@ pub struct Face {
     pub max_id: usize,
     pub vertex_references: Vec<VertexDataReference>,
     pub material_name: Option<String>,
-    pub material: Option<Material>,
+    pub material: Option<Rc<Material>>,
     pub smoothing_group: Option<usize>,
+    pub merging_group: Option<usize>,
 }
 impl Face {
     pub fn partial_new(
@@ -204,6 +488,7 @@ impl Face {
             material_name,
             material: None,
             smoothing_group: None,
+            merging_group: None,
         }
     }
 
@@ -211,7 +496,7 @@ impl Face {
         self.material.is_none()
     }
 
-    pub fn set_material(&mut self, material: Option<Material>) {
+    pub fn set_material(&mut self, material: Option<Rc<Material>>) {
         self.material = material;
     }
 }
@@ -222,6 +507,47 @@ impl PartialEq for Face {
     }
 }
 
+///
+/// Points created by a `p` statement, `vertices` are 1-based indices of `OBJ::vertices`
+///
+#[derive(Debug, Clone, Default)]
+pub struct Point {
+    pub id: usize,
+    pub vertices: Vec<usize>,
+    pub material_name: Option<String>,
+    pub material: Option<Rc<Material>>,
+}
+impl PartialEq for Point {
+    fn eq(&self, other: &Self) -> bool {
+        self.vertices == other.vertices
+    }
+}
+
+///
+/// Polyline created by an `l` statement, the references never have a normal (`vn` is `0`)
+///
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    pub id: usize,
+    pub vertex_references: Vec<VertexDataReference>,
+    pub material_name: Option<String>,
+    pub material: Option<Rc<Material>>,
+}
+impl PartialEq for Line {
+    fn eq(&self, other: &Self) -> bool {
+        self.vertex_references == other.vertex_references
+    }
+}
+
+///
+/// Consecutive faces that share the same material
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialRange {
+    pub material_name: String,
+    pub faces: std::ops::Range<usize>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct OBJ {
     //
@@ -231,27 +557,52 @@ pub struct OBJ {
     pub vertices_texture: Vec<VerticeTexture>,
     pub vertices_normal: Vec<VerticeNormal>,
     pub vertices_parameter_space: Vec<VerticeParameterSpace>,
+    //
+    // Color of each vertex, only filled by the formats that store one (such as PLY)
+    //
+    pub vertices_color: Vec<math::Vec3>,
 
     //
     // Elements
     //
     pub faces: Vec<Face>,
+    pub points: Vec<Point>,
+    pub lines: Vec<Line>,
+
+    //
+    // Free-form geometry, the surfaces are also tessellated into `faces` while loading
+    //
+    pub curves: Vec<Curve>,
+    pub curves_2d: Vec<Curve2D>,
+    pub surfaces: Vec<Surface>,
 
     //
     // Grouping
     //
-    pub name: Option<String>,
+    pub objects: Vec<Object>,
+    pub merging_groups: Vec<MergingGroup>,
 
     //
     // Display/render attributes
     //
     pub mtls_identifiers: Vec<String>,
     pub mtls: Vec<wavefront::mtl::MTL>,
+    pub material_ranges: Vec<MaterialRange>,
 
     //
     // Texture
     //
     pub texture: (u32, u32, Vec<u8>),
+
+    //
+    // Problems that did not prevent the object from loading
+    //
+    pub warnings: Vec<String>,
+    //
+    // Statements skipped when parsing in `ParseMode::Lenient`, by file: the object file is
+    // `None` and the material libraries use their `mtllib` identifier
+    //
+    pub diagnostics: Vec<(Option<String>, Diagnostic)>,
 }
 
 impl OBJ {
@@ -259,109 +610,473 @@ impl OBJ {
         !self.mtls.is_empty()
     }
 
+    ///
+    /// Splits the polygons into triangles, loaders do it before returning the `OBJ`
+    ///
+    pub fn triangulate(&mut self, method: TriangulationMethod) {
+        super::helpers::triangulate_polygons(self, method);
+    }
+
+    ///
+    /// Evaluates every curve (`curv`) into a line strip, following the `ctech` of each curve
+    ///
+    pub fn curve_line_strips(&self) -> Vec<Vec<math::Vec4>> {
+        self.curves
+            .iter()
+            .filter_map(|curve| curve.tessellate(&self.vertices).ok())
+            .collect()
+    }
+
+    ///
+    /// Amount of floats used by each vertex returned by `OBJ::get_raw_vertices` and
+    /// `OBJ::get_indexed_mesh`
+    ///
+    pub const RAW_VERTEX_LENGTH: usize = 25;
+
+    ///
+    /// Expands every face into a list of vertices, each one made of `OBJ::RAW_VERTEX_LENGTH` floats:
+    ///
+    /// * `[0..4]` Position
+    /// * `[4..7]` Color, from `OBJ::vertices_color` when the vertex has one, otherwise `rgb`
+    /// * `[7..10]` Texture coordinates
+    /// * `[10..13]` Ambient reflectivity (`Ka`)
+    /// * `[13..16]` Diffuse reflectivity (`Kd`)
+    /// * `[16..19]` Specular reflectivity (`Ks`)
+    /// * `[19]` Specular exponent (`Ns`)
+    /// * `[20]` Dissolve factor (`d`)
+    /// * `[21]` Illumination model (`illum`), `-1` when the face has no material
+    /// * `[22..25]` Normal, see `OBJ::vertex_normals`
+    ///
     pub fn get_raw_vertices(&self, rgb: math::Vec3) -> Vec<f32> {
-        let mut vertices_texture = self.vertices_texture.clone();
+        let mut vertices = Vec::with_capacity(self.faces.len() * 3 * Self::RAW_VERTEX_LENGTH);
 
-        let aabb = AABB::from(&self.vertices);
+        self.for_each_raw_vertex(rgb, |_, vertex| vertices.extend_from_slice(vertex));
 
-        // if there is no texture vertices we calculate ourselves based on the aabb
-        if vertices_texture.is_empty() {
-            for vertice in &self.vertices {
-                let mut range = math::Vec3 {
-                    x: aabb.max.x - aabb.min.x,
-                    y: aabb.max.y - aabb.min.y,
-                    z: aabb.max.z - aabb.min.z,
-                };
-                if range.x == 0.0 {
-                    range.x = 1.0;
-                }
-                if range.y == 0.0 {
-                    range.y = 1.0;
+        vertices
+    }
+
+    ///
+    /// Same vertices as `OBJ::get_raw_vertices`, but corners that share the same position, texture
+    /// vertex, normal and material are only stored once and the faces are drawn with indices
+    ///
+    pub fn get_indexed_mesh(&self, rgb: math::Vec3) -> IndexedMesh {
+        let mut builder = MeshBuilder::new(Self::RAW_VERTEX_LENGTH);
+        builder.reserve(self.faces.len() * 3);
+
+        self.for_each_raw_vertex(rgb, |key, vertex| builder.push(key, vertex));
+
+        builder.build()
+    }
+
+    ///
+    /// Same vertices as `OBJ::get_indexed_mesh` for the lines, every segment of every polyline is
+    /// drawn as a pair of indices (`GL_LINES`).
+    ///
+    /// Lines have no normal, so the ones with a material use the illumination model `0` (only the
+    /// diffuse color) instead of the one of their material.
+    ///
+    pub fn get_indexed_lines(&self, rgb: math::Vec3) -> IndexedMesh {
+        let mut builder = MeshBuilder::new(Self::RAW_VERTEX_LENGTH);
+        let vertices_texture = self.raw_vertices_texture();
+        let mut raw = [0.0; Self::RAW_VERTEX_LENGTH];
+
+        for line in &self.lines {
+            for segment in line.vertex_references.windows(2) {
+                for reference in segment {
+                    let material = line.material.as_deref();
+                    let normal = math::Vec3::default();
+                    self.fill_raw_vertex(
+                        &mut raw,
+                        reference,
+                        material,
+                        normal,
+                        &vertices_texture,
+                        rgb,
+                    );
+                    if material.is_some() {
+                        raw[21] = 0.0;
+                    }
+
+                    let key = (
+                        reference.v,
+                        reference.vt,
+                        0,
+                        material.map(|m| m.name.as_str()),
+                        [0; 3],
+                    );
+                    builder.push(key, &raw);
                 }
-                if range.z == 0.0 {
-                    range.z = 1.0;
+            }
+        }
+
+        builder.build()
+    }
+
+    ///
+    /// Same as `OBJ::get_indexed_lines`, but for the points (`GL_POINTS`)
+    ///
+    pub fn get_indexed_points(&self, rgb: math::Vec3) -> IndexedMesh {
+        let mut builder = MeshBuilder::new(Self::RAW_VERTEX_LENGTH);
+        let vertices_texture = self.raw_vertices_texture();
+        let mut raw = [0.0; Self::RAW_VERTEX_LENGTH];
+
+        for point in &self.points {
+            for &v in &point.vertices {
+                let reference = VertexDataReference::new(v, 0, 0);
+                let material = point.material.as_deref();
+                let normal = math::Vec3::default();
+                self.fill_raw_vertex(
+                    &mut raw,
+                    &reference,
+                    material,
+                    normal,
+                    &vertices_texture,
+                    rgb,
+                );
+                if material.is_some() {
+                    raw[21] = 0.0;
                 }
 
-                vertices_texture.push(VerticeTexture::new(
+                let key = (v, 0, 0, material.map(|m| m.name.as_str()), [0; 3]);
+                builder.push(key, &raw);
+            }
+        }
+
+        builder.build()
+    }
+
+    ///
+    /// Texture vertices used by the raw vertices, when the file has none they are computed from
+    /// the position of each vertex inside of the aabb and indexed like the vertices
+    ///
+    fn raw_vertices_texture(&self) -> Vec<VerticeTexture> {
+        if !self.vertices_texture.is_empty() {
+            return self.vertices_texture.clone();
+        }
+
+        let aabb = AABB::from(&self.vertices);
+        let mut range = math::Vec3 {
+            x: aabb.max.x - aabb.min.x,
+            y: aabb.max.y - aabb.min.y,
+            z: aabb.max.z - aabb.min.z,
+        };
+        if range.x == 0.0 {
+            range.x = 1.0;
+        }
+        if range.y == 0.0 {
+            range.y = 1.0;
+        }
+        if range.z == 0.0 {
+            range.z = 1.0;
+        }
+
+        self.vertices
+            .iter()
+            .map(|vertice| {
+                VerticeTexture::new(
                     (vertice.x - aabb.min.x) / range.x,
                     (vertice.y - aabb.min.y) / range.y,
                     (vertice.z - aabb.min.z) / range.z,
-                ));
+                )
+            })
+            .collect()
+    }
+
+    ///
+    /// Writes the raw vertex of a corner, see `OBJ::get_raw_vertices` for the layout
+    ///
+    fn fill_raw_vertex(
+        &self,
+        raw: &mut [f32; Self::RAW_VERTEX_LENGTH],
+        reference: &VertexDataReference,
+        material: Option<&Material>,
+        normal: math::Vec3,
+        vertices_texture: &[VerticeTexture],
+        rgb: math::Vec3,
+    ) {
+        let rvt = if self.vertices_texture.is_empty() {
+            // if the texture was generated, we use the vertice reference
+            reference.v - 1
+        } else {
+            // if the texture is from the OBJ file, we use their references
+            if reference.vt == 0 {
+                0
+            } else {
+                reference.vt - 1
+            }
+        };
+
+        // references are validated while parsing
+        let v = &self.vertices[reference.v - 1];
+        let vt = if let Some(texture) = vertices_texture.get(rvt) {
+            texture
+        } else {
+            &VerticeTexture::default()
+        };
+
+        raw[0..4].copy_from_slice(&[v.x, v.y, v.z, v.w]);
+        let color = self.vertices_color.get(reference.v - 1).unwrap_or(&rgb);
+        raw[4..7].copy_from_slice(&[color.x, color.y, color.z]);
+        raw[7..10].copy_from_slice(&[vt.u, vt.v, vt.w]);
+
+        match material {
+            Some(material) => {
+                let ka = material.ambient_reflectivity.rgb();
+                let kd = material.diffuse_reflectivity.rgb();
+                let ks = material.atmosphere_reflectivity.rgb();
+
+                raw[10..13].copy_from_slice(&[ka.r, ka.g, ka.b]);
+                raw[13..16].copy_from_slice(&[kd.r, kd.g, kd.b]);
+                raw[16..19].copy_from_slice(&[ks.r, ks.g, ks.b]);
+                raw[19] = material.specular_highlight_exponent;
+                raw[20] = material.dissolve_factor.factor;
+                raw[21] = material.illumination_model.clone() as u8 as f32;
+            }
+            None => {
+                raw[10..21].copy_from_slice(&[0.0; 11]);
+                raw[21] = -1.0;
             }
         }
 
-        self.faces.iter().fold(
-            Vec::with_capacity(self.faces.len() * 12),
-            |mut acc, face| {
-                for reference in &face.vertex_references {
-                    let rvt = if self.vertices_texture.is_empty() {
-                        // if the texture was generated, we use the vertice reference
-                        reference.v - 1
-                    } else {
-                        // if the texture is from the OBJ file, we use their references
-                        if reference.vt == 0 {
-                            0
-                        } else {
-                            reference.vt - 1
-                        }
-                    };
-
-                    let v = if let Some(vertice) = self.vertices.get(reference.v - 1) {
-                        vertice
-                    } else {
-                        &math::Vec4::default()
-                    };
-                    let vt = if let Some(texture) = vertices_texture.get(rvt) {
-                        texture
-                    } else {
-                        &VerticeTexture::default()
-                    };
-
-                    acc.push(v.x);
-                    acc.push(v.y);
-                    acc.push(v.z);
-                    acc.push(v.w);
-                    acc.push(rgb.x);
-                    acc.push(rgb.y);
-                    acc.push(rgb.z);
-                    acc.push(vt.u);
-                    acc.push(vt.v);
-                    acc.push(vt.w);
-                    acc.push(face.id as f32);
-                    acc.push(face.max_id as f32);
-                }
+        raw[22..25].copy_from_slice(&[normal.x, normal.y, normal.z]);
+    }
+
+    ///
+    /// Calls `callback` for every corner of every face with the raw vertex of the corner and a key
+    /// that is the same for every corner that produces the same raw vertex
+    ///
+    fn for_each_raw_vertex<'a>(
+        &'a self,
+        rgb: math::Vec3,
+        mut callback: impl FnMut(RawVertexKey<'a>, &[f32]),
+    ) {
+        let vertices_texture = self.raw_vertices_texture();
+        let normals = self.vertex_normals(NormalWeighting::default());
+        let mut raw = [0.0; Self::RAW_VERTEX_LENGTH];
 
-                acc
-            },
-        )
+        for (face, normals) in self.faces.iter().zip(normals) {
+            for (reference, normal) in face.vertex_references.iter().zip(normals) {
+                let material = face.material.as_deref();
+                self.fill_raw_vertex(
+                    &mut raw,
+                    reference,
+                    material,
+                    normal,
+                    &vertices_texture,
+                    rgb,
+                );
+
+                // generated normals differ for the same `v` between smoothing groups
+                let key = (
+                    reference.v,
+                    reference.vt,
+                    reference.vn,
+                    material.map(|material| material.name.as_str()),
+                    [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()],
+                );
+                callback(key, &raw);
+            }
+        }
     }
 
-    pub fn get_raw_indices(&self) -> Vec<u32> {
-        self.faces.iter().fold(Vec::new(), |mut acc, face| {
-            face.vertex_references.iter().for_each(|reference| {
-                acc.push((reference.v - 1) as u32);
-            });
-            acc
-        })
+    ///
+    /// Normal of each face, used for flat shading
+    ///
+    pub fn face_normals(&self) -> Vec<math::Vec3> {
+        self.faces
+            .iter()
+            .map(|face| normals::face_normal(&face.vertex_references, &self.vertices).normalize())
+            .collect()
     }
 
-    pub fn load_mtls(&mut self, mtls: Vec<MTL>) -> &Self {
-        self.mtls = mtls;
+    ///
+    /// Normal of each corner of each face, `normals[face][corner]`:
+    ///
+    /// * The normal given by the file when the corner references one (`vn`)
+    /// * The face normal when the face has no smoothing group (`s off`), so its edges stay hard
+    /// * Otherwise the weighted sum of the normals of the faces that share the vertex and belong
+    ///   to the same smoothing group
+    ///
+    pub fn vertex_normals(&self, weighting: NormalWeighting) -> Vec<Vec<math::Vec3>> {
+        let face_normals = self
+            .faces
+            .iter()
+            .map(|face| normals::face_normal(&face.vertex_references, &self.vertices))
+            .collect::<Vec<math::Vec3>>();
 
-        for face in self.faces.iter_mut() {
-            if face.material_name.is_none() {
+        let corner_angle = |face: &Face, corner: usize| {
+            let references = &face.vertex_references;
+            let previous = &references[(corner + references.len() - 1) % references.len()];
+            let next = &references[(corner + 1) % references.len()];
+
+            normals::corner_angle(
+                &self.vertices[previous.v - 1],
+                &self.vertices[references[corner].v - 1],
+                &self.vertices[next.v - 1],
+            )
+        };
+
+        // normals accumulated by smoothing group and vertex
+        let mut smooth_normals: HashMap<(usize, usize), math::Vec3> = HashMap::new();
+        for (face, normal) in self.faces.iter().zip(&face_normals) {
+            let Some(smoothing_group) = face.smoothing_group else {
                 continue;
+            };
+
+            for (corner, reference) in face.vertex_references.iter().enumerate() {
+                let weighted =
+                    normals::weighted_normal(*normal, corner_angle(face, corner), weighting);
+                let sum = smooth_normals
+                    .entry((smoothing_group, reference.v))
+                    .or_default();
+                *sum += weighted;
             }
-            if let Some(name) = face.material_name.clone() {
-                for material in self.mtls.iter() {
-                    face.set_material(material.get(&name).cloned());
-                }
+        }
+
+        self.faces
+            .iter()
+            .zip(&face_normals)
+            .map(|(face, face_normal)| {
+                face.vertex_references
+                    .iter()
+                    .map(|reference| {
+                        if let Some(normal) = self.vertices_normal.get(reference.vn.wrapping_sub(1))
+                        {
+                            return math::Vec3::new(normal.i, normal.j, normal.k).normalize();
+                        }
+
+                        let smooth_normal = face
+                            .smoothing_group
+                            .and_then(|group| smooth_normals.get(&(group, reference.v)))
+                            .filter(|normal| **normal != math::Vec3::default());
+
+                        smooth_normal.unwrap_or(face_normal).normalize()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    ///
+    /// Attaches the materials to the faces, the points and the lines. When a material is defined
+    /// by more than one library, the first library declared by `mtllib` wins.
+    ///
+    /// Materials that are not defined by any library are reported in `OBJ::warnings`.
+    ///
+    pub fn load_mtls(&mut self, mtls: Vec<MTL>) -> &Self {
+        let mut undefined: Vec<String> = Vec::new();
+        let find = |name: Option<&str>| {
+            let name = name?;
+            let material = mtls.iter().find_map(|mtl| mtl.get(name)).cloned();
+
+            if material.is_none() && !undefined.iter().any(|undefined| undefined == name) {
+                undefined.push(name.to_string());
             }
+            material
+        };
+
+        self.assign_materials(find);
+        self.mtls = mtls;
+
+        for name in undefined {
+            self.warnings
+                .push(format!("Material '{}' is not defined by any library", name));
         }
         self
     }
 
-    pub fn get_smoothing_group_by_id(&self, id: usize) -> SmoothingGroup {
+    ///
+    /// Gives every face, point and line the material `find` returns for its material name, the
+    /// name is `None` for the elements declared before any `usemtl`
+    ///
+    /// `find` is called once per name, the elements sharing a name share the material.
+    ///
+    pub fn assign_materials(&mut self, mut find: impl FnMut(Option<&str>) -> Option<Material>) {
+        let mut found: HashMap<Option<String>, Option<Rc<Material>>> = HashMap::new();
+        let mut resolve = |name: &Option<String>| {
+            found
+                .entry(name.clone())
+                .or_insert_with(|| find(name.as_deref()).map(Rc::new))
+                .clone()
+        };
+
+        for face in self.faces.iter_mut() {
+            face.set_material(resolve(&face.material_name));
+        }
+        for point in self.points.iter_mut() {
+            point.material = resolve(&point.material_name);
+        }
+        for line in self.lines.iter_mut() {
+            line.material = resolve(&line.material_name);
+        }
+    }
+
+    ///
+    /// Same as `OBJ::material_ranges`, but only the ones using the given material
+    ///
+    pub fn get_material_ranges_by_name(&self, name: &str) -> Vec<&MaterialRange> {
+        self.material_ranges
+            .iter()
+            .filter(|range| range.material_name == name)
+            .collect()
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn object_by_name(&self, name: &str) -> Option<&Object> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+
+    ///
+    /// Finds the first group with the given name, looking through all objects
+    ///
+    pub fn group_by_name(&self, name: &str) -> Option<&Group> {
+        self.objects
+            .iter()
+            .find_map(|object| object.group_by_name(name))
+    }
+
+    ///
+    /// Every group with the given name, one for each object that declared it
+    ///
+    pub fn groups_by_name(&self, name: &str) -> Vec<&Group> {
+        self.objects
+            .iter()
+            .filter_map(|object| object.group_by_name(name))
+            .collect()
+    }
+
+    pub fn get_group_faces<'a>(&'a self, group: &'a Group) -> impl Iterator<Item = &'a Face> {
+        group
+            .face_indices()
+            .filter_map(|index| self.faces.get(index))
+    }
+
+    pub fn get_object_faces<'a>(&'a self, object: &'a Object) -> impl Iterator<Item = &'a Face> {
+        object
+            .face_indices()
+            .filter_map(|index| self.faces.get(index))
+    }
+
+    pub fn get_group_points<'a>(&'a self, group: &'a Group) -> impl Iterator<Item = &'a Point> {
+        group
+            .point_indices()
+            .filter_map(|index| self.points.get(index))
+    }
+
+    pub fn get_group_lines<'a>(&'a self, group: &'a Group) -> impl Iterator<Item = &'a Line> {
+        group
+            .line_indices()
+            .filter_map(|index| self.lines.get(index))
+    }
+
+    pub fn get_merging_group_by_id(&self, id: usize) -> Option<&MergingGroup> {
+        self.merging_groups.iter().find(|group| group.id == id)
+    }
+
+    pub fn get_smoothing_group_by_id(&self, id: usize) -> SmoothingGroup<'_> {
         let faces = self
             .faces
             .iter()
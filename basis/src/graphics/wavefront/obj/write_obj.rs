@@ -0,0 +1,433 @@
+use std::io::Write;
+
+use super::structs::{ElementKind, VertexDataReference, OBJ};
+use crate::graphics::wavefront::writer::WriteOptions;
+
+impl OBJ {
+    ///
+    /// Writes the object as Wavefront text: the `mtllib` references, the vertex data, then the
+    /// faces, the points and the lines along with the `o`, `g`, `s`, `mg` and `usemtl` statements
+    /// that give them back their object, groups, smoothing group, merging group and material when
+    /// the text is parsed.
+    ///
+    /// Faces hold the triangles of their polygon once loaded, so every triangle is written as its
+    /// own `f` statement. The material libraries are not written, see `mtl::write`.
+    ///
+    pub fn write_to<W: Write>(&self, mut writer: W, options: &WriteOptions) -> std::io::Result<()> {
+        let float = |value: f32| options.float(value);
+
+        if !self.mtls_identifiers.is_empty() {
+            writeln!(writer, "mtllib {}", self.mtls_identifiers.join(" "))?;
+        }
+
+        for v in &self.vertices {
+            write!(writer, "v {} {} {}", float(v.x), float(v.y), float(v.z))?;
+            if v.w != 1.0 {
+                write!(writer, " {}", float(v.w))?;
+            }
+            writeln!(writer)?;
+        }
+        for vt in &self.vertices_texture {
+            write!(writer, "vt {} {}", float(vt.u), float(vt.v))?;
+            if vt.w != 0.0 {
+                write!(writer, " {}", float(vt.w))?;
+            }
+            writeln!(writer)?;
+        }
+        for vn in &self.vertices_normal {
+            writeln!(writer, "vn {} {} {}", float(vn.i), float(vn.j), float(vn.k))?;
+        }
+        for vp in &self.vertices_parameter_space {
+            write!(writer, "vp {} {}", float(vp.u), float(vp.v))?;
+            if vp.w != 1.0 {
+                write!(writer, " {}", float(vp.w))?;
+            }
+            writeln!(writer)?;
+        }
+
+        self.write_elements(&mut writer, options)
+    }
+
+    fn write_elements<W: Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
+        let mut state = WrittenState {
+            object: None,
+            groups: vec!["default"],
+            smoothing_group: None,
+            merging_group: None,
+            material_name: None,
+        };
+
+        let (face_objects, face_groups) = self.element_grouping(ElementKind::Face);
+        for (index, face) in self.faces.iter().enumerate() {
+            state.write_grouping(writer, self, face_objects[index], &face_groups[index])?;
+
+            if face.smoothing_group != state.smoothing_group {
+                match face.smoothing_group {
+                    Some(id) => writeln!(writer, "s {}", id)?,
+                    None => writeln!(writer, "s off")?,
+                }
+                state.smoothing_group = face.smoothing_group;
+            }
+
+            if face.merging_group != state.merging_group {
+                match face
+                    .merging_group
+                    .and_then(|id| self.get_merging_group_by_id(id))
+                {
+                    Some(group) => writeln!(
+                        writer,
+                        "mg {} {}",
+                        group.id,
+                        options.float(group.resolution)
+                    )?,
+                    None => writeln!(writer, "mg off")?,
+                }
+                state.merging_group = face.merging_group;
+            }
+
+            state.write_material(writer, &face.material_name)?;
+
+            let triangles = match face.vertex_references.len() % 3 {
+                0 => face.vertex_references.chunks(3),
+                _ => face.vertex_references.chunks(face.vertex_references.len()),
+            };
+            for triangle in triangles {
+                write!(writer, "f")?;
+                for reference in triangle {
+                    write!(writer, " ")?;
+                    write_reference(writer, reference)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+
+        let (point_objects, point_groups) = self.element_grouping(ElementKind::Point);
+        for (index, point) in self.points.iter().enumerate() {
+            state.write_grouping(writer, self, point_objects[index], &point_groups[index])?;
+            state.write_material(writer, &point.material_name)?;
+
+            write!(writer, "p")?;
+            for v in &point.vertices {
+                write!(writer, " {}", v)?;
+            }
+            writeln!(writer)?;
+        }
+
+        let (line_objects, line_groups) = self.element_grouping(ElementKind::Line);
+        for (index, line) in self.lines.iter().enumerate() {
+            state.write_grouping(writer, self, line_objects[index], &line_groups[index])?;
+            state.write_material(writer, &line.material_name)?;
+
+            write!(writer, "l")?;
+            for reference in &line.vertex_references {
+                write!(writer, " ")?;
+                write_reference(writer, reference)?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Object and group names of every element of the given kind
+    ///
+    fn element_grouping(&self, kind: ElementKind) -> (Vec<Option<usize>>, Vec<Vec<&str>>) {
+        let count = match kind {
+            ElementKind::Point => self.points.len(),
+            ElementKind::Line => self.lines.len(),
+            ElementKind::Face => self.faces.len(),
+        };
+        let mut element_objects = vec![None; count];
+        let mut element_groups = vec![Vec::new(); count];
+
+        for (position, object) in self.objects.iter().enumerate() {
+            for index in object.ranges(kind).iter().flat_map(|range| range.clone()) {
+                if let Some(element_object) = element_objects.get_mut(index) {
+                    *element_object = Some(position);
+                }
+            }
+            for group in &object.groups {
+                for index in group.ranges(kind).iter().flat_map(|range| range.clone()) {
+                    if let Some(names) = element_groups.get_mut(index) {
+                        names.push(group.name.as_str());
+                    }
+                }
+            }
+        }
+
+        (element_objects, element_groups)
+    }
+}
+
+///
+/// Statements written so far that apply to the following elements, it starts as the state of the
+/// parser before the first statement
+///
+struct WrittenState<'a> {
+    object: Option<usize>,
+    groups: Vec<&'a str>,
+    smoothing_group: Option<usize>,
+    merging_group: Option<usize>,
+    material_name: Option<&'a String>,
+}
+
+impl<'a> WrittenState<'a> {
+    fn write_grouping<W: Write>(
+        &mut self,
+        writer: &mut W,
+        obj: &'a OBJ,
+        object: Option<usize>,
+        groups: &[&'a str],
+    ) -> std::io::Result<()> {
+        if let Some(object) = object {
+            if self.object != Some(object) {
+                let name = &obj.objects[object].name;
+                // elements found before any `o` statement go to the "default" object
+                if self.object.is_some() || name != "default" {
+                    writeln!(writer, "o {}", name)?;
+                }
+                self.object = Some(object);
+            }
+        }
+
+        let groups = match groups.is_empty() {
+            true => vec!["default"],
+            false => groups.to_vec(),
+        };
+        if groups != self.groups {
+            writeln!(writer, "g {}", groups.join(" "))?;
+            self.groups = groups;
+        }
+        Ok(())
+    }
+
+    fn write_material<W: Write>(
+        &mut self,
+        writer: &mut W,
+        material_name: &'a Option<String>,
+    ) -> std::io::Result<()> {
+        // the format has no way to go back to elements without a material
+        if let Some(name) = material_name {
+            if self.material_name != Some(name) {
+                writeln!(writer, "usemtl {}", name)?;
+                self.material_name = Some(name);
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Writes the `v`, `v/vt`, `v//vn` or `v/vt/vn` form of a face corner, `0` means missing
+///
+fn write_reference<W: Write>(
+    writer: &mut W,
+    reference: &VertexDataReference,
+) -> std::io::Result<()> {
+    match (reference.vt, reference.vn) {
+        (0, 0) => write!(writer, "{}", reference.v),
+        (vt, 0) => write!(writer, "{}/{}", reference.v, vt),
+        (0, vn) => write!(writer, "{}//{}", reference.v, vn),
+        (vt, vn) => write!(writer, "{}/{}/{}", reference.v, vt, vn),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::{
+        mtl,
+        obj::{self, LoadOptions},
+    };
+
+    fn write(obj: &OBJ, options: &WriteOptions) -> String {
+        let mut output = Vec::new();
+        obj.write_to(&mut output, options)
+            .expect("This should work");
+        String::from_utf8(output).expect("This should work")
+    }
+
+    ///
+    /// References of a triangle, its material name, smoothing group and merging group
+    ///
+    type Triangle = (Vec<usize>, Option<String>, Option<usize>, Option<usize>);
+
+    fn triangles(obj: &OBJ) -> Vec<Triangle> {
+        obj.faces
+            .iter()
+            .flat_map(|face| {
+                face.vertex_references.chunks(3).map(|triangle| {
+                    (
+                        triangle.iter().flat_map(|r| [r.v, r.vt, r.vn]).collect(),
+                        face.material_name.clone(),
+                        face.smoothing_group,
+                        face.merging_group,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    ///
+    /// Object and group names of every triangle
+    ///
+    fn triangle_grouping(obj: &OBJ) -> Vec<(Option<String>, Vec<String>)> {
+        let (objects, groups) = obj.element_grouping(ElementKind::Face);
+
+        obj.faces
+            .iter()
+            .enumerate()
+            .flat_map(|(index, face)| {
+                let object = objects[index].map(|object| obj.objects[object].name.clone());
+                let mut groups = groups[index]
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<String>>();
+                groups.sort();
+                std::iter::repeat_n((object, groups), face.vertex_references.len() / 3)
+            })
+            .collect()
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_write_every_reference_form_and_grouping_statement() {
+        let file = "
+            mtllib a.mtl b.mtl
+            v 0 0 0
+            v 1 0 0 0.5
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0 0.25
+            vt 1 1
+            vn 0 0 1
+            vp 0.5 0.5
+            f 1 2 3
+            o Box
+            g side top
+            s 1
+            usemtl Rock
+            f 1/1 2/2 3/3
+            mg 2 0.5
+            f 1//1 2//1 3//1 4//1
+            g top
+            s off
+            mg off
+            usemtl Dirt
+            f 1/1/1 2/2/1 3/3/1
+";
+
+        let obj = obj::parse(file.as_bytes(), &LoadOptions::default()).expect("This should work");
+        let written = write(&obj, &WriteOptions::default());
+
+        assert_eq!(written, "\
+mtllib a.mtl b.mtl
+v 0 0 0
+v 1 0 0 0.5
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0 0.25
+vt 1 1
+vn 0 0 1
+vp 0.5 0.5
+f 1 2 3
+o Box
+g side top
+s 1
+usemtl Rock
+f 1/1 2/2 3/3
+mg 2 0.5
+f 1//1 2//1 3//1
+f 1//1 3//1 4//1
+g top
+s off
+mg off
+usemtl Dirt
+f 1/1/1 2/2/1 3/3/1
+");
+    }
+
+    #[test]
+    fn it_should_write_floats_with_the_chosen_precision() {
+        let file = "v 0.123456 1 -2.5\nf 1 1 1\n";
+        let obj = obj::parse(file.as_bytes(), &LoadOptions::default()).expect("This should work");
+
+        let written = write(&obj, &WriteOptions { precision: Some(4) });
+
+        assert_eq!(written, "v 0.1235 1.0000 -2.5000\nf 1 1 1\n");
+    }
+
+    #[test]
+    fn it_should_round_trip_every_resource() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../scop/src/resources");
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(directory).expect("This should work") {
+            let path = entry.expect("This should work").path();
+            match path.is_dir() {
+                true => paths.extend(
+                    std::fs::read_dir(&path)
+                        .expect("This should work")
+                        .map(|entry| entry.expect("This should work").path()),
+                ),
+                false => paths.push(path),
+            }
+        }
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "obj"));
+        assert!(paths.len() > 20);
+
+        // these resources are invalid on purpose
+        let invalid = [
+            "empty.obj",
+            "missing_faces.obj",
+            "missing_vertices.obj",
+            "missing_all_except_essential.obj",
+        ];
+
+        for path in paths {
+            let name = path.display();
+            let result = obj::load(&path.to_string_lossy());
+            if path
+                .file_name()
+                .is_some_and(|file_name| invalid.iter().any(|invalid| file_name == *invalid))
+            {
+                assert!(result.is_err(), "{name} should not load");
+                continue;
+            }
+            let first = result.unwrap_or_else(|error| panic!("{name}: {error}"));
+
+            let written = write(&first, &WriteOptions::default());
+            let second = obj::parse(written.as_bytes(), &LoadOptions::default())
+                .unwrap_or_else(|error| panic!("{name}: {error}"));
+
+            assert_eq!(write(&second, &WriteOptions::default()), written, "{name}");
+            assert_eq!(second.vertices, first.vertices, "{name}");
+            assert_eq!(second.vertices_texture, first.vertices_texture, "{name}");
+            assert_eq!(second.vertices_normal, first.vertices_normal, "{name}");
+            assert_eq!(second.mtls_identifiers, first.mtls_identifiers, "{name}");
+            assert!(triangles(&second) == triangles(&first), "{name}");
+            assert_eq!(second.points, first.points, "{name}");
+            assert_eq!(second.lines, first.lines, "{name}");
+            assert!(
+                triangle_grouping(&second) == triangle_grouping(&first),
+                "{name}"
+            );
+
+            for library in &first.mtls {
+                let mut written = Vec::new();
+                mtl::write(library, &mut written, &WriteOptions::default())
+                    .expect("This should work");
+                let result = mtl::parse(std::str::from_utf8(&written).expect("This should work"))
+                    .unwrap_or_else(|error| panic!("{name}: {error}"));
+
+                assert_eq!(&result, library, "{name}");
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+///
+/// How the Wavefront writers format their output
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WriteOptions {
+    ///
+    /// Amount of decimals written for every float, `None` writes the shortest text that reads
+    /// back as the exact same float
+    ///
+    pub precision: Option<usize>,
+}
+
+impl WriteOptions {
+    pub fn float(&self, value: f32) -> Float {
+        Float {
+            value,
+            precision: self.precision,
+        }
+    }
+}
+
+///
+/// A float formatted with the precision of `WriteOptions`
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Float {
+    value: f32,
+    precision: Option<usize>,
+}
+
+impl std::fmt::Display for Float {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.precision {
+            Some(precision) => write!(f, "{:.*}", precision, self.value),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wavefront::obj::parse_float;
+
+    #[test]
+    fn it_should_format_floats_with_the_chosen_precision() {
+        let exact = WriteOptions::default();
+        let rounded = WriteOptions { precision: Some(3) };
+
+        assert_eq!(exact.float(1.0).to_string(), "1");
+        assert_eq!(exact.float(-0.1).to_string(), "-0.1");
+        assert_eq!(rounded.float(1.0).to_string(), "1.000");
+        assert_eq!(rounded.float(-0.12345).to_string(), "-0.123");
+
+        for value in [0.1, 1.0 / 3.0, 1e-7, 123456.79, f32::MAX, f32::MIN_POSITIVE] {
+            let text = exact.float(value).to_string();
+            assert_eq!(parse_float(&text), Ok(value), "{text}");
+        }
+    }
+}
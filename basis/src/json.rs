@@ -0,0 +1,480 @@
+//!
+//! JSON documents (RFC 8259), as used by glTF files.
+//!
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnexpectedEnd(String),
+    InvalidToken(usize, String),
+    InvalidNumber(usize, String),
+    InvalidString(usize, String),
+}
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd(message) => {
+                write!(f, "Unexpected end of JSON: {}", message)
+            }
+            ParseError::InvalidToken(line, message) => {
+                write!(f, "Invalid JSON token at line {}: {}", line, message)
+            }
+            ParseError::InvalidNumber(line, message) => {
+                write!(f, "Invalid JSON number at line {}: {}", line, message)
+            }
+            ParseError::InvalidString(line, message) => {
+                write!(f, "Invalid JSON string at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    ///
+    /// When a key is repeated the last value wins
+    ///
+    Object(HashMap<String, Value>),
+}
+
+impl Value {
+    ///
+    /// Value of a key, `None` when the key is missing or when this is not an object
+    ///
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(object) => object.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        self.as_f64().map(|value| value as f32)
+    }
+
+    ///
+    /// The number when it is a non negative integer, such as an index or a count
+    ///
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|value| *value >= 0.0 && value.fract() == 0.0 && *value <= usize::MAX as f64)
+            .map(|value| value as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&HashMap<String, Value>> {
+        match self {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Deepest nesting of arrays and objects accepted, so hostile files cannot overflow the stack
+///
+const MAX_DEPTH: usize = 256;
+
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        position: 0,
+    };
+
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.position < parser.bytes.len() {
+        return Err(parser.invalid_token("Unexpected data after the document"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    ///
+    /// Line of the current position, only counted when an error is reported
+    ///
+    fn line(&self) -> usize {
+        self.bytes[..self.position.min(self.bytes.len())]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count()
+            + 1
+    }
+
+    fn invalid_token(&self, message: &str) -> ParseError {
+        ParseError::InvalidToken(self.line(), message.to_string())
+    }
+
+    fn invalid_string(&self, message: &str) -> ParseError {
+        ParseError::InvalidString(self.line(), message.to_string())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self, expected: &str) -> Result<u8, ParseError> {
+        self.skip_whitespace();
+        self.bytes
+            .get(self.position)
+            .copied()
+            .ok_or_else(|| ParseError::UnexpectedEnd(format!("Expected {}", expected)))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str, value: Value) -> Result<Value, ParseError> {
+        if !self.bytes[self.position..].starts_with(keyword.as_bytes()) {
+            return Err(self.invalid_token("Expected a value"));
+        }
+        self.position += keyword.len();
+        Ok(value)
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Value, ParseError> {
+        match self.peek("a value")? {
+            b'{' => self.parse_object(depth + 1),
+            b'[' => self.parse_array(depth + 1),
+            b'"' => Ok(Value::String(self.parse_string()?)),
+            b't' => self.expect_keyword("true", Value::Bool(true)),
+            b'f' => self.expect_keyword("false", Value::Bool(false)),
+            b'n' => self.expect_keyword("null", Value::Null),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            _ => Err(self.invalid_token("Expected a value")),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Value, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.invalid_token("Too many nested values"));
+        }
+        // skips the '{'
+        self.position += 1;
+        let mut object = HashMap::new();
+
+        if self.peek("a key or '}'")? == b'}' {
+            self.position += 1;
+            return Ok(Value::Object(object));
+        }
+
+        loop {
+            if self.peek("a key")? != b'"' {
+                return Err(self.invalid_token("Expected a key"));
+            }
+            let key = self.parse_string()?;
+
+            if self.peek("':'")? != b':' {
+                return Err(self.invalid_token("Expected ':' after the key"));
+            }
+            self.position += 1;
+            let value = self.parse_value(depth)?;
+            object.insert(key, value);
+
+            match self.peek("',' or '}'")? {
+                b',' => self.position += 1,
+                b'}' => {
+                    self.position += 1;
+                    return Ok(Value::Object(object));
+                }
+                _ => return Err(self.invalid_token("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Value, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.invalid_token("Too many nested values"));
+        }
+        // skips the '['
+        self.position += 1;
+        let mut values = Vec::new();
+
+        if self.peek("a value or ']'")? == b']' {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value(depth)?);
+
+            match self.peek("',' or ']'")? {
+                b',' => self.position += 1,
+                b']' => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.invalid_token("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.position;
+        let digits = |parser: &mut Parser| {
+            let start = parser.position;
+            while let Some(b'0'..=b'9') = parser.bytes.get(parser.position) {
+                parser.position += 1;
+            }
+            parser.position - start
+        };
+
+        if self.bytes.get(self.position) == Some(&b'-') {
+            self.position += 1;
+        }
+        // leading zeros are not allowed
+        let integer_start = self.position;
+        let integer = digits(self);
+        let valid_integer = integer == 1 || (integer > 1 && self.bytes[integer_start] != b'0');
+
+        let mut valid_fraction = true;
+        if self.bytes.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            valid_fraction = digits(self) > 0;
+        }
+
+        let mut valid_exponent = true;
+        if let Some(b'e' | b'E') = self.bytes.get(self.position) {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.bytes.get(self.position) {
+                self.position += 1;
+            }
+            valid_exponent = digits(self) > 0;
+        }
+
+        // the grammar only allows ASCII characters, the text is valid UTF-8
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or_default();
+        if !valid_integer || !valid_fraction || !valid_exponent {
+            return Err(ParseError::InvalidNumber(
+                self.line(),
+                format!("'{}' is not a number", text),
+            ));
+        }
+
+        text.parse::<f64>().map(Value::Number).map_err(|_| {
+            ParseError::InvalidNumber(self.line(), format!("'{}' is not a number", text))
+        })
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        // skips the opening '"'
+        self.position += 1;
+        let mut string = Vec::new();
+
+        loop {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(ParseError::UnexpectedEnd("Unterminated string".to_string()));
+            };
+            self.position += 1;
+
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.bytes.get(self.position).copied().ok_or_else(|| {
+                        ParseError::UnexpectedEnd("Unterminated string".to_string())
+                    })?;
+                    self.position += 1;
+
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.invalid_string("Unknown escape sequence")),
+                    };
+                    let mut buffer = [0; 4];
+                    string.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                0x00..=0x1f => return Err(self.invalid_string("Unescaped control character")),
+                _ => string.push(byte),
+            }
+        }
+
+        // the bytes come from a `&str` and escapes are encoded as UTF-8
+        String::from_utf8(string).map_err(|_| self.invalid_string("Invalid UTF-8"))
+    }
+
+    ///
+    /// Reads the 4 hexadecimal digits after `\u`, and the low surrogate that must follow a high
+    /// one
+    ///
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let first = self.parse_hex()?;
+
+        let code = match first {
+            0xd800..=0xdbff => {
+                if !self.bytes[self.position..].starts_with(b"\\u") {
+                    return Err(self.invalid_string("Unpaired surrogate"));
+                }
+                self.position += 2;
+                let second = self.parse_hex()?;
+                if !(0xdc00..=0xdfff).contains(&second) {
+                    return Err(self.invalid_string("Unpaired surrogate"));
+                }
+                0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+            }
+            0xdc00..=0xdfff => return Err(self.invalid_string("Unpaired surrogate")),
+            code => code,
+        };
+
+        char::from_u32(code).ok_or_else(|| self.invalid_string("Invalid unicode escape"))
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, ParseError> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .ok_or_else(|| self.invalid_string("Expected 4 hexadecimal digits"))?;
+        self.position += 4;
+
+        u32::from_str_radix(digits, 16).map_err(|_| self.invalid_string("Invalid unicode escape"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_every_kind_of_value() {
+        let text = r#"
+            {
+                "asset": { "version": "2.0", "generator": null },
+                "values": [1, -2.5, 3e2, 0.25E-1, true, false, [], {}],
+                "escaped": "a\"b\\c\/\n\t\u00e9\ud83d\ude00"
+            }
+        "#;
+
+        let result = parse(text).expect("This should work");
+
+        let asset = result.get("asset").expect("This should work");
+        assert_eq!(asset.get("version").and_then(Value::as_str), Some("2.0"));
+        assert!(asset.get("generator").is_some_and(Value::is_null));
+        assert_eq!(
+            result.get("values").and_then(Value::as_array),
+            Some(
+                &[
+                    Value::Number(1.0),
+                    Value::Number(-2.5),
+                    Value::Number(300.0),
+                    Value::Number(0.025),
+                    Value::Bool(true),
+                    Value::Bool(false),
+                    Value::Array(Vec::new()),
+                    Value::Object(HashMap::new()),
+                ][..]
+            )
+        );
+        assert_eq!(
+            result.get("escaped").and_then(Value::as_str),
+            Some("a\"b\\c/\n\té😀")
+        );
+        assert_eq!(Value::Number(3.0).as_usize(), Some(3));
+        assert_eq!(Value::Number(-3.0).as_usize(), None);
+        assert_eq!(Value::Number(3.5).as_usize(), None);
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_documents() {
+        let nested = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        let cases = [
+            (
+                "{\n\"a\": 1,\n}",
+                "Invalid JSON token at line 3: Expected a key",
+            ),
+            ("[1 2]", "Invalid JSON token at line 1: Expected ',' or ']'"),
+            (
+                "{\"a\" 1}",
+                "Invalid JSON token at line 1: Expected ':' after the key",
+            ),
+            (
+                "[01]",
+                "Invalid JSON number at line 1: '01' is not a number",
+            ),
+            (
+                "[1.]",
+                "Invalid JSON number at line 1: '1.' is not a number",
+            ),
+            ("[-]", "Invalid JSON number at line 1: '-' is not a number"),
+            (
+                "\"\\x\"",
+                "Invalid JSON string at line 1: Unknown escape sequence",
+            ),
+            (
+                "\"\\ud83d\"",
+                "Invalid JSON string at line 1: Unpaired surrogate",
+            ),
+            (
+                "\"a\nb\"",
+                "Invalid JSON string at line 2: Unescaped control character",
+            ),
+            ("tru", "Invalid JSON token at line 1: Expected a value"),
+            (
+                "{} {}",
+                "Invalid JSON token at line 1: Unexpected data after the document",
+            ),
+            ("[1,", "Unexpected end of JSON: Expected a value"),
+            ("\"abc", "Unexpected end of JSON: Unterminated string"),
+            (
+                &nested,
+                "Invalid JSON token at line 1: Too many nested values",
+            ),
+        ];
+
+        for (text, message) in cases {
+            let result = parse(text);
+            assert_eq!(result.unwrap_err().to_string(), message, "{text}");
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod graphics;
+pub mod json;
 pub mod math;
 
 pub fn add(left: u64, right: u64) -> u64 {
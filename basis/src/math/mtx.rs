@@ -92,6 +92,19 @@ impl ops::Mul<Mat4> for Mat4 {
     }
 }
 
+impl ops::Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, rhs: Vec4) -> Vec4 {
+        Vec4::new(
+            self.c0.x * rhs.x + self.c1.x * rhs.y + self.c2.x * rhs.z + self.c3.x * rhs.w,
+            self.c0.y * rhs.x + self.c1.y * rhs.y + self.c2.y * rhs.z + self.c3.y * rhs.w,
+            self.c0.z * rhs.x + self.c1.z * rhs.y + self.c2.z * rhs.z + self.c3.z * rhs.w,
+            self.c0.w * rhs.x + self.c1.w * rhs.y + self.c2.w * rhs.z + self.c3.w * rhs.w,
+        )
+    }
+}
+
 impl Mat4 {
     pub fn new(vec: Vec4) -> Self {
         Self {
@@ -290,6 +303,148 @@ impl Mat4 {
     pub fn as_f32_ptr(&self) -> *const f32 {
         ptr::from_ref(&self.c0.x) as *const f32
     }
+
+    pub fn from_columns(columns: [[f32; 4]; 4]) -> Self {
+        let [c0, c1, c2, c3] = columns.map(Vec4::from);
+        Self { c0, c1, c2, c3 }
+    }
+
+    pub fn to_columns(&self) -> [[f32; 4]; 4] {
+        [self.c0, self.c1, self.c2, self.c3].map(<[f32; 4]>::from)
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            c0: Vec4::new(self.c0.x, self.c1.x, self.c2.x, self.c3.x),
+            c1: Vec4::new(self.c0.y, self.c1.y, self.c2.y, self.c3.y),
+            c2: Vec4::new(self.c0.z, self.c1.z, self.c2.z, self.c3.z),
+            c3: Vec4::new(self.c0.w, self.c1.w, self.c2.w, self.c3.w),
+        }
+    }
+
+    ///
+    /// Determinants of the 2x2 sub-matrices of the first two columns, then of the last two, the
+    /// Laplace expansion of the determinant and the inverse are built from them
+    ///
+    fn sub_determinants(&self) -> ([f32; 6], [f32; 6]) {
+        let [a0, a1, a2, a3] = self.to_columns();
+
+        let s = [
+            a0[0] * a1[1] - a1[0] * a0[1],
+            a0[0] * a1[2] - a1[0] * a0[2],
+            a0[0] * a1[3] - a1[0] * a0[3],
+            a0[1] * a1[2] - a1[1] * a0[2],
+            a0[1] * a1[3] - a1[1] * a0[3],
+            a0[2] * a1[3] - a1[2] * a0[3],
+        ];
+        let c = [
+            a2[0] * a3[1] - a3[0] * a2[1],
+            a2[0] * a3[2] - a3[0] * a2[2],
+            a2[0] * a3[3] - a3[0] * a2[3],
+            a2[1] * a3[2] - a3[1] * a2[2],
+            a2[1] * a3[3] - a3[1] * a2[3],
+            a2[2] * a3[3] - a3[2] * a2[3],
+        ];
+
+        (s, c)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.sub_determinants();
+
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    ///
+    /// Inverse of the matrix, `None` when it is singular, such as a projection onto a plane
+    ///
+    pub fn inverse(&self) -> Option<Self> {
+        let [a0, a1, a2, a3] = self.to_columns();
+        let (s, c) = self.sub_determinants();
+
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let d = 1.0 / determinant;
+
+        #[rustfmt::skip]
+        let inverse = Mat4::from_columns([
+            [
+                ( a1[1] * c[5] - a1[2] * c[4] + a1[3] * c[3]) * d,
+                (-a0[1] * c[5] + a0[2] * c[4] - a0[3] * c[3]) * d,
+                ( a3[1] * s[5] - a3[2] * s[4] + a3[3] * s[3]) * d,
+                (-a2[1] * s[5] + a2[2] * s[4] - a2[3] * s[3]) * d,
+            ],
+            [
+                (-a1[0] * c[5] + a1[2] * c[2] - a1[3] * c[1]) * d,
+                ( a0[0] * c[5] - a0[2] * c[2] + a0[3] * c[1]) * d,
+                (-a3[0] * s[5] + a3[2] * s[2] - a3[3] * s[1]) * d,
+                ( a2[0] * s[5] - a2[2] * s[2] + a2[3] * s[1]) * d,
+            ],
+            [
+                ( a1[0] * c[4] - a1[1] * c[2] + a1[3] * c[0]) * d,
+                (-a0[0] * c[4] + a0[1] * c[2] - a0[3] * c[0]) * d,
+                ( a3[0] * s[4] - a3[1] * s[2] + a3[3] * s[0]) * d,
+                (-a2[0] * s[4] + a2[1] * s[2] - a2[3] * s[0]) * d,
+            ],
+            [
+                (-a1[0] * c[3] + a1[1] * c[1] - a1[2] * c[0]) * d,
+                ( a0[0] * c[3] - a0[1] * c[1] + a0[2] * c[0]) * d,
+                (-a3[0] * s[3] + a3[1] * s[1] - a3[2] * s[0]) * d,
+                ( a2[0] * s[3] - a2[1] * s[1] + a2[2] * s[0]) * d,
+            ],
+        ]);
+
+        inverse
+            .to_columns()
+            .iter()
+            .flatten()
+            .all(|n| n.is_finite())
+            .then_some(inverse)
+    }
+
+    ///
+    /// Moves a point, the result is divided by w so it also works with projections
+    ///
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let result = *self * point.extend(1.0);
+
+        if result.w == 0.0 || result.w == 1.0 {
+            return result.truncate();
+        }
+        result.perspective_divide()
+    }
+
+    ///
+    /// Moves a direction, the translation does not apply to it
+    ///
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        (*self * vector.extend(0.0)).truncate()
+    }
+
+    ///
+    /// Matrix that keeps the normals perpendicular to the surfaces moved by the matrix, even with
+    /// a non uniform scale.
+    ///
+    /// It is the inverse transpose of the upper 3x3 scaled by the absolute value of its
+    /// determinant, so it exists for singular matrices too. The normals still have to be
+    /// normalized.
+    ///
+    pub fn normal_matrix(&self) -> Mat3 {
+        let m = Mat3::from(*self);
+        let cofactors = Mat3 {
+            c0: m.c1.cross(m.c2),
+            c1: m.c2.cross(m.c0),
+            c2: m.c0.cross(m.c1),
+        };
+
+        // a mirroring transform flips the cofactors
+        if m.determinant() < 0.0 {
+            return cofactors * -1.0;
+        }
+        cofactors
+    }
 }
 
 impl std::fmt::Display for Mat4 {
@@ -310,6 +465,127 @@ impl std::fmt::Display for Mat4 {
     }
 }
 
+///
+/// 3x3 matrix, such as the rotation and scale of a `Mat4` used to move the normals
+///
+#[repr(C)]
+#[derive(Clone, Default, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Mat3 {
+    pub c0: Vec3, // column 1
+    pub c1: Vec3, // column 2
+    pub c2: Vec3, // column 3
+}
+
+impl ops::Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Self {
+        Self {
+            c0: self * rhs.c0,
+            c1: self * rhs.c1,
+            c2: self * rhs.c2,
+        }
+    }
+}
+
+impl ops::Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        self.c0 * rhs.x + self.c1 * rhs.y + self.c2 * rhs.z
+    }
+}
+
+impl ops::Mul<f32> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            c0: self.c0 * rhs,
+            c1: self.c1 * rhs,
+            c2: self.c2 * rhs,
+        }
+    }
+}
+
+///
+/// Upper 3x3 of the matrix, without the translation and the projection
+///
+impl From<Mat4> for Mat3 {
+    fn from(mat: Mat4) -> Self {
+        Self {
+            c0: mat.c0.truncate(),
+            c1: mat.c1.truncate(),
+            c2: mat.c2.truncate(),
+        }
+    }
+}
+
+impl Mat3 {
+    pub fn new(vec: Vec3) -> Self {
+        Self {
+            c0: Vec3::new(vec.x, 0.0, 0.0),
+            c1: Vec3::new(0.0, vec.y, 0.0),
+            c2: Vec3::new(0.0, 0.0, vec.z),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Mat3::new(Vec3::splat(1.0))
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            c0: Vec3::new(self.c0.x, self.c1.x, self.c2.x),
+            c1: Vec3::new(self.c0.y, self.c1.y, self.c2.y),
+            c2: Vec3::new(self.c0.z, self.c1.z, self.c2.z),
+        }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.c0.dot(self.c1.cross(self.c2))
+    }
+
+    ///
+    /// Inverse of the matrix, `None` when it is singular
+    ///
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        // the rows of the inverse are perpendicular to two of the columns
+        let rows = Mat3 {
+            c0: self.c1.cross(self.c2),
+            c1: self.c2.cross(self.c0),
+            c2: self.c0.cross(self.c1),
+        };
+        let inverse = rows.transpose() * (1.0 / determinant);
+
+        [inverse.c0, inverse.c1, inverse.c2]
+            .iter()
+            .all(|column| column.sum().is_finite())
+            .then_some(inverse)
+    }
+
+    ///
+    /// Extends the matrix with no translation
+    ///
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4 {
+            c0: self.c0.extend(0.0),
+            c1: self.c1.extend(0.0),
+            c2: self.c2.extend(0.0),
+            c3: Vec4::new(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn as_f32_ptr(&self) -> *const f32 {
+        ptr::from_ref(&self.c0.x)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -646,4 +922,174 @@ mod tests {
         assert_eq!(first.c3.z, 4.0);
         assert_eq!(first.c3.w, 5.0);
     }
+
+    fn assert_mat4_near(a: Mat4, b: Mat4) {
+        for (a, b) in a
+            .to_columns()
+            .iter()
+            .flatten()
+            .zip(b.to_columns().iter().flatten())
+        {
+            assert!((a - b).abs() < 1e-5, "\n{a} != {b}");
+        }
+    }
+
+    ///
+    /// A rotation, a non uniform scale and a translation
+    ///
+    fn transform() -> Mat4 {
+        let mut transform = Mat4::identity();
+        transform.scale(Vec3::new(2.0, 3.0, 4.0));
+        transform.rotate_euler(std::f32::consts::FRAC_PI_3, Vec3::new(0.0, 0.0, 1.0));
+        transform.translate(Vec3::new(1.0, -2.0, 5.0));
+        transform
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_transpose_mtx4() {
+        let matrix = Mat4::from_columns([
+            [1.0, 2.0, 3.0, 4.0],
+            [5.0, 6.0, 7.0, 8.0],
+            [9.0, 10.0, 11.0, 12.0],
+            [13.0, 14.0, 15.0, 16.0],
+        ]);
+
+        assert_eq!(matrix.transpose().to_columns(), [
+            [1.0, 5.0, 9.0, 13.0],
+            [2.0, 6.0, 10.0, 14.0],
+            [3.0, 7.0, 11.0, 15.0],
+            [4.0, 8.0, 12.0, 16.0],
+        ]);
+        assert_eq!(matrix.transpose().transpose(), matrix);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_compute_the_determinant_of_mtx4() {
+        let matrix = Mat4::from_columns([
+            [1.0, 2.0, 0.0, 2.0],
+            [2.0, 4.0, 1.0, 7.0],
+            [3.0, 2.0, 5.0, 2.0],
+            [8.0, 6.0, 2.0, 5.0],
+        ]);
+        let singular = Mat4::from_columns([
+            [1.0, 2.0, 3.0, 4.0],
+            [5.0, 6.0, 7.0, 8.0],
+            [9.0, 10.0, 11.0, 12.0],
+            [13.0, 14.0, 15.0, 16.0],
+        ]);
+
+        assert_eq!(Mat4::identity().determinant(), 1.0);
+        assert_eq!(Mat4::new(Vec4::new(2.0, 3.0, 4.0, 1.0)).determinant(), 24.0);
+        assert_eq!(matrix.determinant(), 122.0);
+        assert_eq!(matrix.transpose().determinant(), 122.0);
+        assert_eq!(singular.determinant(), 0.0);
+        assert_eq!(singular.inverse(), None);
+        assert!((transform().determinant() - 24.0).abs() < 1e-4);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_should_invert_mtx4() {
+        let matrix = Mat4::from_columns([
+            [4.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 0.0],
+            [0.0, 1.0, 2.0, 0.0],
+            [1.0, 0.0, 0.0, 1.0],
+        ]);
+
+        assert_eq!(matrix.inverse().expect("This should work").to_columns(), [
+            [0.25, 0.0, 0.0, 0.0],
+            [0.0, -1.0, 1.0, 0.0],
+            [0.0, 0.5, 0.0, 0.0],
+            [-0.25, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(Mat4::identity().inverse(), Some(Mat4::identity()));
+
+        let transform = transform();
+        let inverse = transform.inverse().expect("This should work");
+        assert_mat4_near(transform * inverse, Mat4::identity());
+        assert_mat4_near(inverse * transform, Mat4::identity());
+        assert_mat4_near(inverse.inverse().expect("This should work"), transform);
+    }
+
+    #[test]
+    fn it_should_transform_points_and_vectors() {
+        let mut translation = Mat4::identity();
+        translation.translate(Vec3::new(1.0, 2.0, 3.0));
+        let mut scale = Mat4::identity();
+        scale.scale(Vec3::new(2.0, 2.0, 2.0));
+        let point = Vec3::new(1.0, 1.0, 1.0);
+
+        assert_eq!(translation.transform_point(point), Vec3::new(2.0, 3.0, 4.0));
+        assert_eq!(translation.transform_vector(point), point);
+        assert_eq!(
+            (translation * scale).transform_point(point),
+            Vec3::new(3.0, 4.0, 5.0)
+        );
+        assert_eq!(
+            (translation * scale).transform_vector(point),
+            Vec3::splat(2.0)
+        );
+        assert_eq!(
+            translation * point.extend(1.0),
+            Vec4::new(2.0, 3.0, 4.0, 1.0)
+        );
+
+        // unprojects the center of the near plane back to the camera space
+        let projection = Mat4::symmetric_perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        let near = projection.transform_point(Vec3::new(0.0, 0.0, -0.1));
+        let unprojected = projection
+            .inverse()
+            .expect("This should work")
+            .transform_point(near);
+
+        assert!((near.z + 1.0).abs() < 1e-5);
+        assert!(unprojected.approx_eq(Vec3::new(0.0, 0.0, -0.1), 1e-5));
+    }
+
+    #[test]
+    fn it_should_invert_and_transpose_mtx3() {
+        let matrix = Mat3 {
+            c0: Vec3::new(2.0, 0.0, 1.0),
+            c1: Vec3::new(1.0, 1.0, 0.0),
+            c2: Vec3::new(0.0, 3.0, 1.0),
+        };
+        let inverse = matrix.inverse().expect("This should work");
+
+        assert_eq!(matrix.determinant(), 5.0);
+        assert_eq!(matrix.transpose().c0, Vec3::new(2.0, 1.0, 0.0));
+        assert_eq!(matrix.transpose().transpose(), matrix);
+        assert_eq!(Mat3::identity().inverse(), Some(Mat3::identity()));
+        assert_eq!(Mat3::new(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert_mat4_near((matrix * inverse).to_mat4(), Mat4::identity());
+        assert_eq!(matrix * Vec3::new(1.0, 1.0, 1.0), Vec3::new(3.0, 4.0, 2.0));
+        assert_eq!(
+            Mat3::from(transform()).to_mat4().c3,
+            Vec4::new(0.0, 0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn it_should_keep_the_normals_perpendicular() {
+        let transform = transform();
+        let normal_matrix = transform.normal_matrix();
+        let expected = Mat3::from(transform.inverse().expect("This should work")).transpose();
+
+        // a plane tilted by the non uniform scale, its normal has to follow
+        let normal = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let tangent = Vec3::new(1.0, -1.0, 0.0);
+        let moved = (normal_matrix * normal).normalize();
+
+        assert!(moved.approx_eq((expected * normal).normalize(), 1e-5));
+        assert!(moved.dot(transform.transform_vector(tangent)).abs() < 1e-5);
+
+        let mut mirror = Mat4::identity();
+        mirror.scale(Vec3::new(-1.0, 1.0, 1.0));
+        assert_eq!(
+            mirror.normal_matrix() * Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        );
+    }
 }
@@ -1,23 +1,252 @@
 use std::ops;
 
-pub trait VectorFunctions<T>: ops::Add<T> + ops::Sub<T> + ops::Mul<T> {
-    fn negate(&self) -> Self;
+pub trait VectorFunctions:
+    Copy
+    + ops::Add<Output = Self>
+    + ops::Sub<Output = Self>
+    + ops::Mul<Output = Self>
+    + ops::Mul<f32, Output = Self>
+    + ops::Div<f32, Output = Self>
+    + ops::Neg<Output = Self>
+{
+    ///
+    /// Applies `f` to every component
+    ///
+    fn map(&self, f: impl Fn(f32) -> f32) -> Self;
 
     ///
-    /// Transforms a vector into a unit vector (vector with length 1)
+    /// Applies `f` to every pair of components of the two vectors
     ///
-    fn normalize(&self) -> Self;
-    fn scale(&self, n: f32) -> Self;
+    fn zip_with(&self, v: Self, f: impl Fn(f32, f32) -> f32) -> Self;
 
     ///
-    /// Cross product of two vectors
+    /// Sum of the components
     ///
-    /// The cross product of two vectors is a vector that is perpendicular to the plane formed by
-    /// the two vectors.
+    fn sum(&self) -> f32;
+
     ///
-    /// If the vectors are parallel, the cross product is the zero vector.
+    /// Largest component
+    ///
+    fn max_element(&self) -> f32;
+
+    fn negate(&self) -> Self {
+        self.map(|n| -n)
+    }
+
+    ///
+    /// Transforms a vector into a unit vector (vector with length 1), the zero vector is
+    /// returned as it is
+    ///
+    fn normalize(&self) -> Self {
+        self.try_normalize().unwrap_or(*self)
+    }
+
+    ///
+    /// Unit vector with the same direction, `None` for the zero vector and when the components
+    /// are not finite or too small to divide by the length
+    ///
+    fn try_normalize(&self) -> Option<Self> {
+        let length = self.length();
+        let normalized = *self / length;
+
+        (length > 0.0 && normalized.sum().is_finite()).then_some(normalized)
+    }
+
+    fn scale(&self, n: f32) -> Self {
+        *self * n
+    }
+
+    ///
+    /// Dot product of two vectors
+    ///
+    /// It is the product of their lengths and of the cosine of the angle between them, so it
+    /// is zero for perpendicular vectors.
+    ///
+    fn dot(&self, v: Self) -> f32 {
+        self.zip_with(v, |a, b| a * b).sum()
+    }
+
+    fn length_squared(&self) -> f32 {
+        self.dot(*self)
+    }
+
+    fn length(&self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    fn distance(&self, v: Self) -> f32 {
+        (*self - v).length()
+    }
+
+    ///
+    /// Linear interpolation, `self` when `t` is 0.0 and `v` when `t` is 1.0
+    ///
+    fn lerp(&self, v: Self, t: f32) -> Self {
+        *self + (v - *self) * t
+    }
+
+    fn min(&self, v: Self) -> Self {
+        self.zip_with(v, f32::min)
+    }
+
+    fn max(&self, v: Self) -> Self {
+        self.zip_with(v, f32::max)
+    }
+
+    fn abs(&self) -> Self {
+        self.map(f32::abs)
+    }
+
+    ///
+    /// Clamps every component between the ones of `min` and `max`
+    ///
+    fn clamp(&self, min: Self, max: Self) -> Self {
+        VectorFunctions::max(self, min).min(max)
+    }
+
+    ///
+    /// Whether every component differs by at most `epsilon`, a NaN component never does
+    ///
+    fn approx_eq(&self, v: Self, epsilon: f32) -> bool {
+        let outside = self.zip_with(v, |a, b| if (a - b).abs() <= epsilon { 0.0 } else { 1.0 });
+        outside.sum() == 0.0
+    }
+
+    ///
+    /// Direction of a ray bouncing off a surface, `normal` should be a unit vector
+    ///
+    fn reflect(&self, normal: Self) -> Self {
+        *self - normal * (2.0 * self.dot(normal))
+    }
+
+    ///
+    /// Direction of a ray going through a surface, `self` and `normal` should be unit vectors
+    /// and `eta` is the ratio of the indices of refraction.
     ///
-    fn cross(&self, v: Self) -> Self;
+    /// The zero vector is returned on a total internal reflection.
+    ///
+    fn refract(&self, normal: Self, eta: f32) -> Self {
+        let cos = self.dot(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos * cos);
+
+        if k < 0.0 {
+            return self.map(|_| 0.0);
+        }
+        *self * eta - normal * (eta * cos + k.sqrt())
+    }
+}
+
+///
+/// Component-wise operators, and the operators with a scalar
+///
+macro_rules! vector_operators {
+    ($vector:ident, $($component:ident),+) => {
+        impl ops::Add<$vector> for $vector {
+            type Output = $vector;
+
+            fn add(self, rhs: $vector) -> Self::Output {
+                Self { $($component: self.$component + rhs.$component),+ }
+            }
+        }
+
+        impl ops::Sub<$vector> for $vector {
+            type Output = $vector;
+
+            fn sub(self, rhs: $vector) -> Self::Output {
+                Self { $($component: self.$component - rhs.$component),+ }
+            }
+        }
+
+        impl ops::Mul<$vector> for $vector {
+            type Output = $vector;
+
+            fn mul(self, rhs: $vector) -> Self::Output {
+                Self { $($component: self.$component * rhs.$component),+ }
+            }
+        }
+
+        impl ops::Div<$vector> for $vector {
+            type Output = $vector;
+
+            fn div(self, rhs: $vector) -> Self::Output {
+                Self { $($component: self.$component / rhs.$component),+ }
+            }
+        }
+
+        impl ops::Mul<f32> for $vector {
+            type Output = $vector;
+
+            fn mul(self, rhs: f32) -> Self::Output {
+                Self { $($component: self.$component * rhs),+ }
+            }
+        }
+
+        impl ops::Mul<$vector> for f32 {
+            type Output = $vector;
+
+            fn mul(self, rhs: $vector) -> Self::Output {
+                rhs * self
+            }
+        }
+
+        impl ops::Div<f32> for $vector {
+            type Output = $vector;
+
+            fn div(self, rhs: f32) -> Self::Output {
+                Self { $($component: self.$component / rhs),+ }
+            }
+        }
+
+        impl ops::Neg for $vector {
+            type Output = $vector;
+
+            fn neg(self) -> Self::Output {
+                Self { $($component: -self.$component),+ }
+            }
+        }
+
+        impl ops::AddAssign<$vector> for $vector {
+            fn add_assign(&mut self, rhs: $vector) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl ops::SubAssign<$vector> for $vector {
+            fn sub_assign(&mut self, rhs: $vector) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl ops::MulAssign<f32> for $vector {
+            fn mul_assign(&mut self, rhs: f32) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl ops::DivAssign<f32> for $vector {
+            fn div_assign(&mut self, rhs: f32) {
+                *self = *self / rhs;
+            }
+        }
+
+        impl VectorFunctions for $vector {
+            fn map(&self, f: impl Fn(f32) -> f32) -> Self {
+                Self { $($component: f(self.$component)),+ }
+            }
+
+            fn zip_with(&self, v: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+                Self { $($component: f(self.$component, v.$component)),+ }
+            }
+
+            fn sum(&self) -> f32 {
+                0.0 $(+ self.$component)+
+            }
+
+            fn max_element(&self) -> f32 {
+                f32::NEG_INFINITY $(.max(self.$component))+
+            }
+        }
+    };
 }
 
 #[repr(C)]
@@ -41,86 +270,50 @@ impl Vec4 {
             w: n,
         }
     }
-}
-
-impl ops::Add<Vec4> for Vec4 {
-    type Output = Vec4;
 
-    fn add(self, rhs: Vec4) -> Self::Output {
+    ///
+    /// Cross product of the x, y and z components, the w component is zero
+    ///
+    /// The cross product of two vectors is a vector that is perpendicular to the plane formed by
+    /// the two vectors.
+    ///
+    /// If the vectors are parallel, the cross product is the zero vector.
+    ///
+    pub fn cross(&self, v: Vec4) -> Self {
         Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-            w: self.w + rhs.w,
+            x: (self.y * v.z) - (self.z * v.y),
+            y: (self.z * v.x) - (self.x * v.z),
+            z: (self.x * v.y) - (self.y * v.x),
+            w: 0.0,
         }
     }
-}
 
-impl ops::Sub<Vec4> for Vec4 {
-    type Output = Vec4;
-
-    fn sub(self, rhs: Vec4) -> Self::Output {
-        Self {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
-            w: self.w - rhs.w,
-        }
+    ///
+    /// Drops the w component
+    ///
+    pub fn truncate(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
     }
-}
-
-impl ops::Mul<Vec4> for Vec4 {
-    type Output = Vec4;
 
-    fn mul(self, rhs: Vec4) -> Self::Output {
-        Self {
-            x: self.x * rhs.x,
-            y: self.y * rhs.y,
-            z: self.z * rhs.z,
-            w: self.w * rhs.w,
-        }
+    ///
+    /// Divides x, y and z by w, the homogeneous coordinates of a point become cartesian ones
+    ///
+    pub fn perspective_divide(&self) -> Vec3 {
+        self.truncate() / self.w
     }
 }
 
-impl VectorFunctions<Vec4> for Vec4 {
-    fn negate(&self) -> Self {
-        Self {
-            x: -self.x,
-            y: -self.y,
-            z: -self.z,
-            w: -self.w,
-        }
-    }
-
-    fn normalize(&self) -> Self {
-        let length = f32::sqrt(
-            (self.x * self.x) + (self.y * self.y) + (self.z * self.z) + (self.w * self.w),
-        );
+vector_operators!(Vec4, x, y, z, w);
 
-        Self {
-            x: self.x / length,
-            y: self.y / length,
-            z: self.z / length,
-            w: self.w / length,
-        }
-    }
-
-    fn scale(&self, n: f32) -> Self {
-        Self {
-            x: self.x * n,
-            y: self.y * n,
-            z: self.z * n,
-            w: self.w * n,
-        }
+impl From<[f32; 4]> for Vec4 {
+    fn from([x, y, z, w]: [f32; 4]) -> Self {
+        Self { x, y, z, w }
     }
+}
 
-    fn cross(&self, v: Vec4) -> Self {
-        Self {
-            x: (self.y * v.z) - (self.z * v.y),
-            y: (self.z * v.x) - (self.x * v.z),
-            z: (self.x * v.y) - (self.y * v.x),
-            w: 0.0,
-        }
+impl From<Vec4> for [f32; 4] {
+    fn from(v: Vec4) -> Self {
+        [v.x, v.y, v.z, v.w]
     }
 }
 
@@ -139,84 +332,104 @@ impl Vec3 {
     pub fn splat(n: f32) -> Self {
         Self { x: n, y: n, z: n }
     }
-}
 
-impl ops::Add<Vec3> for Vec3 {
-    type Output = Vec3;
-
-    fn add(self, rhs: Vec3) -> Self::Output {
+    ///
+    /// Cross product of two vectors
+    ///
+    /// The cross product of two vectors is a vector that is perpendicular to the plane formed by
+    /// the two vectors.
+    ///
+    /// If the vectors are parallel, the cross product is the zero vector.
+    ///
+    pub fn cross(&self, v: Vec3) -> Self {
         Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
+            x: (self.y * v.z) - (self.z * v.y),
+            y: (self.z * v.x) - (self.x * v.z),
+            z: (self.x * v.y) - (self.y * v.x),
         }
     }
-}
 
-impl ops::Sub<Vec3> for Vec3 {
-    type Output = Vec3;
+    ///
+    /// Adds a w component, 1.0 for a point and 0.0 for a direction
+    ///
+    pub fn extend(&self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
 
-    fn sub(self, rhs: Vec3) -> Self::Output {
-        Self {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
-        }
+    ///
+    /// Drops the z component
+    ///
+    pub fn truncate(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
     }
 }
 
-impl ops::Mul<Vec3> for Vec3 {
-    type Output = Vec3;
+vector_operators!(Vec3, x, y, z);
 
-    fn mul(self, rhs: Vec3) -> Self::Output {
-        Self {
-            x: self.x * rhs.x,
-            y: self.y * rhs.y,
-            z: self.z * rhs.z,
-        }
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self { x, y, z }
     }
 }
 
-impl VectorFunctions<Vec3> for Vec3 {
-    fn negate(&self) -> Self {
-        Self {
-            x: -self.x,
-            y: -self.y,
-            z: -self.z,
-        }
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+///
+/// Two components vector, such as the texture coordinates of a vertex
+///
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, PartialOrd)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+impl Vec2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
     }
 
-    fn normalize(&self) -> Self {
-        if self.x == 0.0 && self.y == 0.0 && self.z == 0.0 {
-            return *self;
-        }
-        let length = f32::sqrt((self.x * self.x) + (self.y * self.y) + (self.z * self.z));
+    pub fn splat(n: f32) -> Self {
+        Self { x: n, y: n }
+    }
 
-        if length == 0.0 {
-            return *self;
-        }
+    ///
+    /// Z component of the cross product of the two vectors extended with a zero z, it is
+    /// positive when `v` is counterclockwise from `self`
+    ///
+    pub fn cross(&self, v: Vec2) -> f32 {
+        (self.x * v.y) - (self.y * v.x)
+    }
 
+    ///
+    /// The vector rotated by 90 degrees counterclockwise
+    ///
+    pub fn perpendicular(&self) -> Self {
         Self {
-            x: self.x / length,
-            y: self.y / length,
-            z: self.z / length,
+            x: -self.y,
+            y: self.x,
         }
     }
 
-    fn scale(&self, n: f32) -> Self {
-        Self {
-            x: self.x * n,
-            y: self.y * n,
-            z: self.z * n,
-        }
+    pub fn extend(&self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
     }
+}
 
-    fn cross(&self, v: Vec3) -> Self {
-        Self {
-            x: (self.y * v.z) - (self.z * v.y),
-            y: (self.z * v.x) - (self.x * v.z),
-            z: (self.x * v.y) - (self.y * v.x),
-        }
+vector_operators!(Vec2, x, y);
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
     }
 }
 
@@ -233,4 +446,140 @@ mod tests {
         assert_eq!(result.z, 3.0);
         assert_eq!(result.w, 4.0);
     }
+
+    #[test]
+    fn it_should_apply_the_operators() {
+        let mut a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, 5.0, 6.0);
+
+        assert_eq!(a + b, Vec3::new(5.0, 7.0, 9.0));
+        assert_eq!(b - a, Vec3::splat(3.0));
+        assert_eq!(a * b, Vec3::new(4.0, 10.0, 18.0));
+        assert_eq!(b / a, Vec3::new(4.0, 2.5, 2.0));
+        assert_eq!(a * 2.0, Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(b / 2.0, Vec3::new(2.0, 2.5, 3.0));
+        assert_eq!(-a, a.negate());
+
+        a += b;
+        assert_eq!(a, Vec3::new(5.0, 7.0, 9.0));
+        a -= b;
+        a *= 3.0;
+        assert_eq!(a, Vec3::new(3.0, 6.0, 9.0));
+        a /= 3.0;
+        assert_eq!(a, Vec3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(Vec2::new(1.0, 2.0) + Vec2::splat(1.0), Vec2::new(2.0, 3.0));
+        assert_eq!(
+            -Vec4::new(1.0, -2.0, 3.0, -4.0),
+            Vec4::new(-1.0, 2.0, -3.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn it_should_measure_vectors() {
+        let a = Vec3::new(3.0, 4.0, 0.0);
+        let b = Vec3::new(0.0, 0.0, 12.0);
+
+        assert_eq!(a.dot(b), 0.0);
+        assert_eq!(a.dot(Vec3::new(1.0, 2.0, 3.0)), 11.0);
+        assert_eq!(a.length_squared(), 25.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.distance(b), 13.0);
+        assert_eq!(Vec2::new(6.0, 8.0).length(), 10.0);
+        assert_eq!(Vec4::new(1.0, 1.0, 1.0, 1.0).length(), 2.0);
+        assert_eq!(Vec4::new(1.0, 2.0, 3.0, 4.0).sum(), 10.0);
+        assert_eq!(Vec2::new(-1.0, -2.0).max_element(), -1.0);
+    }
+
+    #[test]
+    fn it_should_normalize_vectors() {
+        let a = Vec4::new(0.0, 3.0, 0.0, 4.0);
+
+        assert_eq!(a.normalize(), Vec4::new(0.0, 0.6, 0.0, 0.8));
+        assert_eq!(a.try_normalize(), Some(Vec4::new(0.0, 0.6, 0.0, 0.8)));
+        assert_eq!(Vec4::default().try_normalize(), None);
+        assert_eq!(Vec4::default().normalize(), Vec4::default());
+        assert_eq!(Vec3::default().normalize(), Vec3::default());
+        assert_eq!(Vec2::new(f32::INFINITY, 0.0).try_normalize(), None);
+        assert_eq!(Vec2::new(0.0, -2.0).normalize(), Vec2::new(0.0, -1.0));
+    }
+
+    #[test]
+    fn it_should_apply_component_wise_functions() {
+        let a = Vec3::new(-1.0, 5.0, 0.5);
+        let b = Vec3::new(2.0, -3.0, 0.5);
+
+        assert_eq!(a.min(b), Vec3::new(-1.0, -3.0, 0.5));
+        assert_eq!(a.max(b), Vec3::new(2.0, 5.0, 0.5));
+        assert_eq!(a.abs(), Vec3::new(1.0, 5.0, 0.5));
+        assert_eq!(
+            a.clamp(Vec3::splat(0.0), Vec3::splat(1.0)),
+            Vec3::new(0.0, 1.0, 0.5)
+        );
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec3::new(0.5, 1.0, 0.5));
+        assert_eq!(a.map(|n| n * 2.0), a * 2.0);
+    }
+
+    #[test]
+    fn it_should_compare_with_an_epsilon() {
+        let a = Vec2::new(1.0, 2.0);
+
+        assert!(a.approx_eq(Vec2::new(1.0005, 1.9995), 1e-3));
+        assert!(!a.approx_eq(Vec2::new(1.0, 2.01), 1e-3));
+        assert!(!a.approx_eq(Vec2::new(f32::NAN, 2.0), 1e-3));
+    }
+
+    #[test]
+    fn it_should_reflect_and_refract() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let incident = Vec3::new(1.0, -1.0, 0.0).normalize();
+
+        assert!(incident
+            .reflect(normal)
+            .approx_eq(Vec3::new(1.0, 1.0, 0.0).normalize(), 1e-6));
+        // same indices of refraction, the ray goes straight through
+        assert!(incident.refract(normal, 1.0).approx_eq(incident, 1e-6));
+
+        // from glass to air, sin(45°) * 1.5 is above 1
+        assert_eq!(incident.refract(normal, 1.5), Vec3::default());
+
+        // from air to glass, the ray bends toward the normal following Snell's law
+        let refracted = incident.refract(normal, 1.0 / 1.5);
+        let sin = refracted.cross(-normal).length();
+        assert!((sin - std::f32::consts::FRAC_1_SQRT_2 / 1.5).abs() < 1e-6);
+        assert!((refracted.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_should_convert_between_vectors() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+
+        assert_eq!(a.extend(1.0), Vec4::new(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(a.extend(1.0).truncate(), a);
+        assert_eq!(a.truncate(), Vec2::new(1.0, 2.0));
+        assert_eq!(Vec2::new(1.0, 2.0).extend(3.0), a);
+        assert_eq!(Vec4::new(2.0, 4.0, 6.0, 2.0).perspective_divide(), a);
+        assert_eq!(Vec3::from([1.0, 2.0, 3.0]), a);
+        assert_eq!(<[f32; 3]>::from(a), [1.0, 2.0, 3.0]);
+        assert_eq!(<[f32; 2]>::from(Vec2::from([4.0, 5.0])), [4.0, 5.0]);
+        assert_eq!(Vec4::from(<[f32; 4]>::from(a.extend(0.0))), a.extend(0.0));
+    }
+
+    #[test]
+    fn it_should_cross_vectors() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+
+        assert_eq!(x.cross(y), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(x.cross(x), Vec3::default());
+        assert_eq!(
+            x.extend(1.0).cross(y.extend(1.0)),
+            Vec4::new(0.0, 0.0, 1.0, 0.0)
+        );
+        assert_eq!(Vec2::new(1.0, 0.0).cross(Vec2::new(0.0, 1.0)), 1.0);
+        assert_eq!(Vec2::new(1.0, 0.0).perpendicular(), Vec2::new(0.0, 1.0));
+    }
 }
@@ -1,6 +1,6 @@
-use std::{fs, mem, ptr};
+use std::{io::Read, mem, path::Path, ptr};
 
-use basis::graphics::glw;
+use basis::graphics::{gltf, glw, image, ply, stl, wavefront};
 
 #[allow(dead_code)]
 pub fn draw_square() {
@@ -94,27 +94,100 @@ pub fn draw_triangles(vertices: &[f32]) {
     vao.unbind();
 }
 
-pub fn load_custom_texture(path: &str) -> Result<(u32, u32, Vec<u8>), Box<dyn std::error::Error>> {
-    let file = fs::read_to_string(path)?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelFormat {
+    Obj,
+    Stl,
+    Ply,
+    Gltf,
+}
+
+///
+/// Bytes read to guess the format of a file, enough for the binary STL header and the first
+/// lines of an ASCII one
+///
+const SNIFFED_LENGTH: u64 = 512;
+
+///
+/// Format of a model file from its extension, or from its content when the extension is unknown
+///
+pub fn model_format(filepath: &str) -> std::io::Result<ModelFormat> {
+    let extension = Path::new(filepath)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("obj") => Ok(ModelFormat::Obj),
+        Some("stl") => Ok(ModelFormat::Stl),
+        Some("ply") => Ok(ModelFormat::Ply),
+        Some("gltf") | Some("glb") => Ok(ModelFormat::Gltf),
+        _ => {
+            // only the start of the file is read, the loader reads the whole file afterwards
+            let file = std::fs::File::open(filepath)?;
+            let length = file.metadata()?.len();
+            let mut header = Vec::new();
+            file.take(SNIFFED_LENGTH).read_to_end(&mut header)?;
+
+            if ply::has_signature(&header) {
+                return Ok(ModelFormat::Ply);
+            }
+            if gltf::Format::from_magic(&header).is_some() {
+                return Ok(ModelFormat::Gltf);
+            }
+            match stl::Format::from_header(&header, length) {
+                Some(_) => Ok(ModelFormat::Stl),
+                None => Ok(ModelFormat::Obj),
+            }
+        }
+    }
+}
 
-    let mut lines = file.lines();
-    let mut width = 0;
-    let mut height = 0;
+///
+/// Loads the texture of the model, the `texture_path` when given or else the first diffuse map
+/// used by its faces. Without any, a checkerboard is generated so the texture can still be shown.
+///
+pub fn load_texture(
+    model: &wavefront::obj::OBJ,
+    texture_path: Option<&str>,
+) -> Result<image::Image, image::LoadImageError> {
+    if let Some(path) = texture_path {
+        return image::load(path);
+    }
 
-    // first line `width height`
-    if let Some(line) = lines.next() {
-        let mut dimensions = line.split_whitespace();
-        width = dimensions.next().unwrap_or("").parse::<u32>()?;
-        height = dimensions.next().unwrap_or("").parse::<u32>()?;
+    let diffuse_map = model
+        .faces
+        .iter()
+        .filter_map(|face| face.material.as_ref())
+        .find_map(|material| material.diffuse_map.as_ref());
+
+    if let Some(map) = diffuse_map {
+        match image::load(&map.file_path) {
+            Ok(image) => return Ok(image),
+            Err(err) => eprintln!(
+                "Warning: Could not load texture '{}': {}",
+                map.file_path.display(),
+                err
+            ),
+        }
     }
-    let mut texture_data = Vec::new();
-    // second line `number number number ....`
-    if let Some(line) = lines.next() {
-        let data = line.split_whitespace();
-        for number in data {
-            texture_data.push(number.parse::<u8>()?);
+
+    Ok(checkerboard_texture(256, 32))
+}
+
+fn checkerboard_texture(size: u32, square_size: u32) -> image::Image {
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            let value = if (x / square_size + y / square_size).is_multiple_of(2) {
+                255
+            } else {
+                64
+            };
+            data.extend_from_slice(&[value, value, value, 255]);
         }
     }
 
-    Result::Ok((width, height, texture_data))
+    image::Image::new(size, size, data)
 }
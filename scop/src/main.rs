@@ -4,15 +4,15 @@ mod traits;
 
 use basis::{
     graphics::{
-        self,
+        self, gltf,
         glw::{self},
-        wavefront,
+        ply, stl, wavefront,
         window::Window,
     },
     math::{self, VectorFunctions},
 };
 
-use structs::{Camera, Cube};
+use structs::{Camera, Cube, Light};
 use traits::EntityLifetime;
 
 use std::{env, process::ExitCode};
@@ -24,6 +24,7 @@ fn draw(
     shader: &glw::Shader,
     obj: &structs::Object,
     camera: &Camera,
+    light: &Light,
     texture_percentage: f32,
     window_size: (i32, i32),
 ) {
@@ -52,24 +53,73 @@ fn draw(
         .get_uniform_location("model")
         .uniform_matrix4fv(&model_mat);
 
+    shader
+        .get_uniform_location("lighting_enabled")
+        .uniform1b(light.enabled);
+    shader.get_uniform_location("light_position").uniform3f(
+        light.position.x,
+        light.position.y,
+        light.position.z,
+    );
+    shader.get_uniform_location("light_color").uniform3f(
+        light.color.x,
+        light.color.y,
+        light.color.z,
+    );
+    shader.get_uniform_location("ambient_light").uniform3f(
+        light.ambient.x,
+        light.ambient.y,
+        light.ambient.z,
+    );
+    shader.get_uniform_location("view_position").uniform3f(
+        camera.position.x,
+        camera.position.y,
+        camera.position.z,
+    );
+
     shader.get_uniform_location("object_texture").uniform1i(0);
     shader
         .get_uniform_location("texture_percentage")
         .uniform1f(texture_percentage);
-
-    obj.draw();
+    obj.draw(shader);
 
     shader.unbind();
 }
 
 fn load_model(
     filepath: &str,
+    texture_path: Option<&str>,
     entities: &mut Vec<Box<dyn EntityLifetime>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut obj = structs::Object::new(wavefront::obj::load(filepath)?);
-    obj.set_texture(helpers::load_custom_texture(
-        "scop/src/resources/raw_texture.txt",
-    )?);
+    // a viewer should show as much of a damaged file as it can
+    let options = wavefront::obj::LoadOptions {
+        mode: wavefront::diagnostic::ParseMode::Lenient,
+        ..Default::default()
+    };
+    let model = match helpers::model_format(filepath)? {
+        helpers::ModelFormat::Obj => wavefront::obj::load_with_options(filepath, &options)?,
+        helpers::ModelFormat::Stl => stl::load(filepath)?,
+        helpers::ModelFormat::Ply => ply::load(filepath)?,
+        // the nodes are merged into one model, each mesh placed by its node
+        helpers::ModelFormat::Gltf => gltf::load(filepath)?.to_obj(),
+    };
+    for warning in &model.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    for (file, diagnostic) in &model.diagnostics {
+        // `file:line:column:` lets editors and terminals jump to the statement
+        eprintln!(
+            "{}:{}:{}: {}",
+            file.as_deref().unwrap_or(filepath),
+            diagnostic.line,
+            diagnostic.column,
+            diagnostic
+        );
+    }
+
+    let texture = helpers::load_texture(&model, texture_path)?;
+    let mut obj = structs::Object::new(model);
+    obj.set_texture(texture);
 
     let objs_transformation = [
         (
@@ -181,7 +231,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     let mut entities: Vec<Box<dyn EntityLifetime>> = Vec::new();
 
     if args.get(1).is_none() {
-        println!("usage: scop filepath");
+        println!("usage: scop filepath [texture]");
         return Ok(());
     }
 
@@ -189,8 +239,15 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     window.init_gl();
     glw::enable(gl::DEPTH_TEST);
+    // materials with a dissolve factor are translucent
+    glw::enable(gl::BLEND);
+    glw::blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-    load_model(args[1].as_str(), &mut entities)?;
+    load_model(
+        args[1].as_str(),
+        args.get(2).map(|path| path.as_str()),
+        &mut entities,
+    )?;
 
     let shader = glw::Shader::new();
     shader
@@ -206,6 +263,11 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         math::Vec3::new(0.0, 1.0, 0.0),
         30.,
     );
+    let mut light = Light::new(
+        math::Vec3::new(10.0, 10.0, 10.0), // Position
+        math::Vec3::new(1.0, 1.0, 1.0),    // Color
+        math::Vec3::new(0.2, 0.2, 0.2),    // Ambient
+    );
     let mut is_wireframe = false;
     let mut is_texture_enabled = false;
     let mut texture_percentage: f32 = 0.0;
@@ -232,6 +294,10 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             is_texture_enabled = !is_texture_enabled;
         }
 
+        if window.on_key_press(graphics::glfw::Key::L, graphics::glfw::Modifiers::empty()) {
+            light.enabled = !light.enabled;
+        }
+
         camera.update(&mut window);
         for entity in entities.iter_mut() {
             entity.update(&mut window);
@@ -260,6 +326,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                         &shader,
                         object,
                         &camera,
+                        &light,
                         texture_percentage,
                         window.get_size(),
                     );
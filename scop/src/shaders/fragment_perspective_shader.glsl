@@ -3,21 +3,72 @@ out vec4 FragColor;
 
 in vec3 Color;
 in vec2 TexCoord;
-in float FaceId;
-in float MaxFaceId;
+in vec3 FragPosition;
+in vec3 Normal;
+flat in vec3 Ambient;
+flat in vec3 Diffuse;
+flat in vec3 Specular;
+flat in float Shininess;
+flat in float Dissolve;
+flat in float Illumination;
 
 uniform sampler2D object_texture;
 uniform float texture_percentage;
+// amount of triangles, lines or points of the batch being drawn
+uniform float primitive_count;
+
+uniform bool lighting_enabled;
+uniform vec3 light_position;
+uniform vec3 light_color;
+uniform vec3 ambient_light;
+uniform vec3 view_position;
+
+// Implements the MTL illumination models 0, 1 and 2, any other model is treated as 2
+vec3 illuminate()
+{
+    // 0: color on and ambient off
+    if (!lighting_enabled || Illumination < 0.5) {
+        return Diffuse;
+    }
+
+    // degenerate faces have no normal, so it is computed from the triangle being rasterized
+    vec3 normal = length(Normal) > 0.0
+        ? normalize(Normal)
+        : normalize(cross(dFdx(FragPosition), dFdy(FragPosition)));
+    vec3 light_direction = normalize(light_position - FragPosition);
+
+    // 1: color on and ambient on
+    vec3 ambient = Ambient * ambient_light;
+    vec3 diffuse = Diffuse * max(dot(normal, light_direction), 0.0) * light_color;
+    if (Illumination < 1.5) {
+        return ambient + diffuse;
+    }
+
+    // 2: highlight on
+    vec3 view_direction = normalize(view_position - FragPosition);
+    vec3 halfway = normalize(light_direction + view_direction);
+    float highlight = pow(max(dot(normal, halfway), 0.0), max(Shininess, 1.0));
+    vec3 specular = Specular * highlight * light_color;
+
+    return ambient + diffuse + specular;
+}
 
 void main()
 {
     vec4 texture_color = texture(object_texture, TexCoord);
 
     float color_percentage = 1.0 - texture_percentage;
-    float r = FaceId / MaxFaceId;
-    float g = FaceId / MaxFaceId;
-    float b = FaceId / MaxFaceId;
-    vec4 color = vec4(r, g, b, 1.0);
+    vec4 color;
+    if (Illumination < 0.0) {
+        // elements without material, the vertices are shared between primitives so the one
+        // being rasterized is used to tell them apart
+        float r = float(gl_PrimitiveID) / primitive_count;
+        float g = float(gl_PrimitiveID) / primitive_count;
+        float b = float(gl_PrimitiveID) / primitive_count;
+        color = vec4(r, g, b, 1.0);
+    } else {
+        color = vec4(illuminate(), Dissolve);
+    }
 
     FragColor = texture_color * texture_percentage + color * color_percentage;
 }
@@ -2,8 +2,13 @@
 layout(location = 0) in vec4 aPos;
 layout(location = 1) in vec3 aColor;
 layout(location = 2) in vec2 aTexCoord;
-layout(location = 3) in float aFaceId;
-layout(location = 4) in float aMaxFaceId;
+layout(location = 3) in vec3 aAmbient;
+layout(location = 4) in vec3 aDiffuse;
+layout(location = 5) in vec3 aSpecular;
+layout(location = 6) in float aShininess;
+layout(location = 7) in float aDissolve;
+layout(location = 8) in float aIllumination;
+layout(location = 9) in vec3 aNormal;
 
 uniform mat4 model;
 uniform mat4 view;
@@ -11,8 +16,14 @@ uniform mat4 projection;
 
 out vec3 Color;
 out vec2 TexCoord;
-out float FaceId;
-out float MaxFaceId;
+out vec3 FragPosition;
+out vec3 Normal;
+flat out vec3 Ambient;
+flat out vec3 Diffuse;
+flat out vec3 Specular;
+flat out float Shininess;
+flat out float Dissolve;
+flat out float Illumination;
 
 void main()
 {
@@ -20,6 +31,12 @@ void main()
     gl_Position = projection * view * model * pos;
     Color = aColor;
     TexCoord = aTexCoord;
-    FaceId = aFaceId;
-    MaxFaceId = aMaxFaceId;
+    FragPosition = vec3(model * pos);
+    Normal = mat3(transpose(inverse(model))) * aNormal;
+    Ambient = aAmbient;
+    Diffuse = aDiffuse;
+    Specular = aSpecular;
+    Shininess = aShininess;
+    Dissolve = aDissolve;
+    Illumination = aIllumination;
 }
@@ -33,37 +33,35 @@ impl Controllable for Camera {
     }
 
     fn move_forward(&mut self, deltatime: f32) {
-        self.position = self.position + self.front.scale(self.get_speed(deltatime));
+        self.position += self.front.scale(self.get_speed(deltatime));
     }
 
     fn move_backward(&mut self, deltatime: f32) {
-        self.position = self.position - self.front.scale(self.get_speed(deltatime));
+        self.position -= self.front.scale(self.get_speed(deltatime));
     }
 
     fn move_left(&mut self, deltatime: f32) {
-        self.position = self.position
-            - self
-                .front
-                .cross(self.up)
-                .normalize()
-                .scale(self.get_speed(deltatime));
+        self.position -= self
+            .front
+            .cross(self.up)
+            .normalize()
+            .scale(self.get_speed(deltatime));
     }
 
     fn move_right(&mut self, deltatime: f32) {
-        self.position = self.position
-            + self
-                .front
-                .cross(self.up)
-                .normalize()
-                .scale(self.get_speed(deltatime));
+        self.position += self
+            .front
+            .cross(self.up)
+            .normalize()
+            .scale(self.get_speed(deltatime));
     }
 
     fn move_up(&mut self, deltatime: f32) {
-        self.position = self.position + self.up.scale(self.get_speed(deltatime));
+        self.position += self.up.scale(self.get_speed(deltatime));
     }
 
     fn move_down(&mut self, deltatime: f32) {
-        self.position = self.position - self.up.scale(self.get_speed(deltatime));
+        self.position -= self.up.scale(self.get_speed(deltatime));
     }
 
     #[warn(dead_code)]
@@ -0,0 +1,23 @@
+use basis::math::Vec3;
+
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub position: Vec3,
+    pub color: Vec3,
+    ///
+    /// Light that reaches every face, it is multiplied by the material's ambient reflectivity
+    ///
+    pub ambient: Vec3,
+    pub enabled: bool,
+}
+
+impl Light {
+    pub fn new(position: Vec3, color: Vec3, ambient: Vec3) -> Self {
+        Self {
+            position,
+            color,
+            ambient,
+            enabled: true,
+        }
+    }
+}
@@ -1,7 +1,9 @@
 mod camera;
 mod cube;
+mod light;
 mod object;
 
 pub use camera::*;
 pub use cube::*;
+pub use light::*;
 pub use object::*;
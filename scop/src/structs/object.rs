@@ -3,11 +3,124 @@ use std::{ffi::c_void, mem, ptr};
 use basis::{
     graphics::{
         glw::{self},
+        image,
+        mesh::{IndexedMesh, Indices},
         wavefront,
     },
     math::{Quaternion, Vec3, VectorFunctions},
 };
 
+///
+/// Diameter in pixels of the points of the models
+///
+const POINT_SIZE: f32 = 4.0;
+
+///
+/// Primitives of the same kind drawn with a single call, each batch has its own VAO
+///
+#[derive(Debug, Clone)]
+struct Batch {
+    mode: gl::types::GLenum,
+    vao: glw::Vao,
+    mesh: IndexedMesh,
+}
+
+impl Batch {
+    fn new(mode: gl::types::GLenum) -> Batch {
+        Batch {
+            mode,
+            vao: glw::Vao::new(),
+            mesh: IndexedMesh::default(),
+        }
+    }
+
+    fn primitive_count(&self) -> usize {
+        let vertices_per_primitive = match self.mode {
+            gl::TRIANGLES => 3,
+            gl::LINES => 2,
+            _ => 1,
+        };
+        self.mesh.indices.len() / vertices_per_primitive
+    }
+
+    ///
+    /// Stores the mesh and setup the VAO, VBO and EBO
+    ///
+    fn upload(&mut self, mesh: IndexedMesh) {
+        self.mesh = mesh;
+
+        self.vao.bind();
+        let vbo = glw::BufferObject::new(gl::ARRAY_BUFFER, gl::STATIC_DRAW);
+        let ebo = glw::BufferObject::new(gl::ELEMENT_ARRAY_BUFFER, gl::STATIC_DRAW);
+        vbo.bind();
+        ebo.bind();
+        vbo.store_f32(&self.mesh.vertices);
+        match &self.mesh.indices {
+            Indices::U16(indices) => ebo.store_u16(indices),
+            Indices::U32(indices) => ebo.store_u32(indices),
+        }
+
+        let stride_length = (wavefront::obj::OBJ::RAW_VERTEX_LENGTH
+            * mem::size_of::<gl::types::GLfloat>())
+            as gl::types::GLsizei;
+        let start_pointer = ptr::null::<gl::types::GLfloat>();
+        let position_attribute = glw::VertexAttribute::new(
+            0,
+            4,
+            gl::FLOAT,
+            gl::FALSE,
+            stride_length,
+            start_pointer as *const c_void,
+        );
+        position_attribute.enable();
+
+        let color_attribute =
+            glw::VertexAttribute::new(1, 3, gl::FLOAT, gl::FALSE, stride_length, unsafe {
+                start_pointer.add(4) as *const c_void
+            });
+        color_attribute.enable();
+
+        let texture_coordinate_attribute =
+            glw::VertexAttribute::new(2, 3, gl::FLOAT, gl::FALSE, stride_length, unsafe {
+                start_pointer.add(7) as *const c_void
+            });
+        texture_coordinate_attribute.enable();
+
+        // Material: Ka, Kd, Ks, Ns, d and illum
+        let material_attributes = [(3, 10), (3, 13), (3, 16), (1, 19), (1, 20), (1, 21)];
+        for (index, (size, offset)) in material_attributes.into_iter().enumerate() {
+            let material_attribute = glw::VertexAttribute::new(
+                3 + index as u32,
+                size,
+                gl::FLOAT,
+                gl::FALSE,
+                stride_length,
+                unsafe { start_pointer.add(offset) as *const c_void },
+            );
+            material_attribute.enable();
+        }
+
+        let normal_attribute =
+            glw::VertexAttribute::new(9, 3, gl::FLOAT, gl::FALSE, stride_length, unsafe {
+                start_pointer.add(22) as *const c_void
+            });
+        normal_attribute.enable();
+
+        self.vao.unbind();
+    }
+
+    fn draw(&self) {
+        self.vao.bind();
+        glw::draw_elements(
+            self.mode,
+            self.mesh.indices.len() as i32,
+            self.mesh.indices.gl_type(),
+            ptr::null(),
+        );
+        self.vao.unbind();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Object {
     pub position: Vec3,
@@ -17,11 +130,11 @@ pub struct Object {
 
     pub model: wavefront::obj::OBJ,
 
-    vao: glw::Vao,
     texture: glw::Texture,
     cached_center: Vec3,
-    cached_vertices: Vec<f32>,
-    cached_indices: Vec<u32>,
+    triangles: Batch,
+    lines: Batch,
+    points: Batch,
 }
 
 impl Object {
@@ -33,11 +146,11 @@ impl Object {
             rgb: Vec3::default(),
 
             model,
-            vao: glw::Vao::new(),
             texture: glw::Texture::new(gl::TEXTURE_2D),
             cached_center: Vec3::default(),
-            cached_vertices: Vec::default(),
-            cached_indices: Vec::default(),
+            triangles: Batch::new(gl::TRIANGLES),
+            lines: Batch::new(gl::LINES),
+            points: Batch::new(gl::POINTS),
         };
         object.recompute();
         object.compute_center();
@@ -60,81 +173,39 @@ impl Object {
         self.cached_center * self.scale // scale by the object's scale
     }
 
-    pub fn set_texture(&mut self, texture: (u32, u32, Vec<u8>)) {
-        self.model.texture = texture;
+    pub fn set_texture(&mut self, texture: image::Image) {
+        self.model.texture = (texture.width, texture.height, texture.data);
         self.recompute();
     }
 
-    pub fn draw(&self) {
-        self.vao.bind();
-        glw::draw_arrays(gl::TRIANGLES, 0, self.cached_vertices.len() as i32);
-        // glw::draw_elements(
-        //     gl::TRIANGLES,
-        //     self.cached_indices.len() as i32,
-        //     gl::UNSIGNED_INT,
-        //     ptr::null(),
-        // );
-        self.vao.unbind();
+    ///
+    /// Draws the triangles, then the lines and the points of the model, `primitive_count` is set
+    /// for each batch since the shaders use it to shade the elements without material
+    ///
+    pub fn draw(&self, shader: &glw::Shader) {
+        glw::point_size(POINT_SIZE);
+
+        for batch in [&self.triangles, &self.lines, &self.points] {
+            if batch.mesh.indices.is_empty() {
+                continue;
+            }
+            shader
+                .get_uniform_location("primitive_count")
+                .uniform1f(batch.primitive_count() as f32);
+            batch.draw();
+        }
     }
 
     ///
-    /// Refetch all raw vertices and raw indices used by draw call
-    /// and setup the VAO and EBO
+    /// Refetch the indexed meshes used by the draw calls
+    /// and setup their VAO, VBO and EBO
     ///
     pub fn recompute(&mut self) {
-        let model_vertices = self.model.get_raw_vertices(self.rgb);
-        let vertices = model_vertices;
-
-        self.cached_vertices = vertices;
-        self.cached_indices = self.model.get_raw_indices();
-
-        self.vao.bind();
-        let vbo = glw::BufferObject::new(gl::ARRAY_BUFFER, gl::STATIC_DRAW);
-        // let ebo = glw::BufferObject::new(gl::ELEMENT_ARRAY_BUFFER, gl::STATIC_DRAW);
-        vbo.bind();
-        // ebo.bind();
-        vbo.store_f32(&self.cached_vertices);
-        // ebo.store_u32(&self.cached_indices);
-
-        let stride_length = 12 * mem::size_of::<gl::types::GLfloat>() as gl::types::GLsizei;
-        let start_pointer = ptr::null::<gl::types::GLfloat>();
-        let position_attribute = glw::VertexAttribute::new(
-            0,
-            4,
-            gl::FLOAT,
-            gl::FALSE,
-            stride_length,
-            start_pointer as *const c_void,
-        );
-        position_attribute.enable();
-
-        let color_attribute =
-            glw::VertexAttribute::new(1, 3, gl::FLOAT, gl::FALSE, stride_length, unsafe {
-                start_pointer.add(4) as *const c_void
-            });
-        color_attribute.enable();
-
-        let texture_coordinate_attribute =
-            glw::VertexAttribute::new(2, 3, gl::FLOAT, gl::FALSE, stride_length, unsafe {
-                start_pointer.add(7) as *const c_void
-            });
-        texture_coordinate_attribute.enable();
-
-        let face_id_attribute =
-            glw::VertexAttribute::new(3, 1, gl::FLOAT, gl::FALSE, stride_length, unsafe {
-                start_pointer.add(10) as *const c_void
-            });
-        face_id_attribute.enable();
-
-        let max_face_id_attribute =
-            glw::VertexAttribute::new(4, 1, gl::FLOAT, gl::FALSE, stride_length, unsafe {
-                start_pointer.add(11) as *const c_void
-            });
-        max_face_id_attribute.enable();
+        self.triangles.upload(self.model.get_indexed_mesh(self.rgb));
+        self.lines.upload(self.model.get_indexed_lines(self.rgb));
+        self.points.upload(self.model.get_indexed_points(self.rgb));
 
         self.recompute_texture();
-
-        self.vao.unbind();
     }
 
     fn recompute_texture(&mut self) {
@@ -166,7 +237,7 @@ impl Object {
     fn compute_center(&mut self) {
         let mut center = Vec3::default();
         for vertice in &self.model.vertices {
-            center = center + Vec3::new(vertice.x, vertice.y, vertice.z);
+            center += Vec3::new(vertice.x, vertice.y, vertice.z);
         }
         self.cached_center = center.scale(1.0 / self.model.vertices.len() as f32);
     }